@@ -0,0 +1,92 @@
+use shared::{
+    api_keys::{create_api_key, list_api_keys, revoke_api_key},
+    dotenv, env,
+    redis::{queue_manager::redis_url, rate_limit::RateLimiter},
+    types::api_key::{DEFAULT_BURST, DEFAULT_REQUESTS_PER_MINUTE, DEFAULT_USAGE_DAYS},
+    Database, Uuid,
+};
+
+const USAGE: &str = "usage:
+  api-admin create --name NAME [--requests-per-minute N] [--burst N]
+                                    create an api_server key, it is printed this once
+  api-admin list                    every key with its limits, revoked ones included
+  api-admin revoke ID               stop accepting a key
+  api-admin usage ID [--days N]     daily request counts of a key";
+
+// api_server key management. only needs postgres, and redis for usage
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    dotenv().ok();
+
+    let args: Vec<String> = env::args().skip(1).collect();
+    let Some(command) = args.first() else {
+        eprintln!("{}", USAGE);
+        std::process::exit(2);
+    };
+
+    let db = Database::connect(env::var("DATABASE_URL").expect("DATABASE_URL must be set")).await?;
+    match command.as_str() {
+        "create" => {
+            let Some(name) = args.iter().position(|arg| arg == "--name").and_then(|i| args.get(i + 1)) else {
+                eprintln!("{}", USAGE);
+                std::process::exit(2);
+            };
+            let (created, key) = create_api_key(
+                &db,
+                name,
+                flag_value(&args, "--requests-per-minute").unwrap_or(DEFAULT_REQUESTS_PER_MINUTE),
+                flag_value(&args, "--burst").unwrap_or(DEFAULT_BURST),
+            )
+            .await?;
+            println!("created key {} ({}), store it now, it can't be shown again:", created.id, created.name);
+            println!("{}", key);
+        }
+        "list" => {
+            for key in list_api_keys(&db).await? {
+                let state = if key.active { "active" } else { "revoked" };
+                println!(
+                    "{}  {}...  {:>6}/min  burst {:<5} {:<8} {}",
+                    key.id, key.key_prefix, key.requests_per_minute, key.burst, state, key.name
+                );
+            }
+        }
+        "revoke" => {
+            let id = key_id(&args);
+            if !revoke_api_key(&db, id).await? {
+                eprintln!("no active key {}", id);
+                std::process::exit(1);
+            }
+            // every api_server replica caches keys on its own, see KEY_CACHE_TTL
+            println!("revoked {}, it stops working within 30s per replica", id);
+        }
+        "usage" => {
+            let limiter = RateLimiter::new(&redis_url())?;
+            for day in limiter.usage(key_id(&args), flag_value(&args, "--days").unwrap_or(DEFAULT_USAGE_DAYS)).await? {
+                println!("{}  {:>8} requests  {:>6} throttled", day.day, day.requests, day.throttled);
+            }
+        }
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    }
+
+    Ok(())
+}
+
+fn key_id(args: &[String]) -> Uuid {
+    match args.get(1).and_then(|id| Uuid::parse_str(id).ok()) {
+        Some(id) => id,
+        None => {
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    }
+}
+
+fn flag_value<T: std::str::FromStr>(args: &[String], flag: &str) -> Option<T> {
+    args.iter()
+        .position(|arg| arg == flag)
+        .and_then(|i| args.get(i + 1))
+        .and_then(|value| value.parse().ok())
+}
@@ -0,0 +1,232 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::extract::rejection::QueryRejection;
+use axum::extract::{Request, State as MiddlewareState};
+use axum::http::header::{AUTHORIZATION, CONTENT_TYPE, ETAG, RETRY_AFTER};
+use axum::http::{HeaderMap, HeaderName, HeaderValue, Method};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Extension;
+use sea_orm::DbErr;
+use shared::{
+    api_keys::{find_active_key, hash_key},
+    entities::api_key,
+    env,
+    redis::rate_limit::{RateDecision, RateLimiter},
+    types::api_key::{UsageQuery, UsageResponse, DEFAULT_USAGE_DAYS},
+    DatabaseConnection, Json, Query,
+};
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+use crate::error::{ApiError, ErrorBody};
+
+pub const API_KEY_HEADER: HeaderName = HeaderName::from_static("x-api-key");
+const LIMIT_HEADER: HeaderName = HeaderName::from_static("x-ratelimit-limit");
+const REMAINING_HEADER: HeaderName = HeaderName::from_static("x-ratelimit-remaining");
+
+// how long a replica keeps trusting a key it looked up. the cache is per replica, so a revoked key stops working
+// within 30s per replica, not everywhere at once
+const KEY_CACHE_TTL: Duration = Duration::from_secs(30);
+// unknown keys are cached too, this keeps someone trying random ones from growing the cache forever
+const MAX_CACHED_KEYS: usize = 10_000;
+const DEFAULT_CORS_ORIGINS: &str = "http://localhost:3000";
+
+// looked up keys by their hash, with when they were looked up. None for keys that don't exist or were revoked
+type KeyCache = HashMap<String, (Option<api_key::Model>, Instant)>;
+
+// what the api key middleware needs, built once in main
+#[derive(Clone)]
+pub struct ApiAccess {
+    db: DatabaseConnection,
+    limiter: Arc<RateLimiter>,
+    keys: Arc<Mutex<KeyCache>>,
+    required: bool,
+}
+
+impl ApiAccess {
+    // API_KEYS_REQUIRED=false lets requests without a key through unlimited, a key that is sent is still checked
+    pub fn from_env(db: DatabaseConnection, limiter: Arc<RateLimiter>) -> Self {
+        let required = env::var("API_KEYS_REQUIRED").map_or(true, |value| value != "false");
+        Self {
+            db,
+            limiter,
+            keys: Arc::default(),
+            required,
+        }
+    }
+
+    async fn lookup(&self, key: &str) -> Result<Option<api_key::Model>, DbErr> {
+        let hash = hash_key(key);
+        if let Some((cached, at)) = self.keys.lock().unwrap().get(&hash) {
+            if at.elapsed() < KEY_CACHE_TTL {
+                return Ok(cached.clone());
+            }
+        }
+
+        let found = find_active_key(&self.db, key).await?;
+        let mut keys = self.keys.lock().unwrap();
+        if keys.len() >= MAX_CACHED_KEYS {
+            keys.clear();
+        }
+        keys.insert(hash, (found.clone(), Instant::now()));
+        Ok(found)
+    }
+}
+
+// the key from the x-api-key header, `Authorization: Bearer <key>` or ?api_key= for EventSource and
+// websocket clients, which can't set headers
+fn presented_key(request: &Request) -> Option<String> {
+    let headers = request.headers();
+    if let Some(key) = headers.get(API_KEY_HEADER).and_then(|value| value.to_str().ok()) {
+        return Some(key.trim().to_string());
+    }
+    if let Some(key) = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+    {
+        return Some(key.trim().to_string());
+    }
+    request
+        .uri()
+        .query()?
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("api_key="))
+        .map(str::to_string)
+}
+
+fn rate_headers(headers: &mut HeaderMap, key: &api_key::Model, decision: &RateDecision) {
+    headers.insert(LIMIT_HEADER, HeaderValue::from(key.burst));
+    headers.insert(REMAINING_HEADER, HeaderValue::from(decision.remaining));
+}
+
+// every api route goes through this. the key's row ends up in the request extensions for handlers that want it.
+// when redis can't be reached requests are let through unlimited rather than failing the whole api
+pub async fn require_api_key(
+    MiddlewareState(access): MiddlewareState<ApiAccess>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let Some(key) = presented_key(&request) else {
+        if access.required {
+            return ApiError::Unauthorized("Missing API key, send it in the X-API-Key header".to_string())
+                .into_response();
+        }
+        return next.run(request).await;
+    };
+
+    let key = match access.lookup(&key).await {
+        Ok(Some(key)) => key,
+        Ok(None) => return ApiError::Unauthorized("Unknown or revoked API key".to_string()).into_response(),
+        Err(e) => return ApiError::from(e).into_response(),
+    };
+
+    let decision = match access.limiter.take(key.id, key.requests_per_minute, key.burst).await {
+        Ok(decision) => Some(decision),
+        Err(e) => {
+            println!("Rate limiter unavailable, letting the request through: {}", e);
+            None
+        }
+    };
+    if let Some(decision) = decision.filter(|decision| !decision.allowed) {
+        let mut response = ApiError::RateLimited(decision.retry_after).into_response();
+        rate_headers(response.headers_mut(), &key, &decision);
+        return response;
+    }
+
+    request.extensions_mut().insert(key.clone());
+    let mut response = next.run(request).await;
+    if let Some(decision) = decision {
+        rate_headers(response.headers_mut(), &key, &decision);
+    }
+    response
+}
+
+// CORS_ALLOWED_ORIGINS is a comma separated list of origins, `*` allows any
+pub fn cors_from_env() -> CorsLayer {
+    let origins = env::var("CORS_ALLOWED_ORIGINS").unwrap_or_else(|_| DEFAULT_CORS_ORIGINS.to_string());
+    let allow_origin = if origins.trim() == "*" {
+        AllowOrigin::any()
+    } else {
+        AllowOrigin::list(
+            origins
+                .split(',')
+                .map(str::trim)
+                .filter(|origin| !origin.is_empty())
+                .map(|origin| {
+                    HeaderValue::from_str(origin)
+                        .unwrap_or_else(|_| panic!("invalid origin in CORS_ALLOWED_ORIGINS: {}", origin))
+                }),
+        )
+    };
+
+    CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods([Method::GET, Method::POST, Method::PATCH, Method::DELETE])
+        .allow_headers([API_KEY_HEADER, AUTHORIZATION, CONTENT_TYPE])
+        .expose_headers([LIMIT_HEADER, REMAINING_HEADER, RETRY_AFTER, ETAG])
+        .max_age(Duration::from_secs(600))
+}
+
+#[utoipa::path(
+    get,
+    path = "/usage",
+    tag = "account",
+    params(UsageQuery),
+    responses(
+        (status = 200, description = "the calling key's limits and daily request counts", body = UsageResponse),
+        (status = 401, description = "no api key was sent", body = ErrorBody),
+    )
+)]
+// GET /usage?days=, what the key this request was made with has used
+pub async fn get_usage(
+    key: Option<Extension<api_key::Model>>,
+    Extension(limiter): Extension<Arc<RateLimiter>>,
+    query: Result<Query<UsageQuery>, QueryRejection>,
+) -> Result<Json<UsageResponse>, ApiError> {
+    let Query(query) = query?;
+    let Some(Extension(key)) = key else {
+        return Err(ApiError::Unauthorized("Send an API key to see its usage".to_string()));
+    };
+
+    let days = limiter
+        .usage(key.id, query.days.unwrap_or(DEFAULT_USAGE_DAYS))
+        .await
+        .map_err(|e| ApiError::Unavailable(format!("redis: {}", e)))?;
+    Ok(Json(UsageResponse {
+        key_id: key.id,
+        name: key.name,
+        key_prefix: key.key_prefix,
+        requests_per_minute: key.requests_per_minute,
+        burst: key.burst,
+        days,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key_of(request: Request) -> Option<String> {
+        presented_key(&request)
+    }
+
+    #[test]
+    fn reads_the_key_from_header_bearer_or_query() {
+        let request = |builder: axum::http::request::Builder| builder.body(axum::body::Body::empty()).unwrap();
+        let get = || Request::builder().uri("/events?owner=x&api_key=from_query");
+
+        assert_eq!(
+            key_of(request(get().header("x-api-key", "from_header"))).as_deref(),
+            Some("from_header")
+        );
+        assert_eq!(
+            key_of(request(get().header("authorization", "Bearer from_bearer"))).as_deref(),
+            Some("from_bearer")
+        );
+        assert_eq!(key_of(request(get())).as_deref(), Some("from_query"));
+        assert_eq!(key_of(request(Request::builder().uri("/details/x?api_keys=no"))), None);
+    }
+}
@@ -0,0 +1,106 @@
+use std::future::Future;
+
+use axum::http::header::{CACHE_CONTROL, CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use axum::http::HeaderMap;
+use axum::response::{IntoResponse, Response};
+use chrono::DateTime;
+use serde::Serialize;
+use shared::{
+    redis::cache::{CacheKey, CacheLookup, CachedResponse, ResponseCache},
+    StatusCode,
+};
+
+use crate::error::ApiError;
+
+// the cached response for `key`, or the one built from what `load` returns, which then gets cached.
+// errors aren't cached, and redis being unavailable only costs the cache. an entry is only stored when the worker
+// hasn't invalidated its key since the lookup, otherwise it may have been built from data the worker just replaced
+pub async fn read_through<T, F>(cache: &ResponseCache, key: CacheKey, load: F) -> Result<CachedResponse, ApiError>
+where
+    T: Serialize,
+    F: Future<Output = Result<T, ApiError>>,
+{
+    let version = match cache.get(&key).await {
+        Ok(CacheLookup::Hit(entry)) => return Ok(entry),
+        Ok(CacheLookup::Miss(version)) => Some(version),
+        Err(e) => {
+            println!("Response cache unavailable, reading through: {}", e);
+            None
+        }
+    };
+
+    let body = serde_json::to_string(&load.await?).map_err(|e| ApiError::Internal(e.to_string()))?;
+    let entry = CachedResponse::new(body);
+    if let Some(version) = version {
+        if let Err(e) = cache.store(&key, &entry, version).await {
+            println!("Failed to cache a response: {}", e);
+        }
+    }
+    Ok(entry)
+}
+
+// search results are keyed on the route and everything that was asked, in its parsed form so equivalent
+// requests share an entry
+pub fn search_key(route: &str, request: &impl Serialize) -> CacheKey {
+    let request = serde_json::to_string(request).expect("search requests are plain structs and always serialize");
+    CacheKey::Search(format!("{} {}", route, request))
+}
+
+// the json body with its ETag and Last-Modified. pass the request headers of a GET to answer a matching
+// If-None-Match or If-Modified-Since with an empty 304. no-cache makes browsers revalidate instead of guessing
+pub fn respond(entry: CachedResponse, request_headers: Option<&HeaderMap>) -> Response {
+    let last_modified = entry.last_modified.format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+    let headers = [
+        (ETAG, entry.etag.clone()),
+        (LAST_MODIFIED, last_modified),
+        (CACHE_CONTROL, "no-cache".to_string()),
+    ];
+
+    if request_headers.is_some_and(|request_headers| not_modified(&entry, request_headers)) {
+        return (StatusCode::NOT_MODIFIED, headers).into_response();
+    }
+    (headers, [(CONTENT_TYPE, "application/json")], entry.body).into_response()
+}
+
+// If-None-Match wins when both are sent, like RFC 9110 says
+fn not_modified(entry: &CachedResponse, request_headers: &HeaderMap) -> bool {
+    if let Some(if_none_match) = request_headers.get(IF_NONE_MATCH) {
+        return if_none_match.to_str().is_ok_and(|tags| {
+            tags.split(',')
+                .map(|tag| tag.trim().trim_start_matches("W/"))
+                .any(|tag| tag == "*" || tag == entry.etag)
+        });
+    }
+    request_headers
+        .get(IF_MODIFIED_SINCE)
+        .and_then(|since| since.to_str().ok())
+        .and_then(|since| DateTime::parse_from_rfc2822(since).ok())
+        .is_some_and(|since| entry.last_modified <= since)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(entry: &CachedResponse, header: (&'static str, &str)) -> StatusCode {
+        let mut request_headers = HeaderMap::new();
+        request_headers.insert(header.0, header.1.parse().unwrap());
+        respond(entry.clone(), Some(&request_headers)).status()
+    }
+
+    #[test]
+    fn answers_conditional_requests() {
+        let entry = CachedResponse::new("{}".to_string());
+        let response = respond(entry.clone(), None);
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[ETAG], entry.etag.as_str());
+        let last_modified = response.headers()[LAST_MODIFIED].to_str().unwrap().to_string();
+
+        assert_eq!(status(&entry, ("if-none-match", &entry.etag)), StatusCode::NOT_MODIFIED);
+        assert_eq!(status(&entry, ("if-none-match", &format!("\"x\", W/{}", entry.etag))), StatusCode::NOT_MODIFIED);
+        assert_eq!(status(&entry, ("if-none-match", "\"x\"")), StatusCode::OK);
+        assert_eq!(status(&entry, ("if-modified-since", &last_modified)), StatusCode::NOT_MODIFIED);
+        assert_eq!(status(&entry, ("if-modified-since", "Thu, 01 Jan 2015 00:00:00 GMT")), StatusCode::OK);
+        assert_eq!(status(&entry, ("if-modified-since", "yesterday")), StatusCode::OK);
+    }
+}
@@ -0,0 +1,130 @@
+use axum::extract::rejection::JsonRejection;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use shared::{
+    assets::das::{
+        get_asset, get_asset_batch, get_assets_by_authority, get_assets_by_creator, get_assets_by_group,
+        get_assets_by_owner, search_assets, DasError,
+    },
+    types::das::{GetAssetBatchParams, GetAssetParams, JsonRpcError, JsonRpcRequest, JsonRpcResponse},
+    DatabaseConnection, Json, State,
+};
+
+use crate::AppState;
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+const ASSET_NOT_FOUND: i64 = -32000; // what DAS providers answer getAsset with for unknown ids
+
+#[utoipa::path(
+    post,
+    path = "/rpc",
+    tag = "das",
+    request_body(content = JsonRpcRequest, description = "one call, or an array of them for a batch"),
+    responses((status = 200, description = "json-rpc result or error, an array for a batch", body = JsonRpcResponse))
+)]
+// POST /rpc, the DAS json-rpc methods served from our own tables. like any json-rpc server
+// the http status is always 200 and failures are in the `error` member. batches (arrays of calls) work too
+pub async fn das_rpc(State((db, _, _)): State<AppState>, body: Result<Json<Value>, JsonRejection>) -> Json<Value> {
+    let body = match body {
+        Ok(Json(body)) => body,
+        Err(rejection) => return Json(to_value(failure(Value::Null, PARSE_ERROR, rejection.body_text()))),
+    };
+
+    match body {
+        Value::Array(calls) if !calls.is_empty() => {
+            let mut responses = Vec::with_capacity(calls.len());
+            for call in calls {
+                responses.push(to_value(handle_call(&db, call).await));
+            }
+            Json(Value::Array(responses))
+        }
+        call => Json(to_value(handle_call(&db, call).await)),
+    }
+}
+
+async fn handle_call(db: &DatabaseConnection, call: Value) -> JsonRpcResponse {
+    let request: JsonRpcRequest = match serde_json::from_value(call) {
+        Ok(request) => request,
+        Err(e) => return failure(Value::Null, INVALID_REQUEST, e.to_string()),
+    };
+    let id = request.id.clone();
+
+    match dispatch(db, request).await {
+        Ok(result) => JsonRpcResponse {
+            jsonrpc: "2.0",
+            id,
+            result: Some(result),
+            error: None,
+        },
+        Err(error) => JsonRpcResponse {
+            jsonrpc: "2.0",
+            id,
+            result: None,
+            error: Some(error),
+        },
+    }
+}
+
+async fn dispatch(db: &DatabaseConnection, request: JsonRpcRequest) -> Result<Value, JsonRpcError> {
+    let params = request.params;
+    match request.method.as_str() {
+        "getAsset" => {
+            let params: GetAssetParams = parse(params)?;
+            match get_asset(db, &params.id).await.map_err(das_error)? {
+                Some(asset) => Ok(to_value(asset)),
+                None => Err(error(ASSET_NOT_FOUND, "Asset Not Found")),
+            }
+        }
+        "getAssetBatch" => {
+            let params: GetAssetBatchParams = parse(params)?;
+            Ok(to_value(get_asset_batch(db, &params.ids).await.map_err(das_error)?))
+        }
+        "getAssetsByOwner" => Ok(to_value(get_assets_by_owner(db, &parse(params)?).await.map_err(das_error)?)),
+        "getAssetsByCreator" => Ok(to_value(get_assets_by_creator(db, &parse(params)?).await.map_err(das_error)?)),
+        "getAssetsByGroup" => Ok(to_value(get_assets_by_group(db, &parse(params)?).await.map_err(das_error)?)),
+        "getAssetsByAuthority" => Ok(to_value(
+            get_assets_by_authority(db, &parse(params)?).await.map_err(das_error)?,
+        )),
+        "searchAssets" => Ok(to_value(search_assets(db, &parse(params)?).await.map_err(das_error)?)),
+        other => Err(error(METHOD_NOT_FOUND, &format!("Method not found : {}", other))),
+    }
+}
+
+fn parse<T: DeserializeOwned>(params: Value) -> Result<T, JsonRpcError> {
+    serde_json::from_value(params).map_err(|e| error(INVALID_PARAMS, &e.to_string()))
+}
+
+fn das_error(e: DasError) -> JsonRpcError {
+    match e {
+        DasError::InvalidParams(msg) => error(INVALID_PARAMS, &msg),
+        DasError::Db(e) => {
+            println!("DAS database error: {}", e);
+            error(INTERNAL_ERROR, "Internal error")
+        }
+    }
+}
+
+fn error(code: i64, message: &str) -> JsonRpcError {
+    JsonRpcError {
+        code,
+        message: message.to_string(),
+    }
+}
+
+fn failure(id: Value, code: i64, message: String) -> JsonRpcResponse {
+    JsonRpcResponse {
+        jsonrpc: "2.0",
+        id,
+        result: None,
+        error: Some(JsonRpcError { code, message }),
+    }
+}
+
+fn to_value<T: Serialize>(value: T) -> Value {
+    serde_json::to_value(value).expect("DAS responses are plain structs and always serialize")
+}
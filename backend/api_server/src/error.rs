@@ -0,0 +1,160 @@
+use std::time::Duration;
+
+use axum::extract::rejection::{JsonRejection, QueryRejection};
+use axum::http::header::RETRY_AFTER;
+use axum::response::{IntoResponse, Response};
+use sea_orm::DbErr;
+use serde::Serialize;
+use shared::{search::SearchIndexError, Json, StatusCode};
+use utoipa::ToSchema;
+
+// every handler error goes through this, so clients always get the status plus a body like
+// {"error": "not_found", "message": "No mint found for ..."}
+#[derive(Debug)]
+pub enum ApiError {
+    NotFound(String),
+    BadRequest(String),
+    Unauthorized(String), // no api key, or one that doesn't exist (anymore)
+    RateLimited(Duration), // the key's bucket is empty, this is how long until the next token
+    Unavailable(String), // postgres or the search backend can't be reached, worth retrying
+    Internal(String),
+}
+
+// the body of every error response, ApiErrorBody in the frontend types
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ErrorBody {
+    // not_found, bad_request, unauthorized, rate_limited, service_unavailable or internal_error
+    pub error: &'static str,
+    pub message: String,
+}
+
+impl ApiError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ApiError::RateLimited(_) => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::NotFound(_) => "not_found",
+            ApiError::BadRequest(_) => "bad_request",
+            ApiError::Unauthorized(_) => "unauthorized",
+            ApiError::RateLimited(_) => "rate_limited",
+            ApiError::Unavailable(_) => "service_unavailable",
+            ApiError::Internal(_) => "internal_error",
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        let code = self.code();
+        // server side failures are logged in full, the client only gets a generic message for them
+        let mut retry_after = None;
+        let message = match self {
+            ApiError::NotFound(msg) | ApiError::BadRequest(msg) | ApiError::Unauthorized(msg) => msg,
+            ApiError::RateLimited(wait) => {
+                // whole seconds, rounded up so a client that waits exactly this long gets a token
+                let seconds = wait.as_millis().div_ceil(1000).max(1) as u64;
+                retry_after = Some(seconds);
+                format!("Rate limit exceeded, retry in {} seconds", seconds)
+            }
+            ApiError::Unavailable(msg) => {
+                println!("Dependency unavailable: {}", msg);
+                "A backing service is unavailable, try again later".to_string()
+            }
+            ApiError::Internal(msg) => {
+                println!("Internal error: {}", msg);
+                "Internal server error".to_string()
+            }
+        };
+        let mut response = (status, Json(ErrorBody { error: code, message })).into_response();
+        if let Some(seconds) = retry_after {
+            response.headers_mut().insert(RETRY_AFTER, seconds.into());
+        }
+        response
+    }
+}
+
+impl From<DbErr> for ApiError {
+    fn from(e: DbErr) -> Self {
+        match e {
+            DbErr::Conn(_) | DbErr::ConnectionAcquire(_) => ApiError::Unavailable(e.to_string()),
+            DbErr::RecordNotFound(msg) => ApiError::NotFound(msg),
+            _ => ApiError::Internal(e.to_string()),
+        }
+    }
+}
+
+impl From<SearchIndexError> for ApiError {
+    fn from(e: SearchIndexError) -> Self {
+        match e {
+            SearchIndexError::InvalidRequest(msg) => ApiError::BadRequest(msg),
+            SearchIndexError::Unavailable(_) => ApiError::Unavailable(e.to_string()),
+            SearchIndexError::Failed(_) => ApiError::Internal(e.to_string()),
+        }
+    }
+}
+
+// malformed bodies and query strings get the same error body instead of axum's plain text one
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        ApiError::BadRequest(rejection.body_text())
+    }
+}
+
+impl From<QueryRejection> for ApiError {
+    fn from(rejection: QueryRejection) -> Self {
+        ApiError::BadRequest(rejection.body_text())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_backend_errors_to_statuses() {
+        let status = |e: ApiError| e.status();
+        assert_eq!(
+            status(SearchIndexError::InvalidRequest("bad cursor".to_string()).into()),
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            status(SearchIndexError::Unavailable("refused".to_string()).into()),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        assert_eq!(
+            status(SearchIndexError::Failed("bad response".to_string()).into()),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+        assert_eq!(
+            status(DbErr::Conn(sea_orm::RuntimeErr::Internal("down".to_string())).into()),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        assert_eq!(
+            status(DbErr::RecordNotFound("mint".to_string()).into()),
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            status(DbErr::Custom("constraint".to_string()).into()),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+    }
+
+    #[test]
+    fn rate_limited_rounds_retry_after_up() {
+        let response = ApiError::RateLimited(Duration::from_millis(1200)).into_response();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[RETRY_AFTER], "2");
+        let response = ApiError::RateLimited(Duration::ZERO).into_response();
+        assert_eq!(response.headers()[RETRY_AFTER], "1");
+    }
+}
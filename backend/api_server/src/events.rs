@@ -0,0 +1,155 @@
+use std::convert::Infallible;
+use std::sync::Arc;
+
+use axum::extract::rejection::QueryRejection;
+use axum::extract::ws::{rejection::WebSocketUpgradeRejection, CloseFrame, Message, WebSocket, WebSocketUpgrade};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::Response;
+use axum::Extension;
+use futures::stream::{self, Stream};
+use serde_json::json;
+use shared::{
+    redis::events::ChangeFilter,
+    shutdown::Shutdown,
+    types::event::{ChangeEvent, SubscribeQuery},
+    Query, State,
+};
+use tokio::sync::broadcast::{error::RecvError, Receiver};
+
+use crate::{
+    error::{ApiError, ErrorBody},
+    AppState,
+};
+
+// how many changes a slow subscriber can be behind before it starts missing them
+pub const CHANGES_BUFFER: usize = 1024;
+// "going away", what a websocket subscriber is told when the server shuts down
+const CLOSE_GOING_AWAY: u16 = 1001;
+
+enum Delivery {
+    Change(Arc<ChangeEvent>),
+    Lagged(u64), // this many changes were dropped for this subscriber
+}
+
+impl Delivery {
+    fn kind(&self) -> &'static str {
+        match self {
+            Delivery::Change(event) => event.kind.as_str(),
+            Delivery::Lagged(_) => "lagged",
+        }
+    }
+
+    // the ChangeEvent as is, or {"kind": "lagged", "skipped": n}
+    fn to_json(&self) -> String {
+        match self {
+            Delivery::Change(event) => serde_json::to_string(event.as_ref()),
+            Delivery::Lagged(skipped) => serde_json::to_string(&json!({ "kind": "lagged", "skipped": skipped })),
+        }
+        .expect("change events are plain structs and always serialize")
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/events",
+    tag = "events",
+    params(SubscribeQuery),
+    responses(
+        (status = 200, description = "server-sent events named after the change kind",
+            content_type = "text/event-stream", body = ChangeEvent),
+        (status = 400, description = "no filter or an invalid address", body = ErrorBody),
+    )
+)]
+// GET /events?mint=&owner=&collection=&creator=, one server-sent event per matching change, named after its kind
+pub async fn sse_changes(
+    State((_, _, changes)): State<AppState>,
+    Extension(shutdown): Extension<Shutdown>,
+    query: Result<Query<SubscribeQuery>, QueryRejection>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    let filter = subscription(query)?;
+
+    let subscriber = (changes.subscribe(), filter, shutdown);
+    let events = stream::unfold(subscriber, |(mut receiver, filter, shutdown)| async move {
+        let delivery = next_delivery(&mut receiver, &filter, &shutdown).await?;
+        let event = Event::default().event(delivery.kind()).data(delivery.to_json());
+        Some((Ok(event), (receiver, filter, shutdown)))
+    });
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/events/ws",
+    tag = "events",
+    params(SubscribeQuery),
+    responses(
+        (status = 101, description = "websocket upgrade, then one ChangeEvent json text message per matching change"),
+        (status = 400, description = "no filter, an invalid address or not a websocket request", body = ErrorBody),
+    )
+)]
+// GET /events/ws with the same query params, one text message per matching change. the server only sends,
+// whatever the client sends besides a close is ignored
+pub async fn ws_changes(
+    State((_, _, changes)): State<AppState>,
+    Extension(shutdown): Extension<Shutdown>,
+    upgrade: Result<WebSocketUpgrade, WebSocketUpgradeRejection>,
+    query: Result<Query<SubscribeQuery>, QueryRejection>,
+) -> Result<Response, ApiError> {
+    let upgrade = upgrade.map_err(|rejection| ApiError::BadRequest(rejection.body_text()))?;
+    let filter = subscription(query)?;
+
+    let receiver = changes.subscribe();
+    Ok(upgrade.on_upgrade(move |socket| forward_changes(socket, receiver, filter, shutdown)))
+}
+
+async fn forward_changes(
+    mut socket: WebSocket,
+    mut receiver: Receiver<Arc<ChangeEvent>>,
+    filter: ChangeFilter,
+    shutdown: Shutdown,
+) {
+    loop {
+        tokio::select! {
+            delivery = next_delivery(&mut receiver, &filter, &shutdown) => {
+                let Some(delivery) = delivery else { break };
+                if socket.send(Message::Text(delivery.to_json().into())).await.is_err() {
+                    break;
+                }
+            }
+            incoming = socket.recv() => match incoming {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            }
+        }
+    }
+
+    if shutdown.is_triggered() {
+        let frame = CloseFrame { code: CLOSE_GOING_AWAY, reason: "server shutting down".into() };
+        let _ = socket.send(Message::Close(Some(frame))).await;
+    }
+}
+
+fn subscription(query: Result<Query<SubscribeQuery>, QueryRejection>) -> Result<ChangeFilter, ApiError> {
+    let Query(query) = query?;
+    ChangeFilter::parse(&query).map_err(ApiError::BadRequest)
+}
+
+// the next change this subscriber wants. None once shutdown starts, so open streams don't hold the server up
+async fn next_delivery(
+    receiver: &mut Receiver<Arc<ChangeEvent>>,
+    filter: &ChangeFilter,
+    shutdown: &Shutdown,
+) -> Option<Delivery> {
+    loop {
+        let received = tokio::select! {
+            received = receiver.recv() => received,
+            _ = shutdown.triggered() => return None,
+        };
+        match received {
+            Ok(event) if filter.matches(&event) => return Some(Delivery::Change(event)),
+            Ok(_) => {}
+            Err(RecvError::Lagged(skipped)) => return Some(Delivery::Lagged(skipped)),
+            Err(RecvError::Closed) => return None,
+        }
+    }
+}
@@ -0,0 +1,212 @@
+use std::collections::HashMap;
+
+use async_graphql::dataloader::Loader;
+use async_graphql::Error;
+use sea_orm::prelude::Decimal;
+use sea_orm::{DatabaseConnection, DbBackend, DbErr, FromQueryResult, Statement};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use shared::entities::{mint, nft_creator, nft_json_metadata, nft_metadata, nft_ownership, nft_royalty, token_account};
+
+// every field that needs another table goes through one of these, so a page of 50 mints asking for
+// metadata, royalty and creators is one `= ANY($1)` query per table instead of one per mint.
+// rows are read as jsonb into the entity models like /details does (sqlx can't decode the u16 basis points)
+#[derive(Debug, FromQueryResult)]
+struct JsonRow {
+    row: Value,
+}
+
+async fn load_rows<M: DeserializeOwned>(
+    db: &DatabaseConnection,
+    table: &str,
+    key: &str,
+    order: &str,
+    keys: &[String],
+) -> Result<Vec<M>, Error> {
+    let sql = format!("SELECT to_jsonb({table}) AS row FROM {table} WHERE {key} = ANY($1) ORDER BY {order}");
+    let rows = JsonRow::find_by_statement(Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        sql,
+        vec![keys.to_vec().into()],
+    ))
+    .all(db)
+    .await
+    .map_err(db_error)?;
+
+    rows.into_iter()
+        .map(|row| {
+            serde_json::from_value(row.row)
+                .map_err(|e| db_error(DbErr::Json(format!("Failed to read {} {}", table, e))))
+        })
+        .collect()
+}
+
+// the error is logged in full, the client only sees a generic message like the rest api
+pub fn db_error(e: DbErr) -> Error {
+    println!("GraphQL database error: {}", e);
+    Error::new("Internal server error")
+}
+
+fn by_key<M>(rows: Vec<M>, key: impl Fn(&M) -> &str) -> HashMap<String, M> {
+    rows.into_iter().map(|row| (key(&row).to_string(), row)).collect()
+}
+
+fn grouped<M>(rows: Vec<M>, key: impl Fn(&M) -> &str) -> HashMap<String, Vec<M>> {
+    let mut groups: HashMap<String, Vec<M>> = HashMap::new();
+    for row in rows {
+        groups.entry(key(&row).to_string()).or_default().push(row);
+    }
+    groups
+}
+
+pub struct MintLoader(pub DatabaseConnection);
+
+impl Loader<String> for MintLoader {
+    type Value = mint::Model;
+    type Error = Error;
+
+    async fn load(&self, keys: &[String]) -> Result<HashMap<String, Self::Value>, Self::Error> {
+        let rows: Vec<mint::Model> = load_rows(&self.0, "mint", "mint_address", "mint_address", keys).await?;
+        Ok(by_key(rows, |row| &row.mint_address))
+    }
+}
+
+pub struct MetadataLoader(pub DatabaseConnection);
+
+impl Loader<String> for MetadataLoader {
+    type Value = nft_metadata::Model;
+    type Error = Error;
+
+    async fn load(&self, keys: &[String]) -> Result<HashMap<String, Self::Value>, Self::Error> {
+        let rows: Vec<nft_metadata::Model> =
+            load_rows(&self.0, "nft_metadata", "mint_address", "mint_address", keys).await?;
+        Ok(by_key(rows, |row| &row.mint_address))
+    }
+}
+
+pub struct ContentLoader(pub DatabaseConnection);
+
+impl Loader<String> for ContentLoader {
+    type Value = nft_json_metadata::Model;
+    type Error = Error;
+
+    async fn load(&self, keys: &[String]) -> Result<HashMap<String, Self::Value>, Self::Error> {
+        let rows: Vec<nft_json_metadata::Model> =
+            load_rows(&self.0, "nft_json_metadata", "mint_address", "mint_address", keys).await?;
+        Ok(by_key(rows, |row| &row.mint_address))
+    }
+}
+
+pub struct OwnershipLoader(pub DatabaseConnection);
+
+impl Loader<String> for OwnershipLoader {
+    type Value = nft_ownership::Model;
+    type Error = Error;
+
+    async fn load(&self, keys: &[String]) -> Result<HashMap<String, Self::Value>, Self::Error> {
+        let rows: Vec<nft_ownership::Model> =
+            load_rows(&self.0, "nft_ownership", "mint_address", "mint_address", keys).await?;
+        Ok(by_key(rows, |row| &row.mint_address))
+    }
+}
+
+pub struct RoyaltyLoader(pub DatabaseConnection);
+
+impl Loader<String> for RoyaltyLoader {
+    type Value = nft_royalty::Model;
+    type Error = Error;
+
+    async fn load(&self, keys: &[String]) -> Result<HashMap<String, Self::Value>, Self::Error> {
+        let rows: Vec<nft_royalty::Model> =
+            load_rows(&self.0, "nft_royalty", "mint_address", "mint_address", keys).await?;
+        Ok(by_key(rows, |row| &row.mint_address))
+    }
+}
+
+// keyed by the metadata pda, that's what creators point at
+pub struct CreatorsLoader(pub DatabaseConnection);
+
+impl Loader<String> for CreatorsLoader {
+    type Value = Vec<nft_creator::Model>;
+    type Error = Error;
+
+    async fn load(&self, keys: &[String]) -> Result<HashMap<String, Self::Value>, Self::Error> {
+        let rows: Vec<nft_creator::Model> = load_rows(
+            &self.0,
+            "nft_creator",
+            "metadata_address",
+            "created_at, creator_address",
+            keys,
+        )
+        .await?;
+        Ok(grouped(rows, |row| &row.metadata_address))
+    }
+}
+
+// the (amount, token address) of the last token account of the previous page
+pub type TokenAccountsCursor = (Decimal, String);
+
+// one page of a mint's token accounts, largest balance first
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TokenAccountsPage {
+    pub mint_address: String,
+    pub size: usize,
+    pub after: Option<TokenAccountsCursor>,
+}
+
+// fetches size + 1 rows per mint, the extra one only says there's a next page. the mints of a page ask with the same
+// arguments, so that's one lateral query for all of them
+pub struct TokenAccountsLoader(pub DatabaseConnection);
+
+impl Loader<TokenAccountsPage> for TokenAccountsLoader {
+    type Value = Vec<token_account::Model>;
+    type Error = Error;
+
+    async fn load(&self, keys: &[TokenAccountsPage]) -> Result<HashMap<TokenAccountsPage, Self::Value>, Self::Error> {
+        let mut groups: HashMap<(usize, Option<TokenAccountsCursor>), Vec<String>> = HashMap::new();
+        for key in keys {
+            groups
+                .entry((key.size, key.after.clone()))
+                .or_default()
+                .push(key.mint_address.clone());
+        }
+
+        let mut pages = HashMap::new();
+        for ((size, after), mint_addresses) in groups {
+            let mut values = vec![mint_addresses.into(), (size as i64 + 1).into()];
+            let mut cursor = "";
+            if let Some((amount, token_address)) = &after {
+                values.extend([(*amount).into(), token_address.clone().into()]);
+                cursor = "AND (t.amount < $3 OR (t.amount = $3 AND t.token_address > $4))";
+            }
+            let sql = format!(
+                "SELECT to_jsonb(t) AS row FROM unnest($1::text[]) AS m(mint_address)
+                CROSS JOIN LATERAL (
+                    SELECT * FROM token_accounts t WHERE t.mint_address = m.mint_address {cursor}
+                    ORDER BY t.amount DESC, t.token_address LIMIT $2
+                ) t"
+            );
+            let rows = JsonRow::find_by_statement(Statement::from_sql_and_values(DbBackend::Postgres, sql, values))
+                .all(&self.0)
+                .await
+                .map_err(db_error)?;
+            let rows: Vec<token_account::Model> = rows
+                .into_iter()
+                .map(|row| {
+                    serde_json::from_value(row.row)
+                        .map_err(|e| db_error(DbErr::Json(format!("Failed to read token_accounts {}", e))))
+                })
+                .collect::<Result<_, _>>()?;
+
+            for (mint_address, accounts) in grouped(rows, |row| &row.mint_address) {
+                let key = TokenAccountsPage {
+                    mint_address,
+                    size,
+                    after: after.clone(),
+                };
+                pages.insert(key, accounts);
+            }
+        }
+        Ok(pages)
+    }
+}
@@ -0,0 +1,272 @@
+mod loaders;
+mod objects;
+
+use async_graphql::connection::{Connection, Edge};
+use async_graphql::dataloader::DataLoader;
+use async_graphql::http::GraphiQLSource;
+use async_graphql::{
+    BatchRequest, BatchResponse, Context, EmptyMutation, EmptySubscription, Error, Object, Result, Schema,
+};
+use axum::extract::rejection::JsonRejection;
+use axum::response::Html;
+use sea_orm::{
+    ColumnTrait, DatabaseConnection, EntityTrait, JoinType, QueryFilter, QueryOrder, QuerySelect, RelationTrait,
+};
+use shared::{
+    elasticsearch::classify::is_pubkey,
+    entities::{mint, nft_metadata, nft_ownership},
+    post, Json, Router, State,
+};
+
+use crate::error::ApiError;
+use loaders::{
+    db_error, ContentLoader, CreatorsLoader, MetadataLoader, MintLoader, OwnershipLoader, RoyaltyLoader,
+    TokenAccountsCursor, TokenAccountsLoader,
+};
+use objects::{load_one, Mint};
+
+pub const DEFAULT_PAGE_SIZE: usize = 20;
+pub const MAX_PAGE_SIZE: usize = 100;
+// deep enough for mints { edges { node { metadata { collection { metadata { creators { address } } } } } } }
+pub const MAX_DEPTH: usize = 10;
+// every field counts 1 and a connection multiplies its nodes by `first`, so a full page of 100 mints
+// can ask for ~25 fields, a page of 20 for everything
+pub const MAX_COMPLEXITY: usize = 2500;
+// the depth and complexity limits are per query, this keeps a batch from adding up to more than a few of them
+pub const MAX_BATCH: usize = 10;
+
+pub type ApiSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+pub fn build_schema(db: DatabaseConnection) -> ApiSchema {
+    Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+        .data(DataLoader::new(MintLoader(db.clone()), tokio::spawn))
+        .data(DataLoader::new(MetadataLoader(db.clone()), tokio::spawn))
+        .data(DataLoader::new(ContentLoader(db.clone()), tokio::spawn))
+        .data(DataLoader::new(OwnershipLoader(db.clone()), tokio::spawn))
+        .data(DataLoader::new(RoyaltyLoader(db.clone()), tokio::spawn))
+        .data(DataLoader::new(CreatorsLoader(db.clone()), tokio::spawn))
+        .data(DataLoader::new(TokenAccountsLoader(db.clone()), tokio::spawn))
+        .data(db)
+        .limit_depth(MAX_DEPTH)
+        .limit_complexity(MAX_COMPLEXITY)
+        .finish()
+}
+
+// POST /graphql takes a single query or an array of them. GET /graphql, the graphiql explorer, is mounted
+// next to /docs in main since a browser can't send an api key when opening it
+pub fn router(db: DatabaseConnection) -> Router {
+    Router::new()
+        .route("/graphql", post(graphql))
+        .with_state(build_schema(db))
+}
+
+// query errors (bad address, too deep, too complex) come back in `errors` with a 200 like any graphql server,
+// only a body that isn't a graphql request at all, or a batch over MAX_BATCH, gets the usual error body
+pub async fn graphql(
+    State(schema): State<ApiSchema>,
+    request: Result<Json<BatchRequest>, JsonRejection>,
+) -> Result<Json<BatchResponse>, ApiError> {
+    let Json(request) = request?;
+    if let BatchRequest::Batch(requests) = &request {
+        if requests.len() > MAX_BATCH {
+            return Err(ApiError::BadRequest(format!("At most {} queries per batch", MAX_BATCH)));
+        }
+    }
+    Ok(Json(schema.execute_batch(request).await))
+}
+
+pub async fn graphiql() -> Html<String> {
+    Html(GraphiQLSource::build().endpoint("/graphql").finish())
+}
+
+fn page_size(first: Option<i32>) -> usize {
+    first.map_or(DEFAULT_PAGE_SIZE, |first| (first.max(1) as usize).min(MAX_PAGE_SIZE))
+}
+
+fn check_pubkey(address: &str) -> Result<()> {
+    if is_pubkey(address) {
+        Ok(())
+    } else {
+        Err(Error::new(format!("{} is not a valid base58 address", address)))
+    }
+}
+
+fn parse_token_account_cursor(cursor: &str) -> Result<TokenAccountsCursor> {
+    let invalid = || Error::new(format!("{} is not a token account cursor", cursor));
+    let (amount, token_address) = cursor.split_once(':').ok_or_else(invalid)?;
+    if amount.is_empty() || !amount.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    let amount = amount.parse().map_err(|_| invalid())?;
+    check_pubkey(token_address)?;
+    Ok((amount, token_address.to_string()))
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    // null when the mint isn't indexed
+    async fn mint(&self, ctx: &Context<'_>, address: String) -> Result<Option<Mint>> {
+        check_pubkey(&address)?;
+        Ok(load_one::<MintLoader>(ctx, &address).await?.map(Mint))
+    }
+
+    // mints by address, the cursor is the last mint address of the previous page.
+    // `collection` lists the members of a collection, `owner` what a wallet holds
+    #[graphql(complexity = "page_size(first) * child_complexity")]
+    async fn mints(
+        &self,
+        ctx: &Context<'_>,
+        first: Option<i32>,
+        after: Option<String>,
+        collection: Option<String>,
+        owner: Option<String>,
+    ) -> Result<Connection<String, Mint>> {
+        for address in [&after, &collection, &owner].into_iter().flatten() {
+            check_pubkey(address)?;
+        }
+        let size = page_size(first);
+
+        let mut select = mint::Entity::find()
+            .order_by_asc(mint::Column::MintAddress)
+            .limit(size as u64 + 1);
+        if let Some(after) = &after {
+            select = select.filter(mint::Column::MintAddress.gt(after));
+        }
+        if let Some(collection) = &collection {
+            select = select
+                .join(JoinType::InnerJoin, mint::Relation::NftMetadata.def())
+                .filter(nft_metadata::Column::CollectionMint.eq(collection));
+        }
+        if let Some(owner) = &owner {
+            select = select
+                .join(JoinType::InnerJoin, mint::Relation::NftOwnership.def())
+                .filter(nft_ownership::Column::Owner.eq(owner));
+        }
+
+        let db = ctx.data_unchecked::<DatabaseConnection>();
+        let mut rows = select.all(db).await.map_err(db_error)?;
+        let has_next_page = rows.len() > size;
+        rows.truncate(size);
+
+        let mut connection = Connection::new(after.is_some(), has_next_page);
+        connection.edges.extend(
+            rows.into_iter()
+                .map(|row| Edge::new(row.mint_address.clone(), Mint(row))),
+        );
+        Ok(connection)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sea_orm::prelude::Decimal;
+
+    use super::*;
+
+    // the limits and address checks run before any query is sent, a disconnected connection is enough
+    async fn errors(query: &str) -> Vec<String> {
+        let schema = build_schema(DatabaseConnection::Disconnected);
+        schema
+            .execute(query)
+            .await
+            .errors
+            .into_iter()
+            .map(|e| e.message)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn rejects_queries_nested_too_deep() {
+        // mint, then a metadata/collection pair per level, then metadata and name: depth 3 + 2 * levels
+        let nested = |levels: usize| {
+            format!(
+                "{{ mint(address: \"So11111111111111111111111111111111111111112\") {{ {} name {} }} }}",
+                "metadata { collection { ".repeat(levels) + "metadata {",
+                "} } ".repeat(levels) + "}",
+            )
+        };
+        assert_eq!(errors(&nested(4)).await, vec!["Query is nested too deep.".to_string()]);
+        assert!(!errors(&nested(3))
+            .await
+            .contains(&"Query is nested too deep.".to_string()));
+    }
+
+    #[tokio::test]
+    async fn rejects_queries_too_complex() {
+        let fields = "mintAddress supply decimal mintAuthority freezeAuthority isInitialized createdAt
+            metadata { name symbol metadataUri sellerFeeBasisPoints updateAuthority status collectionMint
+                creators { address verified share } }
+            royalty { basisPoints percent royaltyModel target locked }
+            ownership { owner delegate frozen delegated }
+            content { image description attributes properties }";
+        assert_eq!(
+            errors(&format!(
+                "{{ mints(first: 100) {{ edges {{ node {{ {} }} }} }} }}",
+                fields
+            ))
+            .await,
+            vec!["Query is too complex.".to_string()]
+        );
+        assert!(errors(&format!(
+            "{{ mints(first: 2, after: \"bad\") {{ edges {{ node {{ {} }} }} }} }}",
+            fields
+        ))
+        .await
+        .contains(&"bad is not a valid base58 address".to_string()));
+    }
+
+    #[tokio::test]
+    async fn rejects_large_batches() {
+        let batch = |size: usize| {
+            let queries = (0..size).map(|_| async_graphql::Request::new("{ mint(address: \"bad\") { mintAddress } }"));
+            Ok(Json(BatchRequest::Batch(queries.collect())))
+        };
+        let schema = build_schema(DatabaseConnection::Disconnected);
+        assert!(matches!(
+            graphql(State(schema.clone()), batch(MAX_BATCH + 1)).await,
+            Err(ApiError::BadRequest(_))
+        ));
+        let Json(response) = graphql(State(schema), batch(MAX_BATCH)).await.unwrap();
+        assert!(matches!(response, BatchResponse::Batch(responses) if responses.len() == MAX_BATCH));
+    }
+
+    #[tokio::test]
+    async fn token_accounts_are_paged() {
+        // complexity scales with `first`, the invalid cursor stops the query before it reaches the database
+        let query = |first: usize| {
+            format!(
+                "{{ mints(first: 20, after: \"bad\") {{ edges {{ node {{ tokenAccounts(first: {}) {{
+                    edges {{ node {{ owner amount }} }} }} }} }} }} }}",
+                first
+            )
+        };
+        assert_eq!(errors(&query(100)).await, vec!["Query is too complex.".to_string()]);
+        assert_eq!(errors(&query(5)).await, vec!["bad is not a valid base58 address".to_string()]);
+    }
+
+    #[test]
+    fn parses_token_account_cursors() {
+        let address = "So11111111111111111111111111111111111111112";
+        assert_eq!(
+            parse_token_account_cursor(&format!("12:{}", address)).unwrap(),
+            (Decimal::from(12), address.to_string())
+        );
+        assert_eq!(
+            parse_token_account_cursor(&format!("18446744073709551615:{}", address)).unwrap().0,
+            Decimal::from(u64::MAX)
+        );
+        assert!(parse_token_account_cursor(&format!("1.5:{}", address)).is_err());
+        assert!(parse_token_account_cursor(&format!("-1:{}", address)).is_err());
+        assert!(parse_token_account_cursor("12:bad").is_err());
+        assert!(parse_token_account_cursor(address).is_err());
+    }
+
+    #[test]
+    fn clamps_page_size() {
+        assert_eq!(page_size(None), DEFAULT_PAGE_SIZE);
+        assert_eq!(page_size(Some(0)), 1);
+        assert_eq!(page_size(Some(5000)), MAX_PAGE_SIZE);
+    }
+}
@@ -0,0 +1,338 @@
+use async_graphql::connection::{Connection, Edge};
+use async_graphql::dataloader::{DataLoader, Loader};
+use async_graphql::{Context, Error, Json, Object, Result};
+use sea_orm::prelude::DateTimeWithTimeZone;
+use serde_json::Value;
+use shared::entities::{mint, nft_creator, nft_json_metadata, nft_metadata, nft_ownership, nft_royalty, token_account};
+
+use super::loaders::{
+    ContentLoader, CreatorsLoader, MetadataLoader, MintLoader, OwnershipLoader, RoyaltyLoader, TokenAccountsLoader,
+    TokenAccountsPage,
+};
+use super::{page_size, parse_token_account_cursor};
+
+// the graphql types wrap the entity models as they are, field for field, minus ids and the es sync bookkeeping.
+// anything in another table is resolved through the loaders
+
+pub async fn load_one<L>(ctx: &Context<'_>, key: &str) -> Result<Option<L::Value>>
+where
+    L: Loader<String, Error = Error>,
+{
+    ctx.data_unchecked::<DataLoader<L>>().load_one(key.to_string()).await
+}
+
+pub struct Mint(pub mint::Model);
+
+#[Object]
+impl Mint {
+    async fn mint_address(&self) -> &str {
+        &self.0.mint_address
+    }
+
+    async fn decimal(&self) -> i16 {
+        self.0.decimal
+    }
+
+    async fn supply(&self) -> i64 {
+        self.0.supply
+    }
+
+    async fn mint_authority(&self) -> Option<&str> {
+        self.0.mint_authority.as_deref()
+    }
+
+    async fn freeze_authority(&self) -> Option<&str> {
+        self.0.freeze_authority.as_deref()
+    }
+
+    async fn is_initialized(&self) -> bool {
+        self.0.is_initialized
+    }
+
+    async fn created_at(&self) -> DateTimeWithTimeZone {
+        self.0.created_at
+    }
+
+    async fn metadata(&self, ctx: &Context<'_>) -> Result<Option<NftMetadata>> {
+        Ok(load_one::<MetadataLoader>(ctx, &self.0.mint_address)
+            .await?
+            .map(NftMetadata))
+    }
+
+    // the off chain json the metadata uri points at
+    async fn content(&self, ctx: &Context<'_>) -> Result<Option<Content>> {
+        Ok(load_one::<ContentLoader>(ctx, &self.0.mint_address).await?.map(Content))
+    }
+
+    async fn ownership(&self, ctx: &Context<'_>) -> Result<Option<Ownership>> {
+        Ok(load_one::<OwnershipLoader>(ctx, &self.0.mint_address)
+            .await?
+            .map(Ownership))
+    }
+
+    async fn royalty(&self, ctx: &Context<'_>) -> Result<Option<Royalty>> {
+        Ok(load_one::<RoyaltyLoader>(ctx, &self.0.mint_address).await?.map(Royalty))
+    }
+
+    // largest balance first, the cursor is `{amount}:{token address}` of the last account of the previous page
+    #[graphql(complexity = "page_size(first) * child_complexity")]
+    async fn token_accounts(
+        &self,
+        ctx: &Context<'_>,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> Result<Connection<String, TokenAccount>> {
+        let size = page_size(first);
+        let page = TokenAccountsPage {
+            mint_address: self.0.mint_address.clone(),
+            size,
+            after: after.as_deref().map(parse_token_account_cursor).transpose()?,
+        };
+        let mut accounts = ctx
+            .data_unchecked::<DataLoader<TokenAccountsLoader>>()
+            .load_one(page)
+            .await?
+            .unwrap_or_default();
+        let has_next_page = accounts.len() > size;
+        accounts.truncate(size);
+
+        let mut connection = Connection::new(after.is_some(), has_next_page);
+        connection.edges.extend(accounts.into_iter().map(|account| {
+            Edge::new(
+                format!("{}:{}", account.amount, account.token_address),
+                TokenAccount(account),
+            )
+        }));
+        Ok(connection)
+    }
+}
+
+pub struct NftMetadata(pub nft_metadata::Model);
+
+#[Object]
+impl NftMetadata {
+    async fn metadata_address(&self) -> Option<&str> {
+        self.0.metadata_address.as_deref()
+    }
+
+    async fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    async fn symbol(&self) -> Option<&str> {
+        self.0.symbol.as_deref()
+    }
+
+    async fn metadata_uri(&self) -> &str {
+        &self.0.metadata_uri
+    }
+
+    async fn seller_fee_basis_points(&self) -> i16 {
+        self.0.seller_fee_basis_points
+    }
+
+    async fn update_authority(&self) -> &str {
+        &self.0.update_authority
+    }
+
+    async fn primary_sale_happened(&self) -> bool {
+        self.0.primary_sale_happened
+    }
+
+    async fn is_mutable(&self) -> bool {
+        self.0.is_mutable
+    }
+
+    async fn token_standard(&self) -> Option<&str> {
+        self.0.token_standard.as_deref()
+    }
+
+    // active, burned or closed
+    async fn status(&self) -> &str {
+        &self.0.status
+    }
+
+    async fn collection_mint(&self) -> Option<&str> {
+        self.0.collection_mint.as_deref()
+    }
+
+    async fn collection_name(&self) -> Option<&str> {
+        self.0.collection_name.as_deref()
+    }
+
+    async fn collection_verified(&self) -> bool {
+        self.0.collection_verified
+    }
+
+    // the collection nft itself, null when it isn't indexed
+    async fn collection(&self, ctx: &Context<'_>) -> Result<Option<Mint>> {
+        let Some(collection_mint) = &self.0.collection_mint else {
+            return Ok(None);
+        };
+        Ok(load_one::<MintLoader>(ctx, collection_mint).await?.map(Mint))
+    }
+
+    async fn creators(&self, ctx: &Context<'_>) -> Result<Vec<Creator>> {
+        let Some(metadata_address) = &self.0.metadata_address else {
+            return Ok(Vec::new());
+        };
+        let creators = load_one::<CreatorsLoader>(ctx, metadata_address).await?;
+        Ok(creators.unwrap_or_default().into_iter().map(Creator).collect())
+    }
+
+    async fn created_at(&self) -> DateTimeWithTimeZone {
+        self.0.created_at
+    }
+}
+
+pub struct Creator(pub nft_creator::Model);
+
+#[Object]
+impl Creator {
+    async fn address(&self) -> &str {
+        &self.0.creator_address
+    }
+
+    async fn verified(&self) -> bool {
+        self.0.verified
+    }
+
+    async fn share(&self) -> i16 {
+        self.0.share
+    }
+}
+
+pub struct Content(pub nft_json_metadata::Model);
+
+#[Object]
+impl Content {
+    async fn description(&self) -> Option<&str> {
+        self.0.description.as_deref()
+    }
+
+    async fn image(&self) -> Option<&str> {
+        self.0.image.as_deref()
+    }
+
+    async fn animation_url(&self) -> Option<&str> {
+        self.0.animation_url.as_deref()
+    }
+
+    async fn external_url(&self) -> Option<&str> {
+        self.0.external_url.as_deref()
+    }
+
+    // passed through as stored, usually [{"trait_type": ..., "value": ...}]
+    async fn attributes(&self) -> Option<Json<Value>> {
+        self.0.attributes.clone().map(Json)
+    }
+
+    async fn properties(&self) -> Option<Json<Value>> {
+        self.0.properties.clone().map(Json)
+    }
+
+    async fn collection_name(&self) -> Option<&str> {
+        self.0.collection_name.as_deref()
+    }
+
+    async fn collection_family(&self) -> Option<&str> {
+        self.0.collection_family.as_deref()
+    }
+}
+
+pub struct Ownership(pub nft_ownership::Model);
+
+#[Object]
+impl Ownership {
+    async fn owner(&self) -> &str {
+        &self.0.owner
+    }
+
+    async fn delegate(&self) -> Option<&str> {
+        self.0.delegate.as_deref()
+    }
+
+    async fn frozen(&self) -> bool {
+        self.0.frozen
+    }
+
+    async fn delegated(&self) -> bool {
+        self.0.delegated
+    }
+
+    async fn ownership_model(&self) -> &str {
+        &self.0.ownership_model
+    }
+
+    async fn updated_at(&self) -> DateTimeWithTimeZone {
+        self.0.updated_at
+    }
+}
+
+pub struct Royalty(pub nft_royalty::Model);
+
+#[Object]
+impl Royalty {
+    async fn royalty_model(&self) -> &str {
+        &self.0.royalty_model
+    }
+
+    async fn target(&self) -> Option<&str> {
+        self.0.target.as_deref()
+    }
+
+    async fn basis_points(&self) -> u16 {
+        self.0.basis_points
+    }
+
+    async fn percent(&self) -> f64 {
+        self.0.percent
+    }
+
+    async fn primary_sale_happened(&self) -> bool {
+        self.0.primary_sale_happened
+    }
+
+    async fn locked(&self) -> bool {
+        self.0.locked
+    }
+}
+
+pub struct TokenAccount(pub token_account::Model);
+
+#[Object]
+impl TokenAccount {
+    async fn token_address(&self) -> &str {
+        &self.0.token_address
+    }
+
+    async fn owner(&self) -> &str {
+        &self.0.owner
+    }
+
+    // raw base units, a string since a u64 doesn't fit a graphql Int
+    async fn amount(&self) -> String {
+        self.0.amount.to_string()
+    }
+
+    async fn delegate(&self) -> Option<&str> {
+        self.0.delegate.as_deref()
+    }
+
+    async fn delegated_amount(&self) -> &str {
+        &self.0.delegated_amount
+    }
+
+    // 0 uninitialized, 1 initialized, 2 frozen
+    async fn state(&self) -> i16 {
+        self.0.state
+    }
+
+    async fn close_authority(&self) -> Option<&str> {
+        self.0.close_authority.as_deref()
+    }
+
+    async fn is_native(&self) -> bool {
+        self.0.is_native
+    }
+}
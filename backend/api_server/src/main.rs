@@ -1,189 +1,414 @@
+mod access;
+mod cache;
+mod das;
+mod error;
+mod events;
+mod graphql;
+mod openapi;
+mod webhooks;
+
+use std::collections::BTreeMap;
+use std::future::ready;
+use std::sync::Arc;
+
+use access::ApiAccess;
+use axum::extract::rejection::{JsonRejection, QueryRejection};
+use axum::http::HeaderMap;
+use axum::response::Response;
+use axum::{middleware, Extension};
+use error::{ApiError, ErrorBody};
 use shared::{
     dotenv, env,
-    elasticsearch::client::ElasticSearchClient,
-    entities::{mint, nft_metadata},
+    assets::{
+        collections::{find_collection, find_collection_nfts, list_collections},
+        details::{find_asset_details, find_details_batch},
+        owners::{find_holdings, HoldingKind},
+    },
+    elasticsearch::classify::is_pubkey,
+    health::{check, health_router, ping_postgres, ping_redis, HealthReport},
+    redis::{
+        cache::{CacheKey, ResponseCache, ResponseCacheConfig},
+        events::relay_changes,
+        queue_manager::redis_url,
+        rate_limit::RateLimiter,
+    },
+    search::{search_index_from_env, SearchIndex},
+    shutdown::Shutdown,
     types::{
-        elasticsearch::SearchResponse,
-        mint::{MintResponse, PartialMetadata},
+        asset::{
+            AssetDetails, AssetInclude, DetailsBatchRequest, DetailsEntry, DetailsQuery, HoldingsQuery,
+            HoldingsResponse, MAX_DETAILS_BATCH,
+        },
+        collection::{
+            CollectionNftsQuery, CollectionNftsResponse, CollectionSummary, CollectionsQuery, CollectionsResponse,
+        },
+        elasticsearch::{PageRequest, SearchRequest, SearchResponse, SuggestRequest, SuggestResponse},
+        event::ChangeEvent,
     },
-    ColumnTrait, Database, DatabaseConnection, EntityTrait, QueryFilter,
-    Json, Path, Router, State, StatusCode,
-    get, SPL_TOKEN_PROGRAM,
+    Database, DatabaseConnection,
+    Json, Path, Query, Router, State,
+    get,
 };
-use tower_http::cors::{CorsLayer};
+use tokio::sync::broadcast;
+use utoipa_axum::{router::OpenApiRouter, routes};
+use utoipa_scalar::{Scalar, Servable};
+
+const DEFAULT_BIND_ADDR: &str = "localhost:3001";
+
+type AppState = (DatabaseConnection, Arc<dyn SearchIndex>, broadcast::Sender<Arc<ChangeEvent>>);
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     dotenv().ok();
+    let shutdown = Shutdown::from_env();
+    let redis_url = redis_url();
 
-    let elasticsearch = ElasticSearchClient::new(
-        env::var("ELASTICSEARCH_URL").expect("failed to get es_url from env"),
-        env::var("ELASTICSEARCH_INDEX_NAME").expect("failed to get index name from env"),
-    )
-    .await
-    .expect("Error creating a elasticsearch client");
-    
     let db = Database::connect(env::var("DATABASE_URL").expect("DATABASE_URL must be set")).await?;
+    let search_index = search_index_from_env(&db)
+        .await
+        .expect("Error creating the search backend");
 
+    // changes the worker publishes, fanned out to the /events subscribers
+    let (changes, _) = broadcast::channel(events::CHANGES_BUFFER);
+    tokio::spawn(relay_changes(redis_url.clone(), changes.clone()));
+
+    let limiter = Arc::new(RateLimiter::new(&redis_url)?);
+    let cache = Arc::new(ResponseCache::new(&redis_url, ResponseCacheConfig::from_env())?);
+    let access = ApiAccess::from_env(db.clone(), limiter.clone());
+
+    // redis only costs the cache, the rate limits and /events when it's down, so it doesn't fail readiness
+    let (ready_db, ready_search, ready_redis) = (db.clone(), search_index.clone(), redis_url.clone());
+    let readiness = move || {
+        let (db, search_index, redis_url) = (ready_db.clone(), ready_search.clone(), ready_redis.clone());
+        async move {
+            let (postgres, search, redis) = tokio::join!(
+                check(true, ping_postgres(&db)),
+                check(true, search_index.ping()),
+                check(false, ping_redis(&redis_url)),
+            );
+            HealthReport::new([("postgres", postgres), ("search", search), ("redis", redis)])
+        }
+    };
+
+    // every documented route and /graphql need an api key, /openapi.json, the explorers built from it and the
+    // health endpoints don't
+    let (api, spec) = api_router().split_for_parts();
+    let api = api
+        .with_state((db.clone(), search_index, changes))
+        .merge(graphql::router(db))
+        .layer(Extension(limiter))
+        .layer(Extension(cache))
+        .layer(Extension(shutdown.clone()))
+        .layer(middleware::from_fn_with_state(access, access::require_api_key));
     let app = Router::new()
-        .route("/details/{mint_address}", get(get_details))
-        .route("/search/nfts/{query}", get(search_nfts))
-        .with_state((db, elasticsearch))
-        .layer(CorsLayer::very_permissive());
+        .merge(Scalar::with_url("/docs", spec.clone()))
+        .route("/openapi.json", get(move || ready(Json(spec.clone()))))
+        .route("/graphql", get(graphql::graphiql))
+        .merge(health_router(readiness, shutdown.clone()))
+        .merge(api)
+        .layer(access::cors_from_env());
 
-    let listener = tokio::net::TcpListener::bind("localhost:3001")
+    let addr = env::var("API_BIND_ADDR").unwrap_or_else(|_| DEFAULT_BIND_ADDR.to_string());
+    let listener = tokio::net::TcpListener::bind(&addr)
         .await
         .unwrap();
-    println!("The Server is running at port 3001");
-    
-    if let Err(e) = axum::serve(listener, app).await {
+    println!("The Server is running at {}", addr);
+
+    // stops accepting on shutdown and returns once the requests in flight are answered
+    let serving = axum::serve(listener, app).with_graceful_shutdown(async move { shutdown.triggered().await });
+    if let Err(e) = serving.await {
         eprintln!("Server error: {}", e);
         return Err(e.into());
     }
+    println!("Server stopped");
 
     Ok(())
 }
 
+// the routes and their spec come from the same #[utoipa::path] annotations, a route can't be served undocumented
+fn api_router() -> OpenApiRouter<AppState> {
+    OpenApiRouter::with_openapi(openapi::ApiDoc::base())
+        .routes(routes!(details_batch))
+        .routes(routes!(get_details))
+        .routes(routes!(get_collections))
+        .routes(routes!(get_collection))
+        .routes(routes!(get_collection_nfts))
+        .routes(routes!(get_owner_nfts))
+        .routes(routes!(get_owner_tokens))
+        .routes(routes!(search_nfts))
+        .routes(routes!(search))
+        .routes(routes!(suggest))
+        .routes(routes!(das::das_rpc))
+        .routes(routes!(events::sse_changes))
+        .routes(routes!(events::ws_changes))
+        .routes(routes!(webhooks::get_webhooks, webhooks::post_webhook))
+        .routes(routes!(webhooks::get_webhook, webhooks::patch_webhook, webhooks::remove_webhook))
+        .routes(routes!(webhooks::get_deliveries))
+        .routes(routes!(access::get_usage))
+}
+
+#[utoipa::path(
+    get,
+    path = "/details/{mint_address}",
+    tag = "assets",
+    params(("mint_address" = String, Path, description = "base58 mint address"), DetailsQuery),
+    responses(
+        (status = 200, body = AssetDetails, headers(("ETag" = String), ("Last-Modified" = String))),
+        (status = 304, description = "unchanged since the request's If-None-Match or If-Modified-Since"),
+        (status = 400, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
+// the whole asset in one query, `?include=creators,royalty,ownership,content,collection` narrows it down.
+// served from the response cache until the worker writes to the asset
 pub async fn get_details(
-    State((db, _)): State<(DatabaseConnection, ElasticSearchClient)>,
+    State((db, _, _)): State<AppState>,
+    Extension(response_cache): Extension<Arc<ResponseCache>>,
+    headers: HeaderMap,
     Path(mint_address): Path<String>,
-) -> Json<MintResponse> {
-    let mint_details = match mint::Entity::find()
-    .filter(mint::Column::MintAddress.eq(mint_address.clone()))
-    .one(&db)
-    .await {
-        Ok(Some(mint_data)) => mint_data,
-        Ok(None) => {
-            println!("Mint data not found for address: {}", mint_address);
-            return Json(MintResponse {
-                mint_address: String::new(),
-                owner: SPL_TOKEN_PROGRAM.to_string(),
-                mint_authority: String::new(),
-                supply: 0,
-                decimal: 0,
-                is_initialized: false,
-                freeze_authority: None,
-                metadata: PartialMetadata {
-                    name: None,
-                    symbol: None,
-                    metadata_uri: None,
-                    seller_fee_basis_points: 0,
-                    update_authority: None,
-                    is_mutable: false,
-                    primary_sale_happened: false,
-                },
-            });
-        }
-        Err(db_err) => {
-            println!("Database error occurred: {}", db_err);
-            return Json(MintResponse {
-                mint_address: String::new(),
-                owner: SPL_TOKEN_PROGRAM.to_string(),
-                mint_authority: String::new(),
-                supply: 0,
-                decimal: 0,
-                is_initialized: false,
-                freeze_authority: None,
-                metadata: PartialMetadata {
-                    name: None,
-                    symbol: None,
-                    metadata_uri: None,
-                    seller_fee_basis_points: 0,
-                    update_authority: None,
-                    is_mutable: false,
-                    primary_sale_happened: false,
-                },
-            });
-        }
-    };
+    query: Result<Query<DetailsQuery>, QueryRejection>,
+) -> Result<Response, ApiError> {
+    let Query(query) = query?;
+    if !is_pubkey(&mint_address) {
+        return Err(ApiError::BadRequest(format!("{} is not a valid base58 address", mint_address)));
+    }
+    let include = AssetInclude::parse(query.include.as_deref()).map_err(ApiError::BadRequest)?;
 
-    let mint_metadata = match nft_metadata::Entity::find()
-        .filter(nft_metadata::Column::MintAddress.eq(mint_address.clone()))
-        .one(&db)
-        .await
-    {
-        Ok(metadata_opt) => metadata_opt,
-        Err(db_err) => {
-            println!(
-                "Database error occurred while finding metadata {} for the mint address: {}",
-                db_err, mint_address
-            );
-            return Json(MintResponse {
-                mint_address: mint_details.mint_address,
-                owner: SPL_TOKEN_PROGRAM.to_string(),
-                mint_authority: mint_details.mint_authority.unwrap_or_default(),
-                supply: mint_details.supply,
-                decimal: mint_details.decimal,
-                is_initialized: mint_details.is_initialized,
-                freeze_authority: mint_details.freeze_authority,
-                metadata: PartialMetadata {
-                    name: None,
-                    symbol: None,
-                    metadata_uri: None,
-                    seller_fee_basis_points: 0,
-                    update_authority: None,
-                    is_mutable: false,
-                    primary_sale_happened: false,
-                },
-            });
-        }
+    let key = CacheKey::Details {
+        mint_address: mint_address.clone(),
+        variant: include.canonical(),
     };
+    let entry = cache::read_through(&response_cache, key, async {
+        find_asset_details(&db, &mint_address, &include)
+            .await?
+            .ok_or_else(|| ApiError::NotFound(format!("No mint found for {}", mint_address)))
+    })
+    .await?;
+    Ok(cache::respond(entry, Some(&headers)))
+}
 
-    match mint_metadata {
-        Some(metadata) => {
-            println!("Found metadata for the mint");
-            Json(MintResponse {
-                mint_address: mint_details.mint_address,
-                owner: SPL_TOKEN_PROGRAM.to_string(),
-                mint_authority: mint_details.mint_authority.unwrap_or_default(),
-                supply: mint_details.supply,
-                decimal: mint_details.decimal,
-                is_initialized: mint_details.is_initialized,
-                freeze_authority: mint_details.freeze_authority,
-                metadata: PartialMetadata {
-                    name: Some(metadata.name),
-                    symbol: metadata.symbol,
-                    metadata_uri: Some(metadata.metadata_uri),
-                    seller_fee_basis_points: metadata.seller_fee_basis_points,
-                    update_authority: Some(metadata.update_authority),
-                    is_mutable: metadata.is_mutable,
-                    primary_sale_happened: metadata.primary_sale_happened,
-                },
-            })
-        }
-        None => {
-            println!("No metadata found for the mint");
-            Json(MintResponse {
-                mint_address: mint_details.mint_address,
-                owner: SPL_TOKEN_PROGRAM.to_string(),
-                mint_authority: mint_details.mint_authority.unwrap_or_default(),
-                supply: mint_details.supply,
-                decimal: mint_details.decimal,
-                is_initialized: mint_details.is_initialized,
-                freeze_authority: mint_details.freeze_authority,
-                metadata: PartialMetadata {
-                    name: None,
-                    symbol: None,
-                    metadata_uri: None,
-                    seller_fee_basis_points: 0,
-                    update_authority: None,
-                    is_mutable: false,
-                    primary_sale_happened: false,
-                },
-            })
-        }
+#[utoipa::path(
+    post,
+    path = "/details",
+    tag = "assets",
+    request_body = DetailsBatchRequest,
+    responses(
+        (status = 200, description = "one entry per requested mint, keyed by mint address",
+            body = BTreeMap<String, DetailsEntry>),
+        (status = 400, body = ErrorBody),
+    )
+)]
+// many mints in one request, keyed by mint address. unknown mints get a not found entry, the request as a whole
+// only fails when it's malformed
+pub async fn details_batch(
+    State((db, _, _)): State<AppState>,
+    request: Result<Json<DetailsBatchRequest>, JsonRejection>,
+) -> Result<Json<BTreeMap<String, DetailsEntry>>, ApiError> {
+    let Json(mut request) = request?;
+    request.mint_addresses.sort();
+    request.mint_addresses.dedup();
+    if request.mint_addresses.len() > MAX_DETAILS_BATCH {
+        return Err(ApiError::BadRequest(format!("At most {} mint addresses per request", MAX_DETAILS_BATCH)));
+    }
+    if let Some(invalid) = request.mint_addresses.iter().find(|mint_address| !is_pubkey(mint_address)) {
+        return Err(ApiError::BadRequest(format!("{} is not a valid base58 address", invalid)));
     }
+    let include = AssetInclude::parse(request.include.as_deref()).map_err(ApiError::BadRequest)?;
+
+    Ok(Json(find_details_batch(&db, &request.mint_addresses, &include).await?))
 }
 
-pub async fn search_nfts(
-    State((_, elasticsearch)): State<(DatabaseConnection, ElasticSearchClient)>,
-    query: Path<String>,
-) -> Result<Json<SearchResponse>, StatusCode> {
-    match elasticsearch.search_nft(&query, 20).await {
-        Ok(search_response) => Ok(Json(search_response)),
-        Err(e) => {
-            println!("Search error: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+#[utoipa::path(
+    get,
+    path = "/collections",
+    tag = "collections",
+    params(CollectionsQuery),
+    responses((status = 200, body = CollectionsResponse), (status = 400, body = ErrorBody))
+)]
+// `?page=&size=&sort=members|holders|name|newest`
+pub async fn get_collections(
+    State((db, _, _)): State<AppState>,
+    query: Result<Query<CollectionsQuery>, QueryRejection>,
+) -> Result<Json<CollectionsResponse>, ApiError> {
+    let Query(query) = query?;
+    Ok(Json(list_collections(&db, &query).await?))
+}
+
+#[utoipa::path(
+    get,
+    path = "/collections/{collection_mint}",
+    tag = "collections",
+    params(("collection_mint" = String, Path, description = "base58 collection mint address")),
+    responses(
+        (status = 200, body = CollectionSummary),
+        (status = 400, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
+pub async fn get_collection(
+    State((db, _, _)): State<AppState>,
+    Path(collection_mint): Path<String>,
+) -> Result<Json<CollectionSummary>, ApiError> {
+    if !is_pubkey(&collection_mint) {
+        return Err(ApiError::BadRequest(format!("{} is not a valid base58 address", collection_mint)));
+    }
+
+    let collection = find_collection(&db, &collection_mint)
+        .await?
+        .ok_or_else(|| ApiError::NotFound(format!("No collection found for {}", collection_mint)))?;
+    Ok(Json(collection))
+}
+
+#[utoipa::path(
+    get,
+    path = "/collections/{collection_mint}/nfts",
+    tag = "collections",
+    params(("collection_mint" = String, Path, description = "base58 collection mint address"), CollectionNftsQuery),
+    responses((status = 200, body = CollectionNftsResponse), (status = 400, body = ErrorBody))
+)]
+// `?page=&size=&sort=name|newest&verified=&include=`, verified members unless verified=false, include works like
+// it does for /details
+pub async fn get_collection_nfts(
+    State((db, _, _)): State<AppState>,
+    Path(collection_mint): Path<String>,
+    query: Result<Query<CollectionNftsQuery>, QueryRejection>,
+) -> Result<Json<CollectionNftsResponse>, ApiError> {
+    let Query(query) = query?;
+    if !is_pubkey(&collection_mint) {
+        return Err(ApiError::BadRequest(format!("{} is not a valid base58 address", collection_mint)));
+    }
+    let include = AssetInclude::parse(query.include.as_deref()).map_err(ApiError::BadRequest)?;
+
+    Ok(Json(find_collection_nfts(&db, &collection_mint, &query, &include).await?))
+}
+
+#[utoipa::path(
+    get,
+    path = "/owners/{wallet}/nfts",
+    tag = "owners",
+    params(("wallet" = String, Path, description = "base58 wallet address"), HoldingsQuery),
+    responses((status = 200, body = HoldingsResponse), (status = 400, body = ErrorBody))
+)]
+// what a wallet holds, paged with `?size=&cursor=` and narrowed to one collection with `?collection=`
+pub async fn get_owner_nfts(
+    State((db, _, _)): State<AppState>,
+    Path(wallet): Path<String>,
+    query: Result<Query<HoldingsQuery>, QueryRejection>,
+) -> Result<Json<HoldingsResponse>, ApiError> {
+    owner_holdings(&db, wallet, HoldingKind::Nfts, query).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/owners/{wallet}/tokens",
+    tag = "owners",
+    params(("wallet" = String, Path, description = "base58 wallet address"), HoldingsQuery),
+    responses(
+        (status = 200, description = "indexed mints that aren't nfts: editions and tokens with metadata and at most \
+            2 decimals. fungible tokens like USDC aren't indexed and don't show up", body = HoldingsResponse),
+        (status = 400, body = ErrorBody),
+    )
+)]
+// the same as /nfts for the semi fungible mints the worker indexes, not a wallet's full token balance
+pub async fn get_owner_tokens(
+    State((db, _, _)): State<AppState>,
+    Path(wallet): Path<String>,
+    query: Result<Query<HoldingsQuery>, QueryRejection>,
+) -> Result<Json<HoldingsResponse>, ApiError> {
+    owner_holdings(&db, wallet, HoldingKind::Tokens, query).await
+}
+
+async fn owner_holdings(
+    db: &DatabaseConnection,
+    wallet: String,
+    kind: HoldingKind,
+    query: Result<Query<HoldingsQuery>, QueryRejection>,
+) -> Result<Json<HoldingsResponse>, ApiError> {
+    let Query(query) = query?;
+    for address in [Some(&wallet), query.collection.as_ref(), query.cursor.as_ref()].into_iter().flatten() {
+        if !is_pubkey(address) {
+            return Err(ApiError::BadRequest(format!("{} is not a valid base58 address", address)));
         }
     }
-}
\ No newline at end of file
+
+    Ok(Json(find_holdings(db, &wallet, kind, &query).await?))
+}
+
+#[utoipa::path(
+    get,
+    path = "/search/nfts/{query}",
+    tag = "search",
+    params(("query" = String, Path, description = "free text, an address or a symbol"), PageRequest),
+    responses(
+        (status = 200, body = SearchResponse, headers(("ETag" = String), ("Last-Modified" = String))),
+        (status = 304, description = "unchanged since the request's If-None-Match or If-Modified-Since"),
+        (status = 400, body = ErrorBody),
+    )
+)]
+// the search routes are cached for a short while, and until changes reach the search index
+pub async fn search_nfts(
+    State((_, search_index, _)): State<AppState>,
+    Extension(response_cache): Extension<Arc<ResponseCache>>,
+    headers: HeaderMap,
+    Path(query): Path<String>,
+    page: Result<Query<PageRequest>, QueryRejection>,
+) -> Result<Response, ApiError> {
+    let Query(page) = page?;
+    let key = cache::search_key("search_nfts", &(&query, &page));
+    let entry = cache::read_through(&response_cache, key, async {
+        Ok(search_index.search(&query, &page).await?)
+    })
+    .await?;
+    Ok(cache::respond(entry, Some(&headers)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/search",
+    tag = "search",
+    request_body = SearchRequest,
+    responses(
+        (status = 200, body = SearchResponse, headers(("ETag" = String), ("Last-Modified" = String))),
+        (status = 400, body = ErrorBody),
+    )
+)]
+// a POST, so it never answers 304, the ETag is still there for clients that compare them
+pub async fn search(
+    State((_, search_index, _)): State<AppState>,
+    Extension(response_cache): Extension<Arc<ResponseCache>>,
+    request: Result<Json<SearchRequest>, JsonRejection>,
+) -> Result<Response, ApiError> {
+    let Json(request) = request?;
+    let key = cache::search_key("search", &request);
+    let entry = cache::read_through(&response_cache, key, async {
+        Ok(search_index.search_filtered(&request).await?)
+    })
+    .await?;
+    Ok(cache::respond(entry, None))
+}
+
+#[utoipa::path(
+    get,
+    path = "/suggest",
+    tag = "search",
+    params(SuggestRequest),
+    responses(
+        (status = 200, body = SuggestResponse, headers(("ETag" = String), ("Last-Modified" = String))),
+        (status = 304, description = "unchanged since the request's If-None-Match or If-Modified-Since"),
+        (status = 400, body = ErrorBody),
+    )
+)]
+pub async fn suggest(
+    State((_, search_index, _)): State<AppState>,
+    Extension(response_cache): Extension<Arc<ResponseCache>>,
+    headers: HeaderMap,
+    request: Result<Query<SuggestRequest>, QueryRejection>,
+) -> Result<Response, ApiError> {
+    let Query(request) = request?;
+    let key = cache::search_key("suggest", &request);
+    let entry = cache::read_through(&response_cache, key, async {
+        Ok(search_index.suggest(&request.q, request.size).await?)
+    })
+    .await?;
+    Ok(cache::respond(entry, Some(&headers)))
+}
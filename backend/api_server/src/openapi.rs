@@ -0,0 +1,166 @@
+use shared::types::webhook::WebhookPayload;
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, SecurityRequirement, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+
+use crate::error::ErrorBody;
+
+// paths are added by api_router in main.rs, this only holds what isn't tied to a handler.
+// /graphql isn't in here, its schema is served by introspection
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "NFT indexer API",
+        description = "Solana NFT metadata, ownership and search, served from the indexer's Postgres and search \
+            index. Every route needs an api key in the X-API-Key header (or `Authorization: Bearer`, or ?api_key= for \
+            /events). Besides the error responses listed per route, any route can answer 401 for a missing or \
+            unknown key, 429 with a Retry-After header once the key's rate limit is used up, and 500 or 503, all \
+            with an ErrorBody."
+    ),
+    tags(
+        (name = "assets", description = "one asset or many by mint address"),
+        (name = "collections", description = "collection stats and members"),
+        (name = "owners", description = "what a wallet holds"),
+        (name = "search", description = "full text search and autocomplete"),
+        (name = "das", description = "Metaplex DAS json-rpc methods"),
+        (name = "events", description = "live change feeds"),
+        (name = "webhooks", description = "signed change notifications, WebhookPayload is what gets POSTed"),
+        (name = "account", description = "the calling api key's limits and usage"),
+    ),
+    components(schemas(ErrorBody, WebhookPayload)),
+    modifiers(&ApiKeyAuth)
+)]
+pub struct ApiDoc;
+
+// one scheme required by every route, the docs explorer then asks for the key once
+struct ApiKeyAuth;
+
+impl Modify for ApiKeyAuth {
+    fn modify(&self, spec: &mut utoipa::openapi::OpenApi) {
+        let components = spec.components.get_or_insert_with(Default::default);
+        components.add_security_scheme("api_key", SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new("X-API-Key"))));
+        spec.security = Some(vec![SecurityRequirement::new("api_key", Vec::<String>::new())]);
+    }
+}
+
+impl ApiDoc {
+    // utoipa fills the license from Cargo.toml, which has none, and would print an empty one
+    pub fn base() -> utoipa::openapi::OpenApi {
+        let mut spec = Self::openapi();
+        spec.info.license = None;
+        spec
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+    use std::fs;
+
+    use serde_json::Value;
+
+    const SPEC_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/openapi.json");
+    const TYPES_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../src/types/index.ts");
+
+    // spec schema -> the interface in src/types/index.ts that mirrors it
+    const TS_TYPES: &[(&str, &str)] = &[
+        ("AssetDetails", "AssetDetails"),
+        ("Holding", "Holding"),
+        ("HoldingsResponse", "HoldingsResponse"),
+        ("CollectionSummary", "CollectionSummary"),
+        ("CollectionsResponse", "CollectionsResponse"),
+        ("CollectionNftsResponse", "CollectionNftsResponse"),
+        ("SearchResult", "NFTResult"),
+        ("SearchResponse", "SearchResponse"),
+        ("SearchAggregations", "SearchAggregations"),
+        ("Suggestion", "Suggestion"),
+        ("SuggestResponse", "SuggestResponse"),
+        ("ChangeEvent", "ChangeEvent"),
+        ("WebhookFilter", "WebhookFilter"),
+        ("WebhookResponse", "Webhook"),
+        ("WebhookDeliveryResponse", "WebhookDelivery"),
+        ("WebhookPayload", "WebhookPayload"),
+        ("UsageResponse", "UsageResponse"),
+        ("ErrorBody", "ApiErrorBody"),
+    ];
+
+    fn spec() -> Value {
+        let (_, spec) = crate::api_router().split_for_parts();
+        serde_json::to_value(spec).unwrap()
+    }
+
+    // openapi.json is the reviewed copy of the spec. regenerate it after changing a handler or a response type with
+    // UPDATE_OPENAPI=1 cargo test -p api-server openapi
+    #[test]
+    fn spec_matches_checked_in_copy() {
+        let generated = serde_json::to_string_pretty(&spec()).unwrap() + "\n";
+        if std::env::var("UPDATE_OPENAPI").is_ok() {
+            fs::write(SPEC_PATH, &generated).unwrap();
+        }
+        let checked_in = fs::read_to_string(SPEC_PATH).unwrap_or_default();
+        assert!(
+            checked_in == generated,
+            "api_server/openapi.json is out of date, run UPDATE_OPENAPI=1 cargo test -p api-server openapi"
+        );
+    }
+
+    // top level property names of a schema, following $ref and allOf (serde flatten)
+    fn properties(spec: &Value, schema: &Value) -> BTreeSet<String> {
+        if let Some(reference) = schema["$ref"].as_str() {
+            let name = reference.rsplit('/').next().unwrap();
+            return properties(spec, &spec["components"]["schemas"][name]);
+        }
+        let mut names: BTreeSet<String> =
+            schema["properties"].as_object().into_iter().flat_map(|props| props.keys().cloned()).collect();
+        for part in schema["allOf"].as_array().into_iter().flatten() {
+            names.extend(properties(spec, part));
+        }
+        names
+    }
+
+    // (every property, the ones not marked optional) of `export interface name`, nested object types are skipped
+    fn interface_fields(source: &str, name: &str) -> (BTreeSet<String>, BTreeSet<String>) {
+        let header = format!("export interface {} {{", name);
+        let start = source.find(&header).unwrap_or_else(|| panic!("no interface {} in index.ts", name));
+        let (mut all, mut required) = (BTreeSet::new(), BTreeSet::new());
+        let mut depth = 1;
+        for line in source[start + header.len()..].lines() {
+            let code = line.split("//").next().unwrap();
+            if depth == 1 {
+                if let Some((field, _)) = code.trim().split_once(':') {
+                    let optional = field.ends_with('?');
+                    let field = field.trim_end_matches('?').to_string();
+                    if !optional {
+                        required.insert(field.clone());
+                    }
+                    all.insert(field);
+                }
+            }
+            depth += code.matches('{').count();
+            depth -= code.matches('}').count();
+            if depth == 0 {
+                break;
+            }
+        }
+        (all, required)
+    }
+
+    #[test]
+    fn typescript_types_match_the_spec() {
+        let spec = spec();
+        let source = fs::read_to_string(TYPES_PATH).unwrap();
+
+        let mut drift = Vec::new();
+        for (schema, interface) in TS_TYPES {
+            let served = properties(&spec, &spec["components"]["schemas"][schema]);
+            assert!(!served.is_empty(), "{} is not in the spec", schema);
+            let (declared, required) = interface_fields(&source, interface);
+            for missing in served.difference(&declared) {
+                drift.push(format!("{}.{} is served but not declared", interface, missing));
+            }
+            for extra in required.difference(&served) {
+                drift.push(format!("{}.{} is required but never served", interface, extra));
+            }
+        }
+        assert!(drift.is_empty(), "src/types/index.ts drifted from the spec:\n{}", drift.join("\n"));
+    }
+}
@@ -0,0 +1,184 @@
+use axum::extract::rejection::{JsonRejection, QueryRejection};
+use axum::Extension;
+use shared::{
+    entities::api_key,
+    types::webhook::{
+        validate_url, CreateWebhookRequest, DeliveriesQuery, DeliveriesResponse, UpdateWebhookRequest, WebhookResponse,
+    },
+    webhooks::{
+        create_webhook, delete_webhook, destination::check_destination, find_webhook, list_deliveries, list_webhooks,
+        update_webhook,
+    },
+    Json, Path, Query, State, StatusCode, Uuid,
+};
+
+use crate::{
+    error::{ApiError, ErrorBody},
+    AppState,
+};
+
+#[utoipa::path(
+    post,
+    path = "/webhooks",
+    tag = "webhooks",
+    request_body = CreateWebhookRequest,
+    responses(
+        (status = 201, body = WebhookResponse),
+        (status = 400, body = ErrorBody),
+        (status = 401, body = ErrorBody),
+    )
+)]
+// POST /webhooks. the response is the only time the signing secret is shown
+pub async fn post_webhook(
+    State((db, _, _)): State<AppState>,
+    key: Option<Extension<api_key::Model>>,
+    request: Result<Json<CreateWebhookRequest>, JsonRejection>,
+) -> Result<(StatusCode, Json<WebhookResponse>), ApiError> {
+    let owner = owner(key)?;
+    let Json(request) = request?;
+    validate_url(&request.url).map_err(ApiError::BadRequest)?;
+    request.filter.change_filter().map_err(ApiError::BadRequest)?;
+    check_destination(&request.url).await.map_err(ApiError::BadRequest)?;
+
+    Ok((StatusCode::CREATED, Json(create_webhook(&db, owner, &request).await?)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/webhooks",
+    tag = "webhooks",
+    responses((status = 200, body = Vec<WebhookResponse>), (status = 401, body = ErrorBody))
+)]
+// the webhooks of the key making the request
+pub async fn get_webhooks(
+    State((db, _, _)): State<AppState>,
+    key: Option<Extension<api_key::Model>>,
+) -> Result<Json<Vec<WebhookResponse>>, ApiError> {
+    Ok(Json(list_webhooks(&db, owner(key)?).await?))
+}
+
+#[utoipa::path(
+    get,
+    path = "/webhooks/{id}",
+    tag = "webhooks",
+    params(("id" = Uuid, Path)),
+    responses(
+        (status = 200, body = WebhookResponse),
+        (status = 400, body = ErrorBody),
+        (status = 401, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
+pub async fn get_webhook(
+    State((db, _, _)): State<AppState>,
+    key: Option<Extension<api_key::Model>>,
+    Path(id): Path<String>,
+) -> Result<Json<WebhookResponse>, ApiError> {
+    let owner = owner(key)?;
+    let id = webhook_id(&id)?;
+    let webhook = find_webhook(&db, owner, id).await?.ok_or_else(|| not_found(id))?;
+    Ok(Json(webhook))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/webhooks/{id}",
+    tag = "webhooks",
+    params(("id" = Uuid, Path)),
+    request_body = UpdateWebhookRequest,
+    responses(
+        (status = 200, body = WebhookResponse),
+        (status = 400, body = ErrorBody),
+        (status = 401, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
+// PATCH /webhooks/{id}, only the fields in the body change
+pub async fn patch_webhook(
+    State((db, _, _)): State<AppState>,
+    key: Option<Extension<api_key::Model>>,
+    Path(id): Path<String>,
+    request: Result<Json<UpdateWebhookRequest>, JsonRejection>,
+) -> Result<Json<WebhookResponse>, ApiError> {
+    let owner = owner(key)?;
+    let id = webhook_id(&id)?;
+    let Json(request) = request?;
+    if let Some(filter) = &request.filter {
+        filter.change_filter().map_err(ApiError::BadRequest)?;
+    }
+    if let Some(url) = &request.url {
+        validate_url(url).map_err(ApiError::BadRequest)?;
+        check_destination(url).await.map_err(ApiError::BadRequest)?;
+    }
+
+    let webhook = update_webhook(&db, owner, id, &request).await?.ok_or_else(|| not_found(id))?;
+    Ok(Json(webhook))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/webhooks/{id}",
+    tag = "webhooks",
+    params(("id" = Uuid, Path)),
+    responses(
+        (status = 204),
+        (status = 400, body = ErrorBody),
+        (status = 401, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
+pub async fn remove_webhook(
+    State((db, _, _)): State<AppState>,
+    key: Option<Extension<api_key::Model>>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    let owner = owner(key)?;
+    let id = webhook_id(&id)?;
+    if !delete_webhook(&db, owner, id).await? {
+        return Err(not_found(id));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    get,
+    path = "/webhooks/{id}/deliveries",
+    tag = "webhooks",
+    params(("id" = Uuid, Path), DeliveriesQuery),
+    responses(
+        (status = 200, body = DeliveriesResponse),
+        (status = 400, body = ErrorBody),
+        (status = 401, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
+// GET /webhooks/{id}/deliveries?page=&size=&status=, the delivery log newest first
+pub async fn get_deliveries(
+    State((db, _, _)): State<AppState>,
+    key: Option<Extension<api_key::Model>>,
+    Path(id): Path<String>,
+    query: Result<Query<DeliveriesQuery>, QueryRejection>,
+) -> Result<Json<DeliveriesResponse>, ApiError> {
+    let owner = owner(key)?;
+    let id = webhook_id(&id)?;
+    let Query(query) = query?;
+    if find_webhook(&db, owner, id).await?.is_none() {
+        return Err(not_found(id));
+    }
+
+    Ok(Json(list_deliveries(&db, id, &query).await?))
+}
+
+// webhooks belong to the key that created them, so managing them needs one even where the read api is open
+fn owner(key: Option<Extension<api_key::Model>>) -> Result<Uuid, ApiError> {
+    key.map(|Extension(key)| key.id)
+        .ok_or_else(|| ApiError::Unauthorized("Send an API key to manage webhooks".to_string()))
+}
+
+fn webhook_id(id: &str) -> Result<Uuid, ApiError> {
+    Uuid::parse_str(id).map_err(|_| ApiError::BadRequest(format!("{} is not a webhook id", id)))
+}
+
+fn not_found(id: Uuid) -> ApiError {
+    ApiError::NotFound(format!("No webhook {}", id))
+}
@@ -0,0 +1,80 @@
+use chrono::{DateTime, Utc};
+use shared::{
+    dotenv, env,
+    elasticsearch::{
+        client::ElasticSearchClient,
+        consistency::{check_consistency, ConsistencyOptions},
+        reindex::{reindex_from_postgres, rollback, ReindexOptions, RollbackOptions},
+    },
+    Database,
+};
+
+const USAGE: &str = "usage:
+  es-admin reindex [--chunk-size N] [--force]   build a new index version from postgres and swap the aliases
+  es-admin rollback [--to VERSION] [--since RFC3339] [--chunk-size N]
+                                               point the aliases back to an older index version, after replaying
+                                               what it missed (since the next version was created by default)
+  es-admin check [--chunk-size N] [--repair]    compare ES against postgres, --repair fixes what differs";
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    dotenv().ok();
+
+    let args: Vec<String> = env::args().skip(1).collect();
+    let Some(command) = args.first() else {
+        eprintln!("{}", USAGE);
+        std::process::exit(2);
+    };
+
+    let elasticsearch = ElasticSearchClient::new(
+        env::var("ELASTICSEARCH_URL").expect("failed to get es_url from env"),
+        env::var("ELASTICSEARCH_INDEX_NAME").expect("failed to get index name from env"),
+    )
+    .await
+    .expect("Error creating a elasticsearch client");
+
+    match command.as_str() {
+        "reindex" => {
+            let db = Database::connect(env::var("DATABASE_URL").expect("DATABASE_URL must be set")).await?;
+            let options = ReindexOptions {
+                chunk_size: flag_value(&args, "--chunk-size").unwrap_or(500),
+                force: args.iter().any(|arg| arg == "--force"),
+            };
+            reindex_from_postgres(&elasticsearch, &db, options).await?;
+        }
+        "check" => {
+            let db = Database::connect(env::var("DATABASE_URL").expect("DATABASE_URL must be set")).await?;
+            let options = ConsistencyOptions {
+                chunk_size: flag_value(&args, "--chunk-size").unwrap_or(500),
+                repair: args.iter().any(|arg| arg == "--repair"),
+            };
+            let repair = options.repair;
+            let report = check_consistency(&elasticsearch, &db, options).await?;
+            if !repair && !report.is_consistent() {
+                std::process::exit(1); // lets a cron job or CI alert on drift
+            }
+        }
+        "rollback" => {
+            let db = Database::connect(env::var("DATABASE_URL").expect("DATABASE_URL must be set")).await?;
+            let options = RollbackOptions {
+                to_version: flag_value(&args, "--to"),
+                since: flag_value::<DateTime<Utc>>(&args, "--since"),
+                chunk_size: flag_value(&args, "--chunk-size").unwrap_or(500),
+            };
+            rollback(&elasticsearch, &db, options).await?;
+        }
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    }
+
+    Ok(())
+}
+
+fn flag_value<T: std::str::FromStr>(args: &[String], flag: &str) -> Option<T> {
+    args.iter()
+        .position(|arg| arg == flag)
+        .and_then(|i| args.get(i + 1))
+        .and_then(|value| value.parse().ok())
+}
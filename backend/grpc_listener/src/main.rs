@@ -1,19 +1,43 @@
-use shared::{dotenv, env, ys_grpc::grpc_client::GRPCclient};
+use std::sync::Arc;
+
+use shared::{
+    dotenv, env,
+    health::{check, health_router, ping_redis, serve_health, HealthReport},
+    redis::queue_manager::redis_url,
+    shutdown::Shutdown,
+    ys_grpc::grpc_client::GRPCclient,
+};
+
+const DEFAULT_HEALTH_ADDR: &str = "localhost:3003";
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     dotenv().ok();
+    let shutdown = Shutdown::from_env();
+    let redis_url = redis_url();
+
+    let grpc_client = Arc::new(GRPCclient::from_env());
 
-    let grpc_client = GRPCclient::new(
-        env::var("RPC_ENDPOINT").expect("failed to retrieve the rpc from env"),
-        env::var("RPC_TOKEN").expect("failed to fetch token from env"),
-    );
+    let (ready_client, ready_redis) = (grpc_client.clone(), redis_url.clone());
+    let readiness = move || {
+        let (grpc_client, redis_url) = (ready_client.clone(), ready_redis.clone());
+        async move {
+            let (redis, stream) = tokio::join!(
+                check(true, ping_redis(&redis_url)),
+                check(true, async { grpc_client.stream_liveness() }),
+            );
+            HealthReport::new([("redis", redis), ("grpc_stream", stream)])
+        }
+    };
+    let health_addr = env::var("LISTENER_HEALTH_ADDR").unwrap_or_else(|_| DEFAULT_HEALTH_ADDR.to_string());
+    tokio::spawn(serve_health(health_addr, health_router(readiness, shutdown.clone())));
 
     println!("Starting GRPC server...");
-    if let Err(e) = grpc_client.listen_for_updates().await {
+    if let Err(e) = grpc_client.listen_for_updates(&redis_url, shutdown).await {
         eprintln!("GRPC listener Error: {}", e);
-        return Err(e.into());
+        return Err(e);
     }
+    println!("GRPC listener stopped");
 
     Ok(())
 }
@@ -1,27 +1,64 @@
+use std::sync::Arc;
+use std::time::Duration;
+
 use shared::{
     dotenv, env,
-    elasticsearch::client::ElasticSearchClient,
-    redis::{queue_manager::RedisQueue, worker::QueueWorker},
+    elasticsearch::outbox::{OutboxRelay, OutboxRelayConfig},
+    health::{check, health_router, ping_postgres, ping_redis, serve_health, HealthReport},
+    redis::{
+        cache::{ResponseCache, ResponseCacheConfig},
+        queue_manager::{redis_url, RedisQueue},
+        tracked::TrackedAccounts,
+        worker::QueueWorker,
+    },
+    search::search_index_from_env,
+    shutdown::Shutdown,
+    webhooks::dispatcher::{WebhookDispatcher, WebhookDispatcherConfig},
     Database,
 };
 
+const DEFAULT_HEALTH_ADDR: &str = "localhost:3002";
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     dotenv().ok();
+    let shutdown = Shutdown::from_env();
+    let redis_url = redis_url();
 
-    let elasticsearch = ElasticSearchClient::new(
-        env::var("ELASTICSEARCH_URL").expect("failed to get es_url from env"),
-        env::var("ELASTICSEARCH_INDEX_NAME").expect("failed to get index name from env"),
-    )
-    .await
-    .expect("Error creating a elasticsearch client");
-    
     let db = Database::connect(env::var("DATABASE_URL").expect("DATABASE_URL must be set")).await?;
-    let queue = RedisQueue::new().await?;
-    let worker = QueueWorker::new(queue, db, elasticsearch);
+    let search_index = search_index_from_env(&db)
+        .await
+        .expect("Error creating the search backend");
+    let queue = RedisQueue::new(&redis_url).await?;
+    queue.rpc_pool().spawn_health_checks(Duration::from_secs(30));
+    // the worker only drops cached responses, the ttls are the api_server's business
+    let cache = Arc::new(ResponseCache::new(&redis_url, ResponseCacheConfig::default())?);
+
+    let (ready_db, ready_search, ready_redis) = (db.clone(), search_index.clone(), redis_url.clone());
+    let readiness = move || {
+        let (db, search_index, redis_url) = (ready_db.clone(), ready_search.clone(), ready_redis.clone());
+        async move {
+            let (postgres, redis, search) = tokio::join!(
+                check(true, ping_postgres(&db)),
+                check(true, ping_redis(&redis_url)),
+                check(true, search_index.ping()),
+            );
+            HealthReport::new([("postgres", postgres), ("redis", redis), ("search", search)])
+        }
+    };
+    let health_addr = env::var("WORKER_HEALTH_ADDR").unwrap_or_else(|_| DEFAULT_HEALTH_ADDR.to_string());
+    tokio::spawn(serve_health(health_addr, health_router(readiness, shutdown.clone())));
+
+    let relay = OutboxRelay::new(db.clone(), search_index, cache.clone(), OutboxRelayConfig::from_env())
+        .spawn(shutdown.clone());
+    let dispatcher = WebhookDispatcher::new(db.clone(), WebhookDispatcherConfig::from_env()).spawn(shutdown.clone());
+    let worker = QueueWorker::new(queue, db, cache, TrackedAccounts::new(&redis_url)?);
 
     println!("Starting queue worker...");
-    worker.start_processing().await;
+    worker.start_processing(&shutdown).await;
+    // the relay goes last, it flushes what the worker's final messages wrote
+    let _ = tokio::join!(relay, dispatcher);
+    println!("Queue worker stopped");
 
     Ok(())
 }
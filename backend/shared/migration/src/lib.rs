@@ -7,7 +7,37 @@ impl MigratorTrait for Migrator {
     fn migrations() -> Vec<Box<dyn MigrationTrait>> {
         vec![
             Box::new(m20250828_073444_init_tables::Migration),
+            Box::new(m20261018_090000_add_nft_metadata_search_fields::Migration),
+            Box::new(m20261018_100000_add_nft_metadata_status::Migration),
+            Box::new(m20261018_110000_add_nft_metadata_es_sync::Migration),
+            Box::new(m20261018_120000_create_sync_outbox::Migration),
+            Box::new(m20261018_130000_add_token_accounts_owner_index::Migration),
+            Box::new(m20261018_140000_add_nft_metadata_collection_index::Migration),
+            Box::new(m20261018_150000_create_webhooks::Migration),
+            Box::new(m20261018_160000_create_api_keys::Migration),
+            Box::new(m20261018_170000_add_nft_metadata_es_sync_retry::Migration),
+            Box::new(m20261018_180000_create_nft_search_docs::Migration),
+            Box::new(m20261018_190000_create_collection_stats::Migration),
+            Box::new(m20261018_200000_add_webhooks_api_key_id::Migration),
+            Box::new(m20261018_210000_make_token_accounts_amount_numeric::Migration),
+            Box::new(m20261018_220000_add_webhook_deliveries_locked_until::Migration),
+            Box::new(m20261018_230000_disable_keyless_webhooks::Migration),
         ]
     }
 }
 mod m20250828_073444_init_tables;
+mod m20261018_090000_add_nft_metadata_search_fields;
+mod m20261018_100000_add_nft_metadata_status;
+mod m20261018_110000_add_nft_metadata_es_sync;
+mod m20261018_120000_create_sync_outbox;
+mod m20261018_130000_add_token_accounts_owner_index;
+mod m20261018_140000_add_nft_metadata_collection_index;
+mod m20261018_150000_create_webhooks;
+mod m20261018_160000_create_api_keys;
+mod m20261018_170000_add_nft_metadata_es_sync_retry;
+mod m20261018_180000_create_nft_search_docs;
+mod m20261018_190000_create_collection_stats;
+mod m20261018_200000_add_webhooks_api_key_id;
+mod m20261018_210000_make_token_accounts_amount_numeric;
+mod m20261018_220000_add_webhook_deliveries_locked_until;
+mod m20261018_230000_disable_keyless_webhooks;
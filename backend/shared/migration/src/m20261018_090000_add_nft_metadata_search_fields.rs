@@ -0,0 +1,75 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // the search index is rebuilt from postgres, so everything the ES doc carries has to live here too
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(NftMetadata::Table)
+                    .add_column_if_not_exists(text_null(NftMetadata::CollectionName))
+                    .add_column_if_not_exists(string_null(NftMetadata::CollectionMint))
+                    .add_column_if_not_exists(boolean(NftMetadata::CollectionVerified).default(false))
+                    .add_column_if_not_exists(string_null(NftMetadata::TokenStandard))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_nft_creator_metadata_address")
+                    .table(NftCreator::Table)
+                    .col(NftCreator::MetadataAddress)
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_nft_creator_metadata_address")
+                    .table(NftCreator::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(NftMetadata::Table)
+                    .drop_column(NftMetadata::CollectionName)
+                    .drop_column(NftMetadata::CollectionMint)
+                    .drop_column(NftMetadata::CollectionVerified)
+                    .drop_column(NftMetadata::TokenStandard)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum NftMetadata {
+    Table,
+    CollectionName,
+    CollectionMint,
+    CollectionVerified,
+    TokenStandard,
+}
+
+#[derive(DeriveIden)]
+enum NftCreator {
+    Table,
+    MetadataAddress,
+}
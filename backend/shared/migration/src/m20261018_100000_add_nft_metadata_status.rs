@@ -0,0 +1,65 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // burned and closed assets keep their row so the history survives, only the status changes
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(NftMetadata::Table)
+                    .add_column_if_not_exists(string(NftMetadata::Status).default("active"))
+                    .add_column_if_not_exists(timestamp_with_time_zone_null(NftMetadata::StatusUpdatedAt))
+                    .to_owned(),
+            )
+            .await?;
+
+        // a closed metadata account only tells us its own address
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_nft_metadata_metadata_address")
+                    .table(NftMetadata::Table)
+                    .col(NftMetadata::MetadataAddress)
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_nft_metadata_metadata_address")
+                    .table(NftMetadata::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(NftMetadata::Table)
+                    .drop_column(NftMetadata::Status)
+                    .drop_column(NftMetadata::StatusUpdatedAt)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum NftMetadata {
+    Table,
+    MetadataAddress,
+    Status,
+    StatusUpdatedAt,
+}
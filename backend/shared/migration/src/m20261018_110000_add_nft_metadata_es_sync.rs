@@ -0,0 +1,66 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // whether the latest version of the row made it into ES, the worker retries the failed ones
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(NftMetadata::Table)
+                    .add_column_if_not_exists(string(NftMetadata::EsSyncStatus).default("pending"))
+                    .add_column_if_not_exists(text_null(NftMetadata::EsSyncError))
+                    .add_column_if_not_exists(timestamp_with_time_zone_null(NftMetadata::EsSyncUpdatedAt))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_nft_metadata_es_sync_status")
+                    .table(NftMetadata::Table)
+                    .col(NftMetadata::EsSyncStatus)
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_nft_metadata_es_sync_status")
+                    .table(NftMetadata::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(NftMetadata::Table)
+                    .drop_column(NftMetadata::EsSyncStatus)
+                    .drop_column(NftMetadata::EsSyncError)
+                    .drop_column(NftMetadata::EsSyncUpdatedAt)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum NftMetadata {
+    Table,
+    EsSyncStatus,
+    EsSyncError,
+    EsSyncUpdatedAt,
+}
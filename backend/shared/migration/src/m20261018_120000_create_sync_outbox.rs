@@ -0,0 +1,72 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // written in the same transaction as the change it describes, the relay pushes it to ES afterwards
+        manager
+            .create_table(
+                Table::create()
+                    .table(SyncOutbox::Table)
+                    .if_not_exists()
+                    .col(big_integer(SyncOutbox::Id).auto_increment().primary_key())
+                    .col(string(SyncOutbox::MintAddress))
+                    .col(string(SyncOutbox::Operation))
+                    .col(json_binary_null(SyncOutbox::Payload))
+                    .col(string(SyncOutbox::Status).default("pending"))
+                    .col(integer(SyncOutbox::Attempts).default(0))
+                    .col(text_null(SyncOutbox::LastError))
+                    .col(
+                        timestamp_with_time_zone(SyncOutbox::NextAttemptAt)
+                            .default(Expr::current_timestamp()),
+                    )
+                    .col(timestamp_with_time_zone_null(SyncOutbox::ProcessedAt))
+                    .col(
+                        timestamp_with_time_zone(SyncOutbox::CreatedAt)
+                            .default(Expr::current_timestamp()),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_sync_outbox_status_next_attempt_at")
+                    .table(SyncOutbox::Table)
+                    .col(SyncOutbox::Status)
+                    .col(SyncOutbox::NextAttemptAt)
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(SyncOutbox::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum SyncOutbox {
+    Table,
+    Id,
+    MintAddress,
+    Operation,
+    Payload,
+    Status,
+    Attempts,
+    LastError,
+    NextAttemptAt,
+    ProcessedAt,
+    CreatedAt,
+}
@@ -0,0 +1,79 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // a fungible mint has a token account per holder, so mint_address can't stay unique
+        manager
+            .get_connection()
+            .execute_unprepared(
+                "ALTER TABLE token_accounts DROP CONSTRAINT IF EXISTS token_accounts_mint_address_key",
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_token_accounts_mint_address")
+                    .table(TokenAccounts::Table)
+                    .col(TokenAccounts::MintAddress)
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await?;
+
+        // wallet portfolios page through an owner's accounts ordered by mint
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_token_accounts_owner_mint_address")
+                    .table(TokenAccounts::Table)
+                    .col(TokenAccounts::Owner)
+                    .col(TokenAccounts::MintAddress)
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_token_accounts_owner_mint_address")
+                    .table(TokenAccounts::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_token_accounts_mint_address")
+                    .table(TokenAccounts::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .get_connection()
+            .execute_unprepared(
+                "ALTER TABLE token_accounts ADD CONSTRAINT token_accounts_mint_address_key UNIQUE (mint_address)",
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum TokenAccounts {
+    Table,
+    Owner,
+    MintAddress,
+}
@@ -0,0 +1,42 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // the collection endpoints group and filter members by their collection mint
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_nft_metadata_collection_mint")
+                    .table(NftMetadata::Table)
+                    .col(NftMetadata::CollectionMint)
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_nft_metadata_collection_mint")
+                    .table(NftMetadata::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum NftMetadata {
+    Table,
+    CollectionMint,
+}
@@ -0,0 +1,142 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Webhooks::Table)
+                    .if_not_exists()
+                    .col(
+                        pk_uuid(Webhooks::Id)
+                            .uuid()
+                            .not_null()
+                            .default(Expr::cust("gen_random_uuid()")),
+                    )
+                    .col(text(Webhooks::Url))
+                    .col(string(Webhooks::Secret))
+                    .col(json_binary(Webhooks::Filter))
+                    .col(boolean(Webhooks::Active).default(true))
+                    .col(integer(Webhooks::ConsecutiveFailures).default(0))
+                    .col(text_null(Webhooks::DisabledReason))
+                    .col(
+                        timestamp_with_time_zone(Webhooks::CreatedAt)
+                            .default(Expr::current_timestamp()),
+                    )
+                    .col(
+                        timestamp_with_time_zone(Webhooks::UpdatedAt)
+                            .default(Expr::current_timestamp()),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // the queue the dispatcher drains and the delivery log at the same time
+        manager
+            .create_table(
+                Table::create()
+                    .table(WebhookDeliveries::Table)
+                    .if_not_exists()
+                    .col(big_integer(WebhookDeliveries::Id).auto_increment().primary_key())
+                    .col(uuid(WebhookDeliveries::WebhookId))
+                    .col(string(WebhookDeliveries::EventKind))
+                    .col(string(WebhookDeliveries::MintAddress))
+                    .col(json_binary(WebhookDeliveries::Payload))
+                    .col(string(WebhookDeliveries::Status).default("pending"))
+                    .col(integer(WebhookDeliveries::Attempts).default(0))
+                    .col(integer_null(WebhookDeliveries::ResponseStatus))
+                    .col(text_null(WebhookDeliveries::LastError))
+                    .col(
+                        timestamp_with_time_zone(WebhookDeliveries::NextAttemptAt)
+                            .default(Expr::current_timestamp()),
+                    )
+                    .col(timestamp_with_time_zone_null(WebhookDeliveries::DeliveredAt))
+                    .col(
+                        timestamp_with_time_zone(WebhookDeliveries::CreatedAt)
+                            .default(Expr::current_timestamp()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_webhook_deliveries_webhook_id")
+                            .from(WebhookDeliveries::Table, WebhookDeliveries::WebhookId)
+                            .to(Webhooks::Table, Webhooks::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_webhook_deliveries_status_next_attempt_at")
+                    .table(WebhookDeliveries::Table)
+                    .col(WebhookDeliveries::Status)
+                    .col(WebhookDeliveries::NextAttemptAt)
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_webhook_deliveries_webhook_id")
+                    .table(WebhookDeliveries::Table)
+                    .col(WebhookDeliveries::WebhookId)
+                    .col(WebhookDeliveries::Id)
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(WebhookDeliveries::Table).to_owned())
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(Webhooks::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Webhooks {
+    Table,
+    Id,
+    Url,
+    Secret,
+    Filter,
+    Active,
+    ConsecutiveFailures,
+    DisabledReason,
+    CreatedAt,
+    UpdatedAt,
+}
+
+#[derive(DeriveIden)]
+enum WebhookDeliveries {
+    Table,
+    Id,
+    WebhookId,
+    EventKind,
+    MintAddress,
+    Payload,
+    Status,
+    Attempts,
+    ResponseStatus,
+    LastError,
+    NextAttemptAt,
+    DeliveredAt,
+    CreatedAt,
+}
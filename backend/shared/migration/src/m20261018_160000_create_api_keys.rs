@@ -0,0 +1,60 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // only the sha256 of a key is stored, the prefix is kept in the clear so a key can be recognized in a list
+        manager
+            .create_table(
+                Table::create()
+                    .table(ApiKeys::Table)
+                    .if_not_exists()
+                    .col(
+                        pk_uuid(ApiKeys::Id)
+                            .uuid()
+                            .not_null()
+                            .default(Expr::cust("gen_random_uuid()")),
+                    )
+                    .col(text(ApiKeys::Name))
+                    .col(string(ApiKeys::KeyPrefix))
+                    .col(string_uniq(ApiKeys::KeyHash))
+                    .col(integer(ApiKeys::RequestsPerMinute))
+                    .col(integer(ApiKeys::Burst))
+                    .col(boolean(ApiKeys::Active).default(true))
+                    .col(
+                        timestamp_with_time_zone(ApiKeys::CreatedAt)
+                            .default(Expr::current_timestamp()),
+                    )
+                    .col(timestamp_with_time_zone_null(ApiKeys::RevokedAt))
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ApiKeys::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum ApiKeys {
+    Table,
+    Id,
+    Name,
+    KeyPrefix,
+    KeyHash,
+    RequestsPerMinute,
+    Burst,
+    Active,
+    CreatedAt,
+    RevokedAt,
+}
@@ -0,0 +1,73 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // the worker only retries failures ES could accept later (429, 5xx, unreachable), backing off per attempt.
+        // es_sync_retry_at stays null for the ones it gave up on
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(NftMetadata::Table)
+                    .add_column_if_not_exists(small_integer_null(NftMetadata::EsSyncFailureStatus))
+                    .add_column_if_not_exists(integer(NftMetadata::EsSyncAttempts).default(0))
+                    .add_column_if_not_exists(timestamp_with_time_zone_null(NftMetadata::EsSyncRetryAt))
+                    .to_owned(),
+            )
+            .await?;
+
+        // rows that failed before the status was recorded get one more try, which sorts them out
+        manager
+            .get_connection()
+            .execute_unprepared("UPDATE nft_metadata SET es_sync_retry_at = now() WHERE es_sync_status = 'failed'")
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_nft_metadata_es_sync_retry_at")
+                    .table(NftMetadata::Table)
+                    .col(NftMetadata::EsSyncRetryAt)
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_nft_metadata_es_sync_retry_at")
+                    .table(NftMetadata::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(NftMetadata::Table)
+                    .drop_column(NftMetadata::EsSyncFailureStatus)
+                    .drop_column(NftMetadata::EsSyncAttempts)
+                    .drop_column(NftMetadata::EsSyncRetryAt)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum NftMetadata {
+    Table,
+    EsSyncFailureStatus,
+    EsSyncAttempts,
+    EsSyncRetryAt,
+}
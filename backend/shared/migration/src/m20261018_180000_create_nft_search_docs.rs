@@ -0,0 +1,50 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+// the postgres search backend keeps its docs here, in the same shape they have in ES. the generated columns are what
+// gets indexed, search_vector covers the same fields the ES text queries look at
+const UP: &[&str] = &[
+    "CREATE EXTENSION IF NOT EXISTS pg_trgm",
+    "CREATE TABLE IF NOT EXISTS nft_search_docs (
+        mint_address text PRIMARY KEY,
+        doc jsonb NOT NULL,
+        name text GENERATED ALWAYS AS (doc->>'nft_name') STORED,
+        collection_mint text GENERATED ALWAYS AS (doc->'collection'->>'mint') STORED,
+        collection_name text GENERATED ALWAYS AS (doc->'collection'->>'name') STORED,
+        royalty_bps integer GENERATED ALWAYS AS ((doc->>'royalty_bps')::integer) STORED,
+        created_at text GENERATED ALWAYS AS (doc->>'created_at') STORED,
+        search_vector tsvector GENERATED ALWAYS AS (
+            to_tsvector('simple',
+                coalesce(doc->>'nft_name', '') || ' ' ||
+                coalesce(doc->'collection'->>'name', '') || ' ' ||
+                coalesce(doc->>'symbol', ''))
+        ) STORED
+    )",
+    "CREATE INDEX IF NOT EXISTS idx_nft_search_docs_vector ON nft_search_docs USING gin (search_vector)",
+    "CREATE INDEX IF NOT EXISTS idx_nft_search_docs_name_trgm ON nft_search_docs USING gin (name gin_trgm_ops)",
+    "CREATE INDEX IF NOT EXISTS idx_nft_search_docs_doc ON nft_search_docs USING gin (doc jsonb_path_ops)",
+    "CREATE INDEX IF NOT EXISTS idx_nft_search_docs_collection_mint ON nft_search_docs (collection_mint)",
+];
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for statement in UP {
+            manager.get_connection().execute_unprepared(statement).await?;
+        }
+
+        Ok(())
+    }
+
+    // pg_trgm stays, other tables may use it by now
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .get_connection()
+            .execute_unprepared("DROP TABLE IF EXISTS nft_search_docs")
+            .await?;
+
+        Ok(())
+    }
+}
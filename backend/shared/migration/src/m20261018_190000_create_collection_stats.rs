@@ -0,0 +1,54 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+// the per collection numbers /collections lists, so a request reads one row per collection instead of grouping every
+// member and counting holders. the worker refreshes it, see refresh_collection_stats
+const UP: &[&str] = &[
+    "CREATE MATERIALIZED VIEW IF NOT EXISTS collection_stats AS
+    WITH members AS (
+        SELECT collection_mint,
+            count(*) AS members,
+            count(*) FILTER (WHERE collection_verified) AS verified_members,
+            max(collection_name) AS collection_name,
+            mode() WITHIN GROUP (ORDER BY seller_fee_basis_points) AS royalty_bps,
+            min(created_at) AS first_seen
+        FROM nft_metadata
+        WHERE collection_mint IS NOT NULL AND status = 'active'
+        GROUP BY collection_mint
+    ),
+    holders AS (
+        SELECT md.collection_mint, count(DISTINCT o.owner) AS unique_holders
+        FROM nft_metadata md JOIN nft_ownership o ON o.mint_address = md.mint_address
+        WHERE md.collection_mint IS NOT NULL AND md.status = 'active'
+        GROUP BY md.collection_mint
+    )
+    SELECT members.*, coalesce(holders.unique_holders, 0) AS unique_holders
+    FROM members LEFT JOIN holders ON holders.collection_mint = members.collection_mint",
+    // REFRESH ... CONCURRENTLY needs a unique index
+    "CREATE UNIQUE INDEX IF NOT EXISTS idx_collection_stats_collection_mint ON collection_stats (collection_mint)",
+    "CREATE INDEX IF NOT EXISTS idx_collection_stats_members ON collection_stats (verified_members DESC, members DESC)",
+    "CREATE INDEX IF NOT EXISTS idx_collection_stats_unique_holders ON collection_stats (unique_holders DESC)",
+    "CREATE INDEX IF NOT EXISTS idx_collection_stats_first_seen ON collection_stats (first_seen DESC)",
+];
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for statement in UP {
+            manager.get_connection().execute_unprepared(statement).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .get_connection()
+            .execute_unprepared("DROP MATERIALIZED VIEW IF EXISTS collection_stats")
+            .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,32 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+// a webhook belongs to the api key that created it and only that key can see or change it. revoking a key only
+// deactivates it, so its webhooks are switched off on revoke rather than by the cascade. webhooks made before this
+// have no key, m20261018_230000 switches them off
+const UP: &[&str] = &[
+    "ALTER TABLE webhooks ADD COLUMN IF NOT EXISTS api_key_id uuid REFERENCES api_keys (id) ON DELETE CASCADE",
+    "CREATE INDEX IF NOT EXISTS idx_webhooks_api_key_id ON webhooks (api_key_id)",
+];
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for statement in UP {
+            manager.get_connection().execute_unprepared(statement).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .get_connection()
+            .execute_unprepared("ALTER TABLE webhooks DROP COLUMN IF EXISTS api_key_id")
+            .await?;
+
+        Ok(())
+    }
+}
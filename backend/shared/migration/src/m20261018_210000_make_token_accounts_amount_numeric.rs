@@ -0,0 +1,29 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+// token amounts are u64, bigint tops out at i64::MAX. numeric(20, 0) holds every u64 and still sorts and sums
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .get_connection()
+            .execute_unprepared("ALTER TABLE token_accounts ALTER COLUMN amount TYPE numeric(20, 0)")
+            .await?;
+
+        Ok(())
+    }
+
+    // amounts past i64::MAX come back saturated, as they were stored before
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .get_connection()
+            .execute_unprepared(
+                "ALTER TABLE token_accounts ALTER COLUMN amount TYPE bigint USING least(amount, 9223372036854775807)",
+            )
+            .await?;
+
+        Ok(())
+    }
+}
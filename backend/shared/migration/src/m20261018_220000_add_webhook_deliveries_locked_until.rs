@@ -0,0 +1,26 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+// the dispatcher leases the deliveries it claims until it has sent them, so it doesn't hold row locks over http calls
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .get_connection()
+            .execute_unprepared("ALTER TABLE webhook_deliveries ADD COLUMN IF NOT EXISTS locked_until timestamptz")
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .get_connection()
+            .execute_unprepared("ALTER TABLE webhook_deliveries DROP COLUMN IF EXISTS locked_until")
+            .await?;
+
+        Ok(())
+    }
+}
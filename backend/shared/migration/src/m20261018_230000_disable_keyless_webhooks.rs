@@ -0,0 +1,27 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+// webhooks made before they belonged to an api key can't be seen or changed through the api, so they're switched
+// off instead of delivering to whoever set them up. the reason says how to get them back
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .get_connection()
+            .execute_unprepared(
+                "UPDATE webhooks SET active = false, updated_at = now(),
+                    disabled_reason = 'No api key owns this webhook, create it again with a key'
+                WHERE api_key_id IS NULL AND active",
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    // which of them were active before isn't kept, they stay off
+    async fn down(&self, _manager: &SchemaManager) -> Result<(), DbErr> {
+        Ok(())
+    }
+}
@@ -0,0 +1,98 @@
+use rand::RngCore;
+use sea_orm::prelude::Uuid;
+use sea_orm::sea_query::Expr;
+use sea_orm::{ActiveModelTrait, ColumnTrait, ConnectionTrait, DbErr, EntityTrait, QueryFilter, QueryOrder, Set};
+use sha2::{Digest, Sha256};
+
+use crate::entities::{api_key, webhook};
+
+const KEY_PREFIX_LEN: usize = 11; // "ak_" and the first 8 hex characters
+
+// keys are 32 random bytes, so a plain sha256 is enough to keep them out of the database, no slow hash needed
+pub fn hash_key(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+fn generate_key() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    format!("ak_{}", hex::encode(bytes))
+}
+
+// the key is generated here and returned this once, next to the stored row
+pub async fn create_api_key<C: ConnectionTrait>(
+    conn: &C,
+    name: &str,
+    requests_per_minute: i32,
+    burst: i32,
+) -> Result<(api_key::Model, String), DbErr> {
+    let key = generate_key();
+    let created = api_key::ActiveModel {
+        name: Set(name.to_string()),
+        key_prefix: Set(key[..KEY_PREFIX_LEN].to_string()),
+        key_hash: Set(hash_key(&key)),
+        requests_per_minute: Set(requests_per_minute.max(1)),
+        burst: Set(burst.max(1)),
+        ..Default::default()
+    }
+    .insert(conn)
+    .await?;
+    Ok((created, key))
+}
+
+pub async fn list_api_keys<C: ConnectionTrait>(conn: &C) -> Result<Vec<api_key::Model>, DbErr> {
+    api_key::Entity::find()
+        .order_by_asc(api_key::Column::CreatedAt)
+        .all(conn)
+        .await
+}
+
+// the row stays for the record, the key just stops working and so do its webhooks, pending deliveries included.
+// false when there was no active key with that id
+pub async fn revoke_api_key<C: ConnectionTrait>(conn: &C, id: Uuid) -> Result<bool, DbErr> {
+    let result = api_key::Entity::update_many()
+        .col_expr(api_key::Column::Active, Expr::value(false))
+        .col_expr(api_key::Column::RevokedAt, Expr::current_timestamp().into())
+        .filter(api_key::Column::Id.eq(id))
+        .filter(api_key::Column::Active.eq(true))
+        .exec(conn)
+        .await?;
+    if result.rows_affected == 0 {
+        return Ok(false);
+    }
+
+    webhook::Entity::update_many()
+        .col_expr(webhook::Column::Active, Expr::value(false))
+        .col_expr(webhook::Column::DisabledReason, Expr::value("The api key that owns it was revoked"))
+        .col_expr(webhook::Column::UpdatedAt, Expr::current_timestamp().into())
+        .filter(webhook::Column::ApiKeyId.eq(id))
+        .filter(webhook::Column::Active.eq(true))
+        .exec(conn)
+        .await?;
+    Ok(true)
+}
+
+// the active key a request presented, None for unknown and revoked keys alike
+pub async fn find_active_key<C: ConnectionTrait>(conn: &C, key: &str) -> Result<Option<api_key::Model>, DbErr> {
+    api_key::Entity::find()
+        .filter(api_key::Column::KeyHash.eq(hash_key(key)))
+        .filter(api_key::Column::Active.eq(true))
+        .one(conn)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_are_random_and_hashed() {
+        let key = generate_key();
+        assert!(key.starts_with("ak_"));
+        assert_eq!(key.len(), 3 + 64);
+        assert_ne!(key, generate_key());
+        assert_eq!(hash_key(&key), hash_key(&key));
+        assert_ne!(hash_key(&key), key);
+        assert_eq!(hash_key(&key).len(), 64);
+    }
+}
@@ -0,0 +1,219 @@
+use std::collections::HashMap;
+
+use sea_orm::{
+    ColumnTrait, ConnectionTrait, DatabaseConnection, DbBackend, DbErr, EntityTrait, FromQueryResult, PaginatorTrait,
+    QueryFilter, QueryOrder, QuerySelect, Statement, TransactionTrait,
+};
+
+use crate::assets::details::find_assets_details;
+use crate::entities::nft_metadata;
+use crate::types::asset::AssetInclude;
+use crate::types::collection::{
+    CollectionCreator, CollectionNftSort, CollectionNftsQuery, CollectionNftsResponse, CollectionRoyalty,
+    CollectionSort, CollectionSummary, CollectionsQuery, CollectionsResponse, DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE,
+};
+
+// one row per collection mint its active members point at, read from the collection_stats view the worker keeps
+// fresh. the collection nft itself is usually indexed as well, it's where name, symbol, image and royalty are read
+// from first. `{filter}` and `{order}` are fixed strings
+const SUMMARY: &str = "
+SELECT stats.collection_mint, stats.members, stats.verified_members, stats.first_seen, stats.unique_holders,
+    coalesce(collection.name, stats.collection_name) AS name,
+    collection.symbol,
+    json.image,
+    coalesce(collection.seller_fee_basis_points, stats.royalty_bps)::integer AS royalty_bps
+FROM collection_stats stats
+LEFT JOIN nft_metadata collection ON collection.mint_address = stats.collection_mint
+LEFT JOIN nft_json_metadata json ON json.mint_address = stats.collection_mint
+{filter}
+ORDER BY {order}, stats.collection_mint
+LIMIT $1 OFFSET $2";
+
+const COUNT: &str = "SELECT count(*) AS total FROM collection_stats";
+
+// one replica refreshes at a time, the others skip the round instead of queueing behind it
+const REFRESH_LOCK: &str = "SELECT pg_try_advisory_xact_lock(hashtext('collection_stats')) AS locked";
+const REFRESH: &str = "REFRESH MATERIALIZED VIEW CONCURRENTLY collection_stats";
+
+// every creator listed on a member, most used first
+const CREATORS: &str = "
+SELECT md.collection_mint, c.creator_address AS address, bool_or(c.verified) AS verified,
+    count(DISTINCT md.mint_address) AS members
+FROM nft_metadata md JOIN nft_creator c ON c.metadata_address = md.metadata_address
+WHERE md.collection_mint = ANY($1) AND md.status = 'active'
+GROUP BY md.collection_mint, c.creator_address
+ORDER BY members DESC, address";
+
+#[derive(Debug, FromQueryResult)]
+struct SummaryRow {
+    collection_mint: String,
+    members: i64,
+    verified_members: i64,
+    name: Option<String>,
+    symbol: Option<String>,
+    image: Option<String>,
+    royalty_bps: i32,
+    unique_holders: i64,
+}
+
+#[derive(Debug, FromQueryResult)]
+struct CreatorRow {
+    collection_mint: String,
+    address: String,
+    verified: bool,
+    members: i64,
+}
+
+#[derive(Debug, FromQueryResult)]
+struct CountRow {
+    total: i64,
+}
+
+fn page_bounds(page: Option<u64>, size: Option<u64>) -> (u64, u64) {
+    (page.unwrap_or(1).max(1), size.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE))
+}
+
+fn order_by(sort: CollectionSort) -> &'static str {
+    match sort {
+        CollectionSort::Members => "verified_members DESC, members DESC",
+        CollectionSort::Holders => "unique_holders DESC",
+        CollectionSort::Name => "name ASC NULLS LAST",
+        CollectionSort::Newest => "first_seen DESC",
+    }
+}
+
+pub async fn list_collections<C: ConnectionTrait>(
+    conn: &C,
+    query: &CollectionsQuery,
+) -> Result<CollectionsResponse, DbErr> {
+    let (page, size) = page_bounds(query.page, query.size);
+    let sql = SUMMARY.replace("{filter}", "").replace("{order}", order_by(query.sort));
+    let items = summaries(conn, &sql, vec![(size as i64).into(), (((page - 1) * size) as i64).into()]).await?;
+
+    let total = CountRow::find_by_statement(Statement::from_string(DbBackend::Postgres, COUNT))
+        .one(conn)
+        .await?
+        .map_or(0, |row| row.total);
+
+    Ok(CollectionsResponse {
+        items,
+        total,
+        page,
+        size,
+    })
+}
+
+// None when no active nft claims the collection
+pub async fn find_collection<C: ConnectionTrait>(
+    conn: &C,
+    collection_mint: &str,
+) -> Result<Option<CollectionSummary>, DbErr> {
+    let sql = SUMMARY
+        .replace("{filter}", "WHERE stats.collection_mint = $3")
+        .replace("{order}", order_by(CollectionSort::default()));
+    let summaries = summaries(conn, &sql, vec![1i64.into(), 0i64.into(), collection_mint.into()]).await?;
+    Ok(summaries.into_iter().next())
+}
+
+// recomputes collection_stats without blocking the requests reading it. false when another replica was already at it
+pub async fn refresh_collection_stats(db: &DatabaseConnection) -> Result<bool, DbErr> {
+    let txn = db.begin().await?;
+    let locked = txn
+        .query_one(Statement::from_string(DbBackend::Postgres, REFRESH_LOCK))
+        .await?
+        .map(|row| row.try_get::<bool>("", "locked"))
+        .transpose()?
+        .unwrap_or(false);
+    if locked {
+        txn.execute_unprepared(REFRESH).await?;
+    }
+    txn.commit().await?;
+    Ok(locked)
+}
+
+// the active members of a collection, a page at a time. anyone can claim a collection, so only verified members are
+// listed unless `verified=false` asks for the unverified ones
+pub async fn find_collection_nfts<C: ConnectionTrait>(
+    conn: &C,
+    collection_mint: &str,
+    query: &CollectionNftsQuery,
+    include: &AssetInclude,
+) -> Result<CollectionNftsResponse, DbErr> {
+    let (page, size) = page_bounds(query.page, query.size);
+
+    let mut select = nft_metadata::Entity::find()
+        .filter(nft_metadata::Column::CollectionMint.eq(collection_mint))
+        .filter(nft_metadata::Column::Status.eq(nft_metadata::STATUS_ACTIVE))
+        .filter(nft_metadata::Column::CollectionVerified.eq(query.verified.unwrap_or(true)));
+    let total = select.clone().count(conn).await? as i64;
+
+    select = match query.sort {
+        CollectionNftSort::Name => select.order_by_asc(nft_metadata::Column::Name),
+        CollectionNftSort::Newest => select.order_by_desc(nft_metadata::Column::CreatedAt),
+    };
+    let mint_addresses: Vec<String> = select
+        .order_by_asc(nft_metadata::Column::MintAddress)
+        .select_only()
+        .column(nft_metadata::Column::MintAddress)
+        .offset((page - 1) * size)
+        .limit(size)
+        .into_tuple()
+        .all(conn)
+        .await?;
+
+    Ok(CollectionNftsResponse {
+        collection_mint: collection_mint.to_string(),
+        items: find_assets_details(conn, &mint_addresses, include).await?,
+        total,
+        page,
+        size,
+    })
+}
+
+async fn summaries<C: ConnectionTrait>(
+    conn: &C,
+    sql: &str,
+    values: Vec<sea_orm::Value>,
+) -> Result<Vec<CollectionSummary>, DbErr> {
+    let rows = SummaryRow::find_by_statement(Statement::from_sql_and_values(DbBackend::Postgres, sql, values))
+        .all(conn)
+        .await?;
+    if rows.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let collection_mints: Vec<String> = rows.iter().map(|row| row.collection_mint.clone()).collect();
+    let mut creators: HashMap<String, Vec<CollectionCreator>> = HashMap::new();
+    for row in CreatorRow::find_by_statement(Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        CREATORS,
+        vec![collection_mints.into()],
+    ))
+    .all(conn)
+    .await?
+    {
+        creators.entry(row.collection_mint).or_default().push(CollectionCreator {
+            address: row.address,
+            verified: row.verified,
+            members: row.members,
+        });
+    }
+
+    Ok(rows
+        .into_iter()
+        .map(|row| CollectionSummary {
+            creators: creators.remove(&row.collection_mint).unwrap_or_default(),
+            collection_mint: row.collection_mint,
+            name: row.name,
+            symbol: row.symbol,
+            image: row.image,
+            members: row.members,
+            verified_members: row.verified_members,
+            unique_holders: row.unique_holders,
+            royalty: CollectionRoyalty {
+                basis_points: row.royalty_bps,
+                percent: row.royalty_bps as f64 / 10_000.0,
+            },
+        })
+        .collect())
+}
@@ -0,0 +1,498 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use sea_orm::sea_query::{Expr, Order, Query, SimpleExpr};
+use sea_orm::{ColumnTrait, Condition, ConnectionTrait, DbErr, EntityTrait, QueryFilter, QueryOrder, QuerySelect};
+use serde_json::Value;
+
+use crate::assets::details::find_assets_details;
+use crate::entities::{nft_creator, nft_metadata, nft_ownership};
+use crate::types::asset::{AssetDetails, AssetInclude};
+use crate::types::das::{
+    DasConditionType, DasPage, DasSortDirection, DasSortField, GetAssetsByAuthorityParams, GetAssetsByCreatorParams,
+    GetAssetsByGroupParams, GetAssetsByOwnerParams, SearchAssetsParams,
+};
+use crate::types::helius::{
+    AssetContent, AttributeInfo, AuthorityInfo, CompressionInfo, CreatorInfo, FileInfo, GroupingInfo, HeliusAsset,
+    HeliusResult, LinksInfo, MetadataContent, OwnershipInfo, RoyaltyInfo, SupplyInfo,
+};
+
+pub const MAX_LIMIT: u32 = 1000;
+pub const MAX_BATCH_SIZE: usize = 1000;
+const NFT_SCHEMA: &str = "https://schema.metaplex.com/nft1.0.json";
+
+#[derive(Debug)]
+pub enum DasError {
+    InvalidParams(String), // maps to json-rpc -32602
+    Db(DbErr),
+}
+
+impl fmt::Display for DasError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DasError::InvalidParams(msg) => write!(f, "Invalid params : {}", msg),
+            DasError::Db(e) => write!(f, "Database error : {}", e),
+        }
+    }
+}
+
+impl From<DbErr> for DasError {
+    fn from(e: DbErr) -> Self {
+        DasError::Db(e)
+    }
+}
+
+pub async fn get_asset<C: ConnectionTrait>(conn: &C, id: &str) -> Result<Option<HeliusAsset>, DasError> {
+    Ok(get_asset_batch(conn, &[id.to_string()]).await?.pop().flatten())
+}
+
+// one entry per id in the same order, null for the ones we don't know
+pub async fn get_asset_batch<C: ConnectionTrait>(
+    conn: &C,
+    ids: &[String],
+) -> Result<Vec<Option<HeliusAsset>>, DasError> {
+    if ids.len() > MAX_BATCH_SIZE {
+        return Err(DasError::InvalidParams(format!("At most {} ids per batch", MAX_BATCH_SIZE)));
+    }
+
+    let mut found: HashMap<String, HeliusAsset> =
+        find_assets_details(conn, ids, &AssetInclude::ALL)
+            .await?
+            .into_iter()
+            .map(|details| (details.mint.mint_address.clone(), to_das_asset(details)))
+            .collect();
+    Ok(ids.iter().map(|id| found.remove(id)).collect())
+}
+
+pub async fn get_assets_by_owner<C: ConnectionTrait>(
+    conn: &C,
+    params: &GetAssetsByOwnerParams,
+) -> Result<HeliusResult, DasError> {
+    find_page(conn, Condition::all().add(owned_by(&params.owner_address)), &params.page).await
+}
+
+pub async fn get_assets_by_creator<C: ConnectionTrait>(
+    conn: &C,
+    params: &GetAssetsByCreatorParams,
+) -> Result<HeliusResult, DasError> {
+    let verified = params.only_verified.then_some(true);
+    find_page(conn, Condition::all().add(created_by(&params.creator_address, verified)), &params.page).await
+}
+
+pub async fn get_assets_by_group<C: ConnectionTrait>(
+    conn: &C,
+    params: &GetAssetsByGroupParams,
+) -> Result<HeliusResult, DasError> {
+    find_page(conn, Condition::all().add(grouped_by(&params.group_key, &params.group_value)?), &params.page).await
+}
+
+pub async fn get_assets_by_authority<C: ConnectionTrait>(
+    conn: &C,
+    params: &GetAssetsByAuthorityParams,
+) -> Result<HeliusResult, DasError> {
+    let condition = Condition::all().add(nft_metadata::Column::UpdateAuthority.eq(&params.authority_address));
+    find_page(conn, condition, &params.page).await
+}
+
+// every given criterion, combined with AND or OR by conditionType. burnt assets only show up when asked for
+pub async fn search_assets<C: ConnectionTrait>(conn: &C, params: &SearchAssetsParams) -> Result<HeliusResult, DasError> {
+    let mut criteria = match params.condition_type {
+        DasConditionType::All => Condition::all(),
+        DasConditionType::Any => Condition::any(),
+    };
+
+    if let Some(owner) = &params.owner_address {
+        criteria = criteria.add(owned_by(owner));
+    }
+    if let Some(creator) = &params.creator_address {
+        criteria = criteria.add(created_by(creator, params.creator_verified));
+    }
+    if let Some(authority) = &params.authority_address {
+        criteria = criteria.add(nft_metadata::Column::UpdateAuthority.eq(authority));
+    }
+    if let Some((group_key, group_value)) = &params.grouping {
+        criteria = criteria.add(grouped_by(group_key, group_value)?);
+    }
+    if let Some(interface) = &params.interface {
+        criteria = criteria.add(interface_condition(interface)?);
+    }
+    if let Some(frozen) = params.frozen {
+        criteria = criteria.add(ownership_where(nft_ownership::Column::Frozen.eq(frozen)));
+    }
+    if let Some(json_uri) = &params.json_uri {
+        criteria = criteria.add(nft_metadata::Column::MetadataUri.eq(json_uri));
+    }
+
+    let mut condition = Condition::all().add(criteria);
+    condition = match params.burnt {
+        Some(true) => condition.add(nft_metadata::Column::Status.ne(nft_metadata::STATUS_ACTIVE)),
+        _ => condition.add(nft_metadata::Column::Status.eq(nft_metadata::STATUS_ACTIVE)),
+    };
+    find_assets(conn, condition, &params.page).await
+}
+
+// the by* methods only list live assets
+async fn find_page<C: ConnectionTrait>(
+    conn: &C,
+    condition: Condition,
+    page: &DasPage,
+) -> Result<HeliusResult, DasError> {
+    let condition = condition.add(nft_metadata::Column::Status.eq(nft_metadata::STATUS_ACTIVE));
+    find_assets(conn, condition, page).await
+}
+
+async fn find_assets<C: ConnectionTrait>(
+    conn: &C,
+    condition: Condition,
+    page: &DasPage,
+) -> Result<HeliusResult, DasError> {
+    let limit = page.limit.unwrap_or(MAX_LIMIT);
+    if limit == 0 || limit > MAX_LIMIT {
+        return Err(DasError::InvalidParams(format!("limit must be between 1 and {}", MAX_LIMIT)));
+    }
+    let page_number = page.page.unwrap_or(1);
+    if page_number == 0 {
+        return Err(DasError::InvalidParams("page starts at 1".to_string()));
+    }
+
+    let sort = page.sort_by.unwrap_or_default();
+    let order = match sort.sort_direction {
+        DasSortDirection::Asc => Order::Asc,
+        DasSortDirection::Desc => Order::Desc,
+    };
+
+    let mut select = nft_metadata::Entity::find()
+        .select_only()
+        .column(nft_metadata::Column::MintAddress)
+        .filter(condition);
+    select = match sort.sort_by {
+        DasSortField::Created => select.order_by(nft_metadata::Column::CreatedAt, order),
+        DasSortField::Updated | DasSortField::RecentAction => select.order_by(
+            Expr::cust("coalesce(nft_metadata.status_updated_at, nft_metadata.created_at)"),
+            order,
+        ),
+        DasSortField::Id => select.order_by(nft_metadata::Column::MintAddress, order),
+        DasSortField::None => select,
+    };
+
+    // the mint address keeps pages stable when the sort column has ties
+    let mint_addresses: Vec<String> = select
+        .order_by_asc(nft_metadata::Column::MintAddress)
+        .offset(((page_number - 1) as u64) * limit as u64)
+        .limit(limit as u64)
+        .into_tuple()
+        .all(conn)
+        .await?;
+
+    let items: Vec<HeliusAsset> = find_assets_details(conn, &mint_addresses, &AssetInclude::ALL)
+        .await?
+        .into_iter()
+        .map(to_das_asset)
+        .collect();
+
+    // in DAS `total` is the number of items on this page
+    Ok(HeliusResult {
+        total: items.len() as u32,
+        limit,
+        page: page_number,
+        items,
+    })
+}
+
+fn ownership_where(condition: SimpleExpr) -> SimpleExpr {
+    nft_metadata::Column::MintAddress.in_subquery(
+        Query::select()
+            .column(nft_ownership::Column::MintAddress)
+            .from(nft_ownership::Entity)
+            .and_where(condition)
+            .to_owned(),
+    )
+}
+
+fn owned_by(owner: &str) -> SimpleExpr {
+    ownership_where(nft_ownership::Column::Owner.eq(owner))
+}
+
+fn created_by(creator: &str, verified: Option<bool>) -> SimpleExpr {
+    let mut creators = Query::select()
+        .column(nft_creator::Column::MetadataAddress)
+        .from(nft_creator::Entity)
+        .and_where(nft_creator::Column::CreatorAddress.eq(creator))
+        .to_owned();
+    if let Some(verified) = verified {
+        creators.and_where(nft_creator::Column::Verified.eq(verified));
+    }
+    nft_metadata::Column::MetadataAddress.in_subquery(creators)
+}
+
+fn grouped_by(group_key: &str, group_value: &str) -> Result<SimpleExpr, DasError> {
+    if group_key != "collection" {
+        return Err(DasError::InvalidParams(format!("Unsupported group key {}", group_key)));
+    }
+    Ok(nft_metadata::Column::CollectionMint.eq(group_value))
+}
+
+// DAS interface names and the TokenStandard variant names we store
+const INTERFACES: &[(&str, &str)] = &[
+    ("V1_NFT", "NonFungible"),
+    ("V1_PRINT", "NonFungibleEdition"),
+    ("ProgrammableNFT", "ProgrammableNonFungible"),
+    ("ProgrammableNFT", "ProgrammableNonFungibleEdition"),
+    ("FungibleToken", "Fungible"),
+    ("FungibleAsset", "FungibleAsset"),
+];
+
+pub fn interface_for(token_standard: Option<&str>) -> &'static str {
+    token_standard
+        .and_then(|standard| INTERFACES.iter().find(|(_, stored)| *stored == standard))
+        .map(|(interface, _)| *interface)
+        .unwrap_or("Custom")
+}
+
+fn interface_condition(interface: &str) -> Result<SimpleExpr, DasError> {
+    let interface = if interface == "LEGACY_NFT" { "V1_NFT" } else { interface };
+    if interface == "Custom" {
+        return Ok(nft_metadata::Column::TokenStandard.is_null());
+    }
+    let standards: Vec<&str> = INTERFACES
+        .iter()
+        .filter(|(name, _)| *name == interface)
+        .map(|(_, stored)| *stored)
+        .collect();
+    if standards.is_empty() {
+        return Err(DasError::InvalidParams(format!("Unsupported interface {}", interface)));
+    }
+    Ok(nft_metadata::Column::TokenStandard.is_in(standards))
+}
+
+fn mime_from_uri(uri: &str) -> String {
+    let extension = uri.split(['?', '#']).next().unwrap_or_default().rsplit('.').next().unwrap_or_default();
+    match extension.to_lowercase().as_str() {
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "webp" => "image/webp",
+        "mp4" => "video/mp4",
+        "glb" => "model/gltf-binary",
+        "html" => "text/html",
+        _ => "image/png", // most images are served without an extension, png is what DAS providers fall back to as well
+    }
+    .to_string()
+}
+
+// properties.files from the json metadata, or just the image when there are none
+fn files(properties: Option<&Value>, image: Option<&String>) -> Vec<FileInfo> {
+    let listed: Vec<FileInfo> = properties
+        .and_then(|properties| properties["files"].as_array())
+        .map(|files| {
+            files
+                .iter()
+                .filter_map(|file| {
+                    let uri = file["uri"].as_str()?.to_string();
+                    let mime = file["type"]
+                        .as_str()
+                        .map(str::to_string)
+                        .unwrap_or_else(|| mime_from_uri(&uri));
+                    Some(FileInfo {
+                        uri,
+                        cdn_uri: None,
+                        mime,
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    if !listed.is_empty() {
+        return listed;
+    }
+    image
+        .map(|image| {
+            vec![FileInfo {
+                uri: image.clone(),
+                cdn_uri: None,
+                mime: mime_from_uri(image),
+            }]
+        })
+        .unwrap_or_default()
+}
+
+fn attributes(attributes: Option<&Value>) -> Option<Vec<AttributeInfo>> {
+    let attributes = attributes?.as_array()?;
+    Some(
+        attributes
+            .iter()
+            .filter_map(|attribute| {
+                let value = match &attribute["value"] {
+                    Value::String(value) => value.clone(),
+                    Value::Null => return None,
+                    other => other.to_string(), // numbers and bools are common, DAS returns them as strings
+                };
+                Some(AttributeInfo {
+                    trait_type: attribute["trait_type"].as_str().unwrap_or_default().to_string(),
+                    value,
+                })
+            })
+            .collect(),
+    )
+}
+
+// our joined view of an asset in the DAS response shape
+pub fn to_das_asset(details: AssetDetails) -> HeliusAsset {
+    let interface = interface_for(details.token_standard.as_deref());
+    let fungible = matches!(interface, "FungibleToken" | "FungibleAsset");
+    let metadata = details.mint.metadata;
+    let content = details.content;
+
+    HeliusAsset {
+        interface: interface.to_string(),
+        id: details.mint.mint_address,
+        content: AssetContent {
+            schema: Some(NFT_SCHEMA.to_string()),
+            json_uri: metadata.metadata_uri.unwrap_or_default(),
+            files: Some(files(
+                content.as_ref().and_then(|content| content.properties.as_ref()),
+                content.as_ref().and_then(|content| content.image.as_ref()),
+            )),
+            metadata: MetadataContent {
+                attributes: attributes(content.as_ref().and_then(|content| content.attributes.as_ref())),
+                description: content.as_ref().and_then(|content| content.description.clone()),
+                name: metadata.name.unwrap_or_default(),
+                symbol: metadata.symbol,
+                token_standard: details.token_standard,
+            },
+            links: LinksInfo {
+                external_url: content.as_ref().and_then(|content| content.external_url.clone()),
+                image: content.as_ref().and_then(|content| content.image.clone()),
+                animation_url: content.as_ref().and_then(|content| content.animation_url.clone()),
+            },
+        },
+        authorities: metadata
+            .update_authority
+            .map(|address| {
+                vec![AuthorityInfo {
+                    address,
+                    scopes: vec!["full".to_string()],
+                }]
+            })
+            .unwrap_or_default(),
+        compression: Some(CompressionInfo {
+            eligible: false,
+            compressed: false,
+            data_hash: None,
+            creator_hash: None,
+            asset_hash: None,
+            tree: None,
+            seq: None,
+            leaf_id: None,
+        }),
+        grouping: Some(
+            details
+                .collection
+                .and_then(|collection| {
+                    Some(GroupingInfo {
+                        group_key: "collection".to_string(),
+                        group_value: collection.mint?,
+                        verified: collection.verified,
+                        collection_metadata: None,
+                    })
+                })
+                .into_iter()
+                .collect(),
+        ),
+        royalty: details
+            .royalty
+            .map(|royalty| RoyaltyInfo {
+                royalty_model: royalty.royalty_model,
+                target: royalty.target,
+                percent: royalty.percent,
+                basis_points: royalty.basis_points,
+                primary_sale_happened: royalty.primary_sale_happened,
+                locked: royalty.locked,
+            })
+            .unwrap_or(RoyaltyInfo {
+                royalty_model: "creators".to_string(),
+                target: None,
+                percent: 0.0,
+                basis_points: 0,
+                primary_sale_happened: metadata.primary_sale_happened,
+                locked: false,
+            }),
+        creators: details
+            .creators
+            .unwrap_or_default()
+            .into_iter()
+            .map(|creator| CreatorInfo {
+                address: creator.address,
+                share: creator.share.clamp(0, 100) as u8,
+                verified: creator.verified,
+            })
+            .collect(),
+        ownership: details
+            .ownership
+            .map(|ownership| OwnershipInfo {
+                frozen: ownership.frozen,
+                delegated: ownership.delegated,
+                delegate: ownership.delegate,
+                ownership_model: ownership.ownership_model,
+                owner: ownership.owner,
+            })
+            .unwrap_or(OwnershipInfo {
+                frozen: false,
+                delegated: false,
+                delegate: None,
+                ownership_model: if fungible { "token" } else { "single" }.to_string(),
+                owner: String::new(), // no ownership row yet
+            }),
+        supply: SupplyInfo {
+            print_max_supply: 0,
+            print_current_supply: 0,
+            edition_nonce: None,
+        },
+        mutable: metadata.is_mutable,
+        burnt: details
+            .status
+            .is_some_and(|status| status != nft_metadata::STATUS_ACTIVE),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn interfaces_round_trip_through_token_standards() {
+        assert_eq!(interface_for(Some("NonFungible")), "V1_NFT");
+        assert_eq!(interface_for(Some("ProgrammableNonFungibleEdition")), "ProgrammableNFT");
+        assert_eq!(interface_for(None), "Custom");
+        assert!(interface_condition("LEGACY_NFT").is_ok());
+        assert!(interface_condition("Identity").is_err());
+    }
+
+    #[test]
+    fn attribute_values_become_strings() {
+        let parsed = attributes(Some(&json!([
+            { "trait_type": "Level", "value": 3 },
+            { "trait_type": "Fur", "value": "Gold" },
+            { "trait_type": "Empty", "value": null }
+        ])))
+        .unwrap();
+        let values: Vec<&str> = parsed.iter().map(|attribute| attribute.value.as_str()).collect();
+        assert_eq!(values, vec!["3", "Gold"]);
+    }
+
+    #[test]
+    fn files_fall_back_to_the_image() {
+        let listed = files(
+            Some(&json!({ "files": [{ "uri": "https://a/1.mp4", "type": "video/mp4" }] })),
+            Some(&"https://a/1.png".to_string()),
+        );
+        assert_eq!(listed[0].mime, "video/mp4");
+
+        let image_only = files(Some(&json!({})), Some(&"https://a/1.jpg?ext=jpg".to_string()));
+        assert_eq!(image_only[0].uri, "https://a/1.jpg?ext=jpg");
+        assert_eq!(image_only[0].mime, "image/jpeg");
+    }
+}
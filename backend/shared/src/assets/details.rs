@@ -0,0 +1,311 @@
+use std::collections::{BTreeMap, HashMap};
+
+use sea_orm::sea_query::{Expr, PgFunc};
+use sea_orm::{
+    ColumnTrait, ConnectionTrait, DbErr, EntityTrait, FromQueryResult, JoinType, QueryFilter, QuerySelect,
+    RelationTrait, Select,
+};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::entities::{mint, nft_json_metadata, nft_metadata, nft_ownership, nft_royalty};
+use crate::types::asset::{
+    AssetCollection, AssetContent, AssetCreator, AssetDetails, AssetInclude, AssetOwnership, AssetRoyalty,
+    DetailsEntry,
+};
+use crate::types::mint::{MintResponse, PartialMetadata};
+use crate::SPL_TOKEN_PROGRAM;
+
+// the joined rows come back as jsonb and are read into the entity models, a left join with no match is NULL
+const METADATA: &str = "to_jsonb(nft_metadata)";
+const JSON_METADATA: &str = "to_jsonb(nft_json_metadata)";
+const OWNERSHIP: &str = "to_jsonb(nft_ownership)";
+const ROYALTY: &str = "to_jsonb(nft_royalty)";
+// creators hang off the metadata pda, a correlated aggregate keeps it one row per mint
+const CREATORS: &str = "(SELECT jsonb_agg(
+        jsonb_build_object('address', c.creator_address, 'verified', c.verified, 'share', c.share)
+        ORDER BY c.created_at, c.creator_address)
+    FROM nft_creator c WHERE c.metadata_address = nft_metadata.metadata_address)";
+const SKIPPED: &str = "NULL::jsonb";
+
+#[derive(Debug, FromQueryResult)]
+struct AssetRow {
+    mint_address: String,
+    decimal: i16,
+    supply: i64,
+    mint_authority: Option<String>,
+    freeze_authority: Option<String>,
+    is_initialized: bool,
+    metadata: Option<Value>,
+    creators: Option<Value>,
+    json_metadata: Option<Value>,
+    ownership: Option<Value>,
+    royalty: Option<Value>,
+}
+
+// mint joined with every table the included blocks need, through the entity relations
+fn details_query(include: &AssetInclude) -> Select<mint::Entity> {
+    let pick = |included: bool, expr: &str| Expr::cust(if included { expr } else { SKIPPED });
+
+    let mut select = mint::Entity::find()
+        .select_only()
+        .columns([
+            mint::Column::MintAddress,
+            mint::Column::Decimal,
+            mint::Column::Supply,
+            mint::Column::MintAuthority,
+            mint::Column::FreezeAuthority,
+            mint::Column::IsInitialized,
+        ])
+        .join(JoinType::LeftJoin, mint::Relation::NftMetadata.def())
+        .column_as(Expr::cust(METADATA), "metadata")
+        .column_as(pick(include.creators, CREATORS), "creators")
+        .column_as(pick(include.content || include.collection, JSON_METADATA), "json_metadata")
+        .column_as(pick(include.ownership, OWNERSHIP), "ownership")
+        .column_as(pick(include.royalty, ROYALTY), "royalty");
+
+    if include.content || include.collection {
+        select = select.join(JoinType::LeftJoin, mint::Relation::NftJsonMetadata.def());
+    }
+    if include.ownership {
+        select = select.join(JoinType::LeftJoin, mint::Relation::NftOwnership.def());
+    }
+    if include.royalty {
+        select = select.join(JoinType::LeftJoin, mint::Relation::NftRoyalty.def());
+    }
+    select
+}
+
+// everything stored about one mint in a single round trip. None when the mint itself is unknown
+pub async fn find_asset_details<C: ConnectionTrait>(
+    conn: &C,
+    mint_address: &str,
+    include: &AssetInclude,
+) -> Result<Option<AssetDetails>, DbErr> {
+    let row = details_query(include)
+        .filter(mint::Column::MintAddress.eq(mint_address))
+        .into_model::<AssetRow>()
+        .one(conn)
+        .await?;
+
+    row.map(|row| into_details(row, include)).transpose()
+}
+
+// the same for many mints in one query, in the order they were asked for. unknown mints are left out
+pub async fn find_assets_details<C: ConnectionTrait>(
+    conn: &C,
+    mint_addresses: &[String],
+    include: &AssetInclude,
+) -> Result<Vec<AssetDetails>, DbErr> {
+    if mint_addresses.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let mut by_mint: HashMap<String, AssetDetails> = HashMap::new();
+    for row in details_query(include)
+        // one array parameter instead of a placeholder per mint, the statement stays the same for any batch size
+        .filter(
+            Expr::col((mint::Entity, mint::Column::MintAddress)).eq(PgFunc::any(Expr::val(mint_addresses.to_vec()))),
+        )
+        .into_model::<AssetRow>()
+        .all(conn)
+        .await?
+    {
+        let details = into_details(row, include)?;
+        by_mint.insert(details.mint.mint_address.clone(), details);
+    }
+
+    Ok(mint_addresses
+        .iter()
+        .filter_map(|mint_address| by_mint.remove(mint_address))
+        .collect())
+}
+
+// POST /details: every requested mint is a key, the ones we don't know get a not found entry instead of being dropped
+pub async fn find_details_batch<C: ConnectionTrait>(
+    conn: &C,
+    mint_addresses: &[String],
+    include: &AssetInclude,
+) -> Result<BTreeMap<String, DetailsEntry>, DbErr> {
+    let mut entries: BTreeMap<String, DetailsEntry> = find_assets_details(conn, mint_addresses, include)
+        .await?
+        .into_iter()
+        .map(|details| (details.mint.mint_address.clone(), DetailsEntry::Found(Box::new(details))))
+        .collect();
+
+    for mint_address in mint_addresses {
+        entries
+            .entry(mint_address.clone())
+            .or_insert_with(|| DetailsEntry::not_found(mint_address));
+    }
+    Ok(entries)
+}
+
+fn parse<T: DeserializeOwned>(value: Option<Value>, what: &str) -> Result<Option<T>, DbErr> {
+    value
+        .map(|value| serde_json::from_value(value).map_err(|e| DbErr::Json(format!("Failed to read {} {}", what, e))))
+        .transpose()
+}
+
+fn into_details(row: AssetRow, include: &AssetInclude) -> Result<AssetDetails, DbErr> {
+    let metadata: Option<nft_metadata::Model> = parse(row.metadata, "nft_metadata")?;
+    let json_metadata: Option<nft_json_metadata::Model> = parse(row.json_metadata, "nft_json_metadata")?;
+    let ownership: Option<nft_ownership::Model> = parse(row.ownership, "nft_ownership")?;
+    let royalty: Option<nft_royalty::Model> = parse(row.royalty, "nft_royalty")?;
+
+    let creators = if include.creators {
+        // a mint without metadata has no creators at all, one with metadata but no rows has an empty list
+        match (&metadata, row.creators) {
+            (None, _) => None,
+            (Some(_), creators) => Some(parse::<Vec<AssetCreator>>(creators, "nft_creator")?.unwrap_or_default()),
+        }
+    } else {
+        None
+    };
+
+    // nft_royalty is only there for non standard models, everything else is the metadata royalty split between creators
+    let royalty = match (royalty, &metadata) {
+        _ if !include.royalty => None,
+        (Some(royalty), _) => Some(AssetRoyalty {
+            royalty_model: royalty.royalty_model,
+            target: royalty.target,
+            basis_points: royalty.basis_points as i32,
+            percent: royalty.percent,
+            primary_sale_happened: royalty.primary_sale_happened,
+            locked: royalty.locked,
+        }),
+        (None, Some(metadata)) => Some(AssetRoyalty {
+            royalty_model: "creators".to_string(),
+            target: None,
+            basis_points: metadata.seller_fee_basis_points as i32,
+            percent: metadata.seller_fee_basis_points as f64 / 10_000.0,
+            primary_sale_happened: metadata.primary_sale_happened,
+            locked: false,
+        }),
+        (None, None) => None,
+    };
+
+    let ownership = ownership.map(|ownership| AssetOwnership {
+        owner: ownership.owner,
+        delegate: ownership.delegate,
+        frozen: ownership.frozen,
+        delegated: ownership.delegated,
+        ownership_model: ownership.ownership_model,
+    });
+
+    let collection = match &metadata {
+        Some(metadata)
+            if include.collection && (metadata.collection_mint.is_some() || metadata.collection_name.is_some()) =>
+        {
+            Some(AssetCollection {
+                mint: metadata.collection_mint.clone(),
+                name: metadata.collection_name.clone(),
+                verified: metadata.collection_verified,
+                family: json_metadata.as_ref().and_then(|json| json.collection_family.clone()),
+            })
+        }
+        _ => None,
+    };
+
+    let content = json_metadata.filter(|_| include.content).map(|json| AssetContent {
+        description: json.description,
+        image: json.image,
+        animation_url: json.animation_url,
+        external_url: json.external_url,
+        attributes: json.attributes,
+        properties: json.properties,
+    });
+
+    let status = metadata.as_ref().map(|metadata| metadata.status.clone());
+    let token_standard = metadata.as_ref().and_then(|metadata| metadata.token_standard.clone());
+    let partial_metadata = match metadata {
+        Some(metadata) => PartialMetadata {
+            name: Some(metadata.name),
+            symbol: metadata.symbol,
+            metadata_uri: Some(metadata.metadata_uri),
+            seller_fee_basis_points: metadata.seller_fee_basis_points,
+            update_authority: Some(metadata.update_authority),
+            is_mutable: metadata.is_mutable,
+            primary_sale_happened: metadata.primary_sale_happened,
+        },
+        None => PartialMetadata {
+            name: None,
+            symbol: None,
+            metadata_uri: None,
+            seller_fee_basis_points: 0,
+            update_authority: None,
+            is_mutable: false,
+            primary_sale_happened: false,
+        },
+    };
+
+    Ok(AssetDetails {
+        mint: MintResponse {
+            mint_address: row.mint_address,
+            owner: SPL_TOKEN_PROGRAM.to_string(),
+            mint_authority: row.mint_authority.unwrap_or_default(),
+            supply: row.supply,
+            decimal: row.decimal,
+            is_initialized: row.is_initialized,
+            freeze_authority: row.freeze_authority,
+            metadata: partial_metadata,
+        },
+        status,
+        token_standard,
+        creators,
+        royalty,
+        ownership,
+        content,
+        collection,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use sea_orm::{DbBackend, QueryTrait};
+
+    use super::*;
+    use crate::entities::nft_creator;
+
+    #[test]
+    fn relations_join_on_the_right_columns() {
+        let sql = mint::Entity::find()
+            .find_also_related(nft_metadata::Entity)
+            .build(DbBackend::Postgres)
+            .to_string();
+        assert!(sql.contains(r#"ON "mint"."mint_address" = "nft_metadata"."mint_address""#), "{}", sql);
+
+        let sql = nft_metadata::Entity::find()
+            .find_with_related(nft_creator::Entity)
+            .build(DbBackend::Postgres)
+            .to_string();
+        assert!(
+            sql.contains(r#"ON "nft_metadata"."metadata_address" = "nft_creator"."metadata_address""#),
+            "{}",
+            sql
+        );
+    }
+
+    #[test]
+    fn include_defaults_to_everything() {
+        assert_eq!(AssetInclude::parse(None), Ok(AssetInclude::ALL));
+        assert_eq!(AssetInclude::parse(Some("none")), Ok(AssetInclude::NONE));
+        let include = AssetInclude::parse(Some("creators, json")).unwrap();
+        assert!(include.creators && include.content && !include.royalty);
+        assert!(AssetInclude::parse(Some("owners")).is_err());
+    }
+
+    #[test]
+    fn details_query_only_joins_included_tables() {
+        let sql = details_query(&AssetInclude::NONE).build(DbBackend::Postgres).to_string();
+        assert!(sql.contains(r#"LEFT JOIN "nft_metadata""#));
+        assert!(!sql.contains(r#"LEFT JOIN "nft_ownership""#));
+        assert!(!sql.contains("nft_creator"));
+
+        let sql = details_query(&AssetInclude::ALL).build(DbBackend::Postgres).to_string();
+        for table in ["nft_json_metadata", "nft_ownership", "nft_royalty"] {
+            assert!(sql.contains(&format!(r#"LEFT JOIN "{}""#, table)), "{}", sql);
+        }
+        assert!(sql.contains("FROM nft_creator c"));
+    }
+}
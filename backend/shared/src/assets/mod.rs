@@ -0,0 +1,4 @@
+pub mod collections;
+pub mod das;
+pub mod details;
+pub mod owners;
@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+
+use sea_orm::sea_query::{Expr, SimpleExpr};
+use sea_orm::{
+    ColumnTrait, ConnectionTrait, DbErr, EntityTrait, FromQueryResult, JoinType, QueryFilter, QueryOrder, QuerySelect,
+    RelationTrait,
+};
+
+use crate::assets::details::find_assets_details;
+use crate::entities::{mint, nft_metadata, token_account};
+use crate::redis::worker::MAX_INDEXED_DECIMALS;
+use crate::types::asset::{AssetInclude, Holding, HoldingsQuery, HoldingsResponse};
+
+pub const DEFAULT_PAGE_SIZE: i64 = 50;
+pub const MAX_PAGE_SIZE: i64 = 200;
+
+// what a holding embeds, the rest is a /details call away
+const EMBEDDED: AssetInclude = AssetInclude {
+    content: true,
+    collection: true,
+    ..AssetInclude::NONE
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HoldingKind {
+    Nfts,
+    Tokens,
+}
+
+impl HoldingKind {
+    // an nft is a mint with no decimals and a supply of one. tokens are the other mints the worker indexes, editions
+    // and low decimal tokens that have metadata. fungibles like USDC or wrapped SOL are never stored, so never listed
+    fn condition(self) -> SimpleExpr {
+        let nft = mint::Column::Decimal.eq(0).and(mint::Column::Supply.eq(1));
+        match self {
+            HoldingKind::Nfts => nft,
+            HoldingKind::Tokens => nft.not().and(mint::Column::Decimal.lte(MAX_INDEXED_DECIMALS)),
+        }
+    }
+}
+
+#[derive(Debug, FromQueryResult)]
+struct HoldingRow {
+    mint_address: String,
+    amount: String,
+    decimal: i16,
+    token_accounts: i64,
+}
+
+// a page of the mints a wallet holds a non zero balance of, ordered by mint address. the cursor is the last mint
+// of the previous page. accounts of mints we haven't indexed are left out, without the mint there are no decimals
+pub async fn find_holdings<C: ConnectionTrait>(
+    conn: &C,
+    owner: &str,
+    kind: HoldingKind,
+    query: &HoldingsQuery,
+) -> Result<HoldingsResponse, DbErr> {
+    let size = query.size.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+
+    let mut select = token_account::Entity::find()
+        .select_only()
+        .column(token_account::Column::MintAddress)
+        // summed as numeric, a wallet's accounts of one mint can add up past u64
+        .column_as(Expr::cust("sum(token_accounts.amount)::text"), "amount")
+        .column_as(Expr::cust("count(*)"), "token_accounts")
+        .column(mint::Column::Decimal)
+        .join(JoinType::InnerJoin, token_account::Relation::Mint.def())
+        .filter(token_account::Column::Owner.eq(owner))
+        .filter(token_account::Column::Amount.gt(0))
+        .filter(kind.condition());
+
+    if let Some(collection) = &query.collection {
+        select = select
+            .join(JoinType::InnerJoin, mint::Relation::NftMetadata.def())
+            .filter(nft_metadata::Column::CollectionMint.eq(collection.as_str()));
+    }
+    if let Some(cursor) = &query.cursor {
+        select = select.filter(token_account::Column::MintAddress.gt(cursor.as_str()));
+    }
+
+    // one extra row tells whether there's another page
+    let mut rows = select
+        .group_by(token_account::Column::MintAddress)
+        .group_by(mint::Column::Decimal)
+        .order_by_asc(token_account::Column::MintAddress)
+        .limit(size as u64 + 1)
+        .into_model::<HoldingRow>()
+        .all(conn)
+        .await?;
+
+    let next_cursor = if rows.len() as i64 > size {
+        rows.truncate(size as usize);
+        rows.last().map(|row| row.mint_address.clone())
+    } else {
+        None
+    };
+
+    let mint_addresses: Vec<String> = rows.iter().map(|row| row.mint_address.clone()).collect();
+    let mut assets: HashMap<String, _> = find_assets_details(conn, &mint_addresses, &EMBEDDED)
+        .await?
+        .into_iter()
+        .map(|asset| (asset.mint.mint_address.clone(), asset))
+        .collect();
+
+    let items = rows
+        .into_iter()
+        .filter_map(|row| {
+            let asset = assets.remove(&row.mint_address)?;
+            Some(Holding {
+                ui_amount: row.amount.parse::<f64>().unwrap_or_default() / 10f64.powi(row.decimal as i32),
+                ui_amount_string: ui_amount_string(&row.amount, row.decimal),
+                amount: row.amount,
+                decimals: row.decimal,
+                token_accounts: row.token_accounts,
+                mint_address: row.mint_address,
+                asset,
+            })
+        })
+        .collect();
+
+    Ok(HoldingsResponse {
+        owner: owner.to_string(),
+        items,
+        next_cursor,
+    })
+}
+
+// the raw amount, a string of digits, with the decimal point put in like the RPC's uiAmountString: no trailing zeros
+pub fn ui_amount_string(amount: &str, decimals: i16) -> String {
+    let decimals = decimals.max(0) as usize;
+    if decimals == 0 {
+        return amount.to_string();
+    }
+
+    let digits = format!("{:0>width$}", amount, width = decimals + 1);
+    let (whole, fraction) = digits.split_at(digits.len() - decimals);
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.is_empty() {
+        whole.to_string()
+    } else {
+        format!("{}.{}", whole, fraction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ui_amounts_apply_decimals_exactly() {
+        assert_eq!(ui_amount_string("1", 0), "1");
+        assert_eq!(ui_amount_string("1500000", 6), "1.5");
+        assert_eq!(ui_amount_string("42", 9), "0.000000042");
+        assert_eq!(ui_amount_string("3000000000", 9), "3");
+        assert_eq!(ui_amount_string("18446744073709551615", 2), "184467440737095516.15");
+        // two accounts holding u64::MAX each
+        assert_eq!(ui_amount_string("36893488147419103230", 0), "36893488147419103230");
+    }
+}
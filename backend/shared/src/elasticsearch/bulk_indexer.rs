@@ -0,0 +1,110 @@
+use std::collections::HashSet;
+use std::time::Duration;
+
+use crate::elasticsearch::client::{BulkFailure, BulkOp, ElasticSearchClient, ElasticSearchError};
+
+// sends the operations with _bulk and resends only the items that failed with a retryable status, backing off between attempts
+pub async fn index_with_retries(
+    client: &ElasticSearchClient,
+    index: &str,
+    operations: Vec<BulkOp>,
+    max_retries: u32,
+    wait_for_refresh: bool,
+) -> Vec<BulkFailure> {
+    let mut pending = operations;
+    let mut given_up: Vec<BulkFailure> = vec![];
+    let mut attempt = 0;
+
+    loop {
+        let failures = match client.bulk_write(index, &pending, wait_for_refresh).await {
+            Ok(failures) => failures,
+            Err(e) => {
+                println!("Bulk request failed on attempt {} : {}", attempt + 1, e);
+                request_failed(&pending, &e)
+            }
+        };
+
+        let (retryable, permanent) = split_failures(&mut pending, failures);
+        given_up.extend(permanent);
+
+        if retryable.is_empty() || attempt >= max_retries {
+            given_up.extend(retryable);
+            return given_up;
+        }
+
+        attempt += 1;
+        let backoff = Duration::from_millis(200 * 2u64.pow(attempt));
+        println!("Retrying {} operations in {:?}...", pending.len(), backoff);
+        tokio::time::sleep(backoff).await;
+    }
+}
+
+// the whole request failed (connection, 5xx on the endpoint), every operation is still pending
+fn request_failed(pending: &[BulkOp], error: &ElasticSearchError) -> Vec<BulkFailure> {
+    pending
+        .iter()
+        .map(|operation| BulkFailure {
+            id: operation.id().to_string(),
+            status: 503,
+            reason: error.to_string(),
+        })
+        .collect()
+}
+
+// (retryable, permanent) failures, and `pending` cut down to the operations worth sending again.
+// an id can appear more than once in a batch (index then delete), so every operation of a retried id is kept in order
+fn split_failures(pending: &mut Vec<BulkOp>, failures: Vec<BulkFailure>) -> (Vec<BulkFailure>, Vec<BulkFailure>) {
+    let (retryable, permanent): (Vec<BulkFailure>, Vec<BulkFailure>) =
+        failures.into_iter().partition(|failure| failure.is_retryable());
+    let retry_ids: HashSet<&str> = retryable.iter().map(|failure| failure.id.as_str()).collect();
+    pending.retain(|operation| retry_ids.contains(operation.id()));
+    (retryable, permanent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn failure(id: &str, status: u16) -> BulkFailure {
+        BulkFailure { id: id.to_string(), status, reason: "test".to_string() }
+    }
+
+    fn ids(operations: &[BulkOp]) -> Vec<&str> {
+        operations.iter().map(BulkOp::id).collect()
+    }
+
+    #[test]
+    fn only_retryable_failures_are_sent_again() {
+        let update = |id: &str| BulkOp::Update { id: id.to_string(), partial: json!({ "owner": "x" }) };
+        let mut pending = vec![
+            update("ok"),
+            update("rejected"),
+            update("mapping"),
+            update("shard"),
+            BulkOp::Delete("rejected".to_string()),
+        ];
+
+        let (retryable, permanent) = split_failures(
+            &mut pending,
+            vec![failure("rejected", 429), failure("mapping", 400), failure("shard", 503)],
+        );
+        assert_eq!(retryable.len(), 2);
+        assert_eq!(permanent.len(), 1);
+        assert_eq!(permanent[0].id, "mapping");
+        // both operations of "rejected" go again, in their original order
+        assert_eq!(ids(&pending), vec!["rejected", "shard", "rejected"]);
+        assert!(matches!(pending[2], BulkOp::Delete(_)));
+    }
+
+    #[test]
+    fn a_failed_request_retries_everything() {
+        let mut pending = vec![BulkOp::Delete("a".to_string()), BulkOp::Delete("b".to_string())];
+        let failures = request_failed(&pending, &ElasticSearchError::ConnectionError("refused".to_string()));
+        assert!(failures.iter().all(BulkFailure::is_retryable));
+
+        let (retryable, permanent) = split_failures(&mut pending, failures);
+        assert_eq!((retryable.len(), permanent.len()), (2, 0));
+        assert_eq!(ids(&pending), vec!["a", "b"]);
+    }
+}
@@ -0,0 +1,263 @@
+use serde_json::{json, Value};
+
+use crate::elasticsearch::query::name_query;
+
+const MAX_SYMBOL_LEN: usize = 10; // the token metadata program caps symbols at 10 bytes
+
+// what a search box string was recognised as, decides which fields search_nft looks at
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QueryClass {
+    Pubkey(String),     // any account an nft points at: mint, metadata pda, collection, creator, owner, update authority
+    Symbol(String),     // `symbol:DAPE`, `$DAPE` or a bare all caps word like DAPE
+    Collection(String), // `collection:<mint or name>`
+    Creator(String),    // `creator:<address>`
+    Trait {
+        trait_type: String,
+        value: Option<String>, // `trait:Background` matches any value, `trait:Background=Blue` only that one
+    },
+    Text(String),
+}
+
+pub fn classify_query(query: &str) -> QueryClass {
+    let trimmed_query = query.trim();
+
+    if is_pubkey(trimmed_query) {
+        return QueryClass::Pubkey(trimmed_query.to_string());
+    }
+
+    if let Some((prefix, rest)) = trimmed_query.split_once(':') {
+        let rest = rest.trim();
+        if !rest.is_empty() {
+            match prefix.trim().to_lowercase().as_str() {
+                "collection" => return QueryClass::Collection(rest.to_string()),
+                "creator" => return QueryClass::Creator(rest.to_string()),
+                "symbol" => return QueryClass::Symbol(rest.to_string()),
+                "trait" => {
+                    let (trait_type, value) = match rest.split_once('=') {
+                        Some((trait_type, value)) => (trait_type.trim(), Some(value.trim())),
+                        None => (rest, None),
+                    };
+                    if !trait_type.is_empty() {
+                        return QueryClass::Trait {
+                            trait_type: trait_type.to_string(),
+                            value: value.filter(|value| !value.is_empty()).map(str::to_string),
+                        };
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    if let Some(symbol) = trimmed_query.strip_prefix('$') {
+        if is_symbol(symbol) {
+            return QueryClass::Symbol(symbol.to_string());
+        }
+    }
+
+    let all_caps = trimmed_query.chars().any(|c| c.is_ascii_uppercase())
+        && !trimmed_query.chars().any(|c| c.is_ascii_lowercase());
+    if is_symbol(trimmed_query) && all_caps {
+        return QueryClass::Symbol(trimmed_query.to_string());
+    }
+
+    QueryClass::Text(trimmed_query.to_string())
+}
+
+// a pubkey is whatever base58 decodes to exactly 32 bytes, which is anywhere from 32 to 44 characters
+pub fn is_pubkey(value: &str) -> bool {
+    (32..=44).contains(&value.len())
+        && bs58::decode(value)
+            .into_vec()
+            .map(|bytes| bytes.len() == 32)
+            .unwrap_or(false)
+}
+
+fn is_symbol(value: &str) -> bool {
+    (2..=MAX_SYMBOL_LEN).contains(&value.len()) && value.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+impl QueryClass {
+    // the "query" part of the search body for this class
+    pub fn to_query(&self) -> Value {
+        match self {
+            QueryClass::Pubkey(address) => json!({
+                "bool": {
+                    "should": [
+                        { "term": { "mint_address": { "value": address, "boost": 10.0 } } },
+                        { "term": { "metadata_address": { "value": address, "boost": 10.0 } } },
+                        { "term": { "collection.mint": { "value": address, "boost": 3.0 } } },
+                        {
+                            "nested": {
+                                "path": "creators",
+                                "query": { "term": { "creators.address": address } }
+                            }
+                        },
+                        { "term": { "owner": address } },
+                        { "term": { "update_authority": address } }
+                    ],
+                    "minimum_should_match": 1
+                }
+            }),
+            // an all caps word could still be part of a name, so names stay in the query below the exact symbol
+            QueryClass::Symbol(symbol) => json!({
+                "bool": {
+                    "should": [
+                        {
+                            "term": {
+                                "symbol": {
+                                    "value": symbol,
+                                    "boost": 6.0,
+                                    "case_insensitive": true
+                                }
+                            }
+                        },
+                        name_query(symbol)
+                    ],
+                    "minimum_should_match": 1
+                }
+            }),
+            QueryClass::Collection(collection) => json!({
+                "bool": {
+                    "should": [
+                        { "term": { "collection.mint": collection } },
+                        {
+                            "term": {
+                                "collection.name.keyword": {
+                                    "value": collection,
+                                    "boost": 2.0,
+                                    "case_insensitive": true
+                                }
+                            }
+                        },
+                        { "match": { "collection.name": { "query": collection, "operator": "and" } } }
+                    ],
+                    "minimum_should_match": 1
+                }
+            }),
+            QueryClass::Creator(creator) => json!({
+                "nested": {
+                    "path": "creators",
+                    "query": { "term": { "creators.address": creator } }
+                }
+            }),
+            QueryClass::Trait { trait_type, value } => {
+                let mut clauses = vec![json!({
+                    "term": { "attributes.trait_type": { "value": trait_type, "case_insensitive": true } }
+                })];
+                if let Some(value) = value {
+                    clauses.push(json!({
+                        "term": { "attributes.value": { "value": value, "case_insensitive": true } }
+                    }));
+                }
+                json!({
+                    "nested": {
+                        "path": "attributes",
+                        "query": { "bool": { "filter": clauses } }
+                    }
+                })
+            }
+            QueryClass::Text(text) => name_query(text),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINT: &str = "DezXAZ8z7PnrnRJjz3wXBoRgixCa6xjnB7YaB1pPB263";
+
+    #[test]
+    fn base58_pubkeys_of_any_length_are_pubkeys() {
+        assert_eq!(classify_query(MINT), QueryClass::Pubkey(MINT.to_string()));
+        // 32 character address, the old len() > 43 check missed these
+        let short = "11111111111111111111111111111111";
+        assert_eq!(short.len(), 32);
+        assert_eq!(classify_query(short), QueryClass::Pubkey(short.to_string()));
+
+        let query = classify_query(MINT).to_query().to_string();
+        for field in ["mint_address", "metadata_address", "collection.mint", "creators.address", "owner", "update_authority"] {
+            assert!(query.contains(field), "{} missing from pubkey query", field);
+        }
+    }
+
+    #[test]
+    fn long_names_and_invalid_base58_are_text() {
+        let long_name = "The Incredibly Long Named Degenerate Ape Number 4242";
+        assert_eq!(classify_query(long_name), QueryClass::Text(long_name.to_string()));
+        // 0, O, I and l are not in the base58 alphabet
+        let not_base58 = "0OIl0OIl0OIl0OIl0OIl0OIl0OIl0OIl0OIl0OIl0OI";
+        assert_eq!(classify_query(not_base58), QueryClass::Text(not_base58.to_string()));
+    }
+
+    #[test]
+    fn symbols_are_recognised() {
+        assert_eq!(classify_query("DAPE"), QueryClass::Symbol("DAPE".to_string()));
+        assert_eq!(classify_query("$dape"), QueryClass::Symbol("dape".to_string()));
+        assert_eq!(classify_query("symbol: SMB"), QueryClass::Symbol("SMB".to_string()));
+        assert_eq!(classify_query("dape"), QueryClass::Text("dape".to_string()));
+
+        let query = classify_query("DAPE").to_query().to_string();
+        assert!(query.contains("\"symbol\""));
+        assert!(query.contains("nft_name"));
+    }
+
+    #[test]
+    fn collection_prefix() {
+        assert_eq!(
+            classify_query("collection:Mad Lads"),
+            QueryClass::Collection("Mad Lads".to_string())
+        );
+        assert_eq!(
+            classify_query("Collection: Mad Lads"),
+            QueryClass::Collection("Mad Lads".to_string())
+        );
+        let query = classify_query("collection:Mad Lads").to_query().to_string();
+        assert!(query.contains("collection.mint"));
+        assert!(query.contains("collection.name"));
+    }
+
+    #[test]
+    fn creator_prefix() {
+        let class = classify_query(&format!("creator:{}", MINT));
+        assert_eq!(class, QueryClass::Creator(MINT.to_string()));
+        assert_eq!(class.to_query()["nested"]["path"], "creators");
+    }
+
+    #[test]
+    fn trait_prefix_with_and_without_value() {
+        assert_eq!(
+            classify_query("trait:Background=Blue"),
+            QueryClass::Trait {
+                trait_type: "Background".to_string(),
+                value: Some("Blue".to_string()),
+            }
+        );
+        let any_value = classify_query("trait:Background");
+        assert_eq!(
+            any_value,
+            QueryClass::Trait {
+                trait_type: "Background".to_string(),
+                value: None,
+            }
+        );
+        assert_eq!(
+            any_value.to_query()["nested"]["query"]["bool"]["filter"]
+                .as_array()
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn unknown_or_empty_prefixes_fall_through_to_text() {
+        assert_eq!(classify_query("collection:"), QueryClass::Text("collection:".to_string()));
+        assert_eq!(
+            classify_query("Ape #1: The Return"),
+            QueryClass::Text("Ape #1: The Return".to_string())
+        );
+        assert!(classify_query("degen ape").to_query()["bool"]["should"].is_array());
+    }
+}
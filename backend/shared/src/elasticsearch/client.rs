@@ -1,27 +1,41 @@
+use chrono::{DateTime, Utc};
 use core::fmt;
-use elasticsearch::{http::transport::Transport, Elasticsearch, IndexParts, SearchParts};
+use elasticsearch::{
+    http::{request::JsonBody, transport::Transport, StatusCode},
+    params::Refresh,
+    BulkParts, CountParts, Elasticsearch, IndexParts, MgetParts, SearchParts,
+};
 use serde_json::{json, Value};
+use std::collections::HashMap;
 use std::error::Error;
 
+use crate::elasticsearch::classify::classify_query;
+use crate::elasticsearch::query::{
+    apply_page, build_search_query, build_suggest_query, next_cursor, page_size,
+    parse_aggregations, parse_suggestions, parse_total, suggest_size,
+};
+use crate::elasticsearch::mapping::{mapping_version_from_response, nft_index_body, NFT_MAPPING_VERSION};
 use crate::types::{
-    elasticsearch::{SearchResponse, SearchResult},
+    elasticsearch::{PageRequest, SearchRequest, SearchResponse, SearchResult, SuggestResponse},
     elasticsearch::NftDoc,
 };
 
 #[derive(Debug, Clone)]
 pub struct ElasticSearchClient {
     client: Elasticsearch,
-    index_name: String,
+    index_name: String,  // base name, the physical indices are {index_name}_v1, {index_name}_v2...
+    read_alias: String,  // searches always go through this alias
+    write_alias: String, // documents are always written through this alias
 }
 
 impl ElasticSearchClient {
     pub async fn new(
         elasticsearch_url: String,
         index_name: String,
-    ) -> Result<Self, elasticsearch::Error> {
+    ) -> Result<Self, ElasticSearchError> {
         let transport = Transport::single_node(&elasticsearch_url).map_err(|es_error| {
             println!("failed to create transport {}", es_error);
-            es_error
+            ElasticSearchError::ConnectionError(es_error.to_string())
         })?; // first we assure transport layer works.
         let client = Elasticsearch::new(transport); // here we create new client using the transport layer.
 
@@ -43,7 +57,9 @@ impl ElasticSearchClient {
         }
 
         let client_connection = Self {
-            client: client,
+            client,
+            read_alias: format!("{}_read", index_name),
+            write_alias: format!("{}_write", index_name),
             index_name: index_name.to_string(),
         };
 
@@ -51,79 +67,473 @@ impl ElasticSearchClient {
         Ok(client_connection)
     }
 
-    async fn set_up_index(&self) -> Result<(), elasticsearch::Error> {
-        let mapping = json!({
-            "mappings" : {
-                "properties" : {
-                    "mint_address" : {
-                        "type" : "keyword"
-                    },
-                    "collection_name" : {
-                        "type" : "text",
-                        "fields" : {
-                            "keyword" : {
-                                "type" : "keyword"
-                            }
-                        },
-                        "analyzer" : "standard"
-                    }
-                }
-            },
-            "settings" : {
-                "number_of_shards" : 1,
-                "number_of_replicas" : 2,
-                "refresh_interval" : "1s"
+    // only bootstraps the aliases. an existing index is never touched here, mapping changes go through the reindex command
+    async fn set_up_index(&self) -> Result<(), ElasticSearchError> {
+        let read_targets = self.alias_targets(&self.read_alias).await?;
+        if let Some(current_index) = read_targets.first() {
+            println!("Read alias {} points to {}", self.read_alias, current_index);
+            if self.alias_targets(&self.write_alias).await?.is_empty() {
+                println!("Write alias missing, pointing it to {}", current_index);
+                self.send_alias_actions(vec![self.add_alias_action(current_index, &self.write_alias)])
+                    .await?;
             }
-        });
+            self.check_mapping_version(current_index).await;
+            return Ok(());
+        }
+
+        if self.index_exists(&self.index_name).await? {
+            // deployments from before the aliases have a concrete index under the base name, keep serving it until a reindex
+            println!("Legacy index {} found, putting the aliases in front of it", self.index_name);
+            self.send_alias_actions(vec![
+                self.add_alias_action(&self.index_name, &self.read_alias),
+                self.add_alias_action(&self.index_name, &self.write_alias),
+            ])
+            .await?;
+            self.check_mapping_version(&self.index_name).await;
+            return Ok(());
+        }
+
+        println!("Old index not found");
+        println!("Creating new index...");
+        let first_index = self.physical_index_name(1);
+        self.create_physical_index(&first_index).await?;
+        self.send_alias_actions(vec![
+            self.add_alias_action(&first_index, &self.read_alias),
+            self.add_alias_action(&first_index, &self.write_alias),
+        ])
+        .await?;
+        Ok(())
+    }
+
+    pub fn physical_index_name(&self, version: u32) -> String {
+        format!("{}_v{}", self.index_name, version)
+    }
+
+    // the legacy concrete index counts as version 0 so the first reindex lands in _v1
+    pub fn physical_index_version(&self, index: &str) -> Option<u32> {
+        if index == self.index_name {
+            return Some(0);
+        }
+        index
+            .strip_prefix(&format!("{}_v", self.index_name))
+            .and_then(|version| version.parse::<u32>().ok())
+    }
+
+    pub fn read_alias(&self) -> &str {
+        &self.read_alias
+    }
+
+    pub fn write_alias(&self) -> &str {
+        &self.write_alias
+    }
 
+    async fn index_exists(&self, index: &str) -> Result<bool, ElasticSearchError> {
         let exist_response = self
             .client
             .indices()
-            .exists(elasticsearch::indices::IndicesExistsParts::Index(&[
-                &self.index_name
-            ]))
+            .exists(elasticsearch::indices::IndicesExistsParts::Index(&[index]))
             .send()
             .await
             .map_err(|es_error| {
                 println!("failed to check if index exist due to {}", es_error);
-                es_error
+                ElasticSearchError::ConnectionError(es_error.to_string())
+            })?;
+
+        Ok(exist_response.status_code().is_success())
+    }
+
+    pub async fn create_physical_index(&self, index: &str) -> Result<(), ElasticSearchError> {
+        let new_index = self
+            .client
+            .indices()
+            .create(elasticsearch::indices::IndicesCreateParts::Index(index))
+            .body(nft_index_body())
+            .send()
+            .await
+            .map_err(|es_error| {
+                println!("Error in creating a new index due to : {}", es_error);
+                ElasticSearchError::ConnectionError(es_error.to_string())
             })?;
 
-        if exist_response.status_code().as_u16() == 404 {
-            println!("Old index not found");
-            println!("Creating new index...");
+        checked_response(new_index, &format!("Creating index {}", index)).await?;
+        println!("Sucessfully created new Index {}", index);
+        Ok(())
+    }
+
+    pub async fn delete_physical_index(&self, index: &str) -> Result<(), ElasticSearchError> {
+        let response = self
+            .client
+            .indices()
+            .delete(elasticsearch::indices::IndicesDeleteParts::Index(&[index]))
+            .send()
+            .await
+            .map_err(|e| ElasticSearchError::ConnectionError(format!("Failed to delete {} {}", index, e)))?;
+        checked_response(response, &format!("Deleting index {}", index)).await?;
+        println!("Deleted index {}", index);
+        Ok(())
+    }
+
+    // every physical index of this base name, including the legacy one, sorted by version
+    pub async fn physical_indices(&self) -> Result<Vec<(String, u32)>, ElasticSearchError> {
+        let pattern = format!("{}_v*", self.index_name);
+        let response = self
+            .client
+            .indices()
+            .get(elasticsearch::indices::IndicesGetParts::Index(&[&pattern, &self.index_name]))
+            .ignore_unavailable(true)
+            .allow_no_indices(true)
+            .send()
+            .await
+            .map_err(|e| ElasticSearchError::ConnectionError(format!("Failed to list indices {}", e)))?;
+
+        let body = checked_response(response, "Listing indices").await?;
+        let mut indices: Vec<(String, u32)> = body
+            .as_object()
+            .map(|indices| {
+                indices
+                    .keys()
+                    .filter_map(|name| Some((name.clone(), self.physical_index_version(name)?)))
+                    .collect()
+            })
+            .unwrap_or_default();
+        indices.sort_by_key(|(_, version)| *version);
+        Ok(indices)
+    }
+
+    // the index.creation_date setting, which ES keeps as epoch millis in a string
+    pub async fn index_created_at(&self, index: &str) -> Result<DateTime<Utc>, ElasticSearchError> {
+        let response = self
+            .client
+            .indices()
+            .get_settings(elasticsearch::indices::IndicesGetSettingsParts::IndexName(
+                &[index],
+                &["index.creation_date"],
+            ))
+            .send()
+            .await
+            .map_err(|e| ElasticSearchError::ConnectionError(format!("Failed to read settings of {} {}", index, e)))?;
+
+        let body = checked_response(response, &format!("Reading the settings of {}", index)).await?;
+        body[index]["settings"]["index"]["creation_date"]
+            .as_str()
+            .and_then(|millis| millis.parse().ok())
+            .and_then(DateTime::from_timestamp_millis)
+            .ok_or_else(|| ElasticSearchError::IndexError(format!("No creation date in the settings of {}", index)))
+    }
+
+    pub async fn alias_targets(&self, alias: &str) -> Result<Vec<String>, ElasticSearchError> {
+        let response = self
+            .client
+            .indices()
+            .get_alias(elasticsearch::indices::IndicesGetAliasParts::Name(&[alias]))
+            .send()
+            .await
+            .map_err(|e| ElasticSearchError::ConnectionError(format!("Failed to read alias {} {}", alias, e)))?;
+
+        // a missing alias is a 404 whose body is an error, not a map of index names
+        if response.status_code().as_u16() == 404 {
+            return Ok(vec![]);
+        }
 
-            let new_index = self
-                .client
-                .indices()
-                .create(elasticsearch::indices::IndicesCreateParts::Index(
-                    &self.index_name,
+        let body = checked_response(response, &format!("Reading alias {}", alias)).await?;
+        Ok(body
+            .as_object()
+            .map(|indices| indices.keys().cloned().collect())
+            .unwrap_or_default())
+    }
+
+    fn add_alias_action(&self, index: &str, alias: &str) -> Value {
+        json!({ "add": { "index": index, "alias": alias, "is_write_index": alias == self.write_alias } })
+    }
+
+    fn remove_alias_action(&self, index: &str, alias: &str) -> Value {
+        json!({ "remove": { "index": index, "alias": alias } })
+    }
+
+    // all actions in one _aliases call are applied atomically, so readers never see the alias pointing nowhere
+    async fn send_alias_actions(&self, actions: Vec<Value>) -> Result<(), ElasticSearchError> {
+        let response = self
+            .client
+            .indices()
+            .update_aliases()
+            .body(json!({ "actions": actions }))
+            .send()
+            .await
+            .map_err(|e| ElasticSearchError::ConnectionError(format!("Failed to update the aliases {}", e)))?;
+
+        checked_response(response, "Alias update").await?;
+        println!("Aliases updated");
+        Ok(())
+    }
+
+    // moves an alias off every index it currently points to and onto `to` in one atomic step
+    pub async fn point_alias(&self, alias: &str, to: &str) -> Result<(), ElasticSearchError> {
+        let current = self.alias_targets(alias).await?;
+
+        let mut actions: Vec<Value> = current
+            .iter()
+            .filter(|index| index.as_str() != to)
+            .map(|index| self.remove_alias_action(index, alias))
+            .collect();
+        actions.push(self.add_alias_action(to, alias));
+
+        self.send_alias_actions(actions).await.map_err(|e| {
+            ElasticSearchError::IndexError(format!("Failed to point alias {} to {} ({})", alias, to, e))
+        })
+    }
+
+    pub async fn count_docs(&self, index: &str) -> Result<u64, ElasticSearchError> {
+        let response = self
+            .client
+            .count(CountParts::Index(&[index]))
+            .send()
+            .await
+            .map_err(|e| ElasticSearchError::ConnectionError(format!("Failed to count docs in {} {}", index, e)))?;
+
+        let body = checked_response(response, &format!("Counting docs in {}", index)).await?;
+        body["count"]
+            .as_u64()
+            .ok_or_else(|| ElasticSearchError::IndexError(format!("No count in the response for {}", index)))
+    }
+
+    // the stored content_hash of every id the read alias has, ids ES doesn't know are left out of the map
+    pub async fn fetch_content_hashes(
+        &self,
+        ids: &[String],
+    ) -> Result<HashMap<String, Option<String>>, ElasticSearchError> {
+        if ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        let response = self
+            .client
+            .mget(MgetParts::Index(&self.read_alias))
+            ._source_includes(&["content_hash"])
+            .body(json!({ "ids": ids }))
+            .send()
+            .await
+            .map_err(|e| ElasticSearchError::ConnectionError(format!("Failed to fetch docs {}", e)))?;
+
+        if !response.status_code().is_success() {
+            return Err(status_error(
+                response.status_code(),
+                ElasticSearchError::SearchError,
+                format!("Fetching docs returned the status {}", response.status_code()),
+            ));
+        }
+
+        let response_json: Value = response
+            .json()
+            .await
+            .map_err(|e| ElasticSearchError::SearchError(format!("Failed to parse the mget response {}", e)))?;
+
+        Ok(response_json["docs"]
+            .as_array()
+            .map(|docs| docs.as_slice())
+            .unwrap_or_default()
+            .iter()
+            .filter(|doc| doc["found"].as_bool().unwrap_or(false))
+            .filter_map(|doc| {
+                Some((
+                    doc["_id"].as_str()?.to_string(),
+                    doc["_source"]["content_hash"].as_str().map(str::to_string),
                 ))
-                .body(mapping)
-                .send()
-                .await
-                .map_err(|es_error| {
-                    println!("Error in creating a new index due to : {}", es_error);
-                    es_error
-                })?;
-
-            if new_index.status_code().is_success() {
-                println!("Sucessfully created new Index");
-            } else {
-                println!("New index created failed");
+            })
+            .collect())
+    }
+
+    // one page of doc ids in mint_address order, pass the last id back as `after` to get the next page
+    pub async fn scan_doc_ids(&self, after: Option<&str>, size: i64) -> Result<Vec<String>, ElasticSearchError> {
+        let mut scan_query = json!({
+            "size": size,
+            "_source": false,
+            "track_total_hits": false,
+            "query": { "match_all": {} },
+            "sort": [{ "mint_address": { "order": "asc" } }]
+        });
+        if let Some(after) = after {
+            scan_query["search_after"] = json!([after]);
+        }
+
+        let response = self
+            .client
+            .search(SearchParts::Index(&[&self.read_alias]))
+            .body(scan_query)
+            .send()
+            .await
+            .map_err(|e| ElasticSearchError::ConnectionError(format!("Failed to scan doc ids {}", e)))?;
+
+        if !response.status_code().is_success() {
+            return Err(status_error(
+                response.status_code(),
+                ElasticSearchError::SearchError,
+                format!("Scanning doc ids returned the status {}", response.status_code()),
+            ));
+        }
+
+        let response_json: Value = response
+            .json()
+            .await
+            .map_err(|e| ElasticSearchError::SearchError(format!("Failed to parse the scan response {}", e)))?;
+
+        Ok(response_json["hits"]["hits"]
+            .as_array()
+            .map(|hits| hits.as_slice())
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|hit| hit["_id"].as_str().map(str::to_string))
+            .collect())
+    }
+
+    pub async fn refresh_index(&self, index: &str) -> Result<(), ElasticSearchError> {
+        let response = self
+            .client
+            .indices()
+            .refresh(elasticsearch::indices::IndicesRefreshParts::Index(&[index]))
+            .send()
+            .await
+            .map_err(|e| ElasticSearchError::ConnectionError(format!("Failed to refresh {} {}", index, e)))?;
+        checked_response(response, &format!("Refreshing {}", index)).await?;
+        Ok(())
+    }
+
+    pub async fn ping(&self) -> Result<(), ElasticSearchError> {
+        let response = self
+            .client
+            .ping()
+            .send()
+            .await
+            .map_err(|e| ElasticSearchError::ConnectionError(format!("Failed to ping elasticsearch {}", e)))?;
+        if !response.status_code().is_success() {
+            return Err(ElasticSearchError::ConnectionError(format!(
+                "elasticsearch answered the ping with {}",
+                response.status_code()
+            )));
+        }
+        Ok(())
+    }
+
+    // writes a batch of index/update/delete operations with one _bulk call and returns every item ES rejected.
+    // `index` can be a physical index or an alias, the worker passes the write alias
+    pub async fn bulk_write(
+        &self,
+        index: &str,
+        operations: &[BulkOp],
+        wait_for_refresh: bool,
+    ) -> Result<Vec<BulkFailure>, ElasticSearchError> {
+        if operations.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let mut body: Vec<JsonBody<Value>> = Vec::with_capacity(operations.len() * 2);
+        for operation in operations {
+            match operation {
+                BulkOp::Index(doc) => {
+                    let source = serde_json::to_value(doc).map_err(|e| {
+                        ElasticSearchError::DocumentError(format!("Failed to serialize {} {}", doc.mint_address, e))
+                    })?;
+                    body.push(json!({ "index": { "_id": doc.mint_address } }).into());
+                    body.push(source.into());
+                }
+                BulkOp::Update { id, partial } => {
+                    body.push(json!({ "update": { "_id": id } }).into());
+                    body.push(json!({ "doc": partial }).into());
+                }
+                BulkOp::Delete(id) => {
+                    body.push(json!({ "delete": { "_id": id } }).into()); // delete has no source line
+                }
             }
+        }
+
+        let refresh = if wait_for_refresh {
+            Refresh::WaitFor
         } else {
-            println!("Index already exist");
+            Refresh::False
+        };
+
+        let response = self
+            .client
+            .bulk(BulkParts::Index(index))
+            .body(body)
+            .refresh(refresh)
+            .send()
+            .await
+            .map_err(|e| ElasticSearchError::ConnectionError(format!("Bulk request failed {}", e)))?;
+
+        if !response.status_code().is_success() {
+            return Err(status_error(
+                response.status_code(),
+                ElasticSearchError::IndexError,
+                format!("Bulk request returned the status {}", response.status_code()),
+            ));
         }
-        Ok(())
+
+        let response_json: Value = response
+            .json()
+            .await
+            .map_err(|e| ElasticSearchError::IndexError(format!("Failed to parse the bulk response {}", e)))?;
+
+        Ok(parse_bulk_failures(&response_json))
     }
 
-    pub async fn create_nft_index(&self, nft_doc: NftDoc) -> Result<(), ElasticSearchError> {
+    async fn check_mapping_version(&self, index: &str) {
+        let response = match self
+            .client
+            .indices()
+            .get_mapping(elasticsearch::indices::IndicesGetMappingParts::Index(&[index]))
+            .send()
+            .await
+        {
+            Ok(response) => response,
+            Err(e) => {
+                println!("failed to fetch the index mapping {}", e);
+                return;
+            }
+        };
+
+        let mapping_json: Value = match response.json().await {
+            Ok(json) => json,
+            Err(e) => {
+                println!("failed to parse the index mapping {}", e);
+                return;
+            }
+        };
+
+        match mapping_version_from_response(index, &mapping_json) {
+            Some(version) if version == NFT_MAPPING_VERSION => {
+                println!("Index mapping is up to date (v{})", version);
+            }
+            Some(version) => {
+                println!(
+                    "⚠️ Index {} has mapping v{} but the code expects v{}, run the reindex command to pick up the new fields",
+                    index, version, NFT_MAPPING_VERSION
+                );
+            }
+            None => {
+                println!(
+                    "⚠️ Index {} has no mapping version, it was created before v{} and needs a reindex",
+                    index, NFT_MAPPING_VERSION
+                );
+            }
+        }
+    }
+
+    pub async fn create_nft_index(
+        &self,
+        nft_doc: NftDoc,
+        wait_for_refresh: bool,
+    ) -> Result<(), ElasticSearchError> {
+        let refresh = if wait_for_refresh {
+            Refresh::WaitFor
+        } else {
+            Refresh::False
+        };
+
         let response = self
             .client
-            .index(IndexParts::IndexId(&self.index_name, &nft_doc.mint_address))
+            .index(IndexParts::IndexId(&self.write_alias, &nft_doc.mint_address))
             .body(&nft_doc)
-            .refresh(elasticsearch::params::Refresh::WaitFor) // referesh determines when the newly inserted or updated doc becomes searchable
+            .refresh(refresh) // referesh determines when the newly inserted or updated doc becomes searchable
             .send()
             .await
             .map_err(|index_err| {
@@ -137,110 +547,91 @@ impl ElasticSearchClient {
             println!("✅ Successfully indexed NFT: {}", nft_doc.mint_address);
             Ok(())
         } else {
-            Err(ElasticSearchError::IndexError(format!("Indexing failed")))
+            Err(ElasticSearchError::IndexError("Indexing failed".to_string()))
         }
     }
 
     pub async fn search_nft(
         &self,
         query : &str,
-        size: i64,
+        page: &PageRequest,
     ) -> Result<SearchResponse, ElasticSearchError> {
 
         let trimmed_query = query.trim();
         println!("Search Query received : {}", trimmed_query);
-         let search_query = if trimmed_query.len() > 43 {
-            println!("mint address detected as search term");
-            json!({
-                "size": size,
-                "query": {
-                    "term": {
-                        "mint_address": {
-                            "value": trimmed_query
-                        }
-                    }
-                },
-                "sort": [
-                    {"_score": {"order": "desc"}}
-                ]
-            })
-        } else {
-            println!("nft_name detected as search term");
-            json!({
-                "size": size,
-                "query": {
-                    "bool": {
-                        "should": [
-                            // exact phrase match
-                            {
-                                "match_phrase": { 
-                                    "nft_name": {
-                                        "query": trimmed_query,
-                                        "boost": 4.0 // the priority this matching strategy is given
-                                    }
-                                }
-                            },
-                            // EXACT CASE-INSENSITIVE MATCH 
-                            {
-                                "term": {
-                                    "nft_name.keyword": {
-                                        "value": trimmed_query,
-                                        "boost": 3.5,
-                                        "case_insensitive": true
-                                    }
-                                }
-                            },
-                            // FUZZY MATCHING - can handle typos in the word.
-                            {
-                                "match": {
-                                    "nft_name": {
-                                        "query": trimmed_query,
-                                        "fuzziness": "AUTO",
-                                        "operator": "or",
-                                        "boost": 2.5
-                                    }
-                                }
-                            },
-                            // SUBSTRING MATCHING (case-insensitive)
-                            {
-                                "wildcard": {
-                                    "nft_name": {
-                                        "value": format!("*{}*", trimmed_query.to_lowercase()),
-                                        "boost": 2.0,
-                                        "case_insensitive": true
-                                    }
-                                }
-                            },
-                            // PREFIX MATCHING - any name that starts with query (search term)
-                            {
-                                "prefix": {
-                                    "nft_name": {
-                                        "value": trimmed_query.to_lowercase(),
-                                        "boost": 1.5,
-                                        "case_insensitive": true
-                                    }
-                                }
-                            },
-                        ],
-                        "minimum_should_match": 1
-                    }
-                },
-                "sort": [
-                    {"_score": {"order": "desc"}}
-                ]
-            })
-        };
+        let query_class = classify_query(trimmed_query);
+        println!("Search term classified as {:?}", query_class);
+        let mut search_query = json!({
+            "query": query_class.to_query()
+        });
+
+        apply_page(&mut search_query, page)?;
+        self.execute_search(search_query, page).await
+    }
+
+    pub async fn search_filtered(
+        &self,
+        request: &SearchRequest,
+    ) -> Result<SearchResponse, ElasticSearchError> {
+        println!("Filtered search received : {:?}", request);
+        let search_query = build_search_query(request)?;
+        self.execute_search(search_query, &request.page).await
+    }
 
+    pub async fn suggest(
+        &self,
+        query: &str,
+        size: Option<i64>,
+    ) -> Result<SuggestResponse, ElasticSearchError> {
+        let trimmed_query = query.trim();
+        if trimmed_query.is_empty() {
+            return Ok(SuggestResponse { results: vec![] });
+        }
+
+        let size = suggest_size(size);
+        let search_response = self
+            .client
+            .search(SearchParts::Index(&[&self.read_alias]))
+            .body(build_suggest_query(trimmed_query, size))
+            .send()
+            .await
+            .map_err(|e| {
+                ElasticSearchError::ConnectionError(format!("Failed to fetch suggestions {}", e))
+            })?;
+
+        if !search_response.status_code().is_success() {
+            return Err(status_error(
+                search_response.status_code(),
+                ElasticSearchError::SearchError,
+                format!("Suggest request failed with status {}", search_response.status_code()),
+            ));
+        }
+
+        let search_json: Value = search_response.json().await.map_err(|e| {
+            ElasticSearchError::SearchError(format!("Error converting suggest result into JSON {}", e))
+        })?;
+
+        Ok(SuggestResponse {
+            results: parse_suggestions(&search_json, size),
+        })
+    }
+
+    async fn execute_search(
+        &self,
+        search_query: Value,
+        page: &PageRequest,
+    ) -> Result<SearchResponse, ElasticSearchError> {
         let search_response = self
             .client
-            .search(SearchParts::Index(&[&self.index_name])) // ES can search multiple indices (indexes) that's why it takes &[&str]. like &["index1", "index2", "index3"]
+            .search(SearchParts::Index(&[&self.read_alias])) // ES can search multiple indices (indexes) that's why it takes &[&str]. like &["index1", "index2", "index3"]
             .body(search_query)
-            //.from(from) from means how much docs to skips and then return the result. if from(5) then skip the first 5 matching results and show from 6th.
-            .size(size) // size means how much matching docs to return in result. if size(5) then return only 5 matching docs
+            // no .from() here, deep pages go through search_after in the body which doesn't get slower the further you page
+            .size(page_size(page)) // size means how much matching docs to return in result. if size(5) then return only 5 matching docs
             .send()
             .await
+            // send only fails when ES couldn't be reached, error statuses still come back as a response
             .map_err(|e| {
-                ElasticSearchError::SearchError(format!("Failed to search for the text {}", e))
+                ElasticSearchError::ConnectionError(format!("Failed to search for the text {}", e))
             })?;
 
         if search_response.status_code().is_success() {
@@ -252,25 +643,26 @@ impl ElasticSearchClient {
                     e
                 ))
             })?;
-            self.parse_response_data(search_json)
+            self.parse_response_data(search_json, page)
         } else {
-            Err(ElasticSearchError::SearchError(format!(
-                "Unable to parse the search response for search term"
-            )))
+            Err(status_error(
+                search_response.status_code(),
+                ElasticSearchError::SearchError,
+                format!("Search request failed with status {}", search_response.status_code()),
+            ))
         }
     }
 
     fn parse_response_data(
         &self,
         search_response: Value,
+        page: &PageRequest,
     ) -> Result<SearchResponse, ElasticSearchError> {
         let hits = search_response["hits"]["hits"]
             .as_array() // we're using the same dot notation as JS, but only syntax different and we need to explicitly mention the type of value that field contains using (as_str(), as_f64, as_array...etc)
             .ok_or_else(|| {
                 println!("Unable to find hits field in the response");
-                ElasticSearchError::SearchError(format!(
-                    "Unable to retrieve hits array from response"
-                ))
+                ElasticSearchError::SearchError("Unable to retrieve hits array from response".to_string())
             })?; // this is used for return type of Option<T> and map_err is used for Result<T,E>
 
         let search_result_array: Vec<SearchResult> = hits
@@ -287,12 +679,121 @@ impl ElasticSearchClient {
             })
             .collect(); // this fn collects all the some(value) from the iterator and turns them into vec<T> or Hashmap<T>
 
+        let (total, total_is_exact) = parse_total(&search_response);
+
         Ok(SearchResponse {
             results: search_result_array,
+            total,
+            total_is_exact,
+            next_cursor: next_cursor(&search_response, page),
+            aggregations: parse_aggregations(&search_response),
         })
     }
 }
 
+// one line pair (or a single line for deletes) of a _bulk request, every doc is keyed by its mint address
+#[derive(Debug, Clone)]
+pub enum BulkOp {
+    Index(Box<NftDoc>),
+    Update { id: String, partial: Value }, // only the given fields change, the rest of the doc is kept
+    Delete(String),
+}
+
+impl BulkOp {
+    pub fn id(&self) -> &str {
+        match self {
+            BulkOp::Index(doc) => &doc.mint_address,
+            BulkOp::Update { id, .. } => id,
+            BulkOp::Delete(id) => id,
+        }
+    }
+}
+
+impl From<NftDoc> for BulkOp {
+    fn from(doc: NftDoc) -> Self {
+        BulkOp::Index(Box::new(doc))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BulkFailure {
+    pub id: String,
+    pub status: u16,
+    pub reason: String,
+}
+
+impl BulkFailure {
+    // rejections (429) and shard trouble (5xx) go away on their own, mapping errors (400) never will
+    pub fn is_retryable(&self) -> bool {
+        self.status == 429 || self.status >= 500
+    }
+}
+
+// the json body of a 2xx response. anything else is an error carrying ES's own reason, a rejected admin call
+// must never look like it went through
+async fn checked_response(
+    response: elasticsearch::http::response::Response,
+    action: &str,
+) -> Result<Value, ElasticSearchError> {
+    let status = response.status_code();
+    // a proxy in front of ES answers a 502 with html, that's still ES being down
+    let body: Value = response.json().await.map_err(|e| {
+        status_error(
+            status,
+            ElasticSearchError::IndexError,
+            format!("{}: unreadable response ({}) {}", action, status, e),
+        )
+    })?;
+    if !status.is_success() {
+        let reason = body["error"]["reason"].as_str().map_or_else(|| body.to_string(), str::to_string);
+        return Err(status_error(
+            status,
+            ElasticSearchError::IndexError,
+            format!("{} returned the status {}: {}", action, status, reason),
+        ));
+    }
+    Ok(body)
+}
+
+// 429 and 5xx mean ES is overloaded or down, which callers treat like not reaching it at all (a 503 from the api).
+// anything else is a request ES refused, reported as `refused`
+fn status_error(
+    status: StatusCode,
+    refused: fn(String) -> ElasticSearchError,
+    message: String,
+) -> ElasticSearchError {
+    if status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error() {
+        ElasticSearchError::ConnectionError(message)
+    } else {
+        refused(message)
+    }
+}
+
+// the _bulk endpoint returns 200 even when single items fail, the real outcome is per item
+fn parse_bulk_failures(response: &Value) -> Vec<BulkFailure> {
+    if !response["errors"].as_bool().unwrap_or(false) {
+        return vec![];
+    }
+
+    response["items"]
+        .as_array()
+        .map(|items| {
+            items
+                .iter()
+                .filter_map(|item| {
+                    let (_, result) = item.as_object()?.iter().next()?; // {"index": {...}} or {"delete": {...}}
+                    result.get("error")?;
+                    Some(BulkFailure {
+                        id: result["_id"].as_str().unwrap_or_default().to_string(),
+                        status: result["status"].as_u64().unwrap_or(500) as u16,
+                        reason: result["error"]["reason"].as_str().unwrap_or("unknown error").to_string(),
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
 #[derive(Debug)]
 pub enum ElasticSearchError {
     // here we created a enum varaints for elasticSearch errors
@@ -300,6 +801,7 @@ pub enum ElasticSearchError {
     DocumentError(String),
     IndexError(String),
     SearchError(String),
+    InvalidRequest(String), // the caller sent something we can't turn into a query, e.g. a bad cursor
 }
 
 impl fmt::Display for ElasticSearchError {
@@ -312,8 +814,78 @@ impl fmt::Display for ElasticSearchError {
             ElasticSearchError::DocumentError(msg) => write!(f, "Document Error : {}", msg),
             ElasticSearchError::IndexError(msg) => write!(f, "Index Error : {}", msg),
             ElasticSearchError::SearchError(msg) => write!(f, "Search Error : {}", msg),
+            ElasticSearchError::InvalidRequest(msg) => write!(f, "Invalid Request : {}", msg),
         }
     }
 } // ususally things have display implemented automatically. here we just manually implement and explicitly write the custom fmt fn.
 
 impl Error for ElasticSearchError {} // we implement the std::error:Error for the enum bcoz then only it can we used in Result<> struct as error.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overloaded_or_failing_es_is_a_connection_error() {
+        let error = |status: u16| {
+            status_error(
+                StatusCode::from_u16(status).unwrap(),
+                ElasticSearchError::SearchError,
+                "failed".to_string(),
+            )
+        };
+        assert!(matches!(error(503), ElasticSearchError::ConnectionError(_)));
+        assert!(matches!(error(500), ElasticSearchError::ConnectionError(_)));
+        assert!(matches!(error(429), ElasticSearchError::ConnectionError(_)));
+        assert!(matches!(error(400), ElasticSearchError::SearchError(_)));
+        assert!(matches!(error(404), ElasticSearchError::SearchError(_)));
+    }
+
+    #[test]
+    fn bulk_failures_come_from_the_items() {
+        // trimmed from a real _bulk response: an index that worked, a mapping error, a rejection and a delete of a
+        // doc that wasn't there, which ES doesn't count as an error
+        let response = json!({
+            "took": 30,
+            "errors": true,
+            "items": [
+                { "index": { "_index": "nfts_v2", "_id": "ok", "_version": 1, "result": "created", "status": 201 } },
+                { "index": { "_index": "nfts_v2", "_id": "bad", "status": 400, "error": {
+                    "type": "document_parsing_exception",
+                    "reason": "[1:40] failed to parse field [royalty_bps] of type [integer]"
+                } } },
+                { "update": { "_index": "nfts_v2", "_id": "busy", "status": 429, "error": {
+                    "type": "es_rejected_execution_exception",
+                    "reason": "rejected execution of coordinating operation"
+                } } },
+                { "delete": { "_index": "nfts_v2", "_id": "gone", "result": "not_found", "status": 404 } }
+            ]
+        });
+
+        let failures = parse_bulk_failures(&response);
+        assert_eq!(failures.len(), 2);
+        assert_eq!((failures[0].id.as_str(), failures[0].status), ("bad", 400));
+        assert!(failures[0].reason.contains("royalty_bps"));
+        assert!(!failures[0].is_retryable());
+        assert_eq!((failures[1].id.as_str(), failures[1].status), ("busy", 429));
+        assert!(failures[1].is_retryable());
+    }
+
+    #[test]
+    fn bulk_failures_without_details() {
+        // errors false means every item went through, whatever the items say
+        assert!(parse_bulk_failures(&json!({ "errors": false, "items": [{ "index": { "_id": "a", "status": 201 } }] }))
+            .is_empty());
+        assert!(parse_bulk_failures(&json!({ "errors": true })).is_empty());
+
+        // an error without a status or reason still counts, as a retryable 500
+        let failures = parse_bulk_failures(&json!({
+            "errors": true,
+            "items": [{ "update": { "_id": "a", "error": "boom" } }, { "index": { "_id": "b", "status": 201 } }]
+        }));
+        assert_eq!(failures.len(), 1);
+        assert_eq!((failures[0].id.as_str(), failures[0].status), ("a", 500));
+        assert_eq!(failures[0].reason, "unknown error");
+        assert!(failures[0].is_retryable());
+    }
+}
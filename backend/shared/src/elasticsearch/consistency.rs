@@ -0,0 +1,143 @@
+use std::collections::HashSet;
+
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QuerySelect};
+
+use crate::elasticsearch::bulk_indexer::index_with_retries;
+use crate::elasticsearch::client::{BulkOp, ElasticSearchClient, ElasticSearchError};
+use crate::elasticsearch::db_source::{active_rows_after, load_nft_docs, record_sync_results};
+use crate::entities::nft_metadata;
+
+const SAMPLE_SIZE: usize = 20; // ids printed per category, the counts are always complete
+
+pub struct ConsistencyOptions {
+    pub chunk_size: u64,
+    pub repair: bool, // index missing and stale docs, delete orphaned ones
+}
+
+#[derive(Debug, Default)]
+pub struct ConsistencyReport {
+    pub checked: u64,
+    pub missing: Vec<String>,  // active in postgres, not in ES
+    pub stale: Vec<String>,    // in both, but the content hash differs
+    pub orphaned: Vec<String>, // in ES, but not an active row in postgres
+    pub repaired: u64,
+    pub repair_failures: u64,
+}
+
+impl ConsistencyReport {
+    pub fn is_consistent(&self) -> bool {
+        self.missing.is_empty() && self.stale.is_empty() && self.orphaned.is_empty()
+    }
+
+    pub fn print(&self) {
+        println!("Checked {} active rows", self.checked);
+        for (label, ids) in [
+            ("missing", &self.missing),
+            ("stale", &self.stale),
+            ("orphaned", &self.orphaned),
+        ] {
+            println!("{} {} docs", ids.len(), label);
+            for id in ids.iter().take(SAMPLE_SIZE) {
+                println!("  {}", id);
+            }
+            if ids.len() > SAMPLE_SIZE {
+                println!("  ...and {} more", ids.len() - SAMPLE_SIZE);
+            }
+        }
+        if self.repaired > 0 || self.repair_failures > 0 {
+            println!("Repaired {} docs, {} still failing", self.repaired, self.repair_failures);
+        }
+    }
+}
+
+// compares the read alias against nft_metadata in two passes: postgres -> ES finds missing and stale docs,
+// ES -> postgres finds orphans. with `repair` every difference is written through the write alias as it is found
+pub async fn check_consistency(
+    es: &ElasticSearchClient,
+    db: &DatabaseConnection,
+    options: ConsistencyOptions,
+) -> Result<ConsistencyReport, ElasticSearchError> {
+    let db_err = |e: sea_orm::DbErr| ElasticSearchError::DocumentError(e.to_string());
+    let mut report = ConsistencyReport::default();
+
+    let mut last_id = None;
+    loop {
+        let rows = active_rows_after(db, last_id, options.chunk_size).await.map_err(db_err)?;
+        let Some(last) = rows.last() else {
+            break;
+        };
+        last_id = Some(last.id);
+        report.checked += rows.len() as u64;
+        let docs = load_nft_docs(db, &rows).await.map_err(db_err)?;
+        let ids: Vec<String> = docs.iter().map(|doc| doc.mint_address.clone()).collect();
+        let stored_hashes = es.fetch_content_hashes(&ids).await?;
+
+        let mut to_index = vec![];
+        for doc in docs {
+            match stored_hashes.get(&doc.mint_address) {
+                None => report.missing.push(doc.mint_address.clone()),
+                Some(stored) if stored.as_deref() != doc.content_hash.as_deref() => {
+                    report.stale.push(doc.mint_address.clone())
+                }
+                Some(_) => continue,
+            }
+            to_index.push(BulkOp::from(doc));
+        }
+
+        if options.repair && !to_index.is_empty() {
+            repair(es, db, to_index, &mut report).await;
+        }
+    }
+
+    let mut after: Option<String> = None;
+    loop {
+        let ids = es.scan_doc_ids(after.as_deref(), options.chunk_size as i64).await?;
+        let Some(last) = ids.last().cloned() else {
+            break;
+        };
+
+        let active: HashSet<String> = nft_metadata::Entity::find()
+            .select_only()
+            .column(nft_metadata::Column::MintAddress)
+            .filter(nft_metadata::Column::MintAddress.is_in(ids.clone()))
+            .filter(nft_metadata::Column::Status.eq(nft_metadata::STATUS_ACTIVE))
+            .into_tuple::<String>()
+            .all(db)
+            .await
+            .map_err(db_err)?
+            .into_iter()
+            .collect();
+
+        let orphans: Vec<String> = ids.into_iter().filter(|id| !active.contains(id)).collect();
+        if options.repair && !orphans.is_empty() {
+            let deletes = orphans.iter().cloned().map(BulkOp::Delete).collect();
+            repair(es, db, deletes, &mut report).await;
+        }
+        report.orphaned.extend(orphans);
+        after = Some(last);
+    }
+
+    report.print();
+    Ok(report)
+}
+
+async fn repair(
+    es: &ElasticSearchClient,
+    db: &DatabaseConnection,
+    operations: Vec<BulkOp>,
+    report: &mut ConsistencyReport,
+) {
+    let ids: Vec<String> = operations.iter().map(|operation| operation.id().to_string()).collect();
+    let failures = index_with_retries(es, es.write_alias(), operations, 3, false).await;
+    let failed_ids: HashSet<&str> = failures.iter().map(|failure| failure.id.as_str()).collect();
+    let succeeded: Vec<String> = ids
+        .into_iter()
+        .filter(|id| !failed_ids.contains(id.as_str()))
+        .collect();
+
+    report.repaired += succeeded.len() as u64;
+    report.repair_failures += failures.len() as u64;
+    if let Err(e) = record_sync_results(db, &succeeded, &failures).await {
+        println!("Failed to record sync results {}", e);
+    }
+}
@@ -0,0 +1,192 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+use sea_orm::prelude::{DateTimeWithTimeZone, Uuid};
+use sea_orm::sea_query::Expr;
+use sea_orm::{ColumnTrait, ConnectionTrait, DbErr, EntityTrait, QueryFilter, QueryOrder, QuerySelect};
+
+use crate::elasticsearch::client::BulkFailure;
+use crate::entities::{nft_creator, nft_json_metadata, nft_metadata, nft_ownership};
+use crate::types::elasticsearch::NftDoc;
+
+// builds the ES docs for a chunk of nft_metadata rows with one query per related table instead of one per row
+pub async fn load_nft_docs<C: ConnectionTrait>(
+    db: &C,
+    metadata_rows: &[nft_metadata::Model],
+) -> Result<Vec<NftDoc>, DbErr> {
+    if metadata_rows.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let mint_addresses: Vec<String> = metadata_rows
+        .iter()
+        .map(|row| row.mint_address.clone())
+        .collect();
+    let metadata_addresses: Vec<String> = metadata_rows
+        .iter()
+        .filter_map(|row| row.metadata_address.clone())
+        .collect();
+
+    let json_by_mint: HashMap<String, nft_json_metadata::Model> = nft_json_metadata::Entity::find()
+        .filter(nft_json_metadata::Column::MintAddress.is_in(mint_addresses.clone()))
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|row| (row.mint_address.clone(), row))
+        .collect();
+
+    let ownership_by_mint: HashMap<String, nft_ownership::Model> = nft_ownership::Entity::find()
+        .filter(nft_ownership::Column::MintAddress.is_in(mint_addresses))
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|row| (row.mint_address.clone(), row))
+        .collect();
+
+    let mut creators_by_metadata: HashMap<String, Vec<nft_creator::Model>> = HashMap::new();
+    if !metadata_addresses.is_empty() {
+        for creator in nft_creator::Entity::find()
+            .filter(nft_creator::Column::MetadataAddress.is_in(metadata_addresses))
+            .all(db)
+            .await?
+        {
+            creators_by_metadata
+                .entry(creator.metadata_address.clone())
+                .or_default()
+                .push(creator);
+        }
+    }
+
+    Ok(metadata_rows
+        .iter()
+        .map(|row| {
+            let creators = row
+                .metadata_address
+                .as_ref()
+                .and_then(|address| creators_by_metadata.get(address))
+                .map(|creators| creators.as_slice())
+                .unwrap_or_default();
+            NftDoc::from_db(
+                row,
+                json_by_mint.get(&row.mint_address),
+                creators,
+                ownership_by_mint.get(&row.mint_address),
+            )
+        })
+        .collect())
+}
+
+// the next chunk of active rows by id, for full scans that run while the worker writes. an offset would shift when
+// rows before it are retired and skip whatever slid back, a row active for the whole scan is seen exactly once this way
+pub async fn active_rows_after<C: ConnectionTrait>(
+    db: &C,
+    after: Option<Uuid>,
+    limit: u64,
+) -> Result<Vec<nft_metadata::Model>, DbErr> {
+    let mut query = nft_metadata::Entity::find()
+        .filter(nft_metadata::Column::Status.eq(nft_metadata::STATUS_ACTIVE))
+        .order_by_asc(nft_metadata::Column::Id)
+        .limit(limit);
+    if let Some(after) = after {
+        query = query.filter(nft_metadata::Column::Id.gt(after));
+    }
+    query.all(db).await
+}
+
+// failed syncs are retried after 1, 2, 4... minutes, capped at 6 hours, and given up on after SYNC_MAX_ATTEMPTS
+const SYNC_RETRY_BASE_SECS: i64 = 60;
+const SYNC_RETRY_MAX_SECS: i64 = 6 * 60 * 60;
+pub const SYNC_MAX_ATTEMPTS: i32 = 12;
+
+// when the worker should try a failed sync again, none when retrying can't help
+pub fn sync_retry_delay(failure: &BulkFailure, attempts: i32) -> Option<chrono::Duration> {
+    if !failure.is_retryable() || attempts >= SYNC_MAX_ATTEMPTS {
+        return None;
+    }
+    let exponent = attempts.saturating_sub(1).clamp(0, 30) as u32;
+    let secs = SYNC_RETRY_BASE_SECS.saturating_mul(2i64.pow(exponent)).min(SYNC_RETRY_MAX_SECS);
+    Some(chrono::Duration::seconds(secs))
+}
+
+// stores the outcome of a bulk flush on the rows, the worker's retry pass picks up the failed ones that are due
+pub async fn record_sync_results<C: ConnectionTrait>(
+    db: &C,
+    succeeded: &[String],
+    failed: &[BulkFailure],
+) -> Result<(), DbErr> {
+    if !succeeded.is_empty() {
+        nft_metadata::Entity::update_many()
+            .col_expr(nft_metadata::Column::EsSyncStatus, Expr::value(nft_metadata::ES_SYNC_SYNCED))
+            .col_expr(nft_metadata::Column::EsSyncError, Expr::value(Option::<String>::None))
+            .col_expr(nft_metadata::Column::EsSyncFailureStatus, Expr::value(Option::<i16>::None))
+            .col_expr(nft_metadata::Column::EsSyncAttempts, Expr::value(0))
+            .col_expr(nft_metadata::Column::EsSyncRetryAt, Expr::value(Option::<DateTimeWithTimeZone>::None))
+            .col_expr(nft_metadata::Column::EsSyncUpdatedAt, Expr::current_timestamp().into())
+            .filter(nft_metadata::Column::MintAddress.is_in(succeeded.iter().cloned()))
+            .exec(db)
+            .await?;
+    }
+    if failed.is_empty() {
+        return Ok(());
+    }
+
+    let attempts: HashMap<String, i32> = nft_metadata::Entity::find()
+        .select_only()
+        .columns([nft_metadata::Column::MintAddress, nft_metadata::Column::EsSyncAttempts])
+        .filter(nft_metadata::Column::MintAddress.is_in(failed.iter().map(|failure| failure.id.clone())))
+        .into_tuple::<(String, i32)>()
+        .all(db)
+        .await?
+        .into_iter()
+        .collect();
+
+    for failure in failed {
+        let attempts = attempts.get(&failure.id).copied().unwrap_or(0).saturating_add(1);
+        let retry_at = sync_retry_delay(failure, attempts).map(|delay| (Utc::now() + delay).fixed_offset());
+        nft_metadata::Entity::update_many()
+            .col_expr(nft_metadata::Column::EsSyncStatus, Expr::value(nft_metadata::ES_SYNC_FAILED))
+            .col_expr(
+                nft_metadata::Column::EsSyncError,
+                Expr::value(format!("{} : {}", failure.status, failure.reason)),
+            )
+            .col_expr(nft_metadata::Column::EsSyncFailureStatus, Expr::value(failure.status as i16))
+            .col_expr(nft_metadata::Column::EsSyncAttempts, Expr::value(attempts))
+            .col_expr(nft_metadata::Column::EsSyncRetryAt, Expr::value(retry_at))
+            .col_expr(nft_metadata::Column::EsSyncUpdatedAt, Expr::current_timestamp().into())
+            .filter(nft_metadata::Column::MintAddress.eq(&failure.id))
+            .exec(db)
+            .await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn failure(status: u16) -> BulkFailure {
+        BulkFailure {
+            id: "mint".to_string(),
+            status,
+            reason: "reason".to_string(),
+        }
+    }
+
+    #[test]
+    fn permanent_failures_are_not_retried() {
+        assert_eq!(sync_retry_delay(&failure(400), 1), None);
+        assert_eq!(sync_retry_delay(&failure(409), 1), None);
+    }
+
+    #[test]
+    fn transient_failures_back_off_until_the_cap() {
+        assert_eq!(sync_retry_delay(&failure(429), 1), Some(chrono::Duration::seconds(60)));
+        assert_eq!(sync_retry_delay(&failure(503), 2), Some(chrono::Duration::seconds(120)));
+        assert_eq!(sync_retry_delay(&failure(500), 4), Some(chrono::Duration::seconds(480)));
+        assert_eq!(
+            sync_retry_delay(&failure(502), SYNC_MAX_ATTEMPTS - 1),
+            Some(chrono::Duration::seconds(SYNC_RETRY_MAX_SECS))
+        );
+        assert_eq!(sync_retry_delay(&failure(503), SYNC_MAX_ATTEMPTS), None);
+    }
+}
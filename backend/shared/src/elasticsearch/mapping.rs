@@ -0,0 +1,153 @@
+use serde_json::{json, Value};
+
+// stored in the index _meta so a deployment can tell which mapping an existing index was created with
+pub const NFT_MAPPING_VERSION: u32 = 5;
+
+pub fn nft_index_body() -> Value {
+    json!({
+        "mappings" : {
+            "_meta" : {
+                "mapping_version" : NFT_MAPPING_VERSION
+            },
+            "dynamic" : "strict", // a field missing from here is a bug, not something ES should guess the type for
+            "properties" : {
+                "mint_address" : {
+                    "type" : "keyword"
+                },
+                "metadata_address" : {
+                    "type" : "keyword"
+                },
+                "nft_name" : {
+                    "type" : "text",
+                    "analyzer" : "standard",
+                    "fields" : {
+                        "keyword" : {
+                            "type" : "keyword",
+                            "ignore_above" : 256
+                        },
+                        "prefix" : prefix_subfield()
+                    }
+                },
+                "symbol" : {
+                    "type" : "keyword"
+                },
+                "collection" : {
+                    "properties" : {
+                        "name" : {
+                            "type" : "text",
+                            "analyzer" : "standard",
+                            "fields" : {
+                                "keyword" : {
+                                    "type" : "keyword",
+                                    "ignore_above" : 256
+                                },
+                                "prefix" : prefix_subfield()
+                            }
+                        },
+                        "mint" : {
+                            "type" : "keyword"
+                        },
+                        "verified" : {
+                            "type" : "boolean"
+                        }
+                    }
+                },
+                "creators" : {
+                    "type" : "nested",
+                    "properties" : {
+                        "address" : {
+                            "type" : "keyword"
+                        },
+                        "verified" : {
+                            "type" : "boolean"
+                        },
+                        "share" : {
+                            "type" : "short"
+                        }
+                    }
+                },
+                "update_authority" : {
+                    "type" : "keyword"
+                },
+                "token_standard" : {
+                    "type" : "keyword"
+                },
+                "attributes" : {
+                    "type" : "nested", // nested keeps each trait_type paired with its own value when filtering
+                    "properties" : {
+                        "trait_type" : {
+                            "type" : "keyword"
+                        },
+                        "value" : {
+                            "type" : "keyword"
+                        }
+                    }
+                },
+                "image" : {
+                    "type" : "keyword",
+                    "index" : false // only returned for display, never searched
+                },
+                "royalty_bps" : {
+                    "type" : "integer"
+                },
+                "owner" : {
+                    "type" : "keyword"
+                },
+                "created_at" : {
+                    "type" : "date"
+                },
+                "updated_at" : {
+                    "type" : "date"
+                },
+                "content_hash" : {
+                    "type" : "keyword",
+                    "index" : false // only read back by the consistency check
+                }
+            }
+        },
+        "settings" : {
+            "number_of_shards" : 1,
+            "number_of_replicas" : 2,
+            "refresh_interval" : "1s",
+            "analysis" : {
+                "tokenizer" : {
+                    // "Degen Ape" is indexed as d, de, deg... a, ap, ape so a typed prefix is a plain term lookup
+                    "autocomplete_tokenizer" : {
+                        "type" : "edge_ngram",
+                        "min_gram" : 1,
+                        "max_gram" : 20,
+                        "token_chars" : ["letter", "digit"]
+                    }
+                },
+                "analyzer" : {
+                    "autocomplete" : {
+                        "type" : "custom",
+                        "tokenizer" : "autocomplete_tokenizer",
+                        "filter" : ["lowercase"]
+                    },
+                    "autocomplete_search" : {
+                        "type" : "custom",
+                        "tokenizer" : "standard",
+                        "filter" : ["lowercase"]
+                    }
+                }
+            }
+        }
+    })
+}
+
+// edge-ngram subfield used by /suggest. the query side is not ngrammed, otherwise "ape" would also match "a..."
+fn prefix_subfield() -> Value {
+    json!({
+        "type" : "text",
+        "analyzer" : "autocomplete",
+        "search_analyzer" : "autocomplete_search"
+    })
+}
+
+// reads mappings._meta.mapping_version out of a GET /{index}/_mapping response
+pub fn mapping_version_from_response(index_name: &str, mapping_response: &Value) -> Option<u32> {
+    mapping_response[index_name]["mappings"]["_meta"]["mapping_version"]
+        .as_u64()
+        .map(|version| version as u32)
+}
@@ -1 +1,9 @@
-pub mod client;
\ No newline at end of file
+pub mod bulk_indexer;
+pub mod classify;
+pub mod client;
+pub mod consistency;
+pub mod db_source;
+pub mod mapping;
+pub mod outbox;
+pub mod query;
+pub mod reindex;
\ No newline at end of file
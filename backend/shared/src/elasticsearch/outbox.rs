@@ -0,0 +1,469 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use sea_orm::sea_query::{Expr, LockBehavior, LockType};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter, QueryOrder,
+    QuerySelect, Set, TransactionTrait,
+};
+use serde_json::Value;
+use tokio::task::JoinHandle;
+
+use crate::elasticsearch::client::{BulkFailure, BulkOp};
+use crate::elasticsearch::db_source::{load_nft_docs, record_sync_results};
+use crate::entities::{nft_metadata, sync_outbox};
+use crate::redis::cache::ResponseCache;
+use crate::search::SearchIndex;
+use crate::shutdown::Shutdown;
+
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+const DONE_RETENTION_DAYS: i64 = 7;
+const MAX_BACKOFF_SECS: i64 = 600;
+const CLAIM_LEASE_SECS: i64 = 5 * 60; // a claimed batch is left alone this long, well past a bulk call's timeout
+
+#[derive(Debug, Clone)]
+pub struct OutboxRelayConfig {
+    pub batch_size: u64,
+    pub poll_interval: Duration, // sleep between polls while the outbox is empty
+    pub max_attempts: i32,       // after this many failed relays a row is marked failed and left alone
+}
+
+impl Default for OutboxRelayConfig {
+    fn default() -> Self {
+        Self {
+            batch_size: 500,
+            poll_interval: Duration::from_millis(500),
+            max_attempts: 10,
+        }
+    }
+}
+
+impl OutboxRelayConfig {
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let env_number = |name: &str| std::env::var(name).ok().and_then(|v| v.parse::<u64>().ok());
+
+        Self {
+            batch_size: env_number("OUTBOX_BATCH_SIZE")
+                .map(|v| v.max(1))
+                .unwrap_or(defaults.batch_size),
+            poll_interval: env_number("OUTBOX_POLL_MS")
+                .map(Duration::from_millis)
+                .unwrap_or(defaults.poll_interval),
+            max_attempts: env_number("OUTBOX_MAX_ATTEMPTS")
+                .map(|v| v as i32)
+                .unwrap_or(defaults.max_attempts),
+        }
+    }
+}
+
+// adds a row to the outbox. pass the transaction that writes the change itself, so both commit or neither does
+pub async fn enqueue_outbox<C: ConnectionTrait>(
+    conn: &C,
+    mint_address: &str,
+    operation: &str,
+    payload: Option<Value>,
+) -> Result<(), DbErr> {
+    sync_outbox::ActiveModel {
+        mint_address: Set(mint_address.to_string()),
+        operation: Set(operation.to_string()),
+        payload: Set(payload),
+        ..Default::default()
+    }
+    .insert(conn)
+    .await?;
+    Ok(())
+}
+
+// pushes outbox rows to the search index in id order. rows are claimed with FOR UPDATE SKIP LOCKED and a lease,
+// so more than one relay can run against the same database
+pub struct OutboxRelay {
+    db: DatabaseConnection,
+    search_index: Arc<dyn SearchIndex>,
+    cache: Arc<ResponseCache>, // cached search results are dropped once a batch reaches the index
+    config: OutboxRelayConfig,
+}
+
+impl OutboxRelay {
+    pub fn new(
+        db: DatabaseConnection,
+        search_index: Arc<dyn SearchIndex>,
+        cache: Arc<ResponseCache>,
+        config: OutboxRelayConfig,
+    ) -> Self {
+        Self {
+            db,
+            search_index,
+            cache,
+            config,
+        }
+    }
+
+    pub fn spawn(self, shutdown: Shutdown) -> JoinHandle<()> {
+        tokio::spawn(self.run(shutdown))
+    }
+
+    pub async fn run(self, shutdown: Shutdown) {
+        println!(
+            "Starting outbox relay (batch of {}, {} attempts max)...",
+            self.config.batch_size, self.config.max_attempts
+        );
+        let mut last_purge = Instant::now();
+        while !shutdown.is_triggered() {
+            match self.relay_batch().await {
+                Ok(0) => shutdown.sleep(self.config.poll_interval).await,
+                Ok(relayed) => println!("Relayed {} outbox rows", relayed),
+                Err(e) => {
+                    println!("Outbox relay error {}", e);
+                    shutdown.sleep(Duration::from_secs(2)).await;
+                }
+            }
+
+            if last_purge.elapsed() >= PURGE_INTERVAL {
+                if let Err(e) = self.purge_done().await {
+                    println!("Failed to purge the outbox {}", e);
+                }
+                last_purge = Instant::now();
+            }
+        }
+
+        // flush what's already due so a restart doesn't leave the index behind, whatever's left stays in the outbox
+        loop {
+            match self.relay_batch().await {
+                Ok(0) => break,
+                Ok(relayed) => println!("Relayed {} outbox rows before stopping", relayed),
+                Err(e) => {
+                    println!("Outbox relay error while stopping {}", e);
+                    break;
+                }
+            }
+        }
+        println!("Outbox relay stopped");
+    }
+
+    // claims one batch of due rows, writes them with one bulk call and records the outcome. returns the rows handled
+    pub async fn relay_batch(&self) -> Result<usize, DbErr> {
+        let rows = self.claim_batch().await?;
+        if rows.is_empty() {
+            return Ok(0);
+        }
+
+        let operations = self.build_operations(&self.db, &rows).await?;
+        // a backend that can't be reached at all fails the whole batch, which then backs off like any other failure
+        let ids: Vec<String> = operations.iter().map(|operation| operation.id().to_string()).collect();
+        let failures = match self.search_index.bulk(operations).await {
+            Ok(failures) => failures,
+            Err(e) => ids
+                .into_iter()
+                .map(|id| BulkFailure {
+                    id,
+                    status: 503,
+                    reason: e.to_string(),
+                })
+                .collect(),
+        };
+
+        // there's one operation per mint in the batch, so its failure is the failure of every row it was built from.
+        // updating a doc that's already gone is not worth retrying
+        let failures: HashMap<String, BulkFailure> = failures
+            .into_iter()
+            .filter(|failure| failure.status != 404)
+            .map(|failure| (failure.id.clone(), failure))
+            .collect();
+
+        let txn = self.db.begin().await?;
+        let mut done_ids = vec![];
+        let mut synced_mints = HashSet::new();
+        // the asset only counts as failed once its outbox row is given up on, until then the relay keeps retrying
+        let mut failed = HashMap::new();
+        for row in &rows {
+            match failures.get(&row.mint_address) {
+                None => {
+                    done_ids.push(row.id);
+                    synced_mints.insert(row.mint_address.clone());
+                }
+                Some(failure) => {
+                    if self.record_failure(&txn, row, failure).await? {
+                        failed.insert(failure.id.clone(), failure.clone());
+                    }
+                }
+            }
+        }
+
+        if !done_ids.is_empty() {
+            sync_outbox::Entity::update_many()
+                .col_expr(sync_outbox::Column::Status, Expr::value(sync_outbox::STATUS_DONE))
+                .col_expr(sync_outbox::Column::ProcessedAt, Expr::current_timestamp().into())
+                .filter(sync_outbox::Column::Id.is_in(done_ids))
+                .exec(&txn)
+                .await?;
+        }
+
+        let synced_mints: Vec<String> = synced_mints.into_iter().collect();
+        let failed: Vec<BulkFailure> = failed.into_values().collect();
+        record_sync_results(&txn, &synced_mints, &failed).await?;
+
+        txn.commit().await?;
+        // bulk only returns once the docs are searchable, so a search after this can't cache the old results
+        if !synced_mints.is_empty() {
+            if let Err(e) = self.cache.invalidate_search().await {
+                println!("Error dropping the cached search results {}", e);
+            }
+        }
+        Ok(rows.len())
+    }
+
+    // takes the due rows out of everyone else's reach by moving next_attempt_at past the bulk call, and commits
+    // before ES is called so no row locks are held meanwhile. a relay that dies mid batch leaves its rows to be
+    // claimed again once the lease runs out
+    async fn claim_batch(&self) -> Result<Vec<sync_outbox::Model>, DbErr> {
+        let txn = self.db.begin().await?;
+        let rows = sync_outbox::Entity::find()
+            .filter(sync_outbox::Column::Status.eq(sync_outbox::STATUS_PENDING))
+            .filter(sync_outbox::Column::NextAttemptAt.lte(Utc::now().fixed_offset()))
+            .order_by_asc(sync_outbox::Column::Id)
+            .limit(self.config.batch_size)
+            .lock_with_behavior(LockType::Update, LockBehavior::SkipLocked)
+            .all(&txn)
+            .await?;
+        if !rows.is_empty() {
+            sync_outbox::Entity::update_many()
+                .col_expr(
+                    sync_outbox::Column::NextAttemptAt,
+                    Expr::value((Utc::now() + chrono::Duration::seconds(CLAIM_LEASE_SECS)).fixed_offset()),
+                )
+                .filter(sync_outbox::Column::Id.is_in(rows.iter().map(|row| row.id)))
+                .exec(&txn)
+                .await?;
+        }
+        txn.commit().await?;
+        Ok(rows)
+    }
+
+    // upserts and deletes are rebuilt from the current rows, which already hold whatever a queued partial update
+    // carried, so a burst of changes to one mint costs one doc build
+    async fn build_operations<C: ConnectionTrait>(
+        &self,
+        conn: &C,
+        rows: &[sync_outbox::Model],
+    ) -> Result<Vec<BulkOp>, DbErr> {
+        let rebuild_mints: Vec<String> = rows
+            .iter()
+            .filter(|row| row.operation != sync_outbox::OPERATION_UPDATE)
+            .map(|row| row.mint_address.clone())
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+
+        let metadata_rows = if rebuild_mints.is_empty() {
+            vec![]
+        } else {
+            nft_metadata::Entity::find()
+                .filter(nft_metadata::Column::MintAddress.is_in(rebuild_mints))
+                .filter(nft_metadata::Column::Status.eq(nft_metadata::STATUS_ACTIVE))
+                .all(conn)
+                .await?
+        };
+        let docs: HashMap<String, BulkOp> = load_nft_docs(conn, &metadata_rows)
+            .await?
+            .into_iter()
+            .map(|doc| (doc.mint_address.clone(), BulkOp::from(doc)))
+            .collect();
+
+        Ok(plan_operations(rows, docs))
+    }
+
+    // true when the row is given up on, after max_attempts or on a failure retrying can't fix
+    async fn record_failure<C: ConnectionTrait>(
+        &self,
+        conn: &C,
+        row: &sync_outbox::Model,
+        failure: &BulkFailure,
+    ) -> Result<bool, DbErr> {
+        let attempts = row.attempts + 1;
+        let gave_up = attempts >= self.config.max_attempts || !failure.is_retryable();
+        let status = if gave_up {
+            sync_outbox::STATUS_FAILED
+        } else {
+            sync_outbox::STATUS_PENDING
+        };
+        let backoff = 2i64.saturating_pow(attempts as u32).min(MAX_BACKOFF_SECS);
+
+        let mut active: sync_outbox::ActiveModel = row.clone().into();
+        active.attempts = Set(attempts);
+        active.status = Set(status.to_string());
+        active.last_error = Set(Some(format!("{} : {}", failure.status, failure.reason)));
+        active.next_attempt_at = Set((Utc::now() + chrono::Duration::seconds(backoff)).fixed_offset());
+        active.update(conn).await?;
+        Ok(gave_up)
+    }
+
+    async fn purge_done(&self) -> Result<(), DbErr> {
+        let cutoff = (Utc::now() - chrono::Duration::days(DONE_RETENTION_DAYS)).fixed_offset();
+        let purged = sync_outbox::Entity::delete_many()
+            .filter(sync_outbox::Column::Status.eq(sync_outbox::STATUS_DONE))
+            .filter(sync_outbox::Column::ProcessedAt.lt(cutoff))
+            .exec(&self.db)
+            .await?;
+        println!("Purged {} relayed outbox rows", purged.rows_affected);
+        Ok(())
+    }
+}
+
+// one operation per mint, at the position of the mint's last row. a mint with an upsert or delete in the batch gets
+// its rebuilt doc from `docs`, or a delete when it's no longer active. a mint with only partial updates gets them
+// merged in order, the way ES would have applied them one by one
+fn plan_operations(rows: &[sync_outbox::Model], mut docs: HashMap<String, BulkOp>) -> Vec<BulkOp> {
+    let mut last_row = HashMap::new();
+    let mut rebuilt = HashSet::new();
+    let mut partials: HashMap<&str, Value> = HashMap::new();
+    for (position, row) in rows.iter().enumerate() {
+        match row.operation.as_str() {
+            sync_outbox::OPERATION_UPSERT | sync_outbox::OPERATION_DELETE => {
+                rebuilt.insert(row.mint_address.as_str());
+            }
+            sync_outbox::OPERATION_UPDATE => {
+                let partial = partials
+                    .entry(row.mint_address.as_str())
+                    .or_insert_with(|| Value::Object(Default::default()));
+                merge_partial(partial, row.payload.clone().unwrap_or_default());
+            }
+            other => {
+                println!("Unknown outbox operation {} for row {}", other, row.id);
+                continue;
+            }
+        }
+        last_row.insert(row.mint_address.as_str(), position);
+    }
+
+    let mut operations = vec![];
+    for (position, row) in rows.iter().enumerate() {
+        let mint_address = row.mint_address.as_str();
+        if last_row.get(mint_address) != Some(&position) {
+            continue;
+        }
+        if rebuilt.contains(mint_address) {
+            // no active row means the asset was retired after the change was queued
+            operations.push(
+                docs.remove(mint_address)
+                    .unwrap_or_else(|| BulkOp::Delete(mint_address.to_string())),
+            );
+        } else if let Some(partial) = partials.remove(mint_address) {
+            operations.push(BulkOp::Update {
+                id: mint_address.to_string(),
+                partial,
+            });
+        }
+    }
+    operations
+}
+
+// objects merge key by key like a partial update does in ES, anything else is replaced
+fn merge_partial(target: &mut Value, patch: Value) {
+    match (target, patch) {
+        (Value::Object(target), Value::Object(patch)) => {
+            for (key, value) in patch {
+                match target.get_mut(&key) {
+                    Some(existing) if existing.is_object() && value.is_object() => merge_partial(existing, value),
+                    _ => {
+                        target.insert(key, value);
+                    }
+                }
+            }
+        }
+        (target, patch) => *target = patch,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn row(id: i64, mint_address: &str, operation: &str, payload: Option<Value>) -> sync_outbox::Model {
+        let now = Utc::now().fixed_offset();
+        sync_outbox::Model {
+            id,
+            mint_address: mint_address.to_string(),
+            operation: operation.to_string(),
+            payload,
+            status: sync_outbox::STATUS_PENDING.to_string(),
+            attempts: 0,
+            last_error: None,
+            next_attempt_at: now,
+            processed_at: None,
+            created_at: now,
+        }
+    }
+
+    fn describe(operations: &[BulkOp]) -> Vec<String> {
+        operations
+            .iter()
+            .map(|operation| match operation {
+                BulkOp::Index(doc) => format!("index {}", doc.mint_address),
+                BulkOp::Update { id, partial } => format!("update {} {}", id, partial),
+                BulkOp::Delete(id) => format!("delete {}", id),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn a_rebuilt_mint_gets_one_operation_at_its_last_row() {
+        let rows = [
+            row(1, "a", sync_outbox::OPERATION_UPSERT, None),
+            row(2, "a", sync_outbox::OPERATION_UPDATE, Some(json!({ "owner": null }))),
+            row(3, "b", sync_outbox::OPERATION_UPDATE, Some(json!({ "owner": null }))),
+            row(4, "a", sync_outbox::OPERATION_UPSERT, None),
+        ];
+        // "a" is no longer active, so its rebuild is a delete
+        assert_eq!(
+            describe(&plan_operations(&rows, HashMap::new())),
+            ["update b {\"owner\":null}", "delete a"]
+        );
+    }
+
+    #[test]
+    fn an_update_after_the_last_upsert_is_folded_into_the_rebuild() {
+        let rows = [
+            row(1, "a", sync_outbox::OPERATION_UPSERT, None),
+            row(2, "a", sync_outbox::OPERATION_UPDATE, Some(json!({ "owner": null }))),
+        ];
+        assert_eq!(describe(&plan_operations(&rows, HashMap::new())), ["delete a"]);
+    }
+
+    #[test]
+    fn partial_updates_alone_are_merged_in_order() {
+        let rows = [
+            row(
+                1,
+                "a",
+                sync_outbox::OPERATION_UPDATE,
+                Some(json!({ "owner": "x", "ownership": { "frozen": true } })),
+            ),
+            row(
+                2,
+                "a",
+                sync_outbox::OPERATION_UPDATE,
+                Some(json!({ "owner": null, "ownership": { "delegated": true } })),
+            ),
+        ];
+        let operations = plan_operations(&rows, HashMap::new());
+        assert_eq!(operations.len(), 1);
+        let BulkOp::Update { id, partial } = &operations[0] else {
+            panic!("expected a partial update, got {:?}", operations[0]);
+        };
+        assert_eq!(id, "a");
+        assert_eq!(
+            partial,
+            &json!({ "owner": null, "ownership": { "frozen": true, "delegated": true } })
+        );
+    }
+
+    #[test]
+    fn unknown_operations_are_skipped() {
+        let rows = [row(1, "a", "reindex", None)];
+        assert!(plan_operations(&rows, HashMap::new()).is_empty());
+    }
+}
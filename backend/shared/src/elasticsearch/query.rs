@@ -0,0 +1,674 @@
+use serde_json::{json, Value};
+
+use crate::elasticsearch::client::ElasticSearchError;
+use crate::types::elasticsearch::{
+    CollectionFacet, FacetBucket, PageRequest, SearchAggregations, SearchFilters, SearchRequest,
+    SearchSort, Suggestion, TraitFacet,
+};
+
+pub const DEFAULT_PAGE_SIZE: i64 = 20;
+pub const MAX_PAGE_SIZE: i64 = 100;
+pub const FACET_SIZE: usize = 20;
+pub const DEFAULT_SUGGEST_SIZE: i64 = 8;
+pub const MAX_SUGGEST_SIZE: i64 = 20;
+pub const SUGGEST_OVERFETCH: i64 = 5; // a popular collection can fill the first hits on its own, fetch extra so de-duplication still leaves enough
+
+// the free text part of a search, scored against nft_name
+pub fn name_query(trimmed_query: &str) -> Value {
+    json!({
+        "bool": {
+            "should": [
+                // exact phrase match
+                {
+                    "match_phrase": {
+                        "nft_name": {
+                            "query": trimmed_query,
+                            "boost": 4.0 // the priority this matching strategy is given
+                        }
+                    }
+                },
+                // EXACT CASE-INSENSITIVE MATCH
+                {
+                    "term": {
+                        "nft_name.keyword": {
+                            "value": trimmed_query,
+                            "boost": 3.5,
+                            "case_insensitive": true
+                        }
+                    }
+                },
+                // FUZZY MATCHING - can handle typos in the word.
+                {
+                    "match": {
+                        "nft_name": {
+                            "query": trimmed_query,
+                            "fuzziness": "AUTO",
+                            "operator": "or",
+                            "boost": 2.5
+                        }
+                    }
+                },
+                // SUBSTRING MATCHING (case-insensitive)
+                {
+                    "wildcard": {
+                        "nft_name": {
+                            "value": format!("*{}*", trimmed_query.to_lowercase()),
+                            "boost": 2.0,
+                            "case_insensitive": true
+                        }
+                    }
+                },
+                // PREFIX MATCHING - any name that starts with query (search term)
+                {
+                    "prefix": {
+                        "nft_name": {
+                            "value": trimmed_query.to_lowercase(),
+                            "boost": 1.5,
+                            "case_insensitive": true
+                        }
+                    }
+                },
+            ],
+            "minimum_should_match": 1
+        }
+    })
+}
+
+// filters go in filter context, so they narrow the results without touching the score
+pub fn filter_clauses(filters: &SearchFilters) -> Vec<Value> {
+    let mut clauses = vec![];
+
+    if let Some(collection) = &filters.collection {
+        clauses.push(json!({
+            "bool": {
+                "should": [
+                    { "term": { "collection.mint": collection } },
+                    { "term": { "collection.name.keyword": collection } }
+                ],
+                "minimum_should_match": 1
+            }
+        }));
+    }
+
+    if let Some(creator) = &filters.creator {
+        clauses.push(json!({
+            "nested": {
+                "path": "creators",
+                "query": { "term": { "creators.address": creator } }
+            }
+        }));
+    }
+
+    if let Some(update_authority) = &filters.update_authority {
+        clauses.push(json!({ "term": { "update_authority": update_authority } }));
+    }
+
+    if let Some(token_standard) = &filters.token_standard {
+        clauses.push(json!({ "term": { "token_standard": token_standard } }));
+    }
+
+    if filters.min_royalty_bps.is_some() || filters.max_royalty_bps.is_some() {
+        let mut range = serde_json::Map::new();
+        if let Some(min) = filters.min_royalty_bps {
+            range.insert("gte".to_string(), json!(min));
+        }
+        if let Some(max) = filters.max_royalty_bps {
+            range.insert("lte".to_string(), json!(max));
+        }
+        clauses.push(json!({ "range": { "royalty_bps": range } }));
+    }
+
+    // one nested query per pair, otherwise trait_type of one attribute could match the value of another
+    for attribute in &filters.attributes {
+        clauses.push(json!({
+            "nested": {
+                "path": "attributes",
+                "query": {
+                    "bool": {
+                        "filter": [
+                            { "term": { "attributes.trait_type": attribute.trait_type } },
+                            { "term": { "attributes.value": attribute.value } }
+                        ]
+                    }
+                }
+            }
+        }));
+    }
+
+    if filters.verified_collection_only {
+        clauses.push(json!({ "term": { "collection.verified": true } }));
+    }
+
+    clauses
+}
+
+pub fn facet_aggregations() -> Value {
+    json!({
+        "collections": {
+            "terms": { "field": "collection.mint", "size": FACET_SIZE },
+            "aggs": {
+                "name": { "terms": { "field": "collection.name.keyword", "size": 1 } }
+            }
+        },
+        "traits": {
+            "nested": { "path": "attributes" },
+            "aggs": {
+                "trait_types": {
+                    "terms": { "field": "attributes.trait_type", "size": FACET_SIZE },
+                    "aggs": {
+                        "values": { "terms": { "field": "attributes.value", "size": FACET_SIZE } }
+                    }
+                }
+            }
+        }
+    })
+}
+
+pub fn build_search_query(request: &SearchRequest) -> Result<Value, ElasticSearchError> {
+    let text = request
+        .query
+        .as_deref()
+        .map(str::trim)
+        .filter(|query| !query.is_empty());
+
+    let must = match text {
+        Some(text) => vec![name_query(text)],
+        None => vec![json!({ "match_all": {} })],
+    };
+
+    let mut search_query = json!({
+        "query": {
+            "bool": {
+                "must": must,
+                "filter": filter_clauses(&request.filters)
+            }
+        }
+    });
+
+    if request.include_facets {
+        search_query["aggs"] = facet_aggregations();
+    }
+
+    apply_page(&mut search_query, &request.page)?;
+    Ok(search_query)
+}
+
+pub fn page_size(page: &PageRequest) -> i64 {
+    page.size.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE)
+}
+
+// mint_address is unique, so it's the tiebreaker that makes search_after stable for every sort
+pub fn sort_clauses(sort: SearchSort) -> Value {
+    let tiebreaker = json!({ "mint_address": { "order": "asc" } });
+    match sort {
+        SearchSort::Relevance => json!([{ "_score": { "order": "desc" } }, tiebreaker]),
+        SearchSort::Name => json!([
+            { "nft_name.keyword": { "order": "asc", "missing": "_last" } },
+            tiebreaker
+        ]),
+        SearchSort::Newest => json!([
+            { "created_at": { "order": "desc", "missing": "_last" } },
+            tiebreaker
+        ]),
+        SearchSort::Royalty => json!([
+            { "royalty_bps": { "order": "asc", "missing": "_last" } },
+            tiebreaker
+        ]),
+    }
+}
+
+// sets size, sort, search_after and hit counting on an already built query
+pub fn apply_page(search_query: &mut Value, page: &PageRequest) -> Result<(), ElasticSearchError> {
+    search_query["size"] = json!(page_size(page));
+    search_query["sort"] = sort_clauses(page.sort);
+    search_query["track_total_hits"] = json!(true);
+
+    if let Some(cursor) = &page.cursor {
+        search_query["search_after"] = decode_cursor(cursor, page.sort)?;
+    }
+    Ok(())
+}
+
+// the cursor is the sort values of the last hit, tagged with the sort they belong to and base58 encoded so clients treat it as opaque
+pub fn encode_cursor(sort: SearchSort, sort_values: &Value) -> String {
+    let payload = json!({ "sort": sort, "after": sort_values });
+    bs58::encode(payload.to_string()).into_string()
+}
+
+pub fn decode_cursor(cursor: &str, sort: SearchSort) -> Result<Value, ElasticSearchError> {
+    let invalid = || ElasticSearchError::InvalidRequest("Invalid cursor".to_string());
+
+    let bytes = bs58::decode(cursor).into_vec().map_err(|_| invalid())?;
+    let payload: Value = serde_json::from_slice(&bytes).map_err(|_| invalid())?;
+
+    if payload["sort"] != json!(sort) {
+        return Err(ElasticSearchError::InvalidRequest(
+            "Cursor was issued for a different sort".to_string(),
+        ));
+    }
+
+    match &payload["after"] {
+        Value::Array(values) if !values.is_empty() => Ok(payload["after"].clone()),
+        _ => Err(invalid()),
+    }
+}
+
+// only hand out a cursor when the page came back full, a short page is the last one
+pub fn next_cursor(search_response: &Value, page: &PageRequest) -> Option<String> {
+    let hits = search_response["hits"]["hits"].as_array()?;
+    if (hits.len() as i64) < page_size(page) {
+        return None;
+    }
+    let last_sort = hits.last()?.get("sort")?;
+    Some(encode_cursor(page.sort, last_sort))
+}
+
+// hits.total comes back as {"value": n, "relation": "eq" | "gte"}
+pub fn parse_total(search_response: &Value) -> (u64, bool) {
+    let total = &search_response["hits"]["total"];
+    (
+        total["value"].as_u64().unwrap_or(0),
+        total["relation"].as_str().unwrap_or("eq") == "eq",
+    )
+}
+
+pub fn parse_aggregations(search_response: &Value) -> Option<SearchAggregations> {
+    let aggregations = search_response.get("aggregations")?;
+
+    let buckets = |value: &Value| value["buckets"].as_array().cloned().unwrap_or_default();
+
+    let collections = buckets(&aggregations["collections"])
+        .iter()
+        .filter_map(|bucket| {
+            Some(CollectionFacet {
+                mint: bucket["key"].as_str()?.to_string(),
+                name: buckets(&bucket["name"])
+                    .first()
+                    .and_then(|name| name["key"].as_str())
+                    .map(str::to_string),
+                count: bucket["doc_count"].as_u64().unwrap_or(0),
+            })
+        })
+        .collect();
+
+    let traits = buckets(&aggregations["traits"]["trait_types"])
+        .iter()
+        .filter_map(|bucket| {
+            Some(TraitFacet {
+                trait_type: bucket["key"].as_str()?.to_string(),
+                count: bucket["doc_count"].as_u64().unwrap_or(0),
+                values: buckets(&bucket["values"])
+                    .iter()
+                    .filter_map(|value| {
+                        Some(FacetBucket {
+                            value: value["key"].as_str()?.to_string(),
+                            count: value["doc_count"].as_u64().unwrap_or(0),
+                        })
+                    })
+                    .collect(),
+            })
+        })
+        .collect();
+
+    Some(SearchAggregations {
+        collections,
+        traits,
+    })
+}
+
+pub fn suggest_size(size: Option<i64>) -> i64 {
+    size.unwrap_or(DEFAULT_SUGGEST_SIZE).clamp(1, MAX_SUGGEST_SIZE)
+}
+
+// typeahead query, only term lookups against the edge-ngram subfields so it stays cheap as the index grows
+pub fn build_suggest_query(trimmed_query: &str, size: i64) -> Value {
+    json!({
+        "size": size * SUGGEST_OVERFETCH,
+        "_source": ["mint_address", "nft_name", "collection", "image"],
+        "track_total_hits": false,
+        "timeout": "200ms",
+        "query": {
+            "bool": {
+                "should": [
+                    {
+                        "term": {
+                            "nft_name.keyword": {
+                                "value": trimmed_query,
+                                "boost": 5.0,
+                                "case_insensitive": true
+                            }
+                        }
+                    },
+                    {
+                        "match": {
+                            "nft_name.prefix": {
+                                "query": trimmed_query,
+                                "operator": "and",
+                                "boost": 2.0
+                            }
+                        }
+                    },
+                    {
+                        "match": {
+                            "collection.name.prefix": {
+                                "query": trimmed_query,
+                                "operator": "and"
+                            }
+                        }
+                    }
+                ],
+                "minimum_should_match": 1
+            }
+        }
+    })
+}
+
+pub fn parse_suggestions(search_response: &Value, size: i64) -> Vec<Suggestion> {
+    let suggestions = search_response["hits"]["hits"]
+        .as_array()
+        .map(|hits| hits.as_slice())
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|hit| {
+            let source = &hit["_source"];
+            let collection = &source["collection"];
+            Some(Suggestion {
+                mint_address: source["mint_address"].as_str()?.to_string(),
+                nft_name: source["nft_name"].as_str()?.to_string(),
+                score: hit["_score"].as_f64().unwrap_or(0.0),
+                collection_name: collection["name"].as_str().map(str::to_string),
+                collection_mint: collection["mint"].as_str().map(str::to_string),
+                image: source["image"].as_str().map(str::to_string),
+            })
+        });
+    dedupe_by_collection(suggestions, size)
+}
+
+// keeps the best scoring suggestion of every collection, suggestions without a collection are all kept.
+// expects the input ordered best first
+pub fn dedupe_by_collection(suggestions: impl IntoIterator<Item = Suggestion>, size: i64) -> Vec<Suggestion> {
+    let mut seen_collections = std::collections::HashSet::new();
+    suggestions
+        .into_iter()
+        .filter(|suggestion| {
+            match suggestion.collection_mint.as_ref().or(suggestion.collection_name.as_ref()) {
+                Some(collection) => seen_collections.insert(collection.clone()),
+                None => true,
+            }
+        })
+        .take(size as usize)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::elasticsearch::AttributeFilter;
+
+    fn request_with(filters: SearchFilters) -> SearchRequest {
+        SearchRequest {
+            query: Some("mad lads".to_string()),
+            filters,
+            page: PageRequest::default(),
+            include_facets: false,
+        }
+    }
+
+    #[test]
+    fn text_query_goes_in_must_and_defaults_the_size() {
+        let query = build_search_query(&request_with(SearchFilters::default())).unwrap();
+
+        assert_eq!(query["size"], DEFAULT_PAGE_SIZE);
+        assert_eq!(
+            query["query"]["bool"]["must"][0]["bool"]["should"][0]["match_phrase"]["nft_name"]["query"],
+            "mad lads"
+        );
+        assert_eq!(query["query"]["bool"]["filter"], json!([]));
+        assert!(query.get("aggs").is_none());
+    }
+
+    #[test]
+    fn empty_query_matches_everything() {
+        let mut request = request_with(SearchFilters::default());
+        request.query = Some("   ".to_string());
+
+        let query = build_search_query(&request).unwrap();
+        assert_eq!(query["query"]["bool"]["must"][0], json!({ "match_all": {} }));
+    }
+
+    #[test]
+    fn collection_filter_matches_mint_or_name() {
+        let clauses = filter_clauses(&SearchFilters {
+            collection: Some("J1S9H3QjnRtBbbuD4HjPV6RpRhwuk4zKbxsnCHuTgh9w".to_string()),
+            ..Default::default()
+        });
+
+        assert_eq!(clauses.len(), 1);
+        let should = &clauses[0]["bool"]["should"];
+        assert_eq!(should[0]["term"]["collection.mint"], "J1S9H3QjnRtBbbuD4HjPV6RpRhwuk4zKbxsnCHuTgh9w");
+        assert_eq!(should[1]["term"]["collection.name.keyword"], "J1S9H3QjnRtBbbuD4HjPV6RpRhwuk4zKbxsnCHuTgh9w");
+    }
+
+    #[test]
+    fn creator_filter_is_nested() {
+        let clauses = filter_clauses(&SearchFilters {
+            creator: Some("creator1".to_string()),
+            ..Default::default()
+        });
+
+        assert_eq!(clauses[0]["nested"]["path"], "creators");
+        assert_eq!(clauses[0]["nested"]["query"]["term"]["creators.address"], "creator1");
+    }
+
+    #[test]
+    fn scalar_filters_are_terms() {
+        let clauses = filter_clauses(&SearchFilters {
+            update_authority: Some("authority1".to_string()),
+            token_standard: Some("ProgrammableNonFungible".to_string()),
+            verified_collection_only: true,
+            ..Default::default()
+        });
+
+        assert_eq!(clauses.len(), 3);
+        assert_eq!(clauses[0]["term"]["update_authority"], "authority1");
+        assert_eq!(clauses[1]["term"]["token_standard"], "ProgrammableNonFungible");
+        assert_eq!(clauses[2]["term"]["collection.verified"], true);
+    }
+
+    #[test]
+    fn royalty_range_only_sets_the_given_bounds() {
+        let min_only = filter_clauses(&SearchFilters {
+            min_royalty_bps: Some(500),
+            ..Default::default()
+        });
+        assert_eq!(min_only[0]["range"]["royalty_bps"], json!({ "gte": 500 }));
+
+        let both = filter_clauses(&SearchFilters {
+            min_royalty_bps: Some(100),
+            max_royalty_bps: Some(1000),
+            ..Default::default()
+        });
+        assert_eq!(both[0]["range"]["royalty_bps"], json!({ "gte": 100, "lte": 1000 }));
+    }
+
+    #[test]
+    fn each_attribute_pair_gets_its_own_nested_query() {
+        let clauses = filter_clauses(&SearchFilters {
+            attributes: vec![
+                AttributeFilter {
+                    trait_type: "Background".to_string(),
+                    value: "Blue".to_string(),
+                },
+                AttributeFilter {
+                    trait_type: "Eyes".to_string(),
+                    value: "Laser".to_string(),
+                },
+            ],
+            ..Default::default()
+        });
+
+        assert_eq!(clauses.len(), 2);
+        let first = &clauses[0]["nested"];
+        assert_eq!(first["path"], "attributes");
+        assert_eq!(first["query"]["bool"]["filter"][0]["term"]["attributes.trait_type"], "Background");
+        assert_eq!(first["query"]["bool"]["filter"][1]["term"]["attributes.value"], "Blue");
+        assert_eq!(clauses[1]["nested"]["query"]["bool"]["filter"][1]["term"]["attributes.value"], "Laser");
+    }
+
+    #[test]
+    fn facets_are_only_requested_when_asked() {
+        let mut request = request_with(SearchFilters::default());
+        request.include_facets = true;
+
+        let query = build_search_query(&request).unwrap();
+        assert_eq!(query["aggs"]["collections"]["terms"]["field"], "collection.mint");
+        assert_eq!(query["aggs"]["traits"]["nested"]["path"], "attributes");
+    }
+
+    #[test]
+    fn parses_collection_and_trait_buckets() {
+        let response = json!({
+            "aggregations": {
+                "collections": {
+                    "buckets": [
+                        {
+                            "key": "collection_mint",
+                            "doc_count": 12,
+                            "name": { "buckets": [{ "key": "Mad Lads", "doc_count": 12 }] }
+                        }
+                    ]
+                },
+                "traits": {
+                    "doc_count": 40,
+                    "trait_types": {
+                        "buckets": [
+                            {
+                                "key": "Background",
+                                "doc_count": 12,
+                                "values": { "buckets": [{ "key": "Blue", "doc_count": 7 }] }
+                            }
+                        ]
+                    }
+                }
+            }
+        });
+
+        let aggregations = parse_aggregations(&response).unwrap();
+        assert_eq!(aggregations.collections[0].mint, "collection_mint");
+        assert_eq!(aggregations.collections[0].name.as_deref(), Some("Mad Lads"));
+        assert_eq!(aggregations.collections[0].count, 12);
+        assert_eq!(aggregations.traits[0].trait_type, "Background");
+        assert_eq!(aggregations.traits[0].values[0].value, "Blue");
+        assert_eq!(aggregations.traits[0].values[0].count, 7);
+
+        assert!(parse_aggregations(&json!({ "hits": {} })).is_none());
+    }
+
+    #[test]
+    fn page_size_is_defaulted_and_capped() {
+        assert_eq!(page_size(&PageRequest::default()), DEFAULT_PAGE_SIZE);
+        let huge = PageRequest {
+            size: Some(10_000),
+            ..Default::default()
+        };
+        assert_eq!(page_size(&huge), MAX_PAGE_SIZE);
+        let zero = PageRequest {
+            size: Some(0),
+            ..Default::default()
+        };
+        assert_eq!(page_size(&zero), 1);
+    }
+
+    #[test]
+    fn every_sort_ends_with_the_mint_address_tiebreaker() {
+        for sort in [SearchSort::Relevance, SearchSort::Name, SearchSort::Newest, SearchSort::Royalty] {
+            let clauses = sort_clauses(sort);
+            let clauses = clauses.as_array().unwrap();
+            assert_eq!(clauses.last().unwrap()["mint_address"]["order"], "asc");
+        }
+        assert_eq!(sort_clauses(SearchSort::Newest)[0]["created_at"]["order"], "desc");
+        assert_eq!(sort_clauses(SearchSort::Name)[0]["nft_name.keyword"]["order"], "asc");
+    }
+
+    #[test]
+    fn cursor_round_trips_into_search_after() {
+        let cursor = encode_cursor(SearchSort::Royalty, &json!([500, "mint_b"]));
+        let page = PageRequest {
+            size: Some(10),
+            cursor: Some(cursor),
+            sort: SearchSort::Royalty,
+        };
+
+        let mut query = json!({ "query": { "match_all": {} } });
+        apply_page(&mut query, &page).unwrap();
+        assert_eq!(query["search_after"], json!([500, "mint_b"]));
+        assert_eq!(query["size"], 10);
+        assert_eq!(query["track_total_hits"], true);
+    }
+
+    #[test]
+    fn cursor_from_another_sort_or_garbage_is_rejected() {
+        let cursor = encode_cursor(SearchSort::Name, &json!(["a", "mint_a"]));
+        assert!(decode_cursor(&cursor, SearchSort::Newest).is_err());
+        assert!(decode_cursor("not-base58-0OIl", SearchSort::Name).is_err());
+        assert!(decode_cursor(&bs58::encode("{}").into_string(), SearchSort::Name).is_err());
+    }
+
+    #[test]
+    fn next_cursor_only_for_full_pages() {
+        let page = PageRequest {
+            size: Some(2),
+            ..Default::default()
+        };
+        let full = json!({ "hits": { "hits": [
+            { "sort": [3.2, "mint_a"] },
+            { "sort": [1.5, "mint_b"] }
+        ] } });
+        let cursor = next_cursor(&full, &page).unwrap();
+        assert_eq!(decode_cursor(&cursor, SearchSort::Relevance).unwrap(), json!([1.5, "mint_b"]));
+
+        let short = json!({ "hits": { "hits": [{ "sort": [3.2, "mint_a"] }] } });
+        assert!(next_cursor(&short, &page).is_none());
+    }
+
+    #[test]
+    fn parses_total_hits() {
+        let exact = json!({ "hits": { "total": { "value": 42, "relation": "eq" } } });
+        assert_eq!(parse_total(&exact), (42, true));
+        let lower_bound = json!({ "hits": { "total": { "value": 10000, "relation": "gte" } } });
+        assert_eq!(parse_total(&lower_bound), (10000, false));
+    }
+
+    #[test]
+    fn suggest_queries_only_the_prefix_subfields() {
+        let query = build_suggest_query("degen", suggest_size(None));
+
+        assert_eq!(query["size"], DEFAULT_SUGGEST_SIZE * SUGGEST_OVERFETCH);
+        assert_eq!(query["track_total_hits"], false);
+        let rendered = query["query"].to_string();
+        assert!(rendered.contains("nft_name.prefix"));
+        assert!(rendered.contains("collection.name.prefix"));
+        assert!(!rendered.contains("wildcard"));
+        assert_eq!(suggest_size(Some(1000)), MAX_SUGGEST_SIZE);
+    }
+
+    #[test]
+    fn suggestions_are_deduplicated_by_collection() {
+        let response = json!({
+            "hits": { "hits": [
+                { "_score": 9.0, "_source": { "mint_address": "a", "nft_name": "Degen Ape #1", "collection": { "name": "Degen Ape Academy", "mint": "DAA", "verified": true } } },
+                { "_score": 8.0, "_source": { "mint_address": "b", "nft_name": "Degen Ape #2", "collection": { "name": "Degen Ape Academy", "mint": "DAA", "verified": true } } },
+                { "_score": 7.0, "_source": { "mint_address": "c", "nft_name": "Degen One" } },
+                { "_score": 6.0, "_source": { "mint_address": "d", "nft_name": "Degen Two" } },
+                { "_score": 5.0, "_source": { "mint_address": "e", "nft_name": "Degenerate", "collection": { "name": "Other", "mint": null, "verified": false } } }
+            ]}
+        });
+
+        let suggestions = parse_suggestions(&response, 10);
+        let mints: Vec<&str> = suggestions.iter().map(|s| s.mint_address.as_str()).collect();
+        assert_eq!(mints, vec!["a", "c", "d", "e"]);
+        assert_eq!(suggestions[0].collection_mint.as_deref(), Some("DAA"));
+
+        assert_eq!(parse_suggestions(&response, 2).len(), 2);
+    }
+}
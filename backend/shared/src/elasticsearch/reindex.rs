@@ -0,0 +1,227 @@
+use std::collections::HashSet;
+
+use chrono::{DateTime, Utc};
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, PaginatorTrait, QueryFilter, QuerySelect};
+
+use crate::elasticsearch::bulk_indexer::index_with_retries;
+use crate::elasticsearch::client::{BulkOp, ElasticSearchClient, ElasticSearchError};
+use crate::elasticsearch::db_source::{active_rows_after, load_nft_docs};
+use crate::entities::{nft_metadata, sync_outbox};
+
+// outbox rows get their created_at when the worker's transaction starts, so one that began before the backfill
+// can commit after it. replaying this far back covers any transaction the worker actually runs
+const REPLAY_MARGIN: chrono::Duration = chrono::Duration::minutes(1);
+
+pub struct ReindexOptions {
+    pub chunk_size: u64,
+    pub force: bool, // swap the read alias even when the counts don't match
+}
+
+pub struct RollbackOptions {
+    pub to_version: Option<u32>,      // the newest version below the live one when unset
+    pub since: Option<DateTime<Utc>>, // replay from here instead of the creation of the index after the target
+    pub chunk_size: u64,
+}
+
+// builds a fresh physical index from postgres and swaps the aliases over to it.
+// live updates keep going to the old index through the backfill, so it stays complete for as long as it's read.
+// once the counts check out the write alias moves, everything written since the backfill started is replayed
+// into the new index and only then the read alias follows. a failed reindex deletes its index and changes nothing
+pub async fn reindex_from_postgres(
+    es: &ElasticSearchClient,
+    db: &DatabaseConnection,
+    options: ReindexOptions,
+) -> Result<String, ElasticSearchError> {
+    let db_err = |e: sea_orm::DbErr| ElasticSearchError::DocumentError(e.to_string());
+
+    let indices = es.physical_indices().await?;
+    let next_version = indices.last().map(|(_, version)| version + 1).unwrap_or(1);
+    let new_index = es.physical_index_name(next_version);
+    let started_at = Utc::now();
+
+    println!("Reindexing into {}...", new_index);
+    es.create_physical_index(&new_index).await?;
+
+    let (expected, actual) = match backfill(es, db, &new_index, options.chunk_size).await {
+        Ok(counts) => counts,
+        Err(e) => {
+            discard(es, &new_index).await;
+            return Err(e);
+        }
+    };
+    if actual != expected && !options.force {
+        discard(es, &new_index).await;
+        return Err(ElasticSearchError::IndexError(format!(
+            "Count mismatch, {} docs missing ({} in postgres, {} were indexed). {} was deleted and the aliases \
+             are untouched, rerun with --force to swap anyway",
+            expected.saturating_sub(actual),
+            expected,
+            actual,
+            new_index
+        )));
+    }
+
+    es.point_alias(es.write_alias(), &new_index).await?;
+    // from here on the new index gets every write, the replay fills in what only the old one got meanwhile
+    let replayed = replay_since(es, db, &new_index, started_at - REPLAY_MARGIN, options.chunk_size)
+        .await
+        .map_err(db_err)?;
+    println!("Replayed {} assets written during the backfill", replayed);
+    es.refresh_index(&new_index).await?;
+
+    es.point_alias(es.read_alias(), &new_index).await?;
+    println!("✅ {} is now live, previous indices are kept for rollback", new_index);
+    Ok(new_index)
+}
+
+// indexes every active asset into `index` and returns (rows in postgres, docs in the index) once it's refreshed
+async fn backfill(
+    es: &ElasticSearchClient,
+    db: &DatabaseConnection,
+    index: &str,
+    chunk_size: u64,
+) -> Result<(u64, u64), ElasticSearchError> {
+    let db_err = |e: sea_orm::DbErr| ElasticSearchError::DocumentError(e.to_string());
+
+    // burned and closed assets stay in postgres but are never searchable
+    let mut indexed = 0u64;
+    let mut failed = 0u64;
+    let mut last_id = None;
+    loop {
+        let rows = active_rows_after(db, last_id, chunk_size).await.map_err(db_err)?;
+        let Some(last) = rows.last() else {
+            break;
+        };
+        last_id = Some(last.id);
+
+        let docs = load_nft_docs(db, &rows).await.map_err(db_err)?;
+        let doc_count = docs.len() as u64;
+        let failures = index_with_retries(es, index, docs.into_iter().map(BulkOp::from).collect(), 3, false).await;
+        for failure in &failures {
+            println!("Failed to index {} : {}", failure.id, failure.reason);
+        }
+        failed += failures.len() as u64;
+        indexed += doc_count - failures.len() as u64;
+        println!("Indexed {} docs so far ({} failed)", indexed, failed);
+    }
+
+    es.refresh_index(index).await?;
+
+    // live writes still go to the old index, so the two only agree if nothing changed during the scan. an asset
+    // created behind the cursor isn't indexed and one retired after its chunk still is, the replay after the swap
+    // fixes both but a busy worker can fail the check until then
+    let expected = nft_metadata::Entity::find()
+        .filter(nft_metadata::Column::Status.eq(nft_metadata::STATUS_ACTIVE))
+        .count(db)
+        .await
+        .map_err(db_err)?;
+    let actual = es.count_docs(index).await?;
+    println!("Postgres has {} rows, {} has {} docs", expected, index, actual);
+    Ok((expected, actual))
+}
+
+// rebuilds every asset with an outbox row since `since` in `index`, deleting the ones that aren't active anymore.
+// returns how many assets were replayed
+async fn replay_since(
+    es: &ElasticSearchClient,
+    db: &DatabaseConnection,
+    index: &str,
+    since: chrono::DateTime<Utc>,
+    chunk_size: u64,
+) -> Result<usize, sea_orm::DbErr> {
+    let mints: Vec<String> = sync_outbox::Entity::find()
+        .select_only()
+        .column(sync_outbox::Column::MintAddress)
+        .distinct()
+        .filter(sync_outbox::Column::CreatedAt.gte(since.fixed_offset()))
+        .into_tuple()
+        .all(db)
+        .await?;
+
+    for chunk in mints.chunks(chunk_size.max(1) as usize) {
+        let rows = nft_metadata::Entity::find()
+            .filter(nft_metadata::Column::MintAddress.is_in(chunk.to_vec()))
+            .filter(nft_metadata::Column::Status.eq(nft_metadata::STATUS_ACTIVE))
+            .all(db)
+            .await?;
+        let active: HashSet<&str> = rows.iter().map(|row| row.mint_address.as_str()).collect();
+
+        let mut operations: Vec<BulkOp> = chunk
+            .iter()
+            .filter(|mint| !active.contains(mint.as_str()))
+            .map(|mint| BulkOp::Delete(mint.clone()))
+            .collect();
+        operations.extend(load_nft_docs(db, &rows).await?.into_iter().map(BulkOp::from));
+
+        // whatever still fails is left for the consistency check to repair
+        for failure in index_with_retries(es, index, operations, 3, false).await {
+            println!("Failed to replay {} : {}", failure.id, failure.reason);
+        }
+    }
+    Ok(mints.len())
+}
+
+// a reindex that didn't go live leaves nothing behind
+async fn discard(es: &ElasticSearchClient, index: &str) {
+    if let Err(e) = es.delete_physical_index(index).await {
+        println!("Failed to delete the abandoned index {}, delete it by hand : {}", index, e);
+    }
+}
+
+// points both aliases back to an older physical index. defaults to the newest one below the live version.
+// the target stopped getting writes when the reindex after it swapped the write alias, and that reindex started by
+// creating the next index, so everything written since then is replayed into the target before it's read again.
+// it's the forward swap in reverse: write alias, replay, read alias
+pub async fn rollback(
+    es: &ElasticSearchClient,
+    db: &DatabaseConnection,
+    options: RollbackOptions,
+) -> Result<String, ElasticSearchError> {
+    let db_err = |e: sea_orm::DbErr| ElasticSearchError::DocumentError(e.to_string());
+
+    let indices = es.physical_indices().await?;
+    let live_version = es
+        .alias_targets(es.read_alias())
+        .await?
+        .first()
+        .and_then(|index| es.physical_index_version(index));
+
+    let target = match options.to_version {
+        Some(version) => indices.iter().find(|(_, v)| *v == version),
+        None => indices
+            .iter()
+            .rev()
+            .find(|(_, v)| live_version.is_none_or(|live| *v < live)),
+    };
+
+    let Some((target_index, target_version)) = target else {
+        return Err(ElasticSearchError::IndexError(
+            "No previous index version to roll back to".to_string(),
+        ));
+    };
+
+    let since = match options.since {
+        Some(since) => since,
+        None => {
+            let Some((next_index, _)) = indices.iter().find(|(_, v)| v > target_version) else {
+                return Err(ElasticSearchError::IndexError(format!(
+                    "{} is the newest index, there's nothing to roll back from. pass --since to replay anyway",
+                    target_index
+                )));
+            };
+            es.index_created_at(next_index).await? - REPLAY_MARGIN
+        }
+    };
+
+    println!("Rolling back to {}, replaying writes since {}...", target_index, since);
+    es.point_alias(es.write_alias(), target_index).await?;
+    let replayed = replay_since(es, db, target_index, since, options.chunk_size)
+        .await
+        .map_err(db_err)?;
+    println!("Replayed {} assets written since {} stopped getting writes", replayed, target_index);
+    es.refresh_index(target_index).await?;
+
+    es.point_alias(es.read_alias(), target_index).await?;
+    println!("✅ {} is live again", target_index);
+    Ok(target_index.clone())
+}
@@ -0,0 +1,25 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// a client of the api_server. the key itself is only shown when it's created, requests are matched on its hash
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "api_keys")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: Uuid,
+    #[sea_orm(column_type = "Text")]
+    pub name: String,
+    pub key_prefix: String, // the first characters of the key, enough to tell keys apart
+    #[sea_orm(unique)]
+    pub key_hash: String, // hex sha256 of the whole key
+    pub requests_per_minute: i32, // how fast the token bucket refills
+    pub burst: i32,               // how many tokens the bucket holds
+    pub active: bool,
+    pub created_at: DateTimeWithTimeZone,
+    pub revoked_at: Option<DateTimeWithTimeZone>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
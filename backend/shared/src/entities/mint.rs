@@ -21,8 +21,8 @@ pub struct Model {
 pub enum Relation {
     #[sea_orm(has_many = "super::token_account::Entity")]
     TokenAccounts,
-    // #[sea_orm(has_one = "super::nft_metadata::Entity")]
-    // NftMetadata,
+    #[sea_orm(has_one = "super::nft_metadata::Entity")]
+    NftMetadata,
     #[sea_orm(has_one = "super::nft_json_metadata::Entity")]
     NftJsonMetadata,
     #[sea_orm(has_one = "super::nft_ownership::Entity")]
@@ -36,11 +36,12 @@ impl Related<super::token_account::Entity> for Entity {
         Relation::TokenAccounts.def()
     }
 }
-// impl Related<super::nft_metadata::Entity> for Entity {
-//     fn to() -> RelationDef {
-//         Relation::NftMetadata.def()
-//     }
-// }
+
+impl Related<super::nft_metadata::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::NftMetadata.def()
+    }
+}
 
 impl Related<super::nft_json_metadata::Entity> for Entity {
     fn to() -> RelationDef {
@@ -1,7 +1,11 @@
+pub mod api_key;
 pub mod mint;
 pub mod nft_metadata;
 pub mod nft_creator;
 pub mod nft_json_metadata;
 pub mod token_account;
 pub mod nft_ownership;
-pub mod nft_royalty;
\ No newline at end of file
+pub mod nft_royalty;
+pub mod sync_outbox;
+pub mod webhook;
+pub mod webhook_delivery;
\ No newline at end of file
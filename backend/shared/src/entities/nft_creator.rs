@@ -15,18 +15,18 @@ pub struct Model{
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
-    // #[sea_orm(
-    //     belongs_to = "super::nft_metadata::Entity",
-    //     from = "Column::MetadataAddress",
-    //     to = "super::nft_metadata::Column::MetadataAddress"
-    // )]
-    // NftMetadata,
+    #[sea_orm(
+        belongs_to = "super::nft_metadata::Entity",
+        from = "Column::MetadataAddress",
+        to = "super::nft_metadata::Column::MetadataAddress"
+    )]
+    NftMetadata,
 }
 
-// impl Related<super::nft_metadata::Entity> for Entity {
-//     fn to() -> RelationDef {
-//         Relation::NftMetadata.def()
-//     }
-// }
+impl Related<super::nft_metadata::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::NftMetadata.def()
+    }
+}
 
 impl ActiveModelBehavior for ActiveModel {}
\ No newline at end of file
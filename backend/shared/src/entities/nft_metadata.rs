@@ -18,31 +18,54 @@ pub struct Model{
     pub update_authority : String,
     pub primary_sale_happened : bool,
     pub is_mutable : bool, // tells wheather the metadata can be changed or updated
+    #[sea_orm(column_type = "Text", nullable)]
+    pub collection_name : Option<String>,
+    pub collection_mint : Option<String>,
+    pub collection_verified : bool,
+    pub token_standard : Option<String>,
+    pub status : String, // one of the STATUS_* constants below
+    pub status_updated_at : Option<DateTimeWithTimeZone>,
+    pub es_sync_status : String, // one of the ES_SYNC_* constants below
+    #[sea_orm(column_type = "Text", nullable)]
+    pub es_sync_error : Option<String>,
+    pub es_sync_updated_at : Option<DateTimeWithTimeZone>,
+    pub es_sync_failure_status : Option<i16>, // http status of the last failed write, 503 when ES was unreachable
+    pub es_sync_attempts : i32, // failed sync rounds since the last success
+    pub es_sync_retry_at : Option<DateTimeWithTimeZone>, // null when the failure won't go away by retrying
     pub created_at : DateTimeWithTimeZone
 }
+
+pub const STATUS_ACTIVE: &str = "active";
+pub const STATUS_BURNED: &str = "burned"; // supply went to 0 or the metadata account was closed by a burn
+pub const STATUS_CLOSED: &str = "closed"; // the mint account itself was closed
+
+pub const ES_SYNC_PENDING: &str = "pending"; // changed in postgres, not yet confirmed by ES
+pub const ES_SYNC_SYNCED: &str = "synced";
+pub const ES_SYNC_FAILED: &str = "failed"; // ES rejected the write after every retry, the worker retries the transient ones
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
-    // #[sea_orm (
-    //     belongs_to = "super::mint::Entity",
-    //     from = "Column::MintAddress", 
-    //     to = "super::mint::Column::MintAddress"
-    // )]
-    // Mint,
-    
-    // #[sea_orm(has_many = "super::nft_creator::Entity")]
-    // NftCreators,
+    #[sea_orm (
+        belongs_to = "super::mint::Entity",
+        from = "Column::MintAddress",
+        to = "super::mint::Column::MintAddress"
+    )]
+    Mint,
+
+    // creators point at the metadata pda, not the mint
+    #[sea_orm(has_many = "super::nft_creator::Entity")]
+    NftCreators,
 }
 
-// impl Related<super::mint::Entity> for Entity {
-//     fn to() -> RelationDef {
-//         Relation::Mint.def()
-//     }
-// }
+impl Related<super::mint::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Mint.def()
+    }
+}
 
-// impl Related<super::nft_creator::Entity> for Entity {
-//     fn to() -> RelationDef {
-//         Relation::NftCreators.def()
-//     }
-// }
+impl Related<super::nft_creator::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::NftCreators.def()
+    }
+}
 
 impl ActiveModelBehavior for ActiveModel {}
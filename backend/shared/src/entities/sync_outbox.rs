@@ -0,0 +1,34 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// one pending change to push to ES, written in the same transaction as the rows it describes
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "sync_outbox")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i64, // bigserial, the relay processes rows in id order
+    pub mint_address: String,
+    pub operation: String, // one of the OPERATION_* constants below
+    #[sea_orm(column_type = "JsonBinary", nullable)]
+    pub payload: Option<Json>, // the partial doc for OPERATION_UPDATE
+    pub status: String, // one of the STATUS_* constants below
+    pub attempts: i32,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub last_error: Option<String>,
+    pub next_attempt_at: DateTimeWithTimeZone,
+    pub processed_at: Option<DateTimeWithTimeZone>,
+    pub created_at: DateTimeWithTimeZone,
+}
+
+pub const OPERATION_UPSERT: &str = "upsert"; // rebuild the whole doc from postgres at relay time
+pub const OPERATION_UPDATE: &str = "update"; // apply payload as a partial update
+pub const OPERATION_DELETE: &str = "delete";
+
+pub const STATUS_PENDING: &str = "pending";
+pub const STATUS_DONE: &str = "done";
+pub const STATUS_FAILED: &str = "failed"; // gave up after the relay's max attempts
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -8,10 +8,10 @@ pub struct Model {
     pub id : Uuid,
     #[sea_orm(unique)]
     pub token_address: String,
-    #[sea_orm(unique)]
     pub mint_address: String,
     pub owner: String,
-    pub amount: i64,
+    #[sea_orm(column_type = "Decimal(Some((20, 0)))")]
+    pub amount: Decimal, // u64 base units
     
     #[sea_orm(column_type = "Text", nullable)]
     pub delegate: Option<String>,
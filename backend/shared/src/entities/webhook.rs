@@ -0,0 +1,36 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// a partner endpoint that gets the change events matching its filter, signed with its secret
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "webhooks")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: Uuid,
+    #[sea_orm(column_type = "Text")]
+    pub url: String,
+    pub secret: String, // hmac key, only shown when the webhook is created
+    #[sea_orm(column_type = "JsonBinary")]
+    pub filter: Json, // a types::webhook::WebhookFilter
+    pub active: bool,
+    pub consecutive_failures: i32, // failed attempts since the last successful delivery
+    #[sea_orm(column_type = "Text", nullable)]
+    pub disabled_reason: Option<String>,
+    pub api_key_id: Option<Uuid>, // the key that created it, only that key can see or change it
+    pub created_at: DateTimeWithTimeZone,
+    pub updated_at: DateTimeWithTimeZone,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(has_many = "super::webhook_delivery::Entity")]
+    WebhookDeliveries,
+}
+
+impl Related<super::webhook_delivery::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::WebhookDeliveries.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,46 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// one change event for one webhook. pending rows are what the dispatcher sends, the rest is the delivery log
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "webhook_deliveries")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i64,
+    pub webhook_id: Uuid,
+    pub event_kind: String, // types::event::ChangeKind
+    pub mint_address: String,
+    #[sea_orm(column_type = "JsonBinary")]
+    pub payload: Json, // the types::event::ChangeEvent
+    pub status: String, // one of the STATUS_* constants below
+    pub attempts: i32,
+    pub response_status: Option<i32>, // http status of the last attempt, None when it never got a response
+    #[sea_orm(column_type = "Text", nullable)]
+    pub last_error: Option<String>,
+    pub next_attempt_at: DateTimeWithTimeZone,
+    pub locked_until: Option<DateTimeWithTimeZone>, // set while a dispatcher is sending it
+    pub delivered_at: Option<DateTimeWithTimeZone>,
+    pub created_at: DateTimeWithTimeZone,
+}
+
+pub const STATUS_PENDING: &str = "pending";
+pub const STATUS_DELIVERED: &str = "delivered";
+pub const STATUS_FAILED: &str = "failed"; // gave up after the dispatcher's max attempts
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::webhook::Entity",
+        from = "Column::WebhookId",
+        to = "super::webhook::Column::Id"
+    )]
+    Webhook,
+}
+
+impl Related<super::webhook::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Webhook.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
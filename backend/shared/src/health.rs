@@ -0,0 +1,146 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Json};
+use axum::routing::get;
+use axum::Router;
+use redis::{AsyncConnectionConfig, Client};
+use sea_orm::DatabaseConnection;
+use serde::Serialize;
+use serde_json::json;
+
+use crate::shutdown::Shutdown;
+
+// a dependency slower than this counts as down
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckResult {
+    pub ok: bool,
+    pub required: bool, // false for what the process gets by without, like redis for the api_server
+    pub latency_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+// the body of /readyz. degraded means only checks that aren't required failed, which still answers 200
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthReport {
+    pub status: &'static str, // ok, degraded or unavailable
+    pub checks: BTreeMap<&'static str, CheckResult>,
+}
+
+impl HealthReport {
+    pub fn new(checks: impl IntoIterator<Item = (&'static str, CheckResult)>) -> Self {
+        let checks: BTreeMap<&'static str, CheckResult> = checks.into_iter().collect();
+        let status = if checks.values().any(|check| !check.ok && check.required) {
+            "unavailable"
+        } else if checks.values().any(|check| !check.ok) {
+            "degraded"
+        } else {
+            "ok"
+        };
+        Self { status, checks }
+    }
+
+    pub fn status_code(&self) -> StatusCode {
+        if self.status == "unavailable" {
+            StatusCode::SERVICE_UNAVAILABLE
+        } else {
+            StatusCode::OK
+        }
+    }
+}
+
+// runs one probe with the check timeout
+pub async fn check<E: Display>(required: bool, probe: impl Future<Output = Result<(), E>>) -> CheckResult {
+    let started = Instant::now();
+    let error = match tokio::time::timeout(CHECK_TIMEOUT, probe).await {
+        Ok(Ok(())) => None,
+        Ok(Err(e)) => Some(e.to_string()),
+        Err(_) => Some(format!("no answer within {:?}", CHECK_TIMEOUT)),
+    };
+    CheckResult {
+        ok: error.is_none(),
+        required,
+        latency_ms: started.elapsed().as_millis() as u64,
+        error,
+    }
+}
+
+pub async fn ping_postgres(db: &DatabaseConnection) -> Result<(), sea_orm::DbErr> {
+    db.ping().await
+}
+
+// a fresh connection every time, so a probe never reports a connection that only used to work
+pub async fn ping_redis(redis_url: &str) -> redis::RedisResult<()> {
+    let config = AsyncConnectionConfig::new()
+        .set_connection_timeout(CHECK_TIMEOUT)
+        .set_response_timeout(CHECK_TIMEOUT);
+    let mut connection = Client::open(redis_url)?
+        .get_multiplexed_async_connection_with_config(&config)
+        .await?;
+    redis::cmd("PING").query_async(&mut connection).await
+}
+
+// GET /healthz answers as long as the process does, GET /readyz runs `ready`. readyz says unavailable once
+// shutdown started, so load balancers stop sending work before the process stops taking it
+pub fn health_router<F, Fut>(ready: F, shutdown: Shutdown) -> Router
+where
+    F: Fn() -> Fut + Clone + Send + Sync + 'static,
+    Fut: Future<Output = HealthReport> + Send,
+{
+    Router::new()
+        .route("/healthz", get(|| async { Json(json!({ "status": "ok" })) }))
+        .route(
+            "/readyz",
+            get(move || async move {
+                if shutdown.is_triggered() {
+                    return (StatusCode::SERVICE_UNAVAILABLE, Json(json!({ "status": "shutting_down" })))
+                        .into_response();
+                }
+                let report = ready().await;
+                (report.status_code(), Json(report)).into_response()
+            }),
+        )
+}
+
+// the health endpoints of a process that serves nothing else, on their own address. they keep answering while
+// the process drains, /readyz with shutting_down
+pub async fn serve_health(addr: String, router: Router) {
+    let listener = match tokio::net::TcpListener::bind(&addr).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            println!("Couldn't bind the health endpoints to {} : {}", addr, e);
+            return;
+        }
+    };
+    println!("Health endpoints at http://{}/healthz and /readyz", addr);
+    if let Err(e) = axum::serve(listener, router).await {
+        println!("Health endpoints stopped : {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(ok: bool, required: bool) -> CheckResult {
+        CheckResult { ok, required, latency_ms: 1, error: None }
+    }
+
+    #[test]
+    fn only_required_checks_make_it_unavailable() {
+        let report = HealthReport::new([("postgres", result(true, true)), ("redis", result(true, false))]);
+        assert_eq!((report.status, report.status_code()), ("ok", StatusCode::OK));
+
+        let report = HealthReport::new([("postgres", result(true, true)), ("redis", result(false, false))]);
+        assert_eq!((report.status, report.status_code()), ("degraded", StatusCode::OK));
+
+        let report = HealthReport::new([("postgres", result(false, true)), ("redis", result(true, false))]);
+        assert_eq!((report.status, report.status_code()), ("unavailable", StatusCode::SERVICE_UNAVAILABLE));
+    }
+}
@@ -3,7 +3,7 @@ use reqwest::{header::CONTENT_TYPE, Client};
 use crate::entities::nft_metadata::ActiveModel as NftActiveModel;
 use crate::types::{
     helius::{HeliusAsset, HeliusAssetResponse, RequestBody},
-    metadeta::Metadata,
+    metadeta::{Metadata, MetadataCreator},
 };
 use mpl_token_metadata::types::TokenStandard;
 use sea_orm::{ActiveModelTrait, DatabaseConnection, DbErr, Set};
@@ -62,13 +62,12 @@ impl HeliusClient {
                     .unwrap_or_default();
 
                 // Extract collection name if available
-                let collection_name = asset.grouping.as_ref().and_then(|groupings| {
-                    groupings
-                        .iter()
-                        .find(|g| g.group_key == "collection")
-                        .and_then(|g| g.collection_metadata.as_ref())
-                        .map(|meta| meta.name.clone())
+                let collection_group = asset.grouping.as_ref().and_then(|groupings| {
+                    groupings.iter().find(|g| g.group_key == "collection")
                 });
+                let collection_name = collection_group
+                    .and_then(|g| g.collection_metadata.as_ref())
+                    .map(|meta| meta.name.clone());
                 let token_standard = self.map_interface_to_token_standard(&asset.interface);
                 Metadata {
                     mint_address: asset.id,
@@ -80,6 +79,17 @@ impl HeliusClient {
                     update_authority,
                     token_standard,
                     collection: collection_name,
+                    collection_mint: collection_group.map(|g| g.group_value.clone()),
+                    collection_verified: collection_group.is_some_and(|g| g.verified),
+                    creators: asset
+                        .creators
+                        .iter()
+                        .map(|creator| MetadataCreator {
+                            address: creator.address.clone(),
+                            verified: creator.verified,
+                            share: creator.share,
+                        })
+                        .collect(),
                     primary_sale_happened: asset.royalty.primary_sale_happened,
                     is_mutable: asset.mutable,
                 }
@@ -112,10 +122,14 @@ impl HeliusClient {
             name: Set(metadata.name),
             symbol: Set(metadata.symbol),
             metadata_uri: Set(metadata.metadata_uri),
-            seller_fee_basis_points: Set(metadata.seller_fee_basis_points as i16),
+            seller_fee_basis_points: Set(metadata.seller_fee_basis_points),
             update_authority: Set(metadata.update_authority),
             primary_sale_happened: Set(metadata.primary_sale_happened),
             is_mutable: Set(metadata.is_mutable),
+            collection_name: Set(metadata.collection),
+            collection_mint: Set(metadata.collection_mint),
+            collection_verified: Set(metadata.collection_verified),
+            token_standard: Set(metadata.token_standard.map(|t| format!("{:?}", t))),
             ..Default::default()
         };
 
@@ -1,15 +1,22 @@
+pub mod api_keys;
+pub mod assets;
 pub mod elasticsearch;
 pub mod entities;
+pub mod health;
 pub mod helius;
 pub mod redis;
+pub mod rpc;
+pub mod search;
+pub mod shutdown;
 pub mod types;
+pub mod webhooks;
 pub mod ys_grpc;
 
 pub use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::StatusCode,
     response::Json,
-    routing::get,
+    routing::{get, post},
     Router,
 };
 pub use dotenvy::dotenv;
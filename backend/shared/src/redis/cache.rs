@@ -0,0 +1,229 @@
+use std::time::Duration;
+
+use chrono::{DateTime, SubsecRound, Utc};
+use redis::{RedisResult, Script};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::redis::connection::LazyConnection;
+
+const SEARCH_KEY: &str = "cache:search";
+// no asset has more than 32 include combinations, the search hash stops taking new entries at its cap
+const MAX_DETAILS_ENTRIES: usize = 64;
+const MAX_SEARCH_ENTRIES: usize = 10_000;
+// a version only has to outlive the reads that saw it, a read takes seconds at most
+const VERSION_TTL_SECS: u64 = 24 * 60 * 60;
+
+// entries of one asset, or all search results, share a hash that expires `ttl` after its first entry was stored.
+// that way the worker drops them with one DEL and a full hash isn't kept alive by the entries still coming in.
+// nothing is stored when the hash was invalidated since the read that built the entry looked it up (ARGV[5]),
+// the entry could have been built from what the invalidated write replaced
+const STORE_ENTRY: &str = r"
+if (tonumber(redis.call('GET', KEYS[2])) or 0) ~= tonumber(ARGV[5]) then
+    return 0
+end
+if redis.call('HLEN', KEYS[1]) < tonumber(ARGV[4]) or redis.call('HEXISTS', KEYS[1], ARGV[1]) == 1 then
+    redis.call('HSET', KEYS[1], ARGV[1], ARGV[2])
+end
+if redis.call('TTL', KEYS[1]) < 0 then
+    redis.call('EXPIRE', KEYS[1], ARGV[3])
+end
+return 1
+";
+
+const INVALIDATE: &str = r"
+redis.call('DEL', KEYS[1])
+redis.call('INCR', KEYS[2])
+redis.call('EXPIRE', KEYS[2], ARGV[1])
+return 1
+";
+
+#[derive(Debug, Clone)]
+pub struct ResponseCacheConfig {
+    pub details_ttl: Duration, // zero turns the details cache off
+    pub search_ttl: Duration,  // zero turns the search cache off
+}
+
+impl Default for ResponseCacheConfig {
+    fn default() -> Self {
+        Self {
+            details_ttl: Duration::from_secs(300),
+            search_ttl: Duration::from_secs(30),
+        }
+    }
+}
+
+impl ResponseCacheConfig {
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let env_secs = |name: &str| std::env::var(name).ok().and_then(|v| v.parse::<u64>().ok());
+
+        Self {
+            details_ttl: env_secs("DETAILS_CACHE_TTL_SECS")
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.details_ttl),
+            search_ttl: env_secs("SEARCH_CACHE_TTL_SECS")
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.search_ttl),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CacheKey {
+    // `variant` tells apart the shapes one asset is served in, like the ?include= blocks
+    Details { mint_address: String, variant: String },
+    // anything that identifies a search, like the route and its serialized params
+    Search(String),
+}
+
+// a serialized response body with the validators clients revalidate it with
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CachedResponse {
+    pub body: String,
+    pub etag: String,                 // quoted, ready for the ETag header
+    pub last_modified: DateTime<Utc>, // when the body was built, whole seconds like the header
+}
+
+// how many times an entry's hash had been invalidated when it was looked up, store checks it hasn't been since
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheVersion(u64);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CacheLookup {
+    Hit(CachedResponse),
+    Miss(CacheVersion), // pass it to store with the entry built for the miss
+}
+
+impl CachedResponse {
+    pub fn new(body: String) -> Self {
+        let etag = format!("\"{}\"", &hex::encode(Sha256::digest(body.as_bytes()))[..32]);
+        Self {
+            body,
+            etag,
+            last_modified: Utc::now().trunc_subsecs(0),
+        }
+    }
+}
+
+// read-through cache for /details and the search routes, shared by every api_server replica. the worker drops an
+// asset's entries once a write to it is committed and the search entries once changes reach the search index
+pub struct ResponseCache {
+    connection: LazyConnection,
+    store: Script,
+    invalidate: Script,
+    config: ResponseCacheConfig,
+}
+
+impl ResponseCache {
+    pub fn new(redis_url: &str, config: ResponseCacheConfig) -> RedisResult<Self> {
+        Ok(Self {
+            connection: LazyConnection::new(redis_url)?,
+            store: Script::new(STORE_ENTRY),
+            invalidate: Script::new(INVALIDATE),
+            config,
+        })
+    }
+
+    // the entry and the hash's version in one round trip, the version has to be read before whatever builds the entry
+    pub async fn get(&self, key: &CacheKey) -> RedisResult<CacheLookup> {
+        let Some((hash, field, _, _)) = self.location(key) else {
+            return Ok(CacheLookup::Miss(CacheVersion(0)));
+        };
+        let mut connection = self.connection.get().await?;
+        let result: RedisResult<(Option<String>, Option<u64>)> = redis::pipe()
+            .hget(&hash, field)
+            .get(version_key(&hash))
+            .query_async(&mut connection)
+            .await;
+        let (entry, version) = self.connection.checked(result).await?;
+        // an entry that doesn't parse was written by another version, it's a miss and gets overwritten
+        Ok(match entry.and_then(|entry| serde_json::from_str(&entry).ok()) {
+            Some(entry) => CacheLookup::Hit(entry),
+            None => CacheLookup::Miss(CacheVersion(version.unwrap_or(0))),
+        })
+    }
+
+    // a no-op when the hash was invalidated since `version` was looked up
+    pub async fn store(&self, key: &CacheKey, entry: &CachedResponse, version: CacheVersion) -> RedisResult<()> {
+        let Some((hash, field, ttl, max)) = self.location(key) else {
+            return Ok(());
+        };
+        let entry = serde_json::to_string(entry).expect("cached responses are plain structs and always serialize");
+        let mut connection = self.connection.get().await?;
+        let result: RedisResult<i64> = self
+            .store
+            .key(&hash)
+            .key(version_key(&hash))
+            .arg(field)
+            .arg(entry)
+            .arg(ttl.as_secs().max(1))
+            .arg(max)
+            .arg(version.0)
+            .invoke_async(&mut connection)
+            .await;
+        self.connection.checked(result).await.map(|_| ())
+    }
+
+    pub async fn invalidate_details(&self, mint_address: &str) -> RedisResult<()> {
+        self.invalidate_hash(&details_key(mint_address)).await
+    }
+
+    pub async fn invalidate_search(&self) -> RedisResult<()> {
+        self.invalidate_hash(SEARCH_KEY).await
+    }
+
+    // drops the entries and bumps the version, so reads that started before can't store what they built
+    async fn invalidate_hash(&self, hash: &str) -> RedisResult<()> {
+        let mut connection = self.connection.get().await?;
+        let result: RedisResult<i64> = self
+            .invalidate
+            .key(hash)
+            .key(version_key(hash))
+            .arg(VERSION_TTL_SECS)
+            .invoke_async(&mut connection)
+            .await;
+        self.connection.checked(result).await.map(|_| ())
+    }
+
+    // (hash, field, ttl, max fields) of a key, None when its cache is turned off
+    fn location(&self, key: &CacheKey) -> Option<(String, String, Duration, usize)> {
+        let location = match key {
+            CacheKey::Details { mint_address, variant } => (
+                details_key(mint_address),
+                variant.clone(),
+                self.config.details_ttl,
+                MAX_DETAILS_ENTRIES,
+            ),
+            CacheKey::Search(request) => (
+                SEARCH_KEY.to_string(),
+                hex::encode(Sha256::digest(request.as_bytes())),
+                self.config.search_ttl,
+                MAX_SEARCH_ENTRIES,
+            ),
+        };
+        (!location.2.is_zero()).then_some(location)
+    }
+}
+
+fn details_key(mint_address: &str) -> String {
+    format!("cache:details:{}", mint_address)
+}
+
+fn version_key(hash: &str) -> String {
+    format!("{}:version", hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn etag_follows_the_body() {
+        let entry = CachedResponse::new(r#"{"mint_address":"a"}"#.to_string());
+        assert_eq!(entry.etag, CachedResponse::new(entry.body.clone()).etag);
+        assert_ne!(entry.etag, CachedResponse::new(r#"{"mint_address":"b"}"#.to_string()).etag);
+        assert!(entry.etag.starts_with('"') && entry.etag.ends_with('"'));
+        assert_eq!(entry.last_modified.timestamp_subsec_nanos(), 0);
+    }
+}
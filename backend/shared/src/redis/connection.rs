@@ -0,0 +1,45 @@
+use std::time::Duration;
+
+use redis::aio::MultiplexedConnection;
+use redis::{AsyncConnectionConfig, Client, RedisResult};
+use tokio::sync::Mutex;
+
+// a slow redis shouldn't hold every request up, callers carry on without it when this runs out
+const TIMEOUT: Duration = Duration::from_millis(250);
+
+// one multiplexed connection for every caller, opened on first use and dropped after an error so the next call
+// reconnects. for request paths that can do without redis, the queues use their own connections
+pub struct LazyConnection {
+    client: Client,
+    connection: Mutex<Option<MultiplexedConnection>>,
+}
+
+impl LazyConnection {
+    pub fn new(redis_url: &str) -> RedisResult<Self> {
+        Ok(Self {
+            client: Client::open(redis_url)?,
+            connection: Mutex::new(None),
+        })
+    }
+
+    pub async fn get(&self) -> RedisResult<MultiplexedConnection> {
+        let mut connection = self.connection.lock().await;
+        if let Some(connection) = connection.as_ref() {
+            return Ok(connection.clone());
+        }
+        let config = AsyncConnectionConfig::new()
+            .set_connection_timeout(TIMEOUT)
+            .set_response_timeout(TIMEOUT);
+        let connected = self.client.get_multiplexed_async_connection_with_config(&config).await?;
+        *connection = Some(connected.clone());
+        Ok(connected)
+    }
+
+    // passes the result through, forgetting the connection when it's an error
+    pub async fn checked<T>(&self, result: RedisResult<T>) -> RedisResult<T> {
+        if result.is_err() {
+            *self.connection.lock().await = None;
+        }
+        result
+    }
+}
@@ -0,0 +1,177 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::StreamExt;
+use redis::{Client, RedisResult};
+use tokio::sync::broadcast;
+use tokio::time::sleep;
+
+use crate::elasticsearch::classify::is_pubkey;
+use crate::types::event::{ChangeEvent, SubscribeQuery};
+
+// the worker publishes every committed change here, each api_server fans them out to its subscribers.
+// pub/sub is fire and forget, a server that's down misses what was sent meanwhile
+pub const CHANGES_CHANNEL: &str = "indexer_changes";
+pub const MAX_SUBSCRIPTION_KEYS: usize = 100;
+
+// which events one subscriber gets, built from SubscribeQuery
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChangeFilter {
+    pub mints: HashSet<String>,
+    pub owners: HashSet<String>,
+    pub collections: HashSet<String>,
+    pub creators: HashSet<String>,
+}
+
+impl ChangeFilter {
+    pub fn parse(query: &SubscribeQuery) -> Result<Self, String> {
+        Self::new(
+            &split(query.mint.as_deref()),
+            &split(query.owner.as_deref()),
+            &split(query.collection.as_deref()),
+            &split(query.creator.as_deref()),
+        )
+    }
+
+    // at least one address is required, a subscription to everything isn't something we serve
+    pub fn new(mints: &[String], owners: &[String], collections: &[String], creators: &[String]) -> Result<Self, String> {
+        let filter = Self {
+            mints: addresses(mints)?,
+            owners: addresses(owners)?,
+            collections: addresses(collections)?,
+            creators: addresses(creators)?,
+        };
+
+        let keys = filter.mints.len() + filter.owners.len() + filter.collections.len() + filter.creators.len();
+        if keys == 0 {
+            return Err("Subscribe to at least one mint, owner, collection or creator".to_string());
+        }
+        if keys > MAX_SUBSCRIPTION_KEYS {
+            return Err(format!("At most {} addresses per subscription", MAX_SUBSCRIPTION_KEYS));
+        }
+        Ok(filter)
+    }
+
+    pub fn matches(&self, event: &ChangeEvent) -> bool {
+        self.mints.contains(&event.mint_address)
+            || event.owner.as_ref().is_some_and(|owner| self.owners.contains(owner))
+            || event.collection.as_ref().is_some_and(|collection| self.collections.contains(collection))
+            || event.creators.iter().any(|creator| self.creators.contains(creator))
+    }
+}
+
+fn split(list: Option<&str>) -> Vec<String> {
+    list.unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|address| !address.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn addresses(list: &[String]) -> Result<HashSet<String>, String> {
+    let mut parsed = HashSet::new();
+    for address in list {
+        if !is_pubkey(address) {
+            return Err(format!("{} is not a valid base58 address", address));
+        }
+        parsed.insert(address.clone());
+    }
+    Ok(parsed)
+}
+
+// forwards everything published on CHANGES_CHANNEL to `changes` until the process exits,
+// reconnecting when redis goes away
+pub async fn relay_changes(redis_url: String, changes: broadcast::Sender<Arc<ChangeEvent>>) {
+    let redis_client = match Client::open(redis_url) {
+        Ok(redis_client) => redis_client,
+        Err(e) => {
+            println!("Couldn't initialize a redis client for {} : {}", CHANGES_CHANNEL, e);
+            return;
+        }
+    };
+    loop {
+        if let Err(e) = forward_changes(&redis_client, &changes).await {
+            println!("Lost the {} subscription : {}, reconnecting...", CHANGES_CHANNEL, e);
+        }
+        sleep(Duration::from_secs(2)).await;
+    }
+}
+
+async fn forward_changes(redis_client: &Client, changes: &broadcast::Sender<Arc<ChangeEvent>>) -> RedisResult<()> {
+    let mut pubsub = redis_client.get_async_pubsub().await?;
+    pubsub.subscribe(CHANGES_CHANNEL).await?;
+    println!("Subscribed to {}", CHANGES_CHANNEL);
+
+    let mut messages = pubsub.on_message();
+    while let Some(message) = messages.next().await {
+        let payload: String = message.get_payload()?;
+        match serde_json::from_str::<ChangeEvent>(&payload) {
+            // no receivers just means nobody is subscribed right now
+            Ok(event) => {
+                let _ = changes.send(Arc::new(event));
+            }
+            Err(e) => println!("Failed to deserialize change event {}", e),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+    use crate::types::event::ChangeKind;
+
+    const MINT: &str = "So11111111111111111111111111111111111111112";
+    const COLLECTION: &str = "J1S9H3QjnRtBbbuD4HjPV6RpRhwuk4zKbxsnCHuTgh9w";
+    const CREATOR: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+
+    fn event() -> ChangeEvent {
+        ChangeEvent {
+            kind: ChangeKind::Metadata,
+            mint_address: MINT.to_string(),
+            owner: None,
+            collection: Some(COLLECTION.to_string()),
+            creators: vec![CREATOR.to_string()],
+            at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn filters_need_valid_addresses() {
+        assert!(ChangeFilter::parse(&SubscribeQuery::default()).is_err());
+        let query = SubscribeQuery {
+            mint: Some("not-an-address".to_string()),
+            ..Default::default()
+        };
+        assert!(ChangeFilter::parse(&query).is_err());
+    }
+
+    #[test]
+    fn events_match_any_of_the_filters() {
+        let by_collection = ChangeFilter::parse(&SubscribeQuery {
+            collection: Some(format!("{}, {}", COLLECTION, CREATOR)),
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(by_collection.matches(&event()));
+
+        let by_owner = ChangeFilter::parse(&SubscribeQuery {
+            owner: Some(MINT.to_string()),
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(!by_owner.matches(&event()));
+
+        let by_creator = ChangeFilter::parse(&SubscribeQuery {
+            owner: Some(COLLECTION.to_string()),
+            creator: Some(CREATOR.to_string()),
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(by_creator.matches(&event()));
+    }
+}
@@ -1,2 +1,7 @@
+pub mod cache;
+pub mod connection;
+pub mod events;
 pub mod queue_manager;
-pub mod worker;
\ No newline at end of file
+pub mod rate_limit;
+pub mod tracked;
+pub mod worker;
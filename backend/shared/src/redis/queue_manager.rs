@@ -1,21 +1,83 @@
-use crate::types::{metadeta::Metadata, mint::MintData};
+use crate::redis::events::CHANGES_CHANNEL;
+use crate::rpc::pool::RpcPool;
+use crate::types::{
+    account::{AccountEvent, TokenAccountData},
+    event::ChangeEvent,
+    metadeta::{JsonMetadata, Metadata, MetadataCreator},
+    mint::MintData,
+};
 use mpl_token_metadata::{accounts::Metadata as MetadataAccount, programs::MPL_TOKEN_METADATA_ID};
 use redis::{AsyncCommands, Client, RedisError, RedisResult};
+use serde::de::DeserializeOwned;
 use solana_client::rpc_client::RpcClient;
 use solana_program::pubkey::Pubkey;
 use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+pub const DEFAULT_REDIS_URL: &str = "redis://localhost:6379";
+// a consumer whose heartbeat is older than this counts as dead and its unacked messages go back in the queue. well
+// past how long one message takes, a live worker that misses it only gets its message handled twice
+pub const HEARTBEAT_TTL_SECS: u64 = 120;
+const CONSUMERS_KEY: &str = "queue:consumers";
+
+// REDIS_URL, shared by the queues, the change feed, the rate limits and the response cache
+pub fn redis_url() -> String {
+    std::env::var("REDIS_URL").unwrap_or_else(|_| DEFAULT_REDIS_URL.to_string())
+}
+
+// proof that a message was taken off a queue, pass it to RedisQueue::ack once the message is handled
+#[derive(Debug)]
+pub struct Receipt {
+    processing_list: String,
+    raw: String,
+}
+
+fn processing_list(queue_name: &str, consumer: &str) -> String {
+    format!("{}:processing:{}", queue_name, consumer)
+}
+
+fn heartbeat_key(consumer: &str) -> String {
+    format!("queue:heartbeat:{}", consumer)
+}
+
+// one message at a time, rpoplpush moves each atomically so two replicas reclaiming the same list can't duplicate one
+async fn move_all(conn: &mut redis::aio::MultiplexedConnection, from: &str, to: &str) -> RedisResult<usize> {
+    let mut moved = 0;
+    loop {
+        let message: Option<String> = conn.rpoplpush(from, to).await?;
+        if message.is_none() {
+            return Ok(moved);
+        }
+        moved += 1;
+    }
+}
 
 pub struct RedisQueue {
     redis_client: Client,
-    rpc_client: RpcClient,
+    rpc_pool: Arc<RpcPool>,
+    http_client: reqwest::Client,
+    consumer: String, // names the list this process keeps its unacked messages in, unique per process
+}
+
+// WORKER_ID, or the host name and pid so replicas never share a processing list
+fn consumer_id() -> String {
+    if let Ok(id) = std::env::var("WORKER_ID") {
+        return id;
+    }
+    let host = std::env::var("HOSTNAME")
+        .ok()
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .map(|host| host.trim().to_string())
+        .filter(|host| !host.is_empty())
+        .unwrap_or_else(|| "worker".to_string());
+    format!("{}-{}", host, std::process::id())
 }
 
 impl RedisQueue {
-    pub async fn new() -> Result<Self, RedisError> {
+    pub async fn new(redis_url: &str) -> Result<Self, RedisError> {
         println!("Initializing redis queue...");
 
-        let redis_url = "redis://localhost:6379";
-
         let redis_client = Client::open(redis_url).map_err(|e| {
             println!("Couldn't initialize a redis client : {}", e);
             e
@@ -23,12 +85,19 @@ impl RedisQueue {
 
         Ok(Self {
             redis_client,
-            rpc_client: RpcClient::new(
-                std::env::var("HELIUS_URL").expect("helius url not found from env"),
-            ),
+            consumer: consumer_id(),
+            rpc_pool: Arc::new(RpcPool::from_env()),
+            http_client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .unwrap_or_default(),
         })
     }
 
+    pub fn rpc_pool(&self) -> Arc<RpcPool> {
+        self.rpc_pool.clone()
+    }
+
     pub async fn enqueue_message(
         &self,
         data: &[u8],
@@ -62,7 +131,7 @@ impl RedisQueue {
 
         let message_json = match serde_json::to_string(&mint_data) {
             Ok(mesage_string) => mesage_string,
-            Err(_) => format!("Error serialing the message into string"),
+            Err(_) => "Error serialing the message into string".to_string(),
         };
 
         println!("Serialized mint data succesfully");
@@ -72,22 +141,103 @@ impl RedisQueue {
         Ok(queue_length)
     }
 
-    pub async fn dequeue_message(&self, queue_name: &str) -> RedisResult<Option<MintData>> {
+    pub async fn dequeue_message(&self, queue_name: &str) -> RedisResult<Option<(MintData, Receipt)>> {
+        self.dequeue(queue_name).await
+    }
+
+    pub async fn enqueue_account_event(&self, event: &AccountEvent, queue_name: &str) -> RedisResult<usize> {
         let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
-        let message_string: Option<String> = conn.rpop(queue_name, None).await?;
+        let message_json = match serde_json::to_string(event) {
+            Ok(message_string) => message_string,
+            Err(_) => "Error serialing the message into string".to_string(),
+        };
+        conn.lpush(queue_name, message_json).await
+    }
 
-        match message_string {
-            Some(message) => match serde_json::from_str::<MintData>(&message) {
-                Ok(mint_data) => Ok(Some(mint_data)),
-                Err(e) => {
-                    println!("Failed to deserialize mint message {}", e);
-                    Ok(None)
-                }
-            },
-            None => Ok(None),
+    // returns how many api servers got it
+    pub async fn publish_change(&self, event: &ChangeEvent) -> RedisResult<usize> {
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        let message_json = serde_json::to_string(event).expect("change events are plain structs and always serialize");
+        conn.publish(CHANGES_CHANNEL, message_json).await
+    }
+
+    pub async fn dequeue_account_event(&self, queue_name: &str) -> RedisResult<Option<(AccountEvent, Receipt)>> {
+        self.dequeue(queue_name).await
+    }
+
+    // moves the oldest message to this consumer's processing list, where it stays until it's acked. a worker that
+    // dies in between finds it there on its next start. messages that don't parse are dropped right away
+    async fn dequeue<T: DeserializeOwned>(&self, queue_name: &str) -> RedisResult<Option<(T, Receipt)>> {
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        let processing_list = self.processing_list(queue_name);
+        let message_string: Option<String> = conn.rpoplpush(queue_name, &processing_list).await?;
+        let Some(raw) = message_string else {
+            return Ok(None);
+        };
+
+        let receipt = Receipt { processing_list, raw };
+        match serde_json::from_str::<T>(&receipt.raw) {
+            Ok(message) => Ok(Some((message, receipt))),
+            Err(e) => {
+                println!("Failed to deserialize a {} message {}", queue_name, e);
+                self.ack(receipt).await?;
+                Ok(None)
+            }
         }
     }
 
+    pub async fn ack(&self, receipt: Receipt) -> RedisResult<()> {
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        conn.lrem(&receipt.processing_list, 1, &receipt.raw).await
+    }
+
+    pub fn consumer(&self) -> &str {
+        &self.consumer
+    }
+
+    // marks this consumer alive for HEARTBEAT_TTL_SECS and registers it, so the other replicas leave its
+    // processing lists alone. the worker calls it well within the ttl
+    pub async fn heartbeat(&self) -> RedisResult<()> {
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        redis::pipe()
+            .set_ex(heartbeat_key(&self.consumer), 1, HEARTBEAT_TTL_SECS)
+            .ignore()
+            .sadd(CONSUMERS_KEY, &self.consumer)
+            .ignore()
+            .query_async(&mut conn)
+            .await
+    }
+
+    // puts back what this consumer took and never acked, for a worker with a fixed WORKER_ID restarting after a
+    // crash or a kill. only call it before taking messages. returns how many
+    pub async fn requeue_unacked(&self, queue_name: &str) -> RedisResult<usize> {
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        move_all(&mut conn, &processing_list(queue_name, &self.consumer), queue_name).await
+    }
+
+    // puts back the unacked messages of every other consumer whose heartbeat ran out and forgets it. live ones,
+    // this one included, keep theirs. returns how many
+    pub async fn reclaim_stale(&self, queue_names: &[&str]) -> RedisResult<usize> {
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        let consumers: Vec<String> = conn.smembers(CONSUMERS_KEY).await?;
+        let mut reclaimed = 0;
+        for consumer in consumers.iter().filter(|consumer| **consumer != self.consumer) {
+            let alive: bool = conn.exists(heartbeat_key(consumer)).await?;
+            if alive {
+                continue;
+            }
+            for queue_name in queue_names {
+                reclaimed += move_all(&mut conn, &processing_list(queue_name, consumer), queue_name).await?;
+            }
+            conn.srem::<_, _, ()>(CONSUMERS_KEY, consumer).await?;
+        }
+        Ok(reclaimed)
+    }
+
+    fn processing_list(&self, queue_name: &str) -> String {
+        processing_list(queue_name, &self.consumer)
+    }
+
     pub fn get_metadata_pda_address(
         &self,
         mint_address: &str,
@@ -116,8 +266,15 @@ impl RedisQueue {
     ) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error>> {
         let metadata_pda = self.get_metadata_pda_address(&mint_address)?;
 
-        match self.rpc_client.get_account(&metadata_pda) {
-            Ok(account) => {
+        // the closure returns solana's ClientError as is, RpcPool::call is where it's dealt with
+        #[allow(clippy::result_large_err)]
+        let fetch = |client: &RpcClient| client.get_account_with_commitment(&metadata_pda, client.commitment());
+        match self.rpc_pool.call(fetch) {
+            Ok(response) => {
+                let Some(account) = response.value else {
+                    println!("Metadata account doesn't exist for mint {}", mint_address);
+                    return Ok(None);
+                };
                 println!("Metadata account found");
                 println!("Data length {} bytes", account.data.len());
 
@@ -129,7 +286,7 @@ impl RedisQueue {
                 }
             }
             Err(rpc_error) => {
-                println!("❌ RPC Error fetching metadata account: {:?}", rpc_error);
+                println!("❌ RPC Error fetching metadata account from every endpoint: {:?}", rpc_error);
                 println!("🔍 Metadata PDA: {}", metadata_pda);
                 println!("🪙 Mint address: {}", mint_address);
                 Ok(None)
@@ -137,6 +294,34 @@ impl RedisQueue {
         }
     }
 
+    // the token account holding the mint's largest balance, for an nft its holder. None when nobody holds any or
+    // the account isn't a plain spl token account
+    pub fn fetch_largest_token_account(
+        &self,
+        mint_address: &str,
+    ) -> Result<Option<TokenAccountData>, Box<dyn std::error::Error>> {
+        let mint = Pubkey::from_str(mint_address)?;
+        #[allow(clippy::result_large_err)]
+        let largest = |client: &RpcClient| client.get_token_largest_accounts(&mint);
+        let Some(holder) = self
+            .rpc_pool
+            .call(largest)?
+            .into_iter()
+            .find(|balance| balance.amount.amount != "0")
+        else {
+            return Ok(None);
+        };
+
+        let token_address = Pubkey::from_str(&holder.address)?;
+        #[allow(clippy::result_large_err)]
+        let fetch = |client: &RpcClient| client.get_account_with_commitment(&token_address, client.commitment());
+        Ok(self
+            .rpc_pool
+            .call(fetch)?
+            .value
+            .and_then(|account| TokenAccountData::parse(holder.address, &account.data)))
+    }
+
     pub async fn parse_metadata_pda_data(
         &self,
         mint_address: String,
@@ -151,7 +336,8 @@ impl RedisQueue {
 
         match MetadataAccount::safe_deserialize(&metadata_account_data) {
             Ok(metadeta) => {
-                if let Some(collection_data) = metadeta.collection {
+                let mut collection_name = None;
+                if let Some(collection_data) = &metadeta.collection {
                     let collection_nft_mint = collection_data.key.to_string();
                     let collection_nft_data = match self.get_metadeta_pda_data(collection_nft_mint)
                     {
@@ -163,37 +349,10 @@ impl RedisQueue {
                     match MetadataAccount::safe_deserialize(&collection_nft_data) {
                         Ok(full_metadata) => {
                             println!("Succesfully parsed the collection nft metadata and adding collection_name");
-
-                            return Ok(Some(Metadata {
-                                mint_address: metadeta.mint.to_string(),
-                                metadata_address: Some(metadata_address.to_string()),
-                                name: metadeta.name,
-                                symbol: Some(metadeta.symbol),
-                                metadata_uri: metadeta.uri,
-                                seller_fee_basis_points: metadeta.seller_fee_basis_points as i16,
-                                token_standard: metadeta.token_standard,
-                                collection: Some(full_metadata.name),
-                                update_authority: metadeta.update_authority.to_string(),
-                                primary_sale_happened: metadeta.primary_sale_happened,
-                                is_mutable: metadeta.is_mutable,
-                            }));
+                            collection_name = Some(full_metadata.name);
                         }
                         Err(e) => {
                             println!("Error deserailzing the collection nft metadata {} ...sending without collection name.", e);
-
-                            return Ok(Some(Metadata {
-                                mint_address: metadeta.mint.to_string(),
-                                metadata_address: Some(metadata_address.to_string()),
-                                name: metadeta.name,
-                                symbol: Some(metadeta.symbol),
-                                metadata_uri: metadeta.uri,
-                                seller_fee_basis_points: metadeta.seller_fee_basis_points as i16,
-                                token_standard: metadeta.token_standard,
-                                collection: None,
-                                update_authority: metadeta.update_authority.to_string(),
-                                primary_sale_happened: metadeta.primary_sale_happened,
-                                is_mutable: metadeta.is_mutable,
-                            }));
                         }
                     }
                 }
@@ -206,7 +365,19 @@ impl RedisQueue {
                     metadata_uri: metadeta.uri,
                     seller_fee_basis_points: metadeta.seller_fee_basis_points as i16,
                     token_standard: metadeta.token_standard,
-                    collection: None,
+                    collection: collection_name,
+                    collection_mint: metadeta.collection.as_ref().map(|c| c.key.to_string()),
+                    collection_verified: metadeta.collection.as_ref().is_some_and(|c| c.verified),
+                    creators: metadeta
+                        .creators
+                        .unwrap_or_default()
+                        .into_iter()
+                        .map(|creator| MetadataCreator {
+                            address: creator.address.to_string(),
+                            verified: creator.verified,
+                            share: creator.share,
+                        })
+                        .collect(),
                     update_authority: metadeta.update_authority.to_string(),
                     primary_sale_happened: metadeta.primary_sale_happened,
                     is_mutable: metadeta.is_mutable,
@@ -215,4 +386,28 @@ impl RedisQueue {
             Err(_) => Ok(None),
         }
     }
+
+    // the metadata_uri points at an off-chain json (arweave, ipfs gateways...). failures here only cost us image and attributes
+    pub async fn fetch_json_metadata(&self, metadata_uri: &str) -> Option<JsonMetadata> {
+        let uri = metadata_uri.replace('\0', "").trim().to_string();
+        if !uri.starts_with("http") {
+            return None;
+        }
+
+        let response = match self.http_client.get(&uri).send().await {
+            Ok(response) => response,
+            Err(e) => {
+                println!("Failed to fetch json metadata from {} : {}", uri, e);
+                return None;
+            }
+        };
+
+        match response.json::<JsonMetadata>().await {
+            Ok(json_metadata) => Some(json_metadata),
+            Err(e) => {
+                println!("Failed to parse json metadata from {} : {}", uri, e);
+                None
+            }
+        }
+    }
 }
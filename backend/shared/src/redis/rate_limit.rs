@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use chrono::{Days, NaiveDate, Utc};
+use redis::{RedisResult, Script};
+use sea_orm::prelude::Uuid;
+
+use crate::redis::connection::LazyConnection;
+use crate::types::api_key::{DailyUsage, MAX_USAGE_DAYS};
+
+// the token bucket and the daily usage counter in one round trip. the bucket refills continuously at ARGV[2]
+// tokens per ms up to ARGV[1] and every request takes one. the clock is redis' own so replicas share one bucket
+// no matter how far their clocks drift. returns {allowed, tokens left, ms until the next token}
+const TAKE_TOKEN: &str = r"
+local capacity = tonumber(ARGV[1])
+local rate = tonumber(ARGV[2])
+local time = redis.call('TIME')
+local now = tonumber(time[1]) * 1000 + math.floor(tonumber(time[2]) / 1000)
+
+local bucket = redis.call('HMGET', KEYS[1], 'tokens', 'at')
+local tokens = tonumber(bucket[1]) or capacity
+local at = tonumber(bucket[2]) or now
+tokens = math.min(capacity, tokens + math.max(0, now - at) * rate)
+
+local allowed = 0
+local wait = 0
+if tokens >= 1 then
+    tokens = tokens - 1
+    allowed = 1
+else
+    wait = math.ceil((1 - tokens) / rate)
+end
+
+redis.call('HSET', KEYS[1], 'tokens', tokens, 'at', now)
+redis.call('PEXPIRE', KEYS[1], math.ceil(capacity / rate) + 1000)
+redis.call('HINCRBY', KEYS[2], allowed == 1 and 'requests' or 'throttled', 1)
+redis.call('EXPIRE', KEYS[2], ARGV[3])
+return {allowed, math.floor(tokens), wait}
+";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateDecision {
+    pub allowed: bool,
+    pub remaining: u32,
+    pub retry_after: Duration, // zero when allowed
+}
+
+// per api key limits and usage counters, shared by every api_server replica through redis
+pub struct RateLimiter {
+    connection: LazyConnection,
+    script: Script,
+}
+
+impl RateLimiter {
+    pub fn new(redis_url: &str) -> RedisResult<Self> {
+        Ok(Self {
+            connection: LazyConnection::new(redis_url)?,
+            script: Script::new(TAKE_TOKEN),
+        })
+    }
+
+    // takes a token from the key's bucket and counts the request, throttled or not
+    pub async fn take(&self, key_id: Uuid, requests_per_minute: i32, burst: i32) -> RedisResult<RateDecision> {
+        let mut connection = self.connection.get().await?;
+        let result: RedisResult<(i64, i64, i64)> = self
+            .script
+            .key(format!("ratelimit:{}", key_id))
+            .key(usage_key(key_id, Utc::now().date_naive()))
+            .arg(burst.max(1))
+            .arg(requests_per_minute.max(1) as f64 / 60_000.0)
+            .arg(MAX_USAGE_DAYS as u64 * 24 * 60 * 60)
+            .invoke_async(&mut connection)
+            .await;
+
+        let (allowed, remaining, wait_ms) = self.connection.checked(result).await?;
+        Ok(RateDecision {
+            allowed: allowed == 1,
+            remaining: remaining.max(0) as u32,
+            retry_after: Duration::from_millis(wait_ms.max(0) as u64),
+        })
+    }
+
+    // the counters of today and the days before it, newest first. days without requests are zeros
+    pub async fn usage(&self, key_id: Uuid, days: u32) -> RedisResult<Vec<DailyUsage>> {
+        let days = usage_days(Utc::now().date_naive(), days);
+        let mut pipe = redis::pipe();
+        for day in &days {
+            pipe.hgetall(usage_key(key_id, *day));
+        }
+
+        let mut connection = self.connection.get().await?;
+        let result: RedisResult<Vec<HashMap<String, u64>>> = pipe.query_async(&mut connection).await;
+
+        Ok(days
+            .into_iter()
+            .zip(self.connection.checked(result).await?)
+            .map(|(day, counters)| DailyUsage {
+                day: day.format("%Y-%m-%d").to_string(),
+                requests: counters.get("requests").copied().unwrap_or(0),
+                throttled: counters.get("throttled").copied().unwrap_or(0),
+            })
+            .collect())
+    }
+}
+
+fn usage_key(key_id: Uuid, day: NaiveDate) -> String {
+    format!("api_usage:{}:{}", key_id, day.format("%Y-%m-%d"))
+}
+
+fn usage_days(today: NaiveDate, days: u32) -> Vec<NaiveDate> {
+    (0..days.clamp(1, MAX_USAGE_DAYS))
+        .filter_map(|back| today.checked_sub_days(Days::new(back.into())))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn usage_covers_today_and_the_days_before() {
+        let today = NaiveDate::from_ymd_opt(2026, 3, 2).unwrap();
+        let days: Vec<String> = usage_days(today, 3).iter().map(|day| day.to_string()).collect();
+        assert_eq!(days, vec!["2026-03-02", "2026-03-01", "2026-02-28"]);
+        assert_eq!(usage_days(today, 0).len(), 1);
+        assert_eq!(usage_days(today, 1000).len(), MAX_USAGE_DAYS as usize);
+    }
+}
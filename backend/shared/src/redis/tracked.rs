@@ -0,0 +1,153 @@
+use redis::{AsyncCommands, RedisResult, Script};
+use sea_orm::{ConnectionTrait, EntityTrait, PaginatorTrait, QueryOrder, QuerySelect};
+
+use crate::entities::{nft_metadata, token_account};
+use crate::redis::connection::LazyConnection;
+
+const TRACKED_KEY: &str = "indexed:accounts";
+const SEEDING_KEY: &str = "indexed:accounts:seeding";
+// redis drops empty sets, this member keeps the set around when there's nothing indexed yet
+const SENTINEL: &str = "";
+const SEED_CHUNK: u64 = 1_000;
+
+// 1 when ARGV[1] is tracked or the set doesn't exist. without the set nothing is known, so nothing is filtered
+const IS_TRACKED: &str = r"
+if redis.call('EXISTS', KEYS[1]) == 0 then
+    return 1
+end
+return redis.call('SISMEMBER', KEYS[1], ARGV[1])
+";
+
+// only adds to a set that exists, a partial set would make the listener drop events of everything missing from it
+const ADD_TRACKED: &str = r"
+if redis.call('EXISTS', KEYS[1]) == 0 then
+    return 0
+end
+return redis.call('SADD', KEYS[1], unpack(ARGV))
+";
+
+// the seeded set merged into the live one, so what other workers added meanwhile is kept
+const PUBLISH_SEEDED: &str = r"
+redis.call('SUNIONSTORE', KEYS[1], KEYS[1], KEYS[2])
+redis.call('DEL', KEYS[2])
+return 1
+";
+
+// the mint, metadata and token account addresses the worker has stored. the listener checks closed accounts and
+// metadata writes against it, so the account event queue only gets what the worker can do something with
+pub struct TrackedAccounts {
+    connection: LazyConnection,
+    is_tracked: Script,
+    add: Script,
+}
+
+impl TrackedAccounts {
+    pub fn new(redis_url: &str) -> RedisResult<Self> {
+        Ok(Self {
+            connection: LazyConnection::new(redis_url)?,
+            is_tracked: Script::new(IS_TRACKED),
+            add: Script::new(ADD_TRACKED),
+        })
+    }
+
+    pub async fn is_tracked(&self, address: &str) -> RedisResult<bool> {
+        let mut connection = self.connection.get().await?;
+        let result: RedisResult<i64> = self
+            .is_tracked
+            .key(TRACKED_KEY)
+            .arg(address)
+            .invoke_async(&mut connection)
+            .await;
+        self.connection.checked(result).await.map(|tracked| tracked == 1)
+    }
+
+    // like is_tracked, except a missing set counts as not tracked
+    pub async fn contains(&self, address: &str) -> RedisResult<bool> {
+        let mut connection = self.connection.get().await?;
+        let result: RedisResult<bool> = connection.sismember(TRACKED_KEY, address).await;
+        self.connection.checked(result).await
+    }
+
+    pub async fn add(&self, addresses: &[&str]) -> RedisResult<()> {
+        if addresses.is_empty() {
+            return Ok(());
+        }
+        let mut connection = self.connection.get().await?;
+        let result: RedisResult<i64> = self
+            .add
+            .key(TRACKED_KEY)
+            .arg(addresses)
+            .invoke_async(&mut connection)
+            .await;
+        self.connection.checked(result).await.map(|_| ())
+    }
+
+    pub async fn remove(&self, address: &str) -> RedisResult<()> {
+        let mut connection = self.connection.get().await?;
+        let result: RedisResult<i64> = connection.srem(TRACKED_KEY, address).await;
+        self.connection.checked(result).await.map(|_| ())
+    }
+
+    // builds the set from postgres when it doesn't exist, on the first start or after redis lost it. until it's
+    // published the listener lets every event through
+    pub async fn seed_if_missing<C: ConnectionTrait>(&self, db: &C) -> Result<(), Box<dyn std::error::Error>> {
+        let mut connection = self.connection.get().await?;
+        let exists: RedisResult<bool> = connection.exists(TRACKED_KEY).await;
+        if self.connection.checked(exists).await? {
+            return Ok(());
+        }
+
+        println!("Seeding the tracked accounts from postgres...");
+        let result: RedisResult<()> = async {
+            connection.del::<_, ()>(SEEDING_KEY).await?;
+            connection.sadd::<_, _, ()>(SEEDING_KEY, SENTINEL).await
+        }
+        .await;
+        self.connection.checked(result).await?;
+
+        let mut seeded = 0;
+        let mut metadata = nft_metadata::Entity::find()
+            .select_only()
+            .columns([nft_metadata::Column::MintAddress, nft_metadata::Column::MetadataAddress])
+            .order_by_asc(nft_metadata::Column::Id)
+            .into_tuple::<(String, Option<String>)>()
+            .paginate(db, SEED_CHUNK);
+        while let Some(rows) = metadata.fetch_and_next().await? {
+            let addresses: Vec<String> = rows
+                .into_iter()
+                .flat_map(|(mint_address, metadata_address)| std::iter::once(mint_address).chain(metadata_address))
+                .collect();
+            seeded += self.add_seeding(&addresses).await?;
+        }
+
+        let mut token_accounts = token_account::Entity::find()
+            .select_only()
+            .column(token_account::Column::TokenAddress)
+            .order_by_asc(token_account::Column::TokenAddress)
+            .into_tuple::<String>()
+            .paginate(db, SEED_CHUNK);
+        while let Some(addresses) = token_accounts.fetch_and_next().await? {
+            seeded += self.add_seeding(&addresses).await?;
+        }
+
+        let mut connection = self.connection.get().await?;
+        let result: RedisResult<i64> = Script::new(PUBLISH_SEEDED)
+            .key(TRACKED_KEY)
+            .key(SEEDING_KEY)
+            .invoke_async(&mut connection)
+            .await;
+        self.connection.checked(result).await?;
+        println!("Tracking {} accounts", seeded);
+        Ok(())
+    }
+
+    async fn add_seeding(&self, addresses: &[String]) -> RedisResult<usize> {
+        if addresses.is_empty() {
+            return Ok(0);
+        }
+        let mut connection = self.connection.get().await?;
+        let result: RedisResult<()> = connection.sadd(SEEDING_KEY, addresses).await;
+        self.connection.checked(result).await?;
+        Ok(addresses.len())
+    }
+}
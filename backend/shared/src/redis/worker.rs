@@ -1,51 +1,178 @@
-use std::time::Duration;
-use tokio::time::sleep;
-
-use crate::elasticsearch::client::ElasticSearchClient;
-use crate::entities::mint::{ActiveModel, Model};
-use crate::entities::nft_metadata::{ActiveModel as NftActiveModel, Model as NftModel};
-use crate::types::elasticsearch::NftDoc;
-use crate::types::metadeta::Metadata;
-use crate::{redis::queue_manager::RedisQueue, types::mint::MintData};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use chrono::Utc;
+
+use crate::assets::collections::refresh_collection_stats;
+use crate::assets::details::find_asset_details;
+use crate::elasticsearch::outbox::enqueue_outbox;
+use crate::entities::mint::{self, ActiveModel, Model};
+use crate::entities::nft_creator::{self, ActiveModel as CreatorActiveModel};
+use crate::entities::nft_json_metadata::{self, ActiveModel as NftJsonActiveModel, Model as NftJsonModel};
+use crate::entities::nft_metadata::{self, ActiveModel as NftActiveModel, Model as NftModel};
+use crate::entities::{nft_ownership, sync_outbox, token_account};
+use crate::types::account::{AccountEvent, TokenAccountData, TOKEN_ACCOUNT_FROZEN};
+use crate::types::asset::AssetInclude;
+use crate::types::event::{ChangeEvent, ChangeKind};
+use crate::types::metadeta::{JsonMetadata, Metadata};
+use crate::webhooks::enqueue_deliveries;
+use crate::redis::cache::ResponseCache;
+use crate::redis::tracked::TrackedAccounts;
+use crate::shutdown::Shutdown;
+use crate::{
+    redis::queue_manager::{Receipt, RedisQueue, HEARTBEAT_TTL_SECS},
+    types::mint::MintData,
+};
+use sea_orm::sea_query::{Expr, OnConflict};
+use sea_orm::prelude::Decimal;
 use sea_orm::Set;
-use sea_orm::{ActiveModelTrait, DatabaseConnection, DbErr};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter, QueryOrder,
+    QuerySelect, TransactionTrait,
+};
 use solana_program::pubkey::Pubkey;
 
+pub const MINT_DATA_QUEUE: &str = "mint_data_message";
+pub const ACCOUNT_EVENT_QUEUE: &str = "account_event_message";
+const SYNC_RETRY_INTERVAL: Duration = Duration::from_secs(60);
+const SYNC_RETRY_BATCH: u64 = 200;
+const COLLECTION_STATS_INTERVAL: Duration = Duration::from_secs(5 * 60);
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(HEARTBEAT_TTL_SECS / 4);
+const RECLAIM_INTERVAL: Duration = Duration::from_secs(60);
+const QUEUES: [&str; 2] = [ACCOUNT_EVENT_QUEUE, MINT_DATA_QUEUE];
+// mints with more decimals are fungible tokens, they aren't stored and neither are their token accounts
+pub const MAX_INDEXED_DECIMALS: i16 = 2;
+// token account writes keep the account event queue busy, the mint queue gets a turn at least this often
+const ACCOUNT_EVENTS_PER_MINT: usize = 10;
+
 pub struct QueueWorker {
     queue: RedisQueue,
     db: DatabaseConnection,
-    elasticsearch_client: ElasticSearchClient,
+    cache: Arc<ResponseCache>,
+    tracked: TrackedAccounts,
 }
 
 impl QueueWorker {
-    pub fn new(queue: RedisQueue, db: DatabaseConnection, client: ElasticSearchClient) -> Self {
-        println!("initializing queue, db connection and es_client for worker to work on...");
+    // ES writes go through the outbox, the relay that drains it is spawned next to the worker
+    pub fn new(queue: RedisQueue, db: DatabaseConnection, cache: Arc<ResponseCache>, tracked: TrackedAccounts) -> Self {
+        println!("initializing queue and db connection for worker to work on...");
         Self {
             queue,
             db,
-            elasticsearch_client: client,
+            cache,
+            tracked,
         }
     }
 
-    pub async fn start_processing(&self) {
-        println!("Started to process to the queue messages...");
-        loop {
-            match self.queue.dequeue_message("mint_data_message").await {
-                Ok(Some(data)) => {
+    // runs until shutdown, which it only notices between messages, so the one being handled is finished and acked
+    pub async fn start_processing(&self, shutdown: &Shutdown) {
+        println!("Started to process to the queue messages as {}...", self.queue.consumer());
+        // without the set the listener passes every account event on, which works, just slower
+        if let Err(e) = self.tracked.seed_if_missing(&self.db).await {
+            println!("Error seeding the tracked accounts {}", e);
+        }
+        self.heartbeat().await;
+        for queue_name in QUEUES {
+            match self.queue.requeue_unacked(queue_name).await {
+                Ok(0) => {}
+                Ok(requeued) => println!("Put {} unacked {} messages back in the queue", requeued, queue_name),
+                Err(e) => println!("Error putting the unacked {} messages back {}", queue_name, e),
+            }
+        }
+        self.reclaim_stale().await;
+
+        let mut last_heartbeat = Instant::now();
+        let mut last_reclaim = Instant::now();
+        let mut last_sync_retry = Instant::now();
+        self.refresh_collection_stats();
+        let mut last_stats_refresh = Instant::now();
+        let mut account_events_in_a_row = 0;
+        while !shutdown.is_triggered() {
+            if last_heartbeat.elapsed() >= HEARTBEAT_INTERVAL {
+                self.heartbeat().await;
+                last_heartbeat = Instant::now();
+            }
+            if last_reclaim.elapsed() >= RECLAIM_INTERVAL {
+                self.reclaim_stale().await;
+                last_reclaim = Instant::now();
+            }
+            if last_sync_retry.elapsed() >= SYNC_RETRY_INTERVAL {
+                self.retry_failed_syncs().await;
+                last_sync_retry = Instant::now();
+            }
+            if last_stats_refresh.elapsed() >= COLLECTION_STATS_INTERVAL {
+                self.refresh_collection_stats();
+                last_stats_refresh = Instant::now();
+            }
+
+            // account events first, a stale doc is worse than a late new one. up to ACCOUNT_EVENTS_PER_MINT of them
+            // before a mint message, so a steady stream of transfers can't hold new mints back
+            if account_events_in_a_row < ACCOUNT_EVENTS_PER_MINT {
+                match self.queue.dequeue_account_event(ACCOUNT_EVENT_QUEUE).await {
+                    Ok(Some((event, receipt))) => {
+                        println!("Recived account event from the queue : {:?}", event);
+                        self.process_account_event(event).await;
+                        self.ack(receipt).await;
+                        account_events_in_a_row += 1;
+                        continue;
+                    }
+                    Ok(None) => {}
+                    Err(e) => {
+                        println!("Error getting the account event from the queue {}", e);
+                    }
+                }
+            }
+            // when account events were skipped there may be more waiting, no reason to sleep
+            let accounts_waiting = account_events_in_a_row >= ACCOUNT_EVENTS_PER_MINT;
+            account_events_in_a_row = 0;
+
+            match self.queue.dequeue_message(MINT_DATA_QUEUE).await {
+                Ok(Some((data, receipt))) => {
                     println!("Recived mint data from the queue");
                     println!("queue message : {:?}", data);
-                    self.process_mint_data(data).await
+                    self.process_mint_data(data).await;
+                    self.ack(receipt).await;
                 }
+                Ok(None) if accounts_waiting => {}
                 Ok(None) => {
                     println!("Queue empty, no mint data recieved. sleeping for some time...");
-                    sleep(Duration::from_millis(100)).await;
+                    shutdown.sleep(Duration::from_millis(100)).await;
                 }
                 Err(e) => {
                     println!("Error getting the message from the queue {}", e);
-                    sleep(Duration::from_secs(2)).await;
+                    shutdown.sleep(Duration::from_secs(2)).await;
                 }
             }
         }
+        println!("Stopped taking queue messages");
+    }
+
+    async fn heartbeat(&self) {
+        if let Err(e) = self.queue.heartbeat().await {
+            println!("Error sending the queue heartbeat {}", e);
+        }
+    }
+
+    // the messages of replicas that died before acking them
+    async fn reclaim_stale(&self) {
+        match self.queue.reclaim_stale(&QUEUES).await {
+            Ok(0) => {}
+            Ok(reclaimed) => println!("Put {} messages of stopped workers back in the queues", reclaimed),
+            Err(e) => println!("Error reclaiming the messages of stopped workers {}", e),
+        }
+    }
+
+    // a failure here only means the listener drops this asset's closes and metadata edits until the set is reseeded
+    async fn track(&self, addresses: &[&str]) {
+        if let Err(e) = self.tracked.add(addresses).await {
+            println!("Error tracking {:?} : {}", addresses, e);
+        }
+    }
+
+    // a message whose ack is lost gets handled again after a restart, which the upserts are fine with
+    async fn ack(&self, receipt: Receipt) {
+        if let Err(e) = self.queue.ack(receipt).await {
+            println!("Error acking a queue message {}", e);
+        }
     }
 
     async fn process_mint_data(&self, mint_data: MintData) {
@@ -53,7 +180,7 @@ impl QueueWorker {
         println!("🔍 Mint details - Decimal: {}, Supply: {}", mint_data.decimal, mint_data.supply);
 
         // Only filter out high-decimal tokens (6+ decimals are definitely not NFTs)
-        if mint_data.decimal > 2 {
+        if mint_data.decimal > MAX_INDEXED_DECIMALS {
             println!("⚠️ Skipping high-decimal token (decimal: {}), definitely not an NFT", mint_data.decimal);
             return;
         }
@@ -61,21 +188,42 @@ impl QueueWorker {
         println!("🎨 Potential NFT/Collection detected - processing...");
         println!("💾 Attempting to save mint to database...");
 
-        // Try to save mint, but don't stop if it already exists
-        let mint_save_result = self.save_mint_to_db(mint_data.clone()).await;
-        match mint_save_result {
-            Ok(_) => {
-                println!("✅ Successfully saved new mint to DB!");
+        // an nft mint starts at supply 0 too, so only a drop from a positive supply counts as a burn
+        let stored_mint = match mint::Entity::find()
+            .filter(mint::Column::MintAddress.eq(&mint_data.mint_address))
+            .one(&self.db)
+            .await
+        {
+            Ok(stored_mint) => stored_mint,
+            Err(e) => {
+                println!("❌ Unexpected database error: {:?}", e);
+                return;
             }
-            Err(db_error) => {
-                let error_string = format!("{:?}", db_error);
-                if error_string.contains("duplicate key") || error_string.contains("already exists") {
-                    println!("ℹ️ Mint already exists in database, continuing with metadata processing...");
-                } else {
-                    println!("❌ Unexpected database error: {:?}", db_error);
-                    return; // Only return on unexpected errors
+        };
+
+        match stored_mint {
+            Some(stored_mint) => {
+                match self.update_mint_in_db(stored_mint.clone(), &mint_data).await {
+                    Ok(_) => self.publish_change(ChangeKind::Mint, &mint_data.mint_address, None).await,
+                    Err(e) => println!("❌ Failed to update stored mint: {:?}", e),
+                }
+                if stored_mint.supply > 0 && mint_data.supply == 0 {
+                    println!("🔥 Supply of {} dropped to 0, marking it burned", mint_data.mint_address);
+                    self.retire_asset(&mint_data.mint_address, nft_metadata::STATUS_BURNED).await;
+                    return;
                 }
+                println!("ℹ️ Mint already exists in database, continuing with metadata processing...");
             }
+            None => match self.save_mint_to_db(mint_data.clone()).await {
+                Ok(_) => {
+                    println!("✅ Successfully saved new mint to DB!");
+                    self.publish_change(ChangeKind::Mint, &mint_data.mint_address, None).await;
+                }
+                Err(db_error) => {
+                    println!("❌ Unexpected database error: {:?}", db_error);
+                    return;
+                }
+            },
         }
 
         println!("📍 Getting the PDA address for the mint...");
@@ -93,48 +241,36 @@ impl QueueWorker {
             }
         };
 
+        self.refresh_metadata(&mint_data.mint_address, metadata_pda_address).await;
+    }
+
+    // reads the metadata account, upserts everything we store about the asset and reindexes it.
+    // used for new mints and again whenever the metadata account changes
+    async fn refresh_metadata(&self, mint_address: &str, metadata_pda_address: Pubkey) {
         println!("🔍 Getting the metadata from the PDA address...");
         match self
             .queue
-            .parse_metadata_pda_data(mint_data.mint_address.clone(), metadata_pda_address)
+            .parse_metadata_pda_data(mint_address.to_string(), metadata_pda_address)
             .await
         {
             Ok(Some(metadata_data)) => {
                 println!("Successfully parsed metadata bytes");
+                // fetched before the transaction starts, so no connection is held open during the http call
+                let json_metadata = self.queue.fetch_json_metadata(&metadata_data.metadata_uri).await;
+
                 println!("Saving the metadata info to the db...");
-                let nft_name_clone = metadata_data.name.clone().replace('\0', "").trim().to_string();
-                
                 match self
-                    .save_metadata_to_db(
-                        metadata_data,
-                        metadata_pda_address,
-                        mint_data.mint_address.clone(),
-                    )
+                    .save_asset_to_db(&metadata_data, json_metadata.as_ref(), metadata_pda_address, mint_address)
                     .await
                 {
                     Ok(_) => {
-                        println!(" Successfully saved metadata to db");
-                        let nft_doc = NftDoc {
-                            mint_address: mint_data.mint_address,
-                            nft_name: nft_name_clone,
-                        };
-                        
-                        match self.elasticsearch_client.create_nft_index(nft_doc).await {
-                            Ok(_) => {
-                                println!(" Successfully indexed NFT in Elasticsearch");
-                            }
-                            Err(e) => {
-                                println!(" Failed to create Elasticsearch index: {}", e);
-                            }
-                        }
+                        println!(" Successfully saved metadata to db, queued for Elasticsearch through the outbox");
+                        self.track(&[mint_address, &metadata_pda_address.to_string()]).await;
+                        self.publish_change(ChangeKind::Metadata, mint_address, None).await;
+                        self.backfill_holder(mint_address).await;
                     }
                     Err(e) => {
-                        let error_string = format!("{:?}", e);
-                        if error_string.contains("duplicate key") {
-                            println!("Metadata already exists, skipping...");
-                        } else {
-                            println!("Error saving metadata to db: {}", e);
-                        }
+                        println!("Error saving metadata to db: {}", e);
                     }
                 }
             }
@@ -151,10 +287,407 @@ impl QueueWorker {
         }
     }
 
+    async fn process_account_event(&self, event: AccountEvent) {
+        match event {
+            AccountEvent::Closed { address, slot } => self.process_account_closed(&address, slot).await,
+            AccountEvent::MetadataChanged {
+                metadata_address,
+                mint_address,
+                ..
+            } => {
+                // only assets we already track are refreshed, new ones come in through their mint account
+                match nft_metadata::Entity::find()
+                    .filter(nft_metadata::Column::MintAddress.eq(&mint_address))
+                    .one(&self.db)
+                    .await
+                {
+                    Ok(Some(_)) => match metadata_address.parse::<Pubkey>() {
+                        Ok(metadata_pda_address) => {
+                            println!("✏️ Metadata of {} changed, refreshing...", mint_address);
+                            self.refresh_metadata(&mint_address, metadata_pda_address).await;
+                        }
+                        Err(e) => println!("Invalid metadata address {} : {}", metadata_address, e),
+                    },
+                    Ok(None) => {}
+                    Err(e) => println!("Error looking up metadata for {} : {}", mint_address, e),
+                }
+            }
+            AccountEvent::TokenAccountChanged { account, slot } => self.process_token_account(account, slot).await,
+        }
+    }
+
+    // keeps token_accounts in step with the chain and hands the nft to whoever holds it now. a transfer is two of
+    // these, the sender's account going to 0 and the receiver's to 1, in either order
+    async fn process_token_account(&self, account: TokenAccountData, slot: u64) {
+        match self.save_token_account(&account).await {
+            Ok(None) => {}
+            Ok(Some(ownership_changed)) => {
+                self.track(&[&account.token_address]).await;
+                if ownership_changed {
+                    let (mint_address, owner) = (&account.mint_address, &account.owner);
+                    println!("NFT {} moved at slot {}, {} holds {}", mint_address, slot, owner, account.amount);
+                    self.publish_change(ChangeKind::Ownership, &account.mint_address, Some(&account.owner)).await;
+                }
+            }
+            Err(e) => println!("Error saving token account {} : {}", account.token_address, e),
+        }
+    }
+
+    // token account writes that came before the asset was tracked were dropped by the listener, and for a fresh
+    // nft that's its first holder. so an nft without an ownership row gets its holder looked up once it's saved
+    async fn backfill_holder(&self, mint_address: &str) {
+        let needs_holder = async {
+            let is_nft = mint::Entity::find()
+                .filter(mint::Column::MintAddress.eq(mint_address))
+                .filter(mint::Column::Decimal.eq(0))
+                .filter(mint::Column::Supply.eq(1))
+                .one(&self.db)
+                .await?
+                .is_some();
+            let held = nft_ownership::Entity::find()
+                .filter(nft_ownership::Column::MintAddress.eq(mint_address))
+                .one(&self.db)
+                .await?
+                .is_some();
+            Ok::<_, DbErr>(is_nft && !held)
+        };
+        match needs_holder.await {
+            Ok(true) => {}
+            Ok(false) => return,
+            Err(e) => {
+                println!("Error checking the ownership of {} : {}", mint_address, e);
+                return;
+            }
+        }
+
+        match self.queue.fetch_largest_token_account(mint_address) {
+            Ok(Some(account)) => {
+                println!("Backfilling the holder of {} from {}", mint_address, account.token_address);
+                self.process_token_account(account, 0).await;
+            }
+            Ok(None) => println!("No holder found for {}", mint_address),
+            Err(e) => println!("Error looking up the holder of {} : {}", mint_address, e),
+        }
+    }
+
+    // None when the mint isn't stored, otherwise whether the nft's owner changed
+    async fn save_token_account(&self, account: &TokenAccountData) -> Result<Option<bool>, DbErr> {
+        let Some(stored_mint) = mint::Entity::find()
+            .filter(mint::Column::MintAddress.eq(&account.mint_address))
+            .one(&self.db)
+            .await?
+        else {
+            return Ok(None);
+        };
+
+        let txn = self.db.begin().await?;
+        token_account::Entity::insert(token_account::ActiveModel {
+            token_address: Set(account.token_address.clone()),
+            mint_address: Set(account.mint_address.clone()),
+            owner: Set(account.owner.clone()),
+            amount: Set(Decimal::from(account.amount)),
+            delegate: Set(account.delegate.clone()),
+            delegated_amount: Set(account.delegated_amount.to_string()),
+            state: Set(account.state.into()),
+            close_authority: Set(account.close_authority.clone()),
+            is_native: Set(account.is_native.is_some()),
+            rent_exempt_reserve: Set(account.is_native.unwrap_or(0).to_string()),
+            ..Default::default()
+        })
+        .on_conflict(
+            OnConflict::column(token_account::Column::TokenAddress)
+                .update_columns([
+                    token_account::Column::Owner,
+                    token_account::Column::Amount,
+                    token_account::Column::Delegate,
+                    token_account::Column::DelegatedAmount,
+                    token_account::Column::State,
+                    token_account::Column::CloseAuthority,
+                    token_account::Column::IsNative,
+                    token_account::Column::RentExemptReserve,
+                ])
+                .to_owned(),
+        )
+        .exec(&txn)
+        .await?;
+
+        // an nft has a single holder, the accounts of fungible and semi fungible mints are only stored
+        let is_nft = stored_mint.decimal == 0 && stored_mint.supply == 1;
+        let ownership_changed = if !is_nft {
+            false
+        } else if account.amount > 0 {
+            let previous = nft_ownership::Entity::find()
+                .filter(nft_ownership::Column::MintAddress.eq(&account.mint_address))
+                .one(&txn)
+                .await?;
+            nft_ownership::Entity::insert(nft_ownership::ActiveModel {
+                mint_address: Set(account.mint_address.clone()),
+                owner: Set(account.owner.clone()),
+                delegate: Set(account.delegate.clone()),
+                frozen: Set(account.state == TOKEN_ACCOUNT_FROZEN),
+                delegated: Set(account.delegate.is_some()),
+                ownership_model: Set("single".to_string()),
+                updated_at: Set(Utc::now().fixed_offset()),
+                ..Default::default()
+            })
+            .on_conflict(
+                OnConflict::column(nft_ownership::Column::MintAddress)
+                    .update_columns([
+                        nft_ownership::Column::Owner,
+                        nft_ownership::Column::Delegate,
+                        nft_ownership::Column::Frozen,
+                        nft_ownership::Column::Delegated,
+                        nft_ownership::Column::UpdatedAt,
+                    ])
+                    .to_owned(),
+            )
+            .exec(&txn)
+            .await?;
+            previous.is_none_or(|previous| previous.owner != account.owner)
+        } else {
+            // only the holder on record letting go clears it, the receiver's side may have landed first
+            nft_ownership::Entity::delete_many()
+                .filter(nft_ownership::Column::MintAddress.eq(&account.mint_address))
+                .filter(nft_ownership::Column::Owner.eq(&account.owner))
+                .exec(&txn)
+                .await?
+                .rows_affected
+                > 0
+        };
+
+        // owner and updated_at of the doc both come from the ownership row, so it's rebuilt rather than patched
+        if ownership_changed {
+            enqueue_outbox(&txn, &account.mint_address, sync_outbox::OPERATION_UPSERT, None).await?;
+        }
+        txn.commit().await?;
+        Ok(Some(ownership_changed))
+    }
+
+    // the data of a closed account is gone, so what it was is looked up in what we stored
+    async fn process_account_closed(&self, address: &str, slot: u64) {
+        let as_mint = nft_metadata::Entity::find()
+            .filter(nft_metadata::Column::MintAddress.eq(address))
+            .one(&self.db)
+            .await;
+        if let Ok(Some(_)) = as_mint {
+            println!("🗑️ Mint {} was closed at slot {}", address, slot);
+            self.retire_asset(address, nft_metadata::STATUS_CLOSED).await;
+            return;
+        }
+
+        let as_metadata = nft_metadata::Entity::find()
+            .filter(nft_metadata::Column::MetadataAddress.eq(address))
+            .one(&self.db)
+            .await;
+        if let Ok(Some(metadata)) = as_metadata {
+            // the token metadata program only closes the metadata account when the nft is burned
+            println!("🔥 Metadata account of {} was closed at slot {}", metadata.mint_address, slot);
+            self.retire_asset(&metadata.mint_address, nft_metadata::STATUS_BURNED).await;
+            return;
+        }
+
+        match token_account::Entity::find()
+            .filter(token_account::Column::TokenAddress.eq(address))
+            .one(&self.db)
+            .await
+        {
+            Ok(Some(closed_account)) => {
+                println!("Token account {} was closed at slot {}", address, slot);
+                match self.clear_ownership(&closed_account).await {
+                    // the owner is who held it until now, the row is gone already
+                    Ok(()) => {
+                        if let Err(e) = self.tracked.remove(address).await {
+                            println!("Error untracking token account {} : {}", address, e);
+                        }
+                        let owner = Some(closed_account.owner.as_str());
+                        self.publish_change(ChangeKind::Ownership, &closed_account.mint_address, owner).await
+                    }
+                    Err(e) => println!("Error clearing ownership for {} : {}", closed_account.mint_address, e),
+                }
+            }
+            Ok(None) => {} // not something we index
+            Err(e) => println!("Error looking up closed account {} : {}", address, e),
+        }
+    }
+
+    // keeps the rows but flags them, and takes the doc out of search
+    async fn retire_asset(&self, mint_address: &str, status: &str) {
+        let result = async {
+            let txn = self.db.begin().await?;
+            let update = nft_metadata::Entity::update_many()
+                .col_expr(nft_metadata::Column::Status, Expr::value(status))
+                .col_expr(nft_metadata::Column::StatusUpdatedAt, Expr::current_timestamp().into())
+                .col_expr(nft_metadata::Column::EsSyncStatus, Expr::value(nft_metadata::ES_SYNC_PENDING))
+                .col_expr(nft_metadata::Column::EsSyncUpdatedAt, Expr::current_timestamp().into())
+                .filter(nft_metadata::Column::MintAddress.eq(mint_address))
+                .exec(&txn)
+                .await?;
+            // not an nft we stored means nothing is indexed either
+            if update.rows_affected > 0 {
+                enqueue_outbox(&txn, mint_address, sync_outbox::OPERATION_DELETE, None).await?;
+            }
+            txn.commit().await?;
+            Ok::<_, DbErr>(update.rows_affected)
+        }
+        .await;
+
+        match result {
+            Ok(0) => {}
+            Ok(_) => {
+                println!("Marked {} as {}", mint_address, status);
+                let kind = if status == nft_metadata::STATUS_CLOSED { ChangeKind::Closed } else { ChangeKind::Burned };
+                self.publish_change(kind, mint_address, None).await;
+            }
+            Err(e) => println!("Error marking {} as {} : {}", mint_address, status, e),
+        }
+    }
+
+    // tells the api servers and the webhooks about a committed write. the asset is read back for the owner,
+    // collection and creators subscribers match on. failing here is only logged, the write itself already happened.
+    // the cached details go first, so a subscriber refetching the asset gets the new one
+    async fn publish_change(&self, kind: ChangeKind, mint_address: &str, owner: Option<&str>) {
+        if let Err(e) = self.cache.invalidate_details(mint_address).await {
+            println!("Error dropping the cached details of {} : {}", mint_address, e);
+        }
+
+        let include = AssetInclude {
+            creators: true,
+            ownership: true,
+            collection: true,
+            ..AssetInclude::NONE
+        };
+        let details = match find_asset_details(&self.db, mint_address, &include).await {
+            Ok(details) => details,
+            Err(e) => {
+                println!("Error loading {} for its change event : {}", mint_address, e);
+                None
+            }
+        };
+
+        let event = ChangeEvent {
+            kind,
+            mint_address: mint_address.to_string(),
+            owner: owner
+                .map(str::to_string)
+                .or_else(|| details.as_ref().and_then(|details| details.ownership.as_ref()).map(|o| o.owner.clone())),
+            collection: details
+                .as_ref()
+                .and_then(|details| details.collection.as_ref())
+                .and_then(|collection| collection.mint.clone()),
+            creators: details
+                .and_then(|details| details.creators)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|creator| creator.address)
+                .collect(),
+            at: Utc::now(),
+        };
+        if let Err(e) = self.queue.publish_change(&event).await {
+            println!("Error publishing the {} change of {} : {}", kind.as_str(), mint_address, e);
+        }
+        if let Err(e) = enqueue_deliveries(&self.db, &event).await {
+            println!("Error queueing webhooks for the {} change of {} : {}", kind.as_str(), mint_address, e);
+        }
+    }
+
+    // in the background, recomputing the view takes a while on a big table and messages keep coming meanwhile
+    fn refresh_collection_stats(&self) {
+        let db = self.db.clone();
+        tokio::spawn(async move {
+            if let Err(e) = refresh_collection_stats(&db).await {
+                println!("Error refreshing the collection stats {}", e);
+            }
+        });
+    }
+
+    // puts the assets whose outbox rows ran out of attempts back in the outbox once their backoff is over. permanent
+    // failures (mapping errors and other 4xx) have no retry_at and stay failed until the asset changes again
+    async fn retry_failed_syncs(&self) {
+        let rows = match nft_metadata::Entity::find()
+            .filter(nft_metadata::Column::EsSyncStatus.eq(nft_metadata::ES_SYNC_FAILED))
+            .filter(nft_metadata::Column::EsSyncRetryAt.lte(Utc::now().fixed_offset()))
+            .order_by_asc(nft_metadata::Column::EsSyncRetryAt)
+            .limit(SYNC_RETRY_BATCH)
+            .all(&self.db)
+            .await
+        {
+            Ok(rows) => rows,
+            Err(e) => {
+                println!("Error loading unsynced rows {}", e);
+                return;
+            }
+        };
+        if rows.is_empty() {
+            return;
+        }
+
+        println!("Retrying ES sync for {} assets...", rows.len());
+        for row in rows {
+            let result = async {
+                let txn = self.db.begin().await?;
+                nft_metadata::Entity::update_many()
+                    .col_expr(nft_metadata::Column::EsSyncStatus, Expr::value(nft_metadata::ES_SYNC_PENDING))
+                    .col_expr(nft_metadata::Column::EsSyncUpdatedAt, Expr::current_timestamp().into())
+                    .filter(nft_metadata::Column::MintAddress.eq(&row.mint_address))
+                    .exec(&txn)
+                    .await?;
+                // the relay turns an upsert of a retired asset into a delete
+                enqueue_outbox(&txn, &row.mint_address, sync_outbox::OPERATION_UPSERT, None).await?;
+                txn.commit().await
+            }
+            .await;
+            if let Err(e) = result {
+                println!("Error re-queueing {} for ES sync : {}", row.mint_address, e);
+            }
+        }
+    }
+
+    async fn clear_ownership(&self, closed_account: &token_account::Model) -> Result<(), DbErr> {
+        let txn = self.db.begin().await?;
+        token_account::Entity::delete_many()
+            .filter(token_account::Column::TokenAddress.eq(&closed_account.token_address))
+            .exec(&txn)
+            .await?;
+
+        let cleared = nft_ownership::Entity::delete_many()
+            .filter(nft_ownership::Column::MintAddress.eq(&closed_account.mint_address))
+            .filter(nft_ownership::Column::Owner.eq(&closed_account.owner))
+            .exec(&txn)
+            .await?;
+
+        // rebuilt like in save_token_account, a patched owner would leave the doc's content_hash stale
+        if cleared.rows_affected > 0 {
+            enqueue_outbox(&txn, &closed_account.mint_address, sync_outbox::OPERATION_UPSERT, None).await?;
+        }
+        txn.commit().await
+    }
+
+    // every row we keep about the asset plus its outbox row, in one transaction. either all of it
+    // lands and the relay will index it, or none of it does and the next update of the mint tries again
+    async fn save_asset_to_db(
+        &self,
+        metadata_data: &Metadata,
+        json_metadata: Option<&JsonMetadata>,
+        metadata_pda_address: Pubkey,
+        mint_address: &str,
+    ) -> Result<NftModel, DbErr> {
+        let txn = self.db.begin().await?;
+        let saved_metadata = self
+            .save_metadata_to_db(&txn, metadata_data.clone(), metadata_pda_address, mint_address.to_string())
+            .await?;
+        self.save_creators_to_db(&txn, metadata_data, metadata_pda_address).await?;
+        if let Some(json) = json_metadata {
+            self.save_json_metadata_to_db(&txn, json, metadata_data).await?;
+        }
+        enqueue_outbox(&txn, mint_address, sync_outbox::OPERATION_UPSERT, None).await?;
+        txn.commit().await?;
+        Ok(saved_metadata)
+    }
+
     async fn save_mint_to_db(&self, mint_data: MintData) -> Result<Model, DbErr> {
         let mint_model = ActiveModel {
             mint_address: Set(mint_data.mint_address),
-            decimal: Set(mint_data.decimal.into()),
+            decimal: Set(mint_data.decimal),
             supply: Set(mint_data.supply),
             mint_authority: Set(Some(mint_data.mint_authority)),
             freeze_authority: Set(mint_data.freeze_authority),
@@ -166,8 +699,18 @@ impl QueueWorker {
         result
     }
 
-    async fn save_metadata_to_db(
+    async fn update_mint_in_db(&self, stored_mint: Model, mint_data: &MintData) -> Result<Model, DbErr> {
+        let mut mint_model: ActiveModel = stored_mint.into();
+        mint_model.supply = Set(mint_data.supply);
+        mint_model.mint_authority = Set(Some(mint_data.mint_authority.clone()));
+        mint_model.freeze_authority = Set(mint_data.freeze_authority.clone());
+        mint_model.is_initialized = Set(mint_data.is_initialized);
+        mint_model.update(&self.db).await
+    }
+
+    async fn save_metadata_to_db<C: ConnectionTrait>(
         &self,
+        conn: &C,
         metadata_data: Metadata,
         metadata_pda_address: Pubkey,
         mint_address: String,
@@ -187,10 +730,110 @@ impl QueueWorker {
             update_authority: Set(clean_update_authority),
             primary_sale_happened: Set(metadata_data.primary_sale_happened),
             is_mutable: Set(metadata_data.is_mutable),
+            collection_name: Set(metadata_data.collection.map(|c| c.replace('\0', "").trim().to_string())),
+            collection_mint: Set(metadata_data.collection_mint),
+            collection_verified: Set(metadata_data.collection_verified),
+            token_standard: Set(metadata_data.token_standard.map(|t| format!("{:?}", t))),
+            es_sync_status: Set(nft_metadata::ES_SYNC_PENDING.to_string()),
+            es_sync_updated_at: Set(Some(Utc::now().fixed_offset())),
             ..Default::default()
         };
 
-        let result = metadata_model.insert(&self.db).await;
-        result
+        // the same mint comes back whenever its metadata changes, so the row is updated in place
+        nft_metadata::Entity::insert(metadata_model)
+            .on_conflict(
+                OnConflict::column(nft_metadata::Column::MintAddress)
+                    .update_columns([
+                        nft_metadata::Column::MetadataAddress,
+                        nft_metadata::Column::Name,
+                        nft_metadata::Column::Symbol,
+                        nft_metadata::Column::MetadataUri,
+                        nft_metadata::Column::SellerFeeBasisPoints,
+                        nft_metadata::Column::UpdateAuthority,
+                        nft_metadata::Column::PrimarySaleHappened,
+                        nft_metadata::Column::IsMutable,
+                        nft_metadata::Column::CollectionName,
+                        nft_metadata::Column::CollectionMint,
+                        nft_metadata::Column::CollectionVerified,
+                        nft_metadata::Column::TokenStandard,
+                        nft_metadata::Column::EsSyncStatus,
+                        nft_metadata::Column::EsSyncUpdatedAt,
+                    ])
+                    .to_owned(),
+            )
+            .exec_with_returning(conn)
+            .await
+    }
+
+    async fn save_creators_to_db<C: ConnectionTrait>(
+        &self,
+        conn: &C,
+        metadata_data: &Metadata,
+        metadata_pda_address: Pubkey,
+    ) -> Result<(), DbErr> {
+        if metadata_data.creators.is_empty() {
+            return Ok(());
+        }
+
+        let creator_models = metadata_data.creators.iter().map(|creator| CreatorActiveModel {
+            metadata_address: Set(metadata_pda_address.to_string()),
+            creator_address: Set(creator.address.clone()),
+            verified: Set(creator.verified),
+            share: Set(creator.share.into()),
+            ..Default::default()
+        });
+
+        // creators can be edited, replace the whole set instead of merging
+        nft_creator::Entity::delete_many()
+            .filter(nft_creator::Column::MetadataAddress.eq(metadata_pda_address.to_string()))
+            .exec(conn)
+            .await?;
+
+        nft_creator::Entity::insert_many(creator_models)
+            .exec(conn)
+            .await?;
+        Ok(())
+    }
+
+    async fn save_json_metadata_to_db<C: ConnectionTrait>(
+        &self,
+        conn: &C,
+        json_metadata: &JsonMetadata,
+        metadata_data: &Metadata,
+    ) -> Result<NftJsonModel, DbErr> {
+        let json_model = NftJsonActiveModel {
+            mint_address: Set(metadata_data.mint_address.clone()),
+            description: Set(json_metadata.description.clone()),
+            image: Set(json_metadata.image.clone()),
+            animation_url: Set(json_metadata.animation_url.clone()),
+            external_url: Set(json_metadata.external_url.clone()),
+            attributes: Set(serde_json::to_value(&json_metadata.attributes).ok()),
+            properties: Set(json_metadata.properties.clone()),
+            collection_name: Set(json_metadata
+                .collection
+                .as_ref()
+                .and_then(|c| c.name.clone())
+                .or_else(|| metadata_data.collection.clone())),
+            collection_family: Set(json_metadata.collection.as_ref().and_then(|c| c.family.clone())),
+            ..Default::default()
+        };
+
+        nft_json_metadata::Entity::insert(json_model)
+            .on_conflict(
+                OnConflict::column(nft_json_metadata::Column::MintAddress)
+                    .update_columns([
+                        nft_json_metadata::Column::Description,
+                        nft_json_metadata::Column::Image,
+                        nft_json_metadata::Column::AnimationUrl,
+                        nft_json_metadata::Column::ExternalUrl,
+                        nft_json_metadata::Column::Attributes,
+                        nft_json_metadata::Column::Properties,
+                        nft_json_metadata::Column::CollectionName,
+                        nft_json_metadata::Column::CollectionFamily,
+                    ])
+                    .to_owned(),
+            )
+            .exec_with_returning(conn)
+            .await
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,219 @@
+use crate::rpc::stats::{parse_endpoint_list, EndpointStats, EndpointStatsSnapshot};
+use solana_client::client_error::ClientError;
+use solana_client::rpc_client::RpcClient;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelectionStrategy {
+    Priority,           // always start from the first healthy endpoint in the configured order
+    WeightedRoundRobin, // spread requests over the healthy endpoints according to their weight
+}
+
+impl SelectionStrategy {
+    pub fn from_env_value(value: &str) -> Self {
+        match value.trim().to_lowercase().as_str() {
+            "priority" | "failover" => SelectionStrategy::Priority,
+            _ => SelectionStrategy::WeightedRoundRobin,
+        }
+    }
+}
+
+pub struct RpcEndpoint {
+    pub url: String,
+    pub weight: u32,
+    client: RpcClient,
+    stats: EndpointStats,
+}
+
+pub struct RpcPool {
+    endpoints: Vec<RpcEndpoint>,
+    strategy: SelectionStrategy,
+    cursor: AtomicUsize,
+}
+
+impl RpcPool {
+    pub fn new(endpoints: Vec<(String, u32)>, strategy: SelectionStrategy) -> Self {
+        println!(
+            "Initializing rpc pool with {} endpoints ({:?})",
+            endpoints.len(),
+            strategy
+        );
+        let endpoints = endpoints
+            .into_iter()
+            .map(|(url, weight)| RpcEndpoint {
+                client: RpcClient::new_with_timeout(url.clone(), Duration::from_secs(15)),
+                url,
+                weight,
+                stats: EndpointStats::default(),
+            })
+            .collect();
+
+        Self {
+            endpoints,
+            strategy,
+            cursor: AtomicUsize::new(0),
+        }
+    }
+
+    // HELIUS_URLS takes a comma separated list, the single HELIUS_URL is still honoured for old deployments
+    pub fn from_env() -> Self {
+        let raw = std::env::var("HELIUS_URLS")
+            .or_else(|_| std::env::var("HELIUS_URL"))
+            .expect("helius url not found from env");
+        let endpoints = parse_endpoint_list(&raw);
+        if endpoints.is_empty() {
+            panic!("no rpc endpoints configured in HELIUS_URLS / HELIUS_URL");
+        }
+        let strategy = SelectionStrategy::from_env_value(
+            &std::env::var("RPC_STRATEGY").unwrap_or_default(),
+        );
+        Self::new(endpoints, strategy)
+    }
+
+    // runs the call against endpoints in selection order, failing over to the next one on any rpc error
+    #[allow(clippy::result_large_err)] // ClientError is solana's type, boxing it here would only move the problem to the callers
+    pub fn call<T, F>(&self, f: F) -> Result<T, ClientError>
+    where
+        F: Fn(&RpcClient) -> Result<T, ClientError>,
+    {
+        let mut last_error = None;
+
+        for index in self.selection_order() {
+            let endpoint = &self.endpoints[index];
+            let started = Instant::now();
+            match f(&endpoint.client) {
+                Ok(value) => {
+                    endpoint.stats.record_success(started.elapsed());
+                    return Ok(value);
+                }
+                Err(e) => {
+                    endpoint.stats.record_failure(started.elapsed());
+                    println!("RPC endpoint {} failed, trying next one : {}", endpoint.url, e);
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        Err(last_error.expect("rpc pool always has at least one endpoint"))
+    }
+
+    // healthy endpoints first (picked by strategy), unhealthy ones are only tried as a last resort
+    fn selection_order(&self) -> Vec<usize> {
+        let (mut healthy, unhealthy): (Vec<usize>, Vec<usize>) =
+            (0..self.endpoints.len()).partition(|&i| self.endpoints[i].stats.is_healthy());
+
+        if self.strategy == SelectionStrategy::WeightedRoundRobin && !healthy.is_empty() {
+            let total_weight: usize = healthy
+                .iter()
+                .map(|&i| self.endpoints[i].weight as usize)
+                .sum();
+            let mut slot = self.cursor.fetch_add(1, Ordering::Relaxed) % total_weight;
+            let first = healthy
+                .iter()
+                .position(|&i| {
+                    let weight = self.endpoints[i].weight as usize;
+                    if slot < weight {
+                        true
+                    } else {
+                        slot -= weight;
+                        false
+                    }
+                })
+                .unwrap_or(0);
+            healthy.rotate_left(first);
+        }
+
+        healthy.extend(unhealthy);
+        healthy
+    }
+
+    pub fn check_health(&self) {
+        for endpoint in &self.endpoints {
+            let started = Instant::now();
+            match endpoint.client.get_health() {
+                Ok(_) => {
+                    endpoint.stats.record_success(started.elapsed());
+                }
+                Err(e) => {
+                    println!("RPC health check failed for {} : {}", endpoint.url, e);
+                    endpoint.stats.record_failure(started.elapsed());
+                    endpoint.stats.set_healthy(false);
+                }
+            }
+        }
+    }
+
+    // the rpc client is blocking, so health checks run on the blocking pool at a fixed interval
+    pub fn spawn_health_checks(self: Arc<Self>, interval: Duration) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                let pool = self.clone();
+                if let Err(e) = tokio::task::spawn_blocking(move || pool.check_health()).await {
+                    println!("RPC health check task failed {}", e);
+                    continue;
+                }
+                for stats in self.stats() {
+                    println!(
+                        "RPC endpoint {} healthy={} requests={} errors={} avg_latency={:.1}ms",
+                        stats.url, stats.healthy, stats.requests, stats.errors, stats.avg_latency_ms
+                    );
+                }
+            }
+        })
+    }
+
+    pub fn stats(&self) -> Vec<EndpointStatsSnapshot> {
+        self.endpoints
+            .iter()
+            .map(|endpoint| endpoint.stats.snapshot(&endpoint.url, endpoint.weight))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RpcClient doesn't connect until it's used, so the pool can be built without a network
+    fn pool_of(weights: &[u32], strategy: SelectionStrategy) -> RpcPool {
+        let endpoints = weights
+            .iter()
+            .enumerate()
+            .map(|(i, &weight)| (format!("http://rpc-{}.invalid", i), weight))
+            .collect();
+        RpcPool::new(endpoints, strategy)
+    }
+
+    fn first_picks(pool: &RpcPool, rounds: usize) -> Vec<usize> {
+        (0..rounds).map(|_| pool.selection_order()[0]).collect()
+    }
+
+    #[test]
+    fn weighted_round_robin_follows_the_weights() {
+        let pool = pool_of(&[1, 3], SelectionStrategy::WeightedRoundRobin);
+        assert_eq!(first_picks(&pool, 8), vec![0, 1, 1, 1, 0, 1, 1, 1]);
+        // every endpoint is still in the order, the rest are the fallbacks
+        assert_eq!(pool.selection_order().len(), 2);
+    }
+
+    #[test]
+    fn priority_always_starts_from_the_top() {
+        let pool = pool_of(&[1, 3], SelectionStrategy::Priority);
+        assert_eq!(first_picks(&pool, 3), vec![0, 0, 0]);
+    }
+
+    #[test]
+    fn unhealthy_endpoints_go_last() {
+        let pool = pool_of(&[5, 1, 1], SelectionStrategy::WeightedRoundRobin);
+        pool.endpoints[0].stats.set_healthy(false);
+        assert_eq!(first_picks(&pool, 4), vec![1, 2, 1, 2]);
+        assert_eq!(pool.selection_order()[2], 0);
+
+        let priority = pool_of(&[1, 1], SelectionStrategy::Priority);
+        priority.endpoints[0].stats.set_healthy(false);
+        assert_eq!(priority.selection_order(), vec![1, 0]);
+    }
+}
@@ -0,0 +1,162 @@
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// after these many failures in a row the endpoint is taken out of rotation until a health check passes
+pub const MAX_CONSECUTIVE_FAILURES: u64 = 3;
+
+// counters are atomics so the stats can be shared between the request path and the health check task without a lock
+#[derive(Debug)]
+pub struct EndpointStats {
+    requests: AtomicU64,
+    errors: AtomicU64,
+    consecutive_failures: AtomicU64,
+    total_latency_micros: AtomicU64,
+    last_latency_micros: AtomicU64,
+    last_success_unix: AtomicU64,
+    healthy: AtomicBool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EndpointStatsSnapshot {
+    pub url: String,
+    pub weight: u32,
+    pub healthy: bool,
+    pub requests: u64,
+    pub errors: u64,
+    pub consecutive_failures: u64,
+    pub avg_latency_ms: f64,
+    pub last_latency_ms: f64,
+    pub last_success_unix: Option<u64>,
+}
+
+impl Default for EndpointStats {
+    fn default() -> Self {
+        Self {
+            requests: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+            consecutive_failures: AtomicU64::new(0),
+            total_latency_micros: AtomicU64::new(0),
+            last_latency_micros: AtomicU64::new(0),
+            last_success_unix: AtomicU64::new(0),
+            healthy: AtomicBool::new(true), // every endpoint starts healthy, the first failures or health check decides otherwise
+        }
+    }
+}
+
+impl EndpointStats {
+    pub fn record_success(&self, latency: Duration) {
+        let micros = latency.as_micros() as u64;
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        self.total_latency_micros.fetch_add(micros, Ordering::Relaxed);
+        self.last_latency_micros.store(micros, Ordering::Relaxed);
+        self.last_success_unix.store(unix_now(), Ordering::Relaxed);
+        self.consecutive_failures.store(0, Ordering::Relaxed);
+        self.healthy.store(true, Ordering::Relaxed);
+    }
+
+    pub fn record_failure(&self, latency: Duration) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        self.errors.fetch_add(1, Ordering::Relaxed);
+        self.last_latency_micros
+            .store(latency.as_micros() as u64, Ordering::Relaxed);
+        let failures = self.consecutive_failures.fetch_add(1, Ordering::Relaxed) + 1;
+        if failures >= MAX_CONSECUTIVE_FAILURES {
+            self.healthy.store(false, Ordering::Relaxed);
+        }
+    }
+
+    pub fn set_healthy(&self, healthy: bool) {
+        if healthy {
+            self.consecutive_failures.store(0, Ordering::Relaxed);
+        }
+        self.healthy.store(healthy, Ordering::Relaxed);
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.healthy.load(Ordering::Relaxed)
+    }
+
+    pub fn snapshot(&self, url: &str, weight: u32) -> EndpointStatsSnapshot {
+        let requests = self.requests.load(Ordering::Relaxed);
+        let errors = self.errors.load(Ordering::Relaxed);
+        let successes = requests.saturating_sub(errors);
+        let total_latency = self.total_latency_micros.load(Ordering::Relaxed);
+        let last_success = self.last_success_unix.load(Ordering::Relaxed);
+
+        EndpointStatsSnapshot {
+            url: url.to_string(),
+            weight,
+            healthy: self.is_healthy(),
+            requests,
+            errors,
+            consecutive_failures: self.consecutive_failures.load(Ordering::Relaxed),
+            avg_latency_ms: if successes == 0 {
+                0.0
+            } else {
+                total_latency as f64 / successes as f64 / 1000.0
+            },
+            last_latency_ms: self.last_latency_micros.load(Ordering::Relaxed) as f64 / 1000.0,
+            last_success_unix: if last_success == 0 {
+                None
+            } else {
+                Some(last_success)
+            },
+        }
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+// endpoint lists come from env as "url1,url2|3,url3" where the optional "|n" suffix is the weight (defaults to 1)
+pub fn parse_endpoint_list(raw: &str) -> Vec<(String, u32)> {
+    raw.split(',')
+        .map(|entry| entry.trim())
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| match entry.rsplit_once('|') {
+            Some((url, weight)) => (
+                url.trim().to_string(),
+                weight.trim().parse::<u32>().unwrap_or(1).max(1),
+            ),
+            None => (entry.to_string(), 1),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_urls_with_optional_weights() {
+        assert_eq!(
+            parse_endpoint_list(" https://a.rpc , https://b.rpc|3,,https://c.rpc|0, https://d.rpc|x "),
+            vec![
+                ("https://a.rpc".to_string(), 1),
+                ("https://b.rpc".to_string(), 3),
+                ("https://c.rpc".to_string(), 1), // a zero weight would never be picked
+                ("https://d.rpc".to_string(), 1),
+            ]
+        );
+        assert!(parse_endpoint_list(" , ").is_empty());
+    }
+
+    #[test]
+    fn consecutive_failures_take_an_endpoint_out() {
+        let stats = EndpointStats::default();
+        for _ in 0..MAX_CONSECUTIVE_FAILURES - 1 {
+            stats.record_failure(Duration::from_millis(5));
+        }
+        assert!(stats.is_healthy());
+        stats.record_failure(Duration::from_millis(5));
+        assert!(!stats.is_healthy());
+        stats.record_success(Duration::from_millis(5));
+        assert!(stats.is_healthy());
+        assert_eq!(stats.snapshot("a", 1).errors, MAX_CONSECUTIVE_FAILURES);
+    }
+}
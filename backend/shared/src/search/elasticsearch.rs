@@ -0,0 +1,51 @@
+use async_trait::async_trait;
+
+use crate::elasticsearch::bulk_indexer::index_with_retries;
+use crate::elasticsearch::client::{BulkFailure, BulkOp, ElasticSearchClient};
+use crate::search::{SearchIndex, SearchIndexError};
+use crate::types::elasticsearch::{NftDoc, PageRequest, SearchRequest, SearchResponse, SuggestResponse};
+
+const BULK_RETRIES: u32 = 2; // callers like the outbox relay retry across batches on their own
+
+#[async_trait]
+impl SearchIndex for ElasticSearchClient {
+    async fn index(&self, doc: NftDoc) -> Result<(), SearchIndexError> {
+        first_failure(self.bulk(vec![doc.into()]).await?)
+    }
+
+    // refresh=wait_for, the relay drops the cached search results right after and they mustn't be rebuilt from
+    // before the refresh. a batch then takes up to the index's refresh_interval (1s)
+    async fn bulk(&self, operations: Vec<BulkOp>) -> Result<Vec<BulkFailure>, SearchIndexError> {
+        Ok(index_with_retries(self, self.write_alias(), operations, BULK_RETRIES, true).await)
+    }
+
+    async fn delete(&self, mint_address: &str) -> Result<(), SearchIndexError> {
+        first_failure(self.bulk(vec![BulkOp::Delete(mint_address.to_string())]).await?)
+    }
+
+    async fn search(&self, query: &str, page: &PageRequest) -> Result<SearchResponse, SearchIndexError> {
+        Ok(self.search_nft(query, page).await?)
+    }
+
+    async fn search_filtered(&self, request: &SearchRequest) -> Result<SearchResponse, SearchIndexError> {
+        Ok(ElasticSearchClient::search_filtered(self, request).await?)
+    }
+
+    async fn suggest(&self, query: &str, size: Option<i64>) -> Result<SuggestResponse, SearchIndexError> {
+        Ok(ElasticSearchClient::suggest(self, query, size).await?)
+    }
+
+    async fn ping(&self) -> Result<(), SearchIndexError> {
+        Ok(ElasticSearchClient::ping(self).await?)
+    }
+}
+
+fn first_failure(failures: Vec<BulkFailure>) -> Result<(), SearchIndexError> {
+    match failures.into_iter().next() {
+        Some(failure) => Err(SearchIndexError::Failed(format!(
+            "{} failed ({}) : {}",
+            failure.id, failure.status, failure.reason
+        ))),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,100 @@
+pub mod elasticsearch;
+pub mod postgres;
+
+use std::error::Error;
+use std::fmt;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use sea_orm::DatabaseConnection;
+
+use crate::elasticsearch::client::{BulkFailure, BulkOp, ElasticSearchClient, ElasticSearchError};
+use crate::search::postgres::PgSearchIndex;
+use crate::types::elasticsearch::{NftDoc, PageRequest, SearchRequest, SearchResponse, SuggestResponse};
+
+// everything the api and the outbox relay need from a search backend. Elasticsearch is the real one,
+// the postgres one (tsvector + pg_trgm) lets local development and CI run the whole pipeline without ES.
+// both report SearchIndexError, so callers handle them the same way
+#[async_trait]
+pub trait SearchIndex: Send + Sync {
+    async fn index(&self, doc: NftDoc) -> Result<(), SearchIndexError>;
+
+    // applies the operations in order and returns the ones that failed. what succeeded is searchable once it returns
+    async fn bulk(&self, operations: Vec<BulkOp>) -> Result<Vec<BulkFailure>, SearchIndexError>;
+
+    async fn delete(&self, mint_address: &str) -> Result<(), SearchIndexError>;
+
+    // the search box: classifies the query and pages through the hits
+    async fn search(&self, query: &str, page: &PageRequest) -> Result<SearchResponse, SearchIndexError>;
+
+    // POST /search: free text plus typed filters and optional facets
+    async fn search_filtered(&self, request: &SearchRequest) -> Result<SearchResponse, SearchIndexError>;
+
+    async fn suggest(&self, query: &str, size: Option<i64>) -> Result<SuggestResponse, SearchIndexError>;
+
+    // whether the backend answers at all, for /readyz
+    async fn ping(&self) -> Result<(), SearchIndexError>;
+}
+
+// what went wrong, whichever backend it was
+#[derive(Debug)]
+pub enum SearchIndexError {
+    Unavailable(String),    // the backend can't be reached or is overloaded, worth retrying
+    InvalidRequest(String), // the caller sent something we can't turn into a query, e.g. a bad cursor
+    Failed(String),         // the backend answered but the request failed
+}
+
+impl fmt::Display for SearchIndexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SearchIndexError::Unavailable(msg) => write!(f, "Search backend unavailable : {}", msg),
+            SearchIndexError::InvalidRequest(msg) => write!(f, "Invalid Request : {}", msg),
+            SearchIndexError::Failed(msg) => write!(f, "Search Error : {}", msg),
+        }
+    }
+}
+
+impl Error for SearchIndexError {}
+
+impl From<ElasticSearchError> for SearchIndexError {
+    fn from(e: ElasticSearchError) -> Self {
+        match e {
+            ElasticSearchError::ConnectionError(_) => SearchIndexError::Unavailable(e.to_string()),
+            ElasticSearchError::InvalidRequest(msg) => SearchIndexError::InvalidRequest(msg),
+            _ => SearchIndexError::Failed(e.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchBackend {
+    Elasticsearch,
+    Postgres,
+}
+
+impl SearchBackend {
+    pub fn from_env_value(value: &str) -> Self {
+        match value.trim().to_lowercase().as_str() {
+            "postgres" | "pg" => SearchBackend::Postgres,
+            _ => SearchBackend::Elasticsearch,
+        }
+    }
+}
+
+// SEARCH_BACKEND picks the implementation, elasticsearch unless it says postgres
+pub async fn search_index_from_env(db: &DatabaseConnection) -> Result<Arc<dyn SearchIndex>, SearchIndexError> {
+    let backend = SearchBackend::from_env_value(&std::env::var("SEARCH_BACKEND").unwrap_or_default());
+    println!("Using the {:?} search backend", backend);
+
+    match backend {
+        SearchBackend::Elasticsearch => {
+            let client = ElasticSearchClient::new(
+                std::env::var("ELASTICSEARCH_URL").expect("failed to get es_url from env"),
+                std::env::var("ELASTICSEARCH_INDEX_NAME").expect("failed to get index name from env"),
+            )
+            .await?;
+            Ok(Arc::new(client))
+        }
+        SearchBackend::Postgres => Ok(Arc::new(PgSearchIndex::new(db.clone()).await?)),
+    }
+}
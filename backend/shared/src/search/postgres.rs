@@ -0,0 +1,555 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use sea_orm::{ConnectionTrait, DatabaseConnection, DbBackend, DbErr, QueryResult, Statement, TransactionTrait};
+use serde_json::{json, Value};
+
+use crate::elasticsearch::classify::{classify_query, QueryClass};
+use crate::elasticsearch::client::{BulkFailure, BulkOp};
+use crate::elasticsearch::query::{
+    decode_cursor, dedupe_by_collection, encode_cursor, page_size, suggest_size, FACET_SIZE, SUGGEST_OVERFETCH,
+};
+use crate::search::{SearchIndex, SearchIndexError};
+use crate::types::elasticsearch::{
+    CollectionFacet, FacetBucket, NftDoc, PageRequest, SearchAggregations, SearchFilters, SearchRequest,
+    SearchResponse, SearchResult, SearchSort, Suggestion, SuggestResponse, TraitFacet,
+};
+
+// Postgres full text search behind the SearchIndex trait. scores are ts_rank + trigram similarity, so the
+// ranking is close to ES but not identical, and totals are always exact
+pub struct PgSearchIndex {
+    db: DatabaseConnection,
+}
+
+impl PgSearchIndex {
+    // nft_search_docs comes from the migrations, this only checks it's there
+    pub async fn new(db: DatabaseConnection) -> Result<Self, SearchIndexError> {
+        let index = Self { db };
+        index.ping().await?;
+        Ok(index)
+    }
+
+    async fn apply(&self, operations: &[BulkOp]) -> Result<Vec<BulkFailure>, DbErr> {
+        let txn = self.db.begin().await?;
+        let mut failures = vec![];
+
+        for operation in operations {
+            let statement = match operation {
+                BulkOp::Index(doc) => {
+                    let source = serde_json::to_value(doc.as_ref()).map_err(|e| DbErr::Json(e.to_string()))?;
+                    statement(
+                        "INSERT INTO nft_search_docs (mint_address, doc) VALUES ($1, $2)
+                         ON CONFLICT (mint_address) DO UPDATE SET doc = EXCLUDED.doc",
+                        vec![doc.mint_address.clone().into(), source.into()],
+                    )
+                }
+                BulkOp::Update { id, partial } => statement(
+                    "UPDATE nft_search_docs SET doc = doc || $2 WHERE mint_address = $1",
+                    vec![id.clone().into(), partial.clone().into()],
+                ),
+                BulkOp::Delete(id) => statement(
+                    "DELETE FROM nft_search_docs WHERE mint_address = $1",
+                    vec![id.clone().into()],
+                ),
+            };
+
+            let result = txn.execute(statement).await?;
+            // same outcome ES reports for an update or delete of a doc that isn't there
+            if result.rows_affected() == 0 && !matches!(operation, BulkOp::Index(_)) {
+                failures.push(BulkFailure {
+                    id: operation.id().to_string(),
+                    status: 404,
+                    reason: "document missing".to_string(),
+                });
+            }
+        }
+
+        txn.commit().await?;
+        Ok(failures)
+    }
+
+    async fn run_search(
+        &self,
+        class: &QueryClass,
+        filters: &SearchFilters,
+        page: &PageRequest,
+        include_facets: bool,
+    ) -> Result<SearchResponse, SearchIndexError> {
+        let mut binds = Binds::default();
+        let (condition, score) = class_condition(class, &mut binds);
+        let mut conditions = vec![condition];
+        conditions.extend(filter_conditions(filters, &mut binds));
+        let where_clause = conditions.join(" AND ");
+
+        let total = self
+            .query_one_i64(
+                &format!("SELECT count(*) AS count FROM nft_search_docs WHERE {}", where_clause),
+                binds.values.clone(),
+            )
+            .await? as u64;
+
+        let aggregations = if include_facets {
+            Some(self.facets(&where_clause, &binds).await?)
+        } else {
+            None
+        };
+
+        let (sort_key, direction) = sort_key(page.sort, &score);
+        let size = page_size(page);
+        let mut page_binds = binds.clone();
+        let after = match &page.cursor {
+            Some(cursor) => cursor_condition(page.sort, &decode_cursor(cursor, page.sort)?, direction, &mut page_binds)?,
+            None => "TRUE".to_string(),
+        };
+
+        let sql = format!(
+            "SELECT mint_address, doc, score, to_jsonb(sort_key) AS sort_value FROM (
+                 SELECT mint_address, doc, {score} AS score, {sort_key} AS sort_key
+                 FROM nft_search_docs WHERE {where_clause}
+             ) hits
+             WHERE {after}
+             ORDER BY sort_key {direction}, mint_address ASC
+             LIMIT {size}"
+        );
+        let rows = self.query_all(&sql, page_binds.values).await?;
+
+        let mut results = Vec::with_capacity(rows.len());
+        let mut last_sort = None;
+        for row in rows {
+            let hit = read_hit(&row)?;
+            last_sort = Some(json!([hit.sort_value, hit.mint_address]));
+            results.push(SearchResult {
+                nft_name: hit.doc["nft_name"].as_str().unwrap_or_default().to_string(),
+                mint_address: hit.mint_address,
+                score: hit.score,
+            });
+        }
+
+        let next_cursor = match last_sort {
+            Some(sort_values) if results.len() as i64 >= size => Some(encode_cursor(page.sort, &sort_values)),
+            _ => None,
+        };
+
+        Ok(SearchResponse {
+            results,
+            total,
+            total_is_exact: true,
+            next_cursor,
+            aggregations,
+        })
+    }
+
+    async fn facets(&self, where_clause: &str, binds: &Binds) -> Result<SearchAggregations, SearchIndexError> {
+        let collection_rows = self
+            .query_all(
+                &format!(
+                    "SELECT collection_mint, max(collection_name) AS collection_name, count(*) AS count
+                     FROM nft_search_docs WHERE {where_clause} AND collection_mint IS NOT NULL
+                     GROUP BY collection_mint ORDER BY count DESC, collection_mint ASC LIMIT {FACET_SIZE}"
+                ),
+                binds.values.clone(),
+            )
+            .await?;
+        let mut collections = vec![];
+        for row in collection_rows {
+            collections.push(CollectionFacet {
+                mint: row.try_get("", "collection_mint").map_err(search_error)?,
+                name: row.try_get("", "collection_name").map_err(search_error)?,
+                count: row.try_get::<i64>("", "count").map_err(search_error)? as u64,
+            });
+        }
+
+        let trait_rows = self
+            .query_all(
+                &format!(
+                    "SELECT attribute.item->>'trait_type' AS trait_type, attribute.item->>'value' AS trait_value, count(*) AS count
+                     FROM nft_search_docs, jsonb_array_elements(coalesce(doc->'attributes', '[]'::jsonb)) AS attribute(item)
+                     WHERE {where_clause}
+                     GROUP BY 1, 2"
+                ),
+                binds.values.clone(),
+            )
+            .await?;
+        let mut buckets: HashMap<String, Vec<FacetBucket>> = HashMap::new();
+        for row in trait_rows {
+            let trait_type: Option<String> = row.try_get("", "trait_type").map_err(search_error)?;
+            let value: Option<String> = row.try_get("", "trait_value").map_err(search_error)?;
+            let (Some(trait_type), Some(value)) = (trait_type, value) else {
+                continue;
+            };
+            buckets.entry(trait_type).or_default().push(FacetBucket {
+                value,
+                count: row.try_get::<i64>("", "count").map_err(search_error)? as u64,
+            });
+        }
+
+        // ES counts docs per trait_type, not values, but an nft carries each trait once so the sum is the same
+        let mut traits: Vec<TraitFacet> = buckets
+            .into_iter()
+            .map(|(trait_type, mut values)| {
+                values.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.value.cmp(&b.value)));
+                let count = values.iter().map(|bucket| bucket.count).sum();
+                values.truncate(FACET_SIZE);
+                TraitFacet {
+                    trait_type,
+                    count,
+                    values,
+                }
+            })
+            .collect();
+        traits.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.trait_type.cmp(&b.trait_type)));
+        traits.truncate(FACET_SIZE);
+
+        Ok(SearchAggregations { collections, traits })
+    }
+
+    async fn query_all(&self, sql: &str, values: Vec<sea_orm::Value>) -> Result<Vec<QueryResult>, SearchIndexError> {
+        self.db
+            .query_all(statement(sql, values))
+            .await
+            .map_err(search_error)
+    }
+
+    async fn query_one_i64(&self, sql: &str, values: Vec<sea_orm::Value>) -> Result<i64, SearchIndexError> {
+        let row = self
+            .db
+            .query_one(statement(sql, values))
+            .await
+            .map_err(search_error)?
+            .ok_or_else(|| SearchIndexError::Failed("count returned no rows".to_string()))?;
+        row.try_get_by_index(0).map_err(search_error)
+    }
+}
+
+#[async_trait]
+impl SearchIndex for PgSearchIndex {
+    async fn index(&self, doc: NftDoc) -> Result<(), SearchIndexError> {
+        self.bulk(vec![doc.into()]).await.map(|_| ())
+    }
+
+    async fn bulk(&self, operations: Vec<BulkOp>) -> Result<Vec<BulkFailure>, SearchIndexError> {
+        if operations.is_empty() {
+            return Ok(vec![]);
+        }
+        // one transaction for the whole batch, so a database error fails every operation and they can all be retried
+        match self.apply(&operations).await {
+            Ok(failures) => Ok(failures),
+            Err(e) => Ok(operations
+                .iter()
+                .map(|operation| BulkFailure {
+                    id: operation.id().to_string(),
+                    status: 503,
+                    reason: e.to_string(),
+                })
+                .collect()),
+        }
+    }
+
+    async fn delete(&self, mint_address: &str) -> Result<(), SearchIndexError> {
+        self.bulk(vec![BulkOp::Delete(mint_address.to_string())]).await.map(|_| ())
+    }
+
+    async fn search(&self, query: &str, page: &PageRequest) -> Result<SearchResponse, SearchIndexError> {
+        self.run_search(&classify_query(query.trim()), &SearchFilters::default(), page, false)
+            .await
+    }
+
+    async fn search_filtered(&self, request: &SearchRequest) -> Result<SearchResponse, SearchIndexError> {
+        let text = request.query.as_deref().map(str::trim).unwrap_or_default();
+        self.run_search(
+            &QueryClass::Text(text.to_string()),
+            &request.filters,
+            &request.page,
+            request.include_facets,
+        )
+        .await
+    }
+
+    async fn suggest(&self, query: &str, size: Option<i64>) -> Result<SuggestResponse, SearchIndexError> {
+        let size = suggest_size(size);
+        let Some(prefix_query) = prefix_tsquery(query) else {
+            return Ok(SuggestResponse { results: vec![] });
+        };
+
+        let sql = format!(
+            "SELECT mint_address, doc,
+                 (CASE WHEN lower(name) = lower($2) THEN 5.0 ELSE 0.0 END
+                  + ts_rank(search_vector, to_tsquery('simple', $1)))::float8 AS score
+             FROM nft_search_docs
+             WHERE search_vector @@ to_tsquery('simple', $1)
+             ORDER BY score DESC, mint_address ASC
+             LIMIT {}",
+            size * SUGGEST_OVERFETCH
+        );
+        let rows = self
+            .query_all(&sql, vec![prefix_query.into(), query.trim().to_string().into()])
+            .await?;
+
+        let mut suggestions = Vec::with_capacity(rows.len());
+        for row in rows {
+            let mint_address: String = row.try_get("", "mint_address").map_err(search_error)?;
+            let score: f64 = row.try_get("", "score").map_err(search_error)?;
+            let doc: Value = row.try_get("", "doc").map_err(search_error)?;
+            let text = |value: &Value| value.as_str().map(str::to_string);
+            suggestions.push(Suggestion {
+                mint_address,
+                nft_name: doc["nft_name"].as_str().unwrap_or_default().to_string(),
+                score,
+                collection_name: text(&doc["collection"]["name"]),
+                collection_mint: text(&doc["collection"]["mint"]),
+                image: text(&doc["image"]),
+            });
+        }
+
+        Ok(SuggestResponse {
+            results: dedupe_by_collection(suggestions, size),
+        })
+    }
+
+    // a database without the nft_search_docs migration can't serve anything either
+    async fn ping(&self) -> Result<(), SearchIndexError> {
+        self.db
+            .execute_unprepared("SELECT 1 FROM nft_search_docs LIMIT 0")
+            .await
+            .map(|_| ())
+            .map_err(search_error)
+    }
+}
+
+// collects bind values and hands out their $n placeholders
+#[derive(Debug, Clone, Default)]
+struct Binds {
+    values: Vec<sea_orm::Value>,
+}
+
+impl Binds {
+    fn bind(&mut self, value: impl Into<sea_orm::Value>) -> String {
+        self.values.push(value.into());
+        format!("${}", self.values.len())
+    }
+}
+
+struct Hit {
+    mint_address: String,
+    doc: Value,
+    score: f64,
+    sort_value: Value,
+}
+
+fn read_hit(row: &QueryResult) -> Result<Hit, SearchIndexError> {
+    Ok(Hit {
+        mint_address: row.try_get("", "mint_address").map_err(search_error)?,
+        doc: row.try_get("", "doc").map_err(search_error)?,
+        score: row.try_get("", "score").map_err(search_error)?,
+        sort_value: row.try_get("", "sort_value").map_err(search_error)?,
+    })
+}
+
+fn statement(sql: &str, values: Vec<sea_orm::Value>) -> Statement {
+    Statement::from_sql_and_values(DbBackend::Postgres, sql, values)
+}
+
+fn search_error(e: DbErr) -> SearchIndexError {
+    match e {
+        DbErr::Conn(_) | DbErr::ConnectionAcquire(_) => SearchIndexError::Unavailable(e.to_string()),
+        _ => SearchIndexError::Failed(e.to_string()),
+    }
+}
+
+fn like_pattern(value: &str) -> String {
+    let escaped = value.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
+fn creator_containment(address: &str) -> Value {
+    json!({ "creators": [{ "address": address }] })
+}
+
+// the WHERE condition and the score expression for one query class, mirrors QueryClass::to_query
+fn class_condition(class: &QueryClass, binds: &mut Binds) -> (String, String) {
+    match class {
+        QueryClass::Text(text) if text.is_empty() => ("TRUE".to_string(), "0.0::float8".to_string()),
+        QueryClass::Text(text) => text_condition(text, binds),
+        QueryClass::Pubkey(address) => {
+            let creator = binds.bind(creator_containment(address));
+            let address = binds.bind(address.clone());
+            (
+                format!(
+                    "(mint_address = {address} OR doc->>'metadata_address' = {address} OR collection_mint = {address}
+                      OR doc @> {creator} OR doc->>'owner' = {address} OR doc->>'update_authority' = {address})"
+                ),
+                format!(
+                    "(CASE WHEN mint_address = {address} OR doc->>'metadata_address' = {address} THEN 10.0
+                           WHEN collection_mint = {address} THEN 3.0 ELSE 1.0 END)::float8"
+                ),
+            )
+        }
+        QueryClass::Symbol(symbol) => {
+            let (text_match, text_score) = text_condition(symbol, binds);
+            let symbol = binds.bind(symbol.clone());
+            (
+                format!("(lower(doc->>'symbol') = lower({symbol}) OR {text_match})"),
+                format!("((CASE WHEN lower(doc->>'symbol') = lower({symbol}) THEN 6.0 ELSE 0.0 END) + {text_score})::float8"),
+            )
+        }
+        QueryClass::Collection(collection) => {
+            let collection = binds.bind(collection.clone());
+            (
+                format!(
+                    "(collection_mint = {collection} OR lower(collection_name) = lower({collection})
+                      OR to_tsvector('simple', coalesce(collection_name, '')) @@ plainto_tsquery('simple', {collection}))"
+                ),
+                format!("(CASE WHEN lower(collection_name) = lower({collection}) THEN 2.0 ELSE 1.0 END)::float8"),
+            )
+        }
+        QueryClass::Creator(creator) => {
+            let creator = binds.bind(creator_containment(creator));
+            (format!("doc @> {creator}"), "1.0::float8".to_string())
+        }
+        QueryClass::Trait { trait_type, value } => {
+            let attribute = match value {
+                Some(value) => json!({ "attributes": [{ "trait_type": trait_type, "value": value }] }),
+                None => json!({ "attributes": [{ "trait_type": trait_type }] }),
+            };
+            let attribute = binds.bind(attribute);
+            (format!("doc @> {attribute}"), "1.0::float8".to_string())
+        }
+    }
+}
+
+// full text on name, collection name and symbol, with trigram similarity standing in for ES fuzziness
+fn text_condition(text: &str, binds: &mut Binds) -> (String, String) {
+    let query = binds.bind(text.to_string());
+    let pattern = binds.bind(like_pattern(text));
+    (
+        format!(
+            "(search_vector @@ websearch_to_tsquery('simple', {query}) OR name % {query} OR name ILIKE {pattern})"
+        ),
+        format!(
+            "((CASE WHEN lower(name) = lower({query}) THEN 3.5 ELSE 0.0 END)
+              + ts_rank(search_vector, websearch_to_tsquery('simple', {query}))
+              + similarity(name, {query}))::float8"
+        ),
+    )
+}
+
+// same filters as query::filter_clauses
+fn filter_conditions(filters: &SearchFilters, binds: &mut Binds) -> Vec<String> {
+    let mut conditions = vec![];
+
+    if let Some(collection) = &filters.collection {
+        let collection = binds.bind(collection.clone());
+        conditions.push(format!("(collection_mint = {collection} OR collection_name = {collection})"));
+    }
+
+    if let Some(creator) = &filters.creator {
+        let creator = binds.bind(creator_containment(creator));
+        conditions.push(format!("doc @> {creator}"));
+    }
+
+    if let Some(update_authority) = &filters.update_authority {
+        let update_authority = binds.bind(update_authority.clone());
+        conditions.push(format!("doc->>'update_authority' = {update_authority}"));
+    }
+
+    if let Some(token_standard) = &filters.token_standard {
+        let token_standard = binds.bind(token_standard.clone());
+        conditions.push(format!("doc->>'token_standard' = {token_standard}"));
+    }
+
+    if let Some(min) = filters.min_royalty_bps {
+        let min = binds.bind(min);
+        conditions.push(format!("royalty_bps >= {min}"));
+    }
+
+    if let Some(max) = filters.max_royalty_bps {
+        let max = binds.bind(max);
+        conditions.push(format!("royalty_bps <= {max}"));
+    }
+
+    // containment of a single element keeps trait_type and value on the same attribute
+    for attribute in &filters.attributes {
+        let attribute = binds.bind(json!({
+            "attributes": [{ "trait_type": attribute.trait_type, "value": attribute.value }]
+        }));
+        conditions.push(format!("doc @> {attribute}"));
+    }
+
+    if filters.verified_collection_only {
+        conditions.push("doc @> '{\"collection\": {\"verified\": true}}'::jsonb".to_string());
+    }
+
+    conditions
+}
+
+// missing values sort last, like `"missing": "_last"` in query::sort_clauses
+fn sort_key(sort: SearchSort, score: &str) -> (String, &'static str) {
+    match sort {
+        SearchSort::Relevance => (score.to_string(), "DESC"),
+        SearchSort::Name => ("coalesce(name, chr(1114111))".to_string(), "ASC"),
+        SearchSort::Newest => ("coalesce(created_at, '')".to_string(), "DESC"),
+        SearchSort::Royalty => ("coalesce(royalty_bps, 2147483647)".to_string(), "ASC"),
+    }
+}
+
+// keyset condition for the hits after the cursor, which holds [sort value, mint_address] of the last hit
+fn cursor_condition(
+    sort: SearchSort,
+    after: &Value,
+    direction: &str,
+    binds: &mut Binds,
+) -> Result<String, SearchIndexError> {
+    let invalid = || SearchIndexError::InvalidRequest("Invalid cursor".to_string());
+    let (Some(value), Some(mint_address)) = (after.get(0), after.get(1).and_then(Value::as_str)) else {
+        return Err(invalid());
+    };
+
+    let value = match sort {
+        SearchSort::Relevance => binds.bind(value.as_f64().ok_or_else(invalid)?),
+        SearchSort::Royalty => format!("{}::integer", binds.bind(value.as_i64().ok_or_else(invalid)?)),
+        SearchSort::Name | SearchSort::Newest => binds.bind(value.as_str().ok_or_else(invalid)?.to_string()),
+    };
+    let mint_address = binds.bind(mint_address.to_string());
+    let beyond = if direction == "DESC" { "<" } else { ">" };
+
+    Ok(format!(
+        "(sort_key {beyond} {value} OR (sort_key = {value} AND mint_address > {mint_address}))"
+    ))
+}
+
+// "bored ap" -> "bored:* & ap:*", only letters and digits make it into the tsquery
+fn prefix_tsquery(query: &str) -> Option<String> {
+    let terms: Vec<String> = query
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|term| !term.is_empty())
+        .map(|term| format!("{}:*", term.to_lowercase()))
+        .collect();
+    (!terms.is_empty()).then(|| terms.join(" & "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefix_tsquery_strips_operators() {
+        assert_eq!(prefix_tsquery("Bored Ap"), Some("bored:* & ap:*".to_string()));
+        assert_eq!(prefix_tsquery("a|b & !c"), Some("a:* & b:* & c:*".to_string()));
+        assert_eq!(prefix_tsquery("  &!: "), None);
+    }
+
+    #[test]
+    fn binds_number_placeholders_in_order() {
+        let mut binds = Binds::default();
+        let conditions = filter_conditions(
+            &SearchFilters {
+                creator: Some("creator".to_string()),
+                min_royalty_bps: Some(100),
+                ..Default::default()
+            },
+            &mut binds,
+        );
+        assert_eq!(conditions, vec!["doc @> $1".to_string(), "royalty_bps >= $2".to_string()]);
+        assert_eq!(binds.values.len(), 2);
+    }
+}
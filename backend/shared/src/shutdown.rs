@@ -0,0 +1,96 @@
+use std::time::Duration;
+
+use tokio::sync::watch;
+
+const DEFAULT_GRACE: Duration = Duration::from_secs(30);
+
+// the stop signal every long running loop checks between units of work. cloning it is cheap, every clone sees
+// the same signal
+#[derive(Clone)]
+pub struct Shutdown {
+    triggered: watch::Receiver<bool>,
+}
+
+impl Shutdown {
+    // triggered by SIGTERM or ctrl-c. a second signal, or still running SHUTDOWN_GRACE_SECS later, exits right away
+    pub fn from_env() -> Self {
+        let grace = std::env::var("SHUTDOWN_GRACE_SECS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .map_or(DEFAULT_GRACE, Duration::from_secs);
+
+        let (sender, triggered) = watch::channel(false);
+        tokio::spawn(async move {
+            wait_for_signal().await;
+            println!("Shutting down, finishing the work in flight (signal again to exit now)...");
+            let _ = sender.send(true);
+            tokio::select! {
+                _ = wait_for_signal() => println!("Second signal, exiting now"),
+                _ = tokio::time::sleep(grace) => println!("Still busy after {:?}, exiting now", grace),
+            }
+            std::process::exit(1);
+        });
+        Self { triggered }
+    }
+
+    // one that only fires when `sender` sends true, for tests and embedding
+    pub fn new() -> (watch::Sender<bool>, Self) {
+        let (sender, triggered) = watch::channel(false);
+        (sender, Self { triggered })
+    }
+
+    pub fn is_triggered(&self) -> bool {
+        *self.triggered.borrow()
+    }
+
+    // resolves once shutdown starts, right away when it already has
+    pub async fn triggered(&self) {
+        let mut triggered = self.triggered.clone();
+        if triggered.wait_for(|triggered| *triggered).await.is_err() {
+            // the sender is gone without ever firing, so it never will
+            std::future::pending::<()>().await;
+        }
+    }
+
+    // sleeps unless shutdown starts first, so idle loops notice it without waiting out their poll interval
+    pub async fn sleep(&self, duration: Duration) {
+        tokio::select! {
+            _ = tokio::time::sleep(duration) => {}
+            _ = self.triggered() => {}
+        }
+    }
+}
+
+async fn wait_for_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut terminate = signal(SignalKind::terminate()).expect("failed to listen for SIGTERM");
+        tokio::select! {
+            _ = terminate.recv() => {}
+            _ = tokio::signal::ctrl_c() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn sleep_ends_early_once_triggered() {
+        let (sender, shutdown) = Shutdown::new();
+        assert!(!shutdown.is_triggered());
+
+        let sleeper = shutdown.clone();
+        let sleeping = tokio::spawn(async move { sleeper.sleep(Duration::from_secs(60)).await });
+        sender.send(true).unwrap();
+        tokio::time::timeout(Duration::from_secs(1), sleeping).await.unwrap().unwrap();
+        assert!(shutdown.is_triggered());
+        shutdown.triggered().await;
+    }
+}
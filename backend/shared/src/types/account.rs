@@ -0,0 +1,99 @@
+use serde::{Deserialize, Serialize};
+
+// spl token accounts are always this long, mints are 82 and multisigs 355
+pub const TOKEN_ACCOUNT_LEN: usize = 165;
+pub const TOKEN_ACCOUNT_FROZEN: u8 = 2; // 0 = Uninitialized, 1 = Initialized, 2 = Frozen
+
+// messages the listener pushes to the "account_event_message" queue, next to the plain mint updates
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AccountEvent {
+    // the account now has 0 lamports or no data. the listener only sends accounts the worker tracks, a mint, a
+    // token account or a metadata account, and the worker finds out which from what it has stored
+    Closed { address : String, slot : u64 },
+    // a metadata account was written, the worker re-reads it and updates the stored asset
+    MetadataChanged { metadata_address : String, mint_address : String, slot : u64 },
+    // a token account of a tracked mint was written, transfers show up as one of these per side
+    TokenAccountChanged { account : TokenAccountData, slot : u64 }
+}
+
+// the fields of an spl token account, in the order they're laid out
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenAccountData {
+    pub token_address : String,
+    pub mint_address : String,
+    pub owner : String,
+    pub amount : u64,
+    pub delegate : Option<String>,
+    pub state : u8,
+    pub is_native : Option<u64>, // the rent exempt reserve of a wrapped sol account
+    pub delegated_amount : u64,
+    pub close_authority : Option<String>,
+}
+
+impl TokenAccountData {
+    // None for anything that isn't an initialized token account
+    pub fn parse(token_address: String, data: &[u8]) -> Option<Self> {
+        if data.len() != TOKEN_ACCOUNT_LEN {
+            return None;
+        }
+        let pubkey = |offset: usize| bs58::encode(&data[offset..offset + 32]).into_string();
+        let u64_at = |offset: usize| u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap());
+        // COption is a 4 byte tag, 1 when the value that follows is set
+        let is_some = |offset: usize| data[offset..offset + 4] == [1, 0, 0, 0];
+
+        let state = data[108];
+        if state == 0 {
+            return None;
+        }
+        Some(Self {
+            token_address,
+            mint_address: pubkey(0),
+            owner: pubkey(32),
+            amount: u64_at(64),
+            delegate: is_some(72).then(|| pubkey(76)),
+            state,
+            is_native: is_some(109).then(|| u64_at(113)),
+            delegated_amount: u64_at(121),
+            close_authority: is_some(129).then(|| pubkey(133)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn account(amount: u64, delegate: Option<[u8; 32]>, state: u8) -> Vec<u8> {
+        let mut data = vec![0u8; TOKEN_ACCOUNT_LEN];
+        data[0..32].copy_from_slice(&[1; 32]);
+        data[32..64].copy_from_slice(&[2; 32]);
+        data[64..72].copy_from_slice(&amount.to_le_bytes());
+        if let Some(delegate) = delegate {
+            data[72] = 1;
+            data[76..108].copy_from_slice(&delegate);
+            data[121..129].copy_from_slice(&1u64.to_le_bytes());
+        }
+        data[108] = state;
+        data
+    }
+
+    #[test]
+    fn parses_the_token_account_layout() {
+        let parsed = TokenAccountData::parse("ata".to_string(), &account(1, Some([3; 32]), TOKEN_ACCOUNT_FROZEN))
+            .unwrap();
+        assert_eq!(parsed.mint_address, bs58::encode([1; 32]).into_string());
+        assert_eq!(parsed.owner, bs58::encode([2; 32]).into_string());
+        assert_eq!(parsed.amount, 1);
+        assert_eq!(parsed.delegate, Some(bs58::encode([3; 32]).into_string()));
+        assert_eq!(parsed.delegated_amount, 1);
+        assert_eq!(parsed.state, TOKEN_ACCOUNT_FROZEN);
+        assert_eq!((parsed.is_native, parsed.close_authority), (None, None));
+    }
+
+    #[test]
+    fn other_accounts_are_not_token_accounts() {
+        assert!(TokenAccountData::parse("mint".to_string(), &[0; 82]).is_none());
+        assert!(TokenAccountData::parse("ata".to_string(), &account(1, None, 0)).is_none());
+    }
+}
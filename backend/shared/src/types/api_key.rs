@@ -0,0 +1,34 @@
+use sea_orm::prelude::Uuid;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+pub const DEFAULT_REQUESTS_PER_MINUTE: i32 = 600;
+pub const DEFAULT_BURST: i32 = 60;
+pub const DEFAULT_USAGE_DAYS: u32 = 7;
+pub const MAX_USAGE_DAYS: u32 = 90; // how long the daily counters are kept
+
+// query params of GET /usage
+#[derive(Debug, Clone, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct UsageQuery {
+    #[serde(default)]
+    pub days: Option<u32>, // today and the days before it
+}
+
+// requests counted for one key on one utc day. throttled ones were answered with a 429 and aren't in `requests`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct DailyUsage {
+    pub day: String, // YYYY-MM-DD
+    pub requests: u64,
+    pub throttled: u64,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct UsageResponse {
+    pub key_id: Uuid,
+    pub name: String,
+    pub key_prefix: String,
+    pub requests_per_minute: i32,
+    pub burst: i32,
+    pub days: Vec<DailyUsage>, // newest first
+}
@@ -0,0 +1,202 @@
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use serde_json::Value;
+
+use crate::types::mint::MintResponse;
+
+// query params of GET /details/{mint_address}
+#[derive(Debug, Clone, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DetailsQuery {
+    #[serde(default)]
+    pub include: Option<String>, // comma separated, see AssetInclude::parse
+}
+
+pub const MAX_DETAILS_BATCH: usize = 250;
+
+// body of POST /details
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct DetailsBatchRequest {
+    pub mint_addresses: Vec<String>, // at most MAX_DETAILS_BATCH
+    #[serde(default)]
+    pub include: Option<String>, // same as the query param of GET /details
+}
+
+// one value of the POST /details map. a missing mint gets the same body GET /details answers 404 with
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(untagged)]
+pub enum DetailsEntry {
+    Found(Box<AssetDetails>),
+    NotFound { error: &'static str, message: String },
+}
+
+impl DetailsEntry {
+    pub fn not_found(mint_address: &str) -> Self {
+        DetailsEntry::NotFound {
+            error: "not_found",
+            message: format!("No mint found for {}", mint_address),
+        }
+    }
+}
+
+// which optional blocks of AssetDetails to load. each one is a join, so clients that only need the basics can skip them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AssetInclude {
+    pub creators: bool,
+    pub royalty: bool,
+    pub ownership: bool,
+    pub content: bool, // the off-chain json: image, description, attributes
+    pub collection: bool,
+}
+
+impl AssetInclude {
+    pub const ALL: AssetInclude = AssetInclude {
+        creators: true,
+        royalty: true,
+        ownership: true,
+        content: true,
+        collection: true,
+    };
+
+    pub const NONE: AssetInclude = AssetInclude {
+        creators: false,
+        royalty: false,
+        ownership: false,
+        content: false,
+        collection: false,
+    };
+
+    // no `include` means everything, otherwise only the listed blocks. `all` and `none` work as well
+    pub fn parse(include: Option<&str>) -> Result<Self, String> {
+        let Some(include) = include else {
+            return Ok(Self::ALL);
+        };
+
+        let mut parsed = Self::NONE;
+        for part in include.split(',').map(str::trim).filter(|part| !part.is_empty()) {
+            match part.to_lowercase().as_str() {
+                "all" => parsed = Self::ALL,
+                "none" => {}
+                "creators" => parsed.creators = true,
+                "royalty" => parsed.royalty = true,
+                "ownership" => parsed.ownership = true,
+                "content" | "json" => parsed.content = true,
+                "collection" => parsed.collection = true,
+                other => return Err(format!("Unknown include option {}", other)),
+            }
+        }
+        Ok(parsed)
+    }
+
+    // the included blocks in a fixed order, the same for every ?include= that parses to this
+    pub fn canonical(&self) -> String {
+        let blocks = [
+            (self.creators, "creators"),
+            (self.royalty, "royalty"),
+            (self.ownership, "ownership"),
+            (self.content, "content"),
+            (self.collection, "collection"),
+        ];
+        let included: Vec<&str> = blocks.iter().filter(|(included, _)| *included).map(|(_, name)| *name).collect();
+        if included.is_empty() {
+            "none".to_string()
+        } else {
+            included.join(",")
+        }
+    }
+}
+
+// the full view of one asset. the flattened MintResponse keeps the old /details shape,
+// the optional blocks are left out when they weren't included or there's nothing stored for them
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct AssetDetails {
+    #[serde(flatten)]
+    pub mint: MintResponse,
+    pub status: Option<String>, // nft_metadata.status, None when the mint has no metadata
+    pub token_standard: Option<String>, // TokenStandard variant name, e.g. NonFungible
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub creators: Option<Vec<AssetCreator>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub royalty: Option<AssetRoyalty>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ownership: Option<AssetOwnership>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<AssetContent>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub collection: Option<AssetCollection>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AssetCreator {
+    pub address: String,
+    pub verified: bool,
+    pub share: i16,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct AssetRoyalty {
+    pub royalty_model: String, // "creators" unless nft_royalty says otherwise
+    pub target: Option<String>,
+    pub basis_points: i32,
+    pub percent: f64, // 0.0 to 1.0
+    pub primary_sale_happened: bool,
+    pub locked: bool,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct AssetOwnership {
+    pub owner: String,
+    pub delegate: Option<String>,
+    pub frozen: bool,
+    pub delegated: bool,
+    pub ownership_model: String,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct AssetContent {
+    pub description: Option<String>,
+    pub image: Option<String>,
+    pub animation_url: Option<String>,
+    pub external_url: Option<String>,
+    pub attributes: Option<Value>,
+    pub properties: Option<Value>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct AssetCollection {
+    pub mint: Option<String>,
+    pub name: Option<String>,
+    pub verified: bool,
+    pub family: Option<String>, // from the json metadata
+}
+
+// query params of GET /owners/{wallet}/nfts and /owners/{wallet}/tokens
+#[derive(Debug, Clone, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct HoldingsQuery {
+    #[serde(default)]
+    pub collection: Option<String>, // collection mint
+    #[serde(default)]
+    pub size: Option<i64>,
+    #[serde(default)]
+    pub cursor: Option<String>, // next_cursor of the previous page
+}
+
+// one mint held by a wallet, summed over all its token accounts for that mint
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Holding {
+    pub mint_address: String,
+    pub amount: String, // raw base units, a string so large u64 balances survive JSON
+    pub decimals: i16,
+    pub ui_amount: f64,
+    pub ui_amount_string: String, // exact, ui_amount can round
+    pub token_accounts: i64,
+    pub asset: AssetDetails, // metadata with the content and collection blocks
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct HoldingsResponse {
+    pub owner: String,
+    pub items: Vec<Holding>,
+    pub next_cursor: Option<String>,
+}
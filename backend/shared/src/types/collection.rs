@@ -0,0 +1,99 @@
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::types::asset::AssetDetails;
+
+pub const DEFAULT_PAGE_SIZE: u64 = 20;
+pub const MAX_PAGE_SIZE: u64 = 100;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CollectionSort {
+    #[default]
+    Members, // most verified members first
+    Holders, // most unique holders first
+    Name,    // a-z
+    Newest,  // most recently seen first
+}
+
+// query params of GET /collections. page is 1 based
+#[derive(Debug, Clone, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CollectionsQuery {
+    #[serde(default)]
+    pub page: Option<u64>,
+    #[serde(default)]
+    pub size: Option<u64>,
+    #[serde(default)]
+    pub sort: CollectionSort,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CollectionNftSort {
+    #[default]
+    Name,   // a-z
+    Newest, // most recently indexed first
+}
+
+// query params of GET /collections/{collection_mint}/nfts
+#[derive(Debug, Clone, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CollectionNftsQuery {
+    #[serde(default)]
+    pub page: Option<u64>,
+    #[serde(default)]
+    pub size: Option<u64>,
+    #[serde(default)]
+    pub sort: CollectionNftSort,
+    #[serde(default)]
+    pub verified: Option<bool>, // verified members unless this is false, then only the unverified ones
+    #[serde(default)]
+    pub include: Option<String>, // like /details, see AssetInclude::parse
+}
+
+// a collection as its active members describe it. name, symbol and image come from the collection nft
+// when we've indexed it, the name falls back to what the members carry
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct CollectionSummary {
+    pub collection_mint: String,
+    pub name: Option<String>,
+    pub symbol: Option<String>,
+    pub image: Option<String>,
+    pub members: i64, // verified or not
+    pub verified_members: i64,
+    pub unique_holders: i64,
+    pub creators: Vec<CollectionCreator>,
+    pub royalty: CollectionRoyalty,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct CollectionCreator {
+    pub address: String,
+    pub verified: bool, // verified on at least one member
+    pub members: i64,   // how many members list this creator
+}
+
+// the collection nft's royalty, or the most common one among the members
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct CollectionRoyalty {
+    pub basis_points: i32,
+    pub percent: f64, // 0.0 to 1.0
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct CollectionsResponse {
+    pub items: Vec<CollectionSummary>,
+    pub total: i64,
+    pub page: u64,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct CollectionNftsResponse {
+    pub collection_mint: String,
+    pub items: Vec<AssetDetails>,
+    pub total: i64,
+    pub page: u64,
+    pub size: u64,
+}
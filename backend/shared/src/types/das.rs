@@ -0,0 +1,153 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+// params of the DAS (Metaplex Digital Asset Standard) json-rpc methods, named like the spec so existing clients work as is.
+// fields we don't support (options, before/after cursors) are accepted and ignored
+
+// one json-rpc call. id can be a number, a string or null, it's echoed back untouched
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct JsonRpcRequest {
+    #[serde(default)]
+    pub jsonrpc: Option<String>,
+    #[serde(default)]
+    pub id: serde_json::Value,
+    pub method: String,
+    #[serde(default)]
+    pub params: serde_json::Value,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct JsonRpcResponse {
+    pub jsonrpc: &'static str,
+    pub id: serde_json::Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<JsonRpcError>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct JsonRpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DasSortField {
+    #[default]
+    Created,
+    Updated,
+    RecentAction, // we don't track actions, sorts like updated
+    Id,
+    None,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DasSortDirection {
+    Asc,
+    #[default]
+    Desc,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DasSort {
+    #[serde(default)]
+    pub sort_by: DasSortField,
+    #[serde(default)]
+    pub sort_direction: DasSortDirection,
+}
+
+// page is 1 based, limit tops out at 1000 like the spec says
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DasPage {
+    #[serde(default)]
+    pub page: Option<u32>,
+    #[serde(default)]
+    pub limit: Option<u32>,
+    #[serde(default)]
+    pub sort_by: Option<DasSort>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GetAssetParams {
+    pub id: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GetAssetBatchParams {
+    pub ids: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetAssetsByOwnerParams {
+    pub owner_address: String,
+    #[serde(flatten)]
+    pub page: DasPage,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetAssetsByCreatorParams {
+    pub creator_address: String,
+    #[serde(default)]
+    pub only_verified: bool,
+    #[serde(flatten)]
+    pub page: DasPage,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetAssetsByGroupParams {
+    pub group_key: String, // only "collection" exists
+    pub group_value: String,
+    #[serde(flatten)]
+    pub page: DasPage,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetAssetsByAuthorityParams {
+    pub authority_address: String,
+    #[serde(flatten)]
+    pub page: DasPage,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DasConditionType {
+    #[default]
+    All,
+    Any,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchAssetsParams {
+    #[serde(default)]
+    pub condition_type: DasConditionType,
+    #[serde(default)]
+    pub owner_address: Option<String>,
+    #[serde(default)]
+    pub creator_address: Option<String>,
+    #[serde(default)]
+    pub creator_verified: Option<bool>,
+    #[serde(default)]
+    pub authority_address: Option<String>,
+    #[serde(default)]
+    pub grouping: Option<(String, String)>, // ["collection", "<collection mint>"]
+    #[serde(default)]
+    pub interface: Option<String>,
+    #[serde(default)]
+    pub burnt: Option<bool>,
+    #[serde(default)]
+    pub frozen: Option<bool>,
+    #[serde(default)]
+    pub json_uri: Option<String>,
+    #[serde(flatten)]
+    pub page: DasPage,
+}
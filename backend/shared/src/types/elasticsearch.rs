@@ -1,19 +1,276 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use sha2::{Digest, Sha256};
 
-#[derive(Debug, Serialize)]
+use crate::entities::{nft_creator, nft_json_metadata, nft_metadata, nft_ownership};
+use crate::types::metadeta::{parse_attributes, JsonAttribute};
+
+#[derive(Debug, Serialize, ToSchema)]
 pub struct SearchResult{
     pub mint_address : String,
     pub nft_name : String,
     pub score : f64
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct SearchResponse{
-    pub results : Vec<SearchResult>
+    pub results : Vec<SearchResult>,
+    pub total : u64,
+    pub total_is_exact : bool, // false when ES stopped counting at its track_total_hits limit
+    pub next_cursor : Option<String>, // pass back as `cursor` for the next page, None on the last page
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aggregations : Option<SearchAggregations>
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchSort{
+    #[default]
+    Relevance,
+    Name,    // a-z
+    Newest,  // most recently indexed first
+    Royalty  // lowest royalty first
+}
+
+// pagination shared by GET /search/nfts/{query} (as query params) and POST /search (in the body)
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PageRequest{
+    #[serde(default)]
+    pub size : Option<i64>,
+    #[serde(default)]
+    pub cursor : Option<String>,
+    #[serde(default)]
+    pub sort : SearchSort
+}
+
+// body of POST /search. every filter is optional and they're ANDed together
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct SearchRequest{
+    #[serde(default)]
+    pub query : Option<String>,
+    #[serde(default)]
+    pub filters : SearchFilters,
+    #[serde(flatten)]
+    pub page : PageRequest,
+    #[serde(default)]
+    pub include_facets : bool
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct SearchFilters{
+    pub collection : Option<String>, // collection mint, or the exact collection name
+    pub creator : Option<String>,
+    pub update_authority : Option<String>,
+    pub token_standard : Option<String>,
+    pub min_royalty_bps : Option<i32>,
+    pub max_royalty_bps : Option<i32>,
+    #[serde(default)]
+    pub attributes : Vec<AttributeFilter>,
+    #[serde(default)]
+    pub verified_collection_only : bool
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AttributeFilter{
+    pub trait_type : String,
+    pub value : String
+}
+
+// query params of GET /suggest
+#[derive(Debug, Clone, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SuggestRequest{
+    pub q : String,
+    #[serde(default)]
+    pub size : Option<i64>
+}
+
+// same shape as SearchResponse.results so the dropdown can render either one
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Suggestion{
+    pub mint_address : String,
+    pub nft_name : String,
+    pub score : f64,
+    pub collection_name : Option<String>,
+    pub collection_mint : Option<String>,
+    pub image : Option<String>
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SuggestResponse{
+    pub results : Vec<Suggestion>
+}
+
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct SearchAggregations{
+    pub collections : Vec<CollectionFacet>,
+    pub traits : Vec<TraitFacet>
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct CollectionFacet{
+    pub mint : String,
+    pub name : Option<String>,
+    pub count : u64
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct TraitFacet{
+    pub trait_type : String,
+    pub count : u64,
+    pub values : Vec<FacetBucket>
 }
 
-#[derive(Debug, Clone,Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct FacetBucket{
+    pub value : String,
+    pub count : u64
+}
+
+// every field here has an explicit entry in elasticsearch::mapping, bump NFT_MAPPING_VERSION when one changes
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct NftDoc{
     pub mint_address : String,
-    pub nft_name : String
-}
\ No newline at end of file
+    #[serde(default)]
+    pub metadata_address : Option<String>,
+    pub nft_name : String,
+    #[serde(default)]
+    pub symbol : Option<String>,
+    #[serde(default)]
+    pub collection : Option<CollectionDoc>,
+    #[serde(default)]
+    pub creators : Vec<CreatorDoc>,
+    #[serde(default)]
+    pub update_authority : Option<String>,
+    #[serde(default)]
+    pub token_standard : Option<String>,
+    #[serde(default)]
+    pub attributes : Vec<AttributeDoc>,
+    #[serde(default)]
+    pub image : Option<String>,
+    #[serde(default)]
+    pub royalty_bps : Option<i32>,
+    #[serde(default)]
+    pub owner : Option<String>,
+    #[serde(default)]
+    pub created_at : Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub updated_at : Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub content_hash : Option<String> // see NftDoc::compute_content_hash
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CollectionDoc{
+    pub name : Option<String>,
+    pub mint : Option<String>,
+    pub verified : bool
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreatorDoc{
+    pub address : String,
+    pub verified : bool,
+    pub share : i16
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AttributeDoc{
+    pub trait_type : String,
+    pub value : String
+}
+
+impl NftDoc {
+    // the search document of an asset, rebuilt from the rows we stored
+    pub fn from_db(
+        metadata: &nft_metadata::Model,
+        json_metadata: Option<&nft_json_metadata::Model>,
+        creators: &[nft_creator::Model],
+        ownership: Option<&nft_ownership::Model>,
+    ) -> Self {
+        let collection_name = metadata
+            .collection_name
+            .clone()
+            .or_else(|| json_metadata.and_then(|json| json.collection_name.clone()));
+        let collection = if collection_name.is_some() || metadata.collection_mint.is_some() {
+            Some(CollectionDoc {
+                name: collection_name,
+                mint: metadata.collection_mint.clone(),
+                verified: metadata.collection_verified,
+            })
+        } else {
+            None
+        };
+
+        let attributes = json_metadata
+            .and_then(|json| json.attributes.clone())
+            .map(|attributes| attribute_docs(&parse_attributes(attributes)))
+            .unwrap_or_default();
+
+        let updated_at = ownership
+            .map(|owner| owner.updated_at.with_timezone(&Utc))
+            .unwrap_or_else(|| metadata.created_at.with_timezone(&Utc));
+
+        NftDoc {
+            mint_address: metadata.mint_address.clone(),
+            metadata_address: metadata.metadata_address.clone(),
+            nft_name: metadata.name.clone(),
+            symbol: metadata.symbol.clone().filter(|symbol| !symbol.is_empty()),
+            collection,
+            creators: creators
+                .iter()
+                .map(|creator| CreatorDoc {
+                    address: creator.creator_address.clone(),
+                    verified: creator.verified,
+                    share: creator.share,
+                })
+                .collect(),
+            update_authority: Some(metadata.update_authority.clone()),
+            token_standard: metadata.token_standard.clone(),
+            attributes,
+            image: json_metadata.and_then(|json| json.image.clone()),
+            royalty_bps: Some(metadata.seller_fee_basis_points.into()),
+            owner: ownership.map(|owner| owner.owner.clone()),
+            created_at: Some(metadata.created_at.with_timezone(&Utc)),
+            updated_at: Some(updated_at),
+            content_hash: None,
+        }
+        .with_content_hash()
+    }
+
+    // sha256 of everything except the timestamps, so two docs built from the same rows hash the same
+    // no matter when they were built. the consistency check compares this against what ES holds
+    pub fn compute_content_hash(&self) -> String {
+        let mut content = self.clone();
+        content.created_at = None;
+        content.updated_at = None;
+        content.content_hash = None;
+        let bytes = serde_json::to_vec(&content).unwrap_or_default();
+        hex::encode(Sha256::digest(&bytes))
+    }
+
+    fn with_content_hash(mut self) -> Self {
+        self.content_hash = Some(self.compute_content_hash());
+        self
+    }
+}
+
+// attributes without a trait_type or with a null value can't be filtered on, so they're dropped
+fn attribute_docs(attributes: &[JsonAttribute]) -> Vec<AttributeDoc> {
+    attributes
+        .iter()
+        .filter_map(|attribute| {
+            let value = match &attribute.value {
+                serde_json::Value::String(s) => s.clone(),
+                serde_json::Value::Null => return None,
+                other => other.to_string(),
+            };
+            Some(AttributeDoc {
+                trait_type: attribute.trait_type.clone()?,
+                value,
+            })
+        })
+        .collect()
+}
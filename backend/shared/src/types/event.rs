@@ -0,0 +1,53 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Mint,      // the mint account was stored or updated
+    Metadata,  // metadata, creators and json metadata were (re)written
+    Ownership, // the nft moved to another token account, or the one holding it was closed
+    Burned,
+    Closed,
+}
+
+impl ChangeKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ChangeKind::Mint => "mint",
+            ChangeKind::Metadata => "metadata",
+            ChangeKind::Ownership => "ownership",
+            ChangeKind::Burned => "burned",
+            ChangeKind::Closed => "closed",
+        }
+    }
+}
+
+// what the worker publishes after a write is committed. owner, collection and creators are what the asset
+// had when the event was sent, they're there so subscribers can be matched without a lookup
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ChangeEvent {
+    pub kind: ChangeKind,
+    pub mint_address: String,
+    pub owner: Option<String>,
+    pub collection: Option<String>, // collection mint
+    #[serde(default)]
+    pub creators: Vec<String>,
+    pub at: DateTime<Utc>,
+}
+
+// query params of GET /events and /events/ws. each one is a comma separated list of addresses,
+// an event is delivered when it matches any of them
+#[derive(Debug, Clone, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SubscribeQuery {
+    #[serde(default)]
+    pub mint: Option<String>,
+    #[serde(default)]
+    pub owner: Option<String>,
+    #[serde(default)]
+    pub collection: Option<String>,
+    #[serde(default)]
+    pub creator: Option<String>,
+}
@@ -8,14 +8,14 @@ pub struct RequestBody{
     pub params: serde_json::Value,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct HeliusAssetResponse {
     pub jsonrpc: String,
     pub result: HeliusResult,
     pub id: u64,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct HeliusResult {
     pub total: u32,
     pub limit: u32,
@@ -23,7 +23,7 @@ pub struct HeliusResult {
     pub items: Vec<HeliusAsset>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct HeliusAsset {
     pub interface: String,
     pub id: String,
@@ -39,7 +39,7 @@ pub struct HeliusAsset {
     pub burnt: bool,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct AssetContent {
     #[serde(rename = "$schema")]
     pub schema: Option<String>,
@@ -49,14 +49,14 @@ pub struct AssetContent {
     pub links: LinksInfo,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct FileInfo {
     pub uri: String,
     pub cdn_uri: Option<String>,
     pub mime: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct MetadataContent {
     pub attributes: Option<Vec<AttributeInfo>>,
     pub description: Option<String>,
@@ -65,26 +65,26 @@ pub struct MetadataContent {
     pub token_standard: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct AttributeInfo {
     pub value: String,
     pub trait_type: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct LinksInfo {
     pub external_url: Option<String>,
     pub image: Option<String>,
     pub animation_url: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct AuthorityInfo {
     pub address: String,
     pub scopes: Vec<String>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct CompressionInfo {
     pub eligible: bool,
     pub compressed: bool,
@@ -96,7 +96,7 @@ pub struct CompressionInfo {
     pub leaf_id: Option<u64>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct GroupingInfo {
     pub group_key: String,
     pub group_value: String,
@@ -104,7 +104,7 @@ pub struct GroupingInfo {
     pub collection_metadata: Option<CollectionMetadata>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct CollectionMetadata {
     pub name: String,
     pub symbol: String,
@@ -113,7 +113,7 @@ pub struct CollectionMetadata {
     pub external_url: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct RoyaltyInfo {
     pub royalty_model: String,
     pub target: Option<String>,
@@ -123,14 +123,14 @@ pub struct RoyaltyInfo {
     pub locked: bool,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct CreatorInfo {
     pub address: String,
     pub share: u8,
     pub verified: bool,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct OwnershipInfo {
     pub frozen: bool,
     pub delegated: bool,
@@ -139,7 +139,7 @@ pub struct OwnershipInfo {
     pub owner: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct SupplyInfo {
     pub print_max_supply: u32,
     pub print_current_supply: u32,
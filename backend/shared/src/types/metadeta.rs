@@ -1,4 +1,5 @@
 use mpl_token_metadata::types::{TokenStandard};
+use serde::{Deserialize, Deserializer, Serialize};
 
 #[derive(Debug, Clone)]
 pub struct Metadata{
@@ -11,6 +12,85 @@ pub struct Metadata{
     pub update_authority : String,
     pub token_standard : Option<TokenStandard>,
     pub collection : Option<String>,
+    pub collection_mint : Option<String>,
+    pub collection_verified : bool,
+    pub creators : Vec<MetadataCreator>,
     pub primary_sale_happened : bool,
     pub is_mutable : bool,
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetadataCreator{
+    pub address : String,
+    pub verified : bool,
+    pub share : u8
+}
+
+// the off-chain json the metadata_uri points to. only the fields we index are typed, the rest stays in properties
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct JsonMetadata{
+    pub description : Option<String>,
+    pub image : Option<String>,
+    pub animation_url : Option<String>,
+    pub external_url : Option<String>,
+    #[serde(default, deserialize_with = "lenient_attributes")]
+    pub attributes : Vec<JsonAttribute>,
+    pub properties : Option<serde_json::Value>,
+    pub collection : Option<JsonCollection>
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JsonAttribute{
+    pub trait_type : Option<String>,
+    #[serde(default)]
+    pub value : serde_json::Value // marketplaces put strings, numbers and booleans in here, or leave it out
+}
+
+// the attributes array as far as it makes sense. anything but an array is no attributes and entries that aren't
+// attribute objects are skipped, so a sloppy attribute never costs the rest of the json metadata
+pub fn parse_attributes(attributes: serde_json::Value) -> Vec<JsonAttribute> {
+    match attributes {
+        serde_json::Value::Array(items) => items
+            .into_iter()
+            .filter_map(|item| serde_json::from_value(item).ok())
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn lenient_attributes<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<JsonAttribute>, D::Error> {
+    Ok(parse_attributes(serde_json::Value::deserialize(deserializer)?))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JsonCollection{
+    pub name : Option<String>,
+    pub family : Option<String>
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn sloppy_attributes_keep_the_rest_of_the_json() {
+        let json: JsonMetadata = serde_json::from_value(json!({
+            "image": "https://arweave.net/img.png",
+            "description": "a lad",
+            "attributes": [{ "trait_type": "Background" }, { "trait_type": "Hat", "value": 3 }, "junk", 7],
+        }))
+        .unwrap();
+        assert_eq!(json.image.as_deref(), Some("https://arweave.net/img.png"));
+        assert_eq!(json.attributes.len(), 2);
+        assert_eq!(json.attributes[0].value, serde_json::Value::Null);
+        assert_eq!(json.attributes[1].value, json!(3));
+
+        for attributes in [json!({ "Hat": "Cap" }), json!("none"), json!(null)] {
+            let json: JsonMetadata =
+                serde_json::from_value(json!({ "description": "a lad", "attributes": attributes })).unwrap();
+            assert_eq!(json.description.as_deref(), Some("a lad"));
+            assert!(json.attributes.is_empty());
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct MintData{
@@ -12,7 +13,7 @@ pub struct MintData{
     pub freeze_authority : Option<String>
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
 pub struct MintResponse{
     pub mint_address : String,
     pub owner : String,
@@ -24,7 +25,7 @@ pub struct MintResponse{
     pub metadata : PartialMetadata
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
 pub struct PartialMetadata{
     pub name : Option<String>,
     pub symbol : Option<String>,
@@ -1,4 +1,11 @@
+pub mod account;
+pub mod asset;
+pub mod collection;
 pub mod mint;
 pub mod metadeta;
 pub mod elasticsearch;
-pub mod helius;
\ No newline at end of file
+pub mod event;
+pub mod das;
+pub mod helius;
+pub mod webhook;
+pub mod api_key;
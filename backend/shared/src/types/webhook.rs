@@ -0,0 +1,153 @@
+use chrono::{DateTime, FixedOffset};
+use sea_orm::prelude::Uuid;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::entities::{webhook, webhook_delivery};
+use crate::redis::events::ChangeFilter;
+use crate::types::event::{ChangeEvent, ChangeKind};
+
+pub const DEFAULT_PAGE_SIZE: u64 = 50;
+pub const MAX_PAGE_SIZE: u64 = 200;
+
+// which change events a webhook gets: any of the addresses has to match, like an /events subscription,
+// and the kind has to be one of `kinds` (all kinds when it's empty)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct WebhookFilter {
+    #[serde(default)]
+    pub mints: Vec<String>,
+    #[serde(default)]
+    pub owners: Vec<String>,
+    #[serde(default)]
+    pub collections: Vec<String>,
+    #[serde(default)]
+    pub creators: Vec<String>,
+    #[serde(default)]
+    pub kinds: Vec<ChangeKind>,
+}
+
+impl WebhookFilter {
+    pub fn change_filter(&self) -> Result<ChangeFilter, String> {
+        ChangeFilter::new(&self.mints, &self.owners, &self.collections, &self.creators)
+    }
+
+    pub fn matches(&self, event: &ChangeEvent) -> bool {
+        (self.kinds.is_empty() || self.kinds.contains(&event.kind))
+            && self.change_filter().is_ok_and(|filter| filter.matches(event))
+    }
+}
+
+// body of POST /webhooks
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct CreateWebhookRequest {
+    pub url: String,
+    pub filter: WebhookFilter,
+}
+
+// body of PATCH /webhooks/{id}. setting active back to true also clears the failure count
+#[derive(Debug, Clone, Default, Deserialize, ToSchema)]
+pub struct UpdateWebhookRequest {
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub filter: Option<WebhookFilter>,
+    #[serde(default)]
+    pub active: Option<bool>,
+}
+
+pub fn validate_url(url: &str) -> Result<(), String> {
+    if url.starts_with("https://") || url.starts_with("http://") {
+        Ok(())
+    } else {
+        Err(format!("{} is not an http(s) url", url))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct WebhookResponse {
+    pub id: Uuid,
+    pub url: String,
+    pub filter: WebhookFilter,
+    pub active: bool,
+    pub consecutive_failures: i32,
+    pub disabled_reason: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>, // only in the response to POST /webhooks
+    pub created_at: DateTime<FixedOffset>,
+    pub updated_at: DateTime<FixedOffset>,
+}
+
+impl From<webhook::Model> for WebhookResponse {
+    fn from(webhook: webhook::Model) -> Self {
+        Self {
+            id: webhook.id,
+            url: webhook.url,
+            filter: serde_json::from_value(webhook.filter).unwrap_or_default(),
+            active: webhook.active,
+            consecutive_failures: webhook.consecutive_failures,
+            disabled_reason: webhook.disabled_reason,
+            secret: None,
+            created_at: webhook.created_at,
+            updated_at: webhook.updated_at,
+        }
+    }
+}
+
+// query params of GET /webhooks/{id}/deliveries, newest first
+#[derive(Debug, Clone, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DeliveriesQuery {
+    #[serde(default)]
+    pub page: Option<u64>,
+    #[serde(default)]
+    pub size: Option<u64>,
+    #[serde(default)]
+    pub status: Option<String>, // pending, delivered or failed
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct WebhookDeliveryResponse {
+    pub id: i64,
+    pub event_kind: String,
+    pub mint_address: String,
+    pub status: String,
+    pub attempts: i32,
+    pub response_status: Option<i32>,
+    pub last_error: Option<String>,
+    pub next_attempt_at: DateTime<FixedOffset>,
+    pub delivered_at: Option<DateTime<FixedOffset>>,
+    pub created_at: DateTime<FixedOffset>,
+}
+
+impl From<webhook_delivery::Model> for WebhookDeliveryResponse {
+    fn from(delivery: webhook_delivery::Model) -> Self {
+        Self {
+            id: delivery.id,
+            event_kind: delivery.event_kind,
+            mint_address: delivery.mint_address,
+            status: delivery.status,
+            attempts: delivery.attempts,
+            response_status: delivery.response_status,
+            last_error: delivery.last_error,
+            next_attempt_at: delivery.next_attempt_at,
+            delivered_at: delivery.delivered_at,
+            created_at: delivery.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct DeliveriesResponse {
+    pub items: Vec<WebhookDeliveryResponse>,
+    pub total: i64,
+    pub page: u64,
+    pub size: u64,
+}
+
+// what gets POSTed to the webhook url. the same delivery_id is sent again on every retry
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct WebhookPayload {
+    pub delivery_id: i64,
+    pub webhook_id: Uuid,
+    pub event: ChangeEvent,
+}
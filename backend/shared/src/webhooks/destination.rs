@@ -0,0 +1,149 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::Url;
+
+// webhook urls come from api clients, so they mustn't reach anything on our side of the network: loopback, private
+// ranges, link-local (cloud metadata lives at 169.254.169.254) and the like
+pub fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(mapped) => is_public_v4(mapped),
+            None => is_public_v6(ip),
+        },
+    }
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    !(ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || ip.is_documentation()
+        || a == 0
+        || (a == 100 && (64..128).contains(&b))) // carrier-grade nat, 100.64.0.0/10
+}
+
+fn is_public_v6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    !(ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        || (first & 0xfe00) == 0xfc00 // unique local, fc00::/7
+        || (first & 0xffc0) == 0xfe80) // link-local, fe80::/10
+}
+
+fn parse(url: &str) -> Result<Url, String> {
+    let parsed = Url::parse(url).map_err(|e| format!("{} is not a valid url: {}", url, e))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(format!("{} is not an http(s) url", url));
+    }
+    if parsed.host_str().is_none_or(str::is_empty) {
+        return Err(format!("{} has no host", url));
+    }
+    Ok(parsed)
+}
+
+// ipv6 hosts come bracketed, [::1]
+fn literal_ip(url: &Url) -> Option<IpAddr> {
+    url.host_str()?.trim_start_matches('[').trim_end_matches(']').parse().ok()
+}
+
+// what the api checks on create and update: the host resolves, and only to public addresses
+pub async fn check_destination(url: &str) -> Result<(), String> {
+    let parsed = parse(url)?;
+    let port = parsed.port_or_known_default().unwrap_or(443);
+    let addresses: Vec<SocketAddr> = match literal_ip(&parsed) {
+        Some(ip) => vec![SocketAddr::new(ip, port)],
+        None => {
+            let host = parsed.host_str().unwrap_or_default();
+            tokio::net::lookup_host((host, port))
+                .await
+                .map_err(|e| format!("{} doesn't resolve: {}", host, e))?
+                .collect()
+        }
+    };
+
+    match addresses.iter().find(|address| !is_public(address.ip())) {
+        Some(address) => Err(format!("{} points at {}, which isn't a public address", url, address.ip())),
+        None if addresses.is_empty() => Err(format!("{} doesn't resolve to any address", url)),
+        None => Ok(()),
+    }
+}
+
+// the dispatcher checks again at send time. names go through PublicResolver, this covers urls with an ip in them
+pub(crate) fn check_literal(url: &str) -> Result<(), String> {
+    match literal_ip(&parse(url)?) {
+        Some(ip) if !is_public(ip) => Err(format!("{} isn't a public address", ip)),
+        _ => Ok(()),
+    }
+}
+
+// resolves for the dispatcher's http client and drops every non-public address, so a name that resolved to a public
+// address when the webhook was saved can't be pointed somewhere internal later. the connection uses what this returns
+pub(crate) struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_string();
+        Box::pin(async move {
+            let addresses: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), 0))
+                .await?
+                .filter(|address| is_public(address.ip()))
+                .collect();
+            if addresses.is_empty() {
+                return Err(format!("{} doesn't resolve to a public address", host).into());
+            }
+            Ok(Box::new(addresses.into_iter()) as Addrs)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn internal_addresses_are_not_public() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "0.0.0.0",
+            "100.64.0.1",
+            "::1",
+            "::",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+        ] {
+            assert!(!is_public(ip.parse().unwrap()), "{} counted as public", ip);
+        }
+        for ip in ["93.184.216.34", "100.128.0.1", "2606:4700::1111"] {
+            assert!(is_public(ip.parse().unwrap()), "{} counted as internal", ip);
+        }
+    }
+
+    #[tokio::test]
+    async fn destinations_must_be_public() {
+        assert!(check_destination("https://93.184.216.34/hook").await.is_ok());
+        assert!(check_destination("ftp://93.184.216.34/hook").await.is_err());
+        assert!(check_destination("http://127.0.0.1:8080/hook").await.is_err());
+        assert!(check_destination("http://[::1]/hook").await.is_err());
+        assert!(check_destination("http://169.254.169.254/latest/meta-data").await.is_err());
+        assert!(check_destination("http://localhost/hook").await.is_err());
+    }
+
+    #[test]
+    fn literal_ips_are_checked_at_send_time() {
+        assert!(check_literal("http://10.0.0.1/hook").is_err());
+        assert!(check_literal("https://93.184.216.34/hook").is_ok());
+        assert!(check_literal("https://example.com/hook").is_ok());
+    }
+}
@@ -0,0 +1,361 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use futures::future::join_all;
+use sea_orm::prelude::DateTimeWithTimeZone;
+use sea_orm::sea_query::{Expr, LockBehavior, LockType, Query};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, Condition, ConnectionTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter,
+    QueryOrder, QuerySelect, Set, TransactionTrait,
+};
+use tokio::task::JoinHandle;
+
+use crate::entities::{webhook, webhook_delivery};
+use crate::shutdown::Shutdown;
+use crate::types::event::ChangeEvent;
+use crate::types::webhook::WebhookPayload;
+use crate::webhooks::destination::{check_literal, PublicResolver};
+use crate::webhooks::{record_webhook_failure, record_webhook_success, sign};
+
+pub const ID_HEADER: &str = "X-Webhook-Id"; // the delivery id, the same on every retry
+pub const TIMESTAMP_HEADER: &str = "X-Webhook-Timestamp"; // unix seconds, part of what's signed
+pub const SIGNATURE_HEADER: &str = "X-Webhook-Signature"; // sha256=<hex hmac of "{timestamp}.{body}">
+
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+const LOG_RETENTION_DAYS: i64 = 30;
+const BASE_BACKOFF_SECS: i64 = 10;
+const MAX_BACKOFF_SECS: i64 = 60 * 60;
+const LEASE_MARGIN: Duration = Duration::from_secs(60);
+
+// a delivery waits while an older one of its webhook is pending a retry or out with another dispatcher, so a
+// receiver gets its deliveries in the order they were queued
+const NOT_BEHIND_OLDER: &str = "NOT EXISTS (
+    SELECT 1 FROM webhook_deliveries older
+    WHERE older.webhook_id = webhook_deliveries.webhook_id AND older.id < webhook_deliveries.id
+        AND older.status = 'pending' AND (older.next_attempt_at > now() OR older.locked_until > now())
+)";
+
+#[derive(Debug, Clone)]
+pub struct WebhookDispatcherConfig {
+    pub batch_size: u64,
+    pub poll_interval: Duration, // sleep between polls while nothing is due
+    pub max_attempts: i32,       // a delivery is marked failed after this many
+    pub disable_after: i32,      // failed attempts in a row before the webhook is switched off
+    pub timeout: Duration,
+    pub allow_private_addresses: bool, // for tests and local setups, lets deliveries go to loopback and private ips
+}
+
+impl Default for WebhookDispatcherConfig {
+    fn default() -> Self {
+        Self {
+            batch_size: 100,
+            poll_interval: Duration::from_secs(1),
+            max_attempts: 8,
+            disable_after: 20,
+            timeout: Duration::from_secs(10),
+            allow_private_addresses: false,
+        }
+    }
+}
+
+impl WebhookDispatcherConfig {
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let env_number = |name: &str| std::env::var(name).ok().and_then(|v| v.parse::<u64>().ok());
+
+        Self {
+            batch_size: env_number("WEBHOOK_BATCH_SIZE")
+                .map(|v| v.max(1))
+                .unwrap_or(defaults.batch_size),
+            poll_interval: env_number("WEBHOOK_POLL_MS")
+                .map(Duration::from_millis)
+                .unwrap_or(defaults.poll_interval),
+            max_attempts: env_number("WEBHOOK_MAX_ATTEMPTS")
+                .map(|v| v as i32)
+                .unwrap_or(defaults.max_attempts),
+            disable_after: env_number("WEBHOOK_DISABLE_AFTER")
+                .map(|v| v as i32)
+                .unwrap_or(defaults.disable_after),
+            timeout: env_number("WEBHOOK_TIMEOUT_MS")
+                .map(Duration::from_millis)
+                .unwrap_or(defaults.timeout),
+            allow_private_addresses: std::env::var("WEBHOOK_ALLOW_PRIVATE_ADDRESSES").is_ok_and(|v| v == "true"),
+        }
+    }
+}
+
+// what one attempt got back. status is None when the request never got a response
+struct Attempt {
+    status: Option<u16>,
+    error: Option<String>,
+}
+
+// sends due deliveries to their webhooks. rows are claimed with a lease like the outbox relay, so more than one
+// dispatcher can run against the same database
+pub struct WebhookDispatcher {
+    db: DatabaseConnection,
+    http_client: reqwest::Client,
+    config: WebhookDispatcherConfig,
+}
+
+impl WebhookDispatcher {
+    // redirects aren't followed, a receiver could otherwise bounce a delivery to an internal address
+    pub fn new(db: DatabaseConnection, config: WebhookDispatcherConfig) -> Self {
+        let mut builder = reqwest::Client::builder()
+            .timeout(config.timeout)
+            .redirect(reqwest::redirect::Policy::none());
+        if !config.allow_private_addresses {
+            builder = builder.dns_resolver(Arc::new(PublicResolver));
+        }
+        Self {
+            db,
+            http_client: builder.build().expect("the webhook http client config is static"),
+            config,
+        }
+    }
+
+    pub fn spawn(self, shutdown: Shutdown) -> JoinHandle<()> {
+        tokio::spawn(self.run(shutdown))
+    }
+
+    pub async fn run(self, shutdown: Shutdown) {
+        println!(
+            "Starting webhook dispatcher ({} attempts max, disabling after {} failures in a row)...",
+            self.config.max_attempts, self.config.disable_after
+        );
+        let mut last_purge = Instant::now();
+        while !shutdown.is_triggered() {
+            match self.dispatch_due().await {
+                Ok(0) => shutdown.sleep(self.config.poll_interval).await,
+                Ok(sent) => println!("Sent {} webhook deliveries", sent),
+                Err(e) => {
+                    println!("Webhook dispatcher error {}", e);
+                    shutdown.sleep(Duration::from_secs(2)).await;
+                }
+            }
+
+            if last_purge.elapsed() >= PURGE_INTERVAL {
+                if let Err(e) = self.purge_log().await {
+                    println!("Failed to purge the webhook delivery log {}", e);
+                }
+                last_purge = Instant::now();
+            }
+        }
+        // deliveries not sent yet stay due and go out after the restart
+        println!("Webhook dispatcher stopped");
+    }
+
+    // claims one batch of due deliveries of active webhooks and sends it. each webhook's deliveries go out one at a
+    // time in id order, different webhooks in parallel. returns the deliveries attempted
+    pub async fn dispatch_due(&self) -> Result<usize, DbErr> {
+        let rows = self.claim_batch().await?;
+        if rows.is_empty() {
+            return Ok(0);
+        }
+
+        let webhook_ids: Vec<_> = rows.iter().map(|row| row.webhook_id).collect();
+        let webhooks: HashMap<_, webhook::Model> = webhook::Entity::find()
+            .filter(webhook::Column::Id.is_in(webhook_ids))
+            .all(&self.db)
+            .await?
+            .into_iter()
+            .map(|webhook| (webhook.id, webhook))
+            .collect();
+
+        // rows come in id order, so every group is too
+        let mut by_webhook: BTreeMap<_, Vec<webhook_delivery::Model>> = BTreeMap::new();
+        for row in rows {
+            by_webhook.entry(row.webhook_id).or_default().push(row);
+        }
+
+        let results = join_all(
+            by_webhook
+                .iter()
+                .map(|(webhook_id, deliveries)| self.send_in_order(deliveries, webhooks.get(webhook_id))),
+        )
+        .await;
+        let mut attempted = 0;
+        for result in results {
+            attempted += result?;
+        }
+        Ok(attempted)
+    }
+
+    // leases the due rows past the time it takes to send them all and commits before any http call, so no row locks
+    // are held meanwhile. a dispatcher that dies mid batch leaves its rows to be claimed again once the lease runs out
+    async fn claim_batch(&self) -> Result<Vec<webhook_delivery::Model>, DbErr> {
+        let now = Utc::now().fixed_offset();
+        let txn = self.db.begin().await?;
+        let rows = webhook_delivery::Entity::find()
+            .filter(webhook_delivery::Column::Status.eq(webhook_delivery::STATUS_PENDING))
+            .filter(webhook_delivery::Column::NextAttemptAt.lte(now))
+            .filter(
+                Condition::any()
+                    .add(webhook_delivery::Column::LockedUntil.is_null())
+                    .add(webhook_delivery::Column::LockedUntil.lte(now)),
+            )
+            .filter(
+                webhook_delivery::Column::WebhookId.in_subquery(
+                    Query::select()
+                        .column(webhook::Column::Id)
+                        .from(webhook::Entity)
+                        .and_where(webhook::Column::Active.eq(true))
+                        .to_owned(),
+                ),
+            )
+            .filter(Expr::cust(NOT_BEHIND_OLDER))
+            .order_by_asc(webhook_delivery::Column::Id)
+            .limit(self.config.batch_size)
+            .lock_with_behavior(LockType::Update, LockBehavior::SkipLocked)
+            .all(&txn)
+            .await?;
+        if !rows.is_empty() {
+            // the whole batch can belong to one webhook, whose deliveries are sent one after the other
+            let lease = self.config.timeout * self.config.batch_size as u32 + LEASE_MARGIN;
+            let locked_until = Utc::now() + chrono::Duration::from_std(lease).unwrap_or(chrono::Duration::hours(1));
+            webhook_delivery::Entity::update_many()
+                .col_expr(
+                    webhook_delivery::Column::LockedUntil,
+                    Expr::value(locked_until.fixed_offset()),
+                )
+                .filter(webhook_delivery::Column::Id.is_in(rows.iter().map(|row| row.id)))
+                .exec(&txn)
+                .await?;
+        }
+        txn.commit().await?;
+        Ok(rows)
+    }
+
+    // stops at the first failure. the deliveries after it are released and wait behind its retry
+    async fn send_in_order(
+        &self,
+        deliveries: &[webhook_delivery::Model],
+        webhook: Option<&webhook::Model>,
+    ) -> Result<usize, DbErr> {
+        for (i, row) in deliveries.iter().enumerate() {
+            let attempt = self.send(row, webhook).await;
+            let failed = attempt.error.is_some();
+            let txn = self.db.begin().await?;
+            self.record_attempt(&txn, row, attempt).await?;
+            txn.commit().await?;
+
+            if failed {
+                let rest = &deliveries[i + 1..];
+                if !rest.is_empty() {
+                    webhook_delivery::Entity::update_many()
+                        .col_expr(
+                            webhook_delivery::Column::LockedUntil,
+                            Expr::value(Option::<DateTimeWithTimeZone>::None),
+                        )
+                        .filter(webhook_delivery::Column::Id.is_in(rest.iter().map(|row| row.id)))
+                        .exec(&self.db)
+                        .await?;
+                }
+                return Ok(i + 1);
+            }
+        }
+        Ok(deliveries.len())
+    }
+
+    async fn send(&self, row: &webhook_delivery::Model, webhook: Option<&webhook::Model>) -> Attempt {
+        let failed = |error: String| Attempt {
+            status: None,
+            error: Some(error),
+        };
+        let Some(webhook) = webhook else {
+            return failed("The webhook is gone".to_string());
+        };
+        if !self.config.allow_private_addresses {
+            if let Err(e) = check_literal(&webhook.url) {
+                return failed(e);
+            }
+        }
+        let event: ChangeEvent = match serde_json::from_value(row.payload.clone()) {
+            Ok(event) => event,
+            Err(e) => return failed(format!("Unreadable payload {}", e)),
+        };
+
+        let body = serde_json::to_string(&WebhookPayload {
+            delivery_id: row.id,
+            webhook_id: webhook.id,
+            event,
+        })
+        .expect("webhook payloads are plain structs and always serialize");
+        let timestamp = Utc::now().timestamp();
+
+        let response = self
+            .http_client
+            .post(&webhook.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(ID_HEADER, row.id.to_string())
+            .header(TIMESTAMP_HEADER, timestamp.to_string())
+            .header(SIGNATURE_HEADER, sign(&webhook.secret, timestamp, &body))
+            .body(body)
+            .send()
+            .await;
+
+        match response {
+            Ok(response) if response.status().is_success() => Attempt {
+                status: Some(response.status().as_u16()),
+                error: None,
+            },
+            Ok(response) => Attempt {
+                status: Some(response.status().as_u16()),
+                error: Some(format!("Responded with {}", response.status())),
+            },
+            Err(e) => failed(e.to_string()),
+        }
+    }
+
+    async fn record_attempt<C: ConnectionTrait>(
+        &self,
+        conn: &C,
+        row: &webhook_delivery::Model,
+        attempt: Attempt,
+    ) -> Result<(), DbErr> {
+        let attempts = row.attempts + 1;
+        let mut active: webhook_delivery::ActiveModel = row.clone().into();
+        active.attempts = Set(attempts);
+        active.response_status = Set(attempt.status.map(i32::from));
+        active.locked_until = Set(None);
+
+        match attempt.error {
+            None => {
+                active.status = Set(webhook_delivery::STATUS_DELIVERED.to_string());
+                active.delivered_at = Set(Some(Utc::now().fixed_offset()));
+                active.last_error = Set(None);
+                active.update(conn).await?;
+                record_webhook_success(conn, row.webhook_id).await
+            }
+            Some(error) => {
+                let status = if attempts >= self.config.max_attempts {
+                    webhook_delivery::STATUS_FAILED
+                } else {
+                    webhook_delivery::STATUS_PENDING
+                };
+                let backoff = BASE_BACKOFF_SECS
+                    .saturating_mul(2i64.saturating_pow(attempts as u32 - 1))
+                    .min(MAX_BACKOFF_SECS);
+
+                active.status = Set(status.to_string());
+                active.last_error = Set(Some(error));
+                active.next_attempt_at = Set((Utc::now() + chrono::Duration::seconds(backoff)).fixed_offset());
+                active.update(conn).await?;
+                record_webhook_failure(conn, row.webhook_id, self.config.disable_after).await
+            }
+        }
+    }
+
+    async fn purge_log(&self) -> Result<(), DbErr> {
+        let cutoff = (Utc::now() - chrono::Duration::days(LOG_RETENTION_DAYS)).fixed_offset();
+        let purged = webhook_delivery::Entity::delete_many()
+            .filter(webhook_delivery::Column::Status.ne(webhook_delivery::STATUS_PENDING))
+            .filter(webhook_delivery::Column::CreatedAt.lt(cutoff))
+            .exec(&self.db)
+            .await?;
+        println!("Purged {} old webhook deliveries", purged.rows_affected);
+        Ok(())
+    }
+}
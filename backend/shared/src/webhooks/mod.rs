@@ -0,0 +1,246 @@
+pub mod destination;
+pub mod dispatcher;
+
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sea_orm::prelude::Uuid;
+use sea_orm::sea_query::{Expr, Query};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DbErr, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder,
+    QuerySelect, Set,
+};
+use sha2::Sha256;
+
+use crate::entities::{api_key, webhook, webhook_delivery};
+use crate::types::event::ChangeEvent;
+use crate::types::webhook::{
+    CreateWebhookRequest, DeliveriesQuery, DeliveriesResponse, UpdateWebhookRequest, WebhookFilter, WebhookResponse,
+    DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE,
+};
+
+// receivers recompute this over `{timestamp}.{body}` with their secret and compare
+pub fn sign(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("hmac takes keys of any length");
+    mac.update(format!("{}.{}", timestamp, body).as_bytes());
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+fn generate_secret() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    format!("whsec_{}", hex::encode(bytes))
+}
+
+// the secret is generated here and returned this once. the webhook belongs to `api_key_id`, the lookups below only
+// find a key's own webhooks
+pub async fn create_webhook<C: ConnectionTrait>(
+    conn: &C,
+    api_key_id: Uuid,
+    request: &CreateWebhookRequest,
+) -> Result<WebhookResponse, DbErr> {
+    let filter = serde_json::to_value(&request.filter).map_err(|e| DbErr::Json(e.to_string()))?;
+    let secret = generate_secret();
+    let created = webhook::ActiveModel {
+        url: Set(request.url.clone()),
+        secret: Set(secret.clone()),
+        filter: Set(filter),
+        api_key_id: Set(Some(api_key_id)),
+        ..Default::default()
+    }
+    .insert(conn)
+    .await?;
+
+    let mut response = WebhookResponse::from(created);
+    response.secret = Some(secret);
+    Ok(response)
+}
+
+pub async fn list_webhooks<C: ConnectionTrait>(conn: &C, api_key_id: Uuid) -> Result<Vec<WebhookResponse>, DbErr> {
+    let webhooks = webhook::Entity::find()
+        .filter(webhook::Column::ApiKeyId.eq(api_key_id))
+        .order_by_asc(webhook::Column::CreatedAt)
+        .all(conn)
+        .await?;
+    Ok(webhooks.into_iter().map(WebhookResponse::from).collect())
+}
+
+pub async fn find_webhook<C: ConnectionTrait>(
+    conn: &C,
+    api_key_id: Uuid,
+    id: Uuid,
+) -> Result<Option<WebhookResponse>, DbErr> {
+    Ok(owned_webhook(conn, api_key_id, id).await?.map(WebhookResponse::from))
+}
+
+async fn owned_webhook<C: ConnectionTrait>(
+    conn: &C,
+    api_key_id: Uuid,
+    id: Uuid,
+) -> Result<Option<webhook::Model>, DbErr> {
+    webhook::Entity::find_by_id(id)
+        .filter(webhook::Column::ApiKeyId.eq(api_key_id))
+        .one(conn)
+        .await
+}
+
+pub async fn update_webhook<C: ConnectionTrait>(
+    conn: &C,
+    api_key_id: Uuid,
+    id: Uuid,
+    request: &UpdateWebhookRequest,
+) -> Result<Option<WebhookResponse>, DbErr> {
+    let Some(stored) = owned_webhook(conn, api_key_id, id).await? else {
+        return Ok(None);
+    };
+
+    let mut active: webhook::ActiveModel = stored.into();
+    if let Some(url) = &request.url {
+        active.url = Set(url.clone());
+    }
+    if let Some(filter) = &request.filter {
+        active.filter = Set(serde_json::to_value(filter).map_err(|e| DbErr::Json(e.to_string()))?);
+    }
+    if let Some(enabled) = request.active {
+        active.active = Set(enabled);
+        if enabled {
+            active.consecutive_failures = Set(0);
+            active.disabled_reason = Set(None);
+        }
+    }
+    active.updated_at = Set(chrono::Utc::now().fixed_offset());
+    Ok(Some(active.update(conn).await?.into()))
+}
+
+// its deliveries go with it
+pub async fn delete_webhook<C: ConnectionTrait>(conn: &C, api_key_id: Uuid, id: Uuid) -> Result<bool, DbErr> {
+    let result = webhook::Entity::delete_by_id(id)
+        .filter(webhook::Column::ApiKeyId.eq(api_key_id))
+        .exec(conn)
+        .await?;
+    Ok(result.rows_affected > 0)
+}
+
+pub async fn list_deliveries<C: ConnectionTrait>(
+    conn: &C,
+    webhook_id: Uuid,
+    query: &DeliveriesQuery,
+) -> Result<DeliveriesResponse, DbErr> {
+    let page = query.page.unwrap_or(1).max(1);
+    let size = query.size.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+
+    let mut select = webhook_delivery::Entity::find().filter(webhook_delivery::Column::WebhookId.eq(webhook_id));
+    if let Some(status) = &query.status {
+        select = select.filter(webhook_delivery::Column::Status.eq(status.as_str()));
+    }
+    let total = select.clone().count(conn).await? as i64;
+    let items = select
+        .order_by_desc(webhook_delivery::Column::Id)
+        .offset((page - 1) * size)
+        .limit(size)
+        .all(conn)
+        .await?;
+
+    Ok(DeliveriesResponse {
+        items: items.into_iter().map(Into::into).collect(),
+        total,
+        page,
+        size,
+    })
+}
+
+// a pending delivery for every active webhook the event matches, the dispatcher sends them. returns how many.
+// revoking a key switches its webhooks off, the key check covers a revoke that got no further than the key
+pub async fn enqueue_deliveries<C: ConnectionTrait>(conn: &C, event: &ChangeEvent) -> Result<usize, DbErr> {
+    let payload = serde_json::to_value(event).map_err(|e| DbErr::Json(e.to_string()))?;
+    let deliveries: Vec<webhook_delivery::ActiveModel> = webhook::Entity::find()
+        .filter(webhook::Column::Active.eq(true))
+        .filter(
+            webhook::Column::ApiKeyId.in_subquery(
+                Query::select()
+                    .column(api_key::Column::Id)
+                    .from(api_key::Entity)
+                    .and_where(api_key::Column::Active.eq(true))
+                    .to_owned(),
+            ),
+        )
+        .all(conn)
+        .await?
+        .into_iter()
+        .filter(|webhook| {
+            serde_json::from_value::<WebhookFilter>(webhook.filter.clone()).is_ok_and(|filter| filter.matches(event))
+        })
+        .map(|webhook| webhook_delivery::ActiveModel {
+            webhook_id: Set(webhook.id),
+            event_kind: Set(event.kind.as_str().to_string()),
+            mint_address: Set(event.mint_address.clone()),
+            payload: Set(payload.clone()),
+            ..Default::default()
+        })
+        .collect();
+
+    let count = deliveries.len();
+    if count > 0 {
+        webhook_delivery::Entity::insert_many(deliveries).exec(conn).await?;
+    }
+    Ok(count)
+}
+
+// a failed attempt, and the webhook is switched off once there have been `disable_after` of them in a row
+pub(crate) async fn record_webhook_failure<C: ConnectionTrait>(
+    conn: &C,
+    id: Uuid,
+    disable_after: i32,
+) -> Result<(), DbErr> {
+    webhook::Entity::update_many()
+        .col_expr(
+            webhook::Column::ConsecutiveFailures,
+            Expr::col(webhook::Column::ConsecutiveFailures).add(1),
+        )
+        .filter(webhook::Column::Id.eq(id))
+        .exec(conn)
+        .await?;
+
+    webhook::Entity::update_many()
+        .col_expr(webhook::Column::Active, Expr::value(false))
+        .col_expr(
+            webhook::Column::DisabledReason,
+            Expr::value(format!("Disabled after {} failed deliveries in a row", disable_after)),
+        )
+        .col_expr(webhook::Column::UpdatedAt, Expr::current_timestamp().into())
+        .filter(webhook::Column::Id.eq(id))
+        .filter(webhook::Column::Active.eq(true))
+        .filter(webhook::Column::ConsecutiveFailures.gte(disable_after))
+        .exec(conn)
+        .await?;
+    Ok(())
+}
+
+pub(crate) async fn record_webhook_success<C: ConnectionTrait>(conn: &C, id: Uuid) -> Result<(), DbErr> {
+    webhook::Entity::update_many()
+        .col_expr(webhook::Column::ConsecutiveFailures, Expr::value(0))
+        .filter(webhook::Column::Id.eq(id))
+        .filter(webhook::Column::ConsecutiveFailures.ne(0))
+        .exec(conn)
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signatures_cover_timestamp_and_body() {
+        let signature = sign("whsec_test", 1_700_000_000, r#"{"a":1}"#);
+        assert!(signature.starts_with("sha256="));
+        assert_eq!(signature.len(), "sha256=".len() + 64);
+        assert_eq!(signature, sign("whsec_test", 1_700_000_000, r#"{"a":1}"#));
+        assert_ne!(signature, sign("whsec_test", 1_700_000_001, r#"{"a":1}"#));
+        assert_ne!(signature, sign("whsec_other", 1_700_000_000, r#"{"a":1}"#));
+    }
+
+    #[test]
+    fn secrets_are_random() {
+        assert_ne!(generate_secret(), generate_secret());
+    }
+}
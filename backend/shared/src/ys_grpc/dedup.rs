@@ -0,0 +1,67 @@
+use std::collections::{HashSet, VecDeque};
+
+// (pubkey, slot, write_version) uniquely identifies one account write, so the same write coming from two redundant streams has the same key
+pub type UpdateKey = (Vec<u8>, u64, u64);
+
+// bounded set of recently seen account writes. once full the oldest keys are evicted first,
+// which is fine since redundant streams deliver the same write within a few slots of each other
+pub struct SeenUpdates {
+    seen: HashSet<UpdateKey>,
+    order: VecDeque<UpdateKey>,
+    capacity: usize,
+}
+
+impl SeenUpdates {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            seen: HashSet::with_capacity(capacity),
+            order: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    // returns true the first time a key is seen and false for every duplicate after that
+    pub fn insert(&mut self, key: UpdateKey) -> bool {
+        if self.seen.contains(&key) {
+            return false;
+        }
+        if self.order.len() >= self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.seen.remove(&oldest);
+            }
+        }
+        self.order.push_back(key.clone());
+        self.seen.insert(key);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(pubkey: u8, slot: u64, write_version: u64) -> UpdateKey {
+        (vec![pubkey; 32], slot, write_version)
+    }
+
+    #[test]
+    fn drops_the_same_write_from_another_stream() {
+        let mut seen = SeenUpdates::new(10);
+        assert!(seen.insert(key(1, 100, 7)));
+        assert!(!seen.insert(key(1, 100, 7)));
+        // a later write of the same account, or the same slot of another account, is new
+        assert!(seen.insert(key(1, 100, 8)));
+        assert!(seen.insert(key(2, 100, 7)));
+    }
+
+    #[test]
+    fn forgets_the_oldest_writes_once_full() {
+        let mut seen = SeenUpdates::new(2);
+        assert!(seen.insert(key(1, 1, 1)));
+        assert!(seen.insert(key(2, 1, 1)));
+        assert!(seen.insert(key(3, 1, 1)));
+        assert!(seen.insert(key(1, 1, 1)), "the oldest key was evicted");
+        assert!(!seen.insert(key(3, 1, 1)));
+        assert_eq!((seen.seen.len(), seen.order.len()), (2, 2));
+    }
+}
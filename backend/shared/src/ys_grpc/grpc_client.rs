@@ -1,39 +1,145 @@
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use futures::SinkExt;
+use serde::Serialize;
+use tokio::sync::mpsc;
 use tokio_stream::StreamExt;
 use yellowstone_grpc_client::{ClientTlsConfig, GeyserGrpcClient};
-use yellowstone_grpc_proto::geyser::{SubscribeRequest, SubscribeRequestFilterAccounts, SubscribeRequestFilterSlots};
+use yellowstone_grpc_proto::geyser::{SubscribeRequest, SubscribeRequestFilterAccounts, SubscribeRequestFilterSlots, SubscribeUpdateAccount};
+use mpl_token_metadata::programs::MPL_TOKEN_METADATA_ID;
 use crate::redis::queue_manager::RedisQueue;
+use crate::redis::tracked::TrackedAccounts;
+use crate::redis::worker::{ACCOUNT_EVENT_QUEUE, MINT_DATA_QUEUE};
+use crate::shutdown::Shutdown;
+use crate::types::account::{AccountEvent, TokenAccountData};
+use crate::rpc::stats::{parse_endpoint_list, EndpointStats, EndpointStatsSnapshot};
+use crate::ys_grpc::dedup::SeenUpdates;
 
 const SPL_TOKEN_PROGRAM: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+const METADATA_V1_KEY: u8 = 4; // first byte of every token metadata account, see mpl_token_metadata::types::Key
+const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+const STREAM_IDLE_TIMEOUT: Duration = Duration::from_secs(30); // slot updates arrive every ~400ms, so a silent stream is a dead stream
+const DEDUP_CAPACITY: usize = 100_000;
+const STATS_LOG_INTERVAL: Duration = Duration::from_secs(60);
+// how often a failover stream on a backup endpoint checks whether a higher priority one is reachable again
+const PRIMARY_PROBE_INTERVAL: Duration = Duration::from_secs(60);
+const PRIMARY_PROBE_TIMEOUT: Duration = Duration::from_secs(5);
 
-pub struct GRPCclient {
-    pub rpc_endpoint: String,
+type StreamError = Box<dyn std::error::Error + Send + Sync>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubscriptionMode {
+    Failover,  // one stream at a time, moving back to the first endpoint in the list as soon as it is reachable
+    Redundant, // one stream per endpoint, duplicates dropped before they reach the queue
+}
+
+impl SubscriptionMode {
+    pub fn from_env_value(value: &str) -> Self {
+        match value.trim().to_lowercase().as_str() {
+            "redundant" => SubscriptionMode::Redundant,
+            _ => SubscriptionMode::Failover,
+        }
+    }
+}
+
+pub struct GrpcEndpoint {
+    pub url: String,
     pub token: String,
+    messages: AtomicU64,
+    last_message_ms: AtomicU64, // unix millis of the last update of any kind, 0 before the first
+    stats: EndpointStats,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GrpcEndpointStats {
+    #[serde(flatten)]
+    pub endpoint: EndpointStatsSnapshot,
+    pub messages: u64,
+}
+
+pub struct GRPCclient {
+    endpoints: Vec<Arc<GrpcEndpoint>>,
+    mode: SubscriptionMode,
+    duplicates: Arc<AtomicU64>,
 }
 
 impl GRPCclient {
-    pub fn new(endpoint: String, token: String) -> Self {
-        println!("Initializing grpc endpoint along with token access...");
+    pub fn new(endpoints: Vec<(String, String)>, mode: SubscriptionMode) -> Self {
+        println!(
+            "Initializing {} grpc endpoints along with token access ({:?})...",
+            endpoints.len(),
+            mode
+        );
         Self {
-            rpc_endpoint: endpoint,
-            token: token,
+            endpoints: endpoints
+                .into_iter()
+                .map(|(url, token)| {
+                    Arc::new(GrpcEndpoint {
+                        url,
+                        token,
+                        messages: AtomicU64::new(0),
+                        last_message_ms: AtomicU64::new(0),
+                        stats: EndpointStats::default(),
+                    })
+                })
+                .collect(),
+            mode,
+            duplicates: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    // RPC_ENDPOINTS is a comma separated list in priority order. RPC_TOKENS lines up with it one to one,
+    // otherwise the single RPC_TOKEN is used for every endpoint. RPC_ENDPOINT still works for one endpoint.
+    // streams are picked by position, a "|n" weight is only meaningful for the HELIUS_URLS pool and is ignored here
+    pub fn from_env() -> Self {
+        let raw_endpoints = std::env::var("RPC_ENDPOINTS")
+            .or_else(|_| std::env::var("RPC_ENDPOINT"))
+            .expect("failed to retrieve the rpc from env");
+        let endpoints = parse_endpoint_list(&raw_endpoints);
+        if endpoints.is_empty() {
+            panic!("no grpc endpoints configured in RPC_ENDPOINTS / RPC_ENDPOINT");
         }
+
+        let tokens: Vec<String> = match std::env::var("RPC_TOKENS") {
+            Ok(raw_tokens) => raw_tokens.split(',').map(|t| t.trim().to_string()).collect(),
+            Err(_) => vec![std::env::var("RPC_TOKEN").expect("failed to fetch token from env")],
+        };
+
+        let endpoints = endpoints
+            .into_iter()
+            .enumerate()
+            .map(|(i, (url, weight))| {
+                if weight != 1 {
+                    println!("Ignoring the weight of grpc endpoint {}, grpc streams go by list order", url);
+                }
+                let token = tokens
+                    .get(i)
+                    .or(tokens.last())
+                    .cloned()
+                    .unwrap_or_default();
+                (url, token)
+            })
+            .collect();
+
+        let mode = SubscriptionMode::from_env_value(&std::env::var("GRPC_MODE").unwrap_or_default());
+        Self::new(endpoints, mode)
     }
 
     async fn client_connection(
-        &self,
+        endpoint: &GrpcEndpoint,
     ) -> Result<
         GeyserGrpcClient<impl yellowstone_grpc_client::Interceptor>,
-        Box<dyn std::error::Error>,
+        StreamError,
     > {
-        println!("Connecting to PublicNode...");
+        println!("Connecting to {}...", endpoint.url);
 
         let client = GeyserGrpcClient::build_from_shared(
-            self.rpc_endpoint.to_string(),
+            endpoint.url.to_string(),
         )?
         .x_token(Some(
-            self.token.to_string(),
+            endpoint.token.to_string(),
         ))?
         .tls_config(ClientTlsConfig::new().with_native_roots())?
         .connect()
@@ -59,6 +165,13 @@ impl GRPCclient {
         filters : vec![], // here we specify in depth account details to filter out precisely
         nonempty_txn_signature : None
     });
+    // metadata edits and burns never touch the mint account, so the metadata accounts are watched too
+    accounts.insert("metadata_accounts".to_string(), SubscribeRequestFilterAccounts{
+        account : vec![],
+        owner : vec![MPL_TOKEN_METADATA_ID.to_string()],
+        filters : vec![],
+        nonempty_txn_signature : None
+    });
 
     println!("Created subscription for the server.");
 
@@ -66,65 +179,317 @@ impl GRPCclient {
 
     SubscribeRequest {
         slots,
-        accounts: accounts,
+        accounts,
         transactions: HashMap::new(),
         transactions_status: HashMap::new(),
         blocks: HashMap::new(),
         blocks_meta: HashMap::new(),
         entry: HashMap::new(),
-        commitment: commitment,
+        commitment,
         accounts_data_slice: vec![],
         ping: None,
         from_slot: None,
     }
 }
 
+// runs until shutdown. the streams stop first, then whatever they already buffered is pushed to the queue
 pub async fn listen_for_updates(
-    &self
+    &self,
+    redis_url: &str,
+    shutdown: Shutdown,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    
-    let mut client = self.client_connection().await?;
     let subscription = self.create_subscription();
-    println!("Starting to listen subscription for messages...");
+    let queue = RedisQueue::new(redis_url).await?;
+    let tracked = TrackedAccounts::new(redis_url)?;
+
+    // every stream pushes its account updates into one channel, so the dedup and the queue writes happen in one place
+    let (sender, mut receiver) = mpsc::channel::<SubscribeUpdateAccount>(10_000);
 
-    let (mut sink, mut stream) = client.subscribe().await?;
+    match self.mode {
+        SubscriptionMode::Failover => {
+            tokio::spawn(Self::run_failover(self.endpoints.clone(), subscription, sender, shutdown.clone()));
+        }
+        SubscriptionMode::Redundant => {
+            for endpoint in &self.endpoints {
+                tokio::spawn(Self::run_redundant(
+                    endpoint.clone(),
+                    subscription.clone(),
+                    sender.clone(),
+                    shutdown.clone(),
+                ));
+            }
+            drop(sender); // the channel closes once every stream is done
+        }
+    }
 
-    // we get 2 objects from client subscription. sink used to sending the request object  and stream for receiving the requested data from grpc
-    sink.send(subscription).await?;
+    let endpoints = self.endpoints.clone();
+    let duplicates = self.duplicates.clone();
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(STATS_LOG_INTERVAL).await;
+            for endpoint in &endpoints {
+                let stats = endpoint.stats.snapshot(&endpoint.url, 1);
+                println!(
+                    "GRPC endpoint {} healthy={} messages={} errors={} connect_latency={:.1}ms",
+                    stats.url,
+                    stats.healthy,
+                    endpoint.messages.load(Ordering::Relaxed),
+                    stats.errors,
+                    stats.avg_latency_ms
+                );
+            }
+            println!("GRPC duplicate updates dropped : {}", duplicates.load(Ordering::Relaxed));
+        }
+    });
 
     println!("Listening for Solana Account updates...");
 
-    let queue = RedisQueue::new().await?;
-
-    // looping continously to get message from server.
-    while let Some(update) = stream.next().await {
-        match update {
-            Ok(msg) => { // basically when u recieve stream of data from validator u get in form of subcribeupdate, in which update_oneof contains the actual data
-                if let Some(update_type) = &msg.update_oneof {
-                    match update_type {
-                        yellowstone_grpc_proto::geyser::subscribe_update::UpdateOneof::Account(account) => {
-                            if let Some(acc) = &account.account {
-            
-                                
-                                if acc.data.len() == 82 {
-                                    let _ = queue.enqueue_message(&acc.data, &acc.owner, "mint_data_message", &acc.pubkey).await.map_err(|e| {
-                                        println!("Error pushing message to the queue due to {}",e);
-                                    });
-                                }
-
-                            }
-                        }
-                        _ => {println!("got other updates...ignore.")}
+    let mut seen_updates = SeenUpdates::new(DEDUP_CAPACITY);
+
+    // looping continously to get message from the streams, until they all stopped and the buffer is empty
+    while let Some(account) = receiver.recv().await {
+        if let Some(acc) = &account.account {
+            if !seen_updates.insert((acc.pubkey.clone(), account.slot, acc.write_version)) {
+                self.duplicates.fetch_add(1, Ordering::Relaxed);
+                continue;
+            }
+
+            if acc.lamports == 0 || acc.data.is_empty() {
+                // a closed account, whatever it used to be. most are token accounts we never stored
+                let address = bs58::encode(&acc.pubkey).into_string();
+                if !is_tracked(&tracked, &address).await {
+                    continue;
+                }
+                let event = AccountEvent::Closed {
+                    address,
+                    slot: account.slot,
+                };
+                let _ = queue.enqueue_account_event(&event, ACCOUNT_EVENT_QUEUE).await.map_err(|e| {
+                    println!("Error pushing closed account to the queue due to {}", e);
+                });
+            } else if acc.owner.as_slice() == MPL_TOKEN_METADATA_ID.as_ref() {
+                // key (1 byte) + update authority (32 bytes) + mint (32 bytes)
+                if acc.data.len() >= 65 && acc.data[0] == METADATA_V1_KEY {
+                    // new assets come in through their mint account, only the ones already stored are refreshed
+                    let metadata_address = bs58::encode(&acc.pubkey).into_string();
+                    if !is_tracked(&tracked, &metadata_address).await {
+                        continue;
                     }
+                    let event = AccountEvent::MetadataChanged {
+                        metadata_address,
+                        mint_address: bs58::encode(&acc.data[33..65]).into_string(),
+                        slot: account.slot,
+                    };
+                    let _ = queue.enqueue_account_event(&event, ACCOUNT_EVENT_QUEUE).await.map_err(|e| {
+                        println!("Error pushing metadata change to the queue due to {}", e);
+                    });
+                }
+            } else if let Some(token_account) =
+                TokenAccountData::parse(bs58::encode(&acc.pubkey).into_string(), &acc.data)
+            {
+                // token accounts are most of what the program writes, only the ones of mints we index matter
+                if !is_tracked_mint(&tracked, &token_account.mint_address).await {
+                    continue;
                 }
+                let event = AccountEvent::TokenAccountChanged {
+                    account: token_account,
+                    slot: account.slot,
+                };
+                let _ = queue.enqueue_account_event(&event, ACCOUNT_EVENT_QUEUE).await.map_err(|e| {
+                    println!("Error pushing token account change to the queue due to {}", e);
+                });
+            } else if acc.data.len() == 82 {
+                let _ = queue.enqueue_message(&acc.data, &acc.owner, MINT_DATA_QUEUE, &acc.pubkey).await.map_err(|e| {
+                    println!("Error pushing message to the queue due to {}",e);
+                });
             }
+        }
+    }
+
+    println!("GRPC streams stopped and the buffered updates are queued");
+    Ok(())
+}
+
+    // always walks the list from the top. a stream on a backup endpoint is dropped once an endpoint before it
+    // answers again, so the listener moves back to the primary without waiting for the backup to fail
+    async fn run_failover(
+        endpoints: Vec<Arc<GrpcEndpoint>>,
+        subscription: SubscribeRequest,
+        sender: mpsc::Sender<SubscribeUpdateAccount>,
+        shutdown: Shutdown,
+    ) {
+        'from_the_top: loop {
+            for (position, endpoint) in endpoints.iter().enumerate() {
+                let streaming = Self::stream_updates(endpoint, subscription.clone(), &sender, &shutdown);
+                let result = tokio::select! {
+                    result = streaming => result,
+                    preferred = Self::wait_for_any(&endpoints[..position]) => {
+                        println!("{} is reachable again, leaving {}", preferred.url, endpoint.url);
+                        continue 'from_the_top;
+                    }
+                };
+                if let Err(e) = result {
+                    println!("Stream error from {}: {}", endpoint.url, e);
+                }
+                if sender.is_closed() || shutdown.is_triggered() {
+                    return;
+                }
+            }
+            println!("All grpc endpoints failed, retrying in {:?}...", RECONNECT_DELAY);
+            shutdown.sleep(RECONNECT_DELAY).await;
+        }
+    }
+
+    // resolves with the first of `endpoints` that answers a ping, checking every PRIMARY_PROBE_INTERVAL.
+    // never resolves for an empty list, the primary has nothing to wait for
+    async fn wait_for_any(endpoints: &[Arc<GrpcEndpoint>]) -> &Arc<GrpcEndpoint> {
+        if endpoints.is_empty() {
+            return std::future::pending().await;
+        }
+        loop {
+            tokio::time::sleep(PRIMARY_PROBE_INTERVAL).await;
+            for endpoint in endpoints {
+                let probe = async {
+                    let mut client = Self::client_connection(endpoint).await?;
+                    client.ping(1).await?;
+                    Ok::<_, StreamError>(())
+                };
+                if let Ok(Ok(())) = tokio::time::timeout(PRIMARY_PROBE_TIMEOUT, probe).await {
+                    return endpoint;
+                }
+            }
+        }
+    }
+
+    async fn run_redundant(
+        endpoint: Arc<GrpcEndpoint>,
+        subscription: SubscribeRequest,
+        sender: mpsc::Sender<SubscribeUpdateAccount>,
+        shutdown: Shutdown,
+    ) {
+        loop {
+            if let Err(e) = Self::stream_updates(&endpoint, subscription.clone(), &sender, &shutdown).await {
+                println!("Stream error from {}: {}", endpoint.url, e);
+            }
+            if sender.is_closed() || shutdown.is_triggered() {
+                return;
+            }
+            shutdown.sleep(RECONNECT_DELAY).await;
+        }
+    }
+
+    // streams until the connection drops, goes quiet or shutdown starts
+    async fn stream_updates(
+        endpoint: &GrpcEndpoint,
+        subscription: SubscribeRequest,
+        sender: &mpsc::Sender<SubscribeUpdateAccount>,
+        shutdown: &Shutdown,
+    ) -> Result<(), StreamError> {
+        let started = Instant::now();
+        let connection = async {
+            let mut client = Self::client_connection(endpoint).await?;
+            // we get 2 objects from client subscription. sink used to sending the request object  and stream for receiving the requested data from grpc
+            let (mut sink, stream) = client.subscribe().await?;
+            sink.send(subscription).await?;
+            Ok::<_, StreamError>((sink, stream))
+        };
+        let connected = tokio::select! {
+            connected = connection => connected,
+            _ = shutdown.triggered() => return Ok(()),
+        };
+        let (_sink, mut stream) = match connected {
+            Ok(connected) => connected,
             Err(e) => {
-                println!("Stream error: {}", e);
-                break;
+                endpoint.stats.record_failure(started.elapsed());
+                return Err(e);
+            }
+        };
+        endpoint.stats.record_success(started.elapsed());
+        println!("Starting to listen subscription for messages from {}...", endpoint.url);
+
+        loop {
+            let next = tokio::select! {
+                next = tokio::time::timeout(STREAM_IDLE_TIMEOUT, stream.next()) => next,
+                _ = shutdown.triggered() => return Ok(()),
+            };
+            let update = match next {
+                Ok(Some(update)) => update,
+                Ok(None) => return Ok(()),
+                Err(_) => {
+                    endpoint.stats.record_failure(STREAM_IDLE_TIMEOUT);
+                    endpoint.stats.set_healthy(false);
+                    return Err(format!("no message received for {:?}", STREAM_IDLE_TIMEOUT).into());
+                }
+            };
+
+            match update {
+                Ok(msg) => { // basically when u recieve stream of data from validator u get in form of subcribeupdate, in which update_oneof contains the actual data
+                    endpoint.messages.fetch_add(1, Ordering::Relaxed);
+                    endpoint.last_message_ms.store(unix_millis(), Ordering::Relaxed);
+                    if let Some(yellowstone_grpc_proto::geyser::subscribe_update::UpdateOneof::Account(account)) = msg.update_oneof {
+                        if sender.send(account).await.is_err() {
+                            return Ok(()); // listener is gone, nothing left to do
+                        }
+                    }
+                }
+                Err(e) => {
+                    endpoint.stats.record_failure(started.elapsed());
+                    return Err(e.into());
+                }
             }
         }
     }
 
-    Ok(())
+    pub fn stats(&self) -> Vec<GrpcEndpointStats> {
+        self.endpoints
+            .iter()
+            .map(|endpoint| GrpcEndpointStats {
+                endpoint: endpoint.stats.snapshot(&endpoint.url, 1),
+                messages: endpoint.messages.load(Ordering::Relaxed),
+            })
+            .collect()
+    }
+
+    pub fn duplicates_dropped(&self) -> u64 {
+        self.duplicates.load(Ordering::Relaxed)
+    }
+
+    // for /readyz: some stream delivered an update, slots included, within the idle timeout
+    pub fn stream_liveness(&self) -> Result<(), String> {
+        let last = self
+            .endpoints
+            .iter()
+            .map(|endpoint| endpoint.last_message_ms.load(Ordering::Relaxed))
+            .max()
+            .unwrap_or(0);
+        if last == 0 {
+            return Err("no grpc update received yet".to_string());
+        }
+        let quiet = Duration::from_millis(unix_millis().saturating_sub(last));
+        if quiet > STREAM_IDLE_TIMEOUT {
+            return Err(format!("no grpc update for {:?}", quiet));
+        }
+        Ok(())
+    }
 }
+
+// an account event is only dropped when redis says it's not ours, when redis can't tell it's passed on
+async fn is_tracked(tracked: &TrackedAccounts, address: &str) -> bool {
+    tracked.is_tracked(address).await.unwrap_or_else(|e| {
+        println!("Couldn't check {} against the tracked accounts, passing it on : {}", address, e);
+        true
+    })
+}
+
+// token account writes are far too many to pass on when redis can't tell, so they're dropped until it can. the
+// worker catches up on the account with its next write
+async fn is_tracked_mint(tracked: &TrackedAccounts, mint_address: &str) -> bool {
+    tracked.contains(mint_address).await.unwrap_or_else(|e| {
+        println!("Couldn't check mint {} against the tracked accounts, dropping the update : {}", mint_address, e);
+        false
+    })
+}
+
+fn unix_millis() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |since| since.as_millis() as u64)
 }
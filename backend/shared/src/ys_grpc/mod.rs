@@ -1 +1,2 @@
+pub mod dedup;
 pub mod grpc_client;
\ No newline at end of file
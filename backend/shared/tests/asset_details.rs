@@ -0,0 +1,259 @@
+// runs the details query and the DAS methods against a migrated database. skipped unless TEST_DATABASE_URL is set,
+// run `cargo run -p migration -- up` against it first
+use shared::{
+    assets::{
+        collections::{find_collection, find_collection_nfts, list_collections, refresh_collection_stats},
+        das::{get_asset, get_asset_batch, get_assets_by_creator, get_assets_by_owner, search_assets},
+        details::{find_asset_details, find_details_batch},
+        owners::{find_holdings, HoldingKind},
+    },
+    types::{
+        asset::{AssetInclude, DetailsEntry, HoldingsQuery},
+        collection::{CollectionNftsQuery, CollectionSort, CollectionsQuery},
+        das::{GetAssetsByCreatorParams, GetAssetsByOwnerParams, SearchAssetsParams},
+    },
+    Database, DatabaseConnection,
+};
+use sea_orm::ConnectionTrait;
+
+const WITH_METADATA: &str = "details-test-mint-1";
+const BARE_MINT: &str = "details-test-mint-2";
+const BARE_NFT: &str = "details-test-mint-3";
+const METADATA_PDA: &str = "details-test-pda-1";
+const HOLDER: &str = "details-test-holder";
+
+async fn fixtures(db: &DatabaseConnection) {
+    db.execute_unprepared(&format!(
+        "DELETE FROM nft_creator WHERE metadata_address = '{METADATA_PDA}';
+         DELETE FROM nft_json_metadata WHERE mint_address = '{WITH_METADATA}';
+         DELETE FROM nft_ownership WHERE mint_address = '{WITH_METADATA}';
+         DELETE FROM nft_metadata WHERE mint_address = '{WITH_METADATA}';
+         DELETE FROM token_accounts WHERE owner = '{HOLDER}';
+         DELETE FROM mint WHERE mint_address IN ('{WITH_METADATA}', '{BARE_MINT}', '{BARE_NFT}');
+
+         INSERT INTO mint (mint_address, decimal, supply, is_initialized)
+         VALUES ('{WITH_METADATA}', 0, 1, true), ('{BARE_MINT}', 2, 1000, true), ('{BARE_NFT}', 0, 1, true);
+         INSERT INTO nft_metadata (mint_address, metadata_address, name, symbol, metadata_uri, seller_fee_basis_points,
+             update_authority, primary_sale_happened, is_mutable, collection_name, collection_mint, collection_verified,
+             token_standard)
+         VALUES ('{WITH_METADATA}', '{METADATA_PDA}', 'Degen Ape #1', 'DAPE', 'https://example.com/1.json', 420,
+             'authority', true, true, 'Degen Apes', 'collection-mint', true, 'NonFungible');
+         INSERT INTO nft_creator (metadata_address, creator_address, verified, share)
+         VALUES ('{METADATA_PDA}', 'creator-1', true, 70), ('{METADATA_PDA}', 'creator-2', false, 30);
+         INSERT INTO nft_json_metadata (mint_address, image, attributes, collection_family)
+         VALUES ('{WITH_METADATA}', 'https://example.com/1.png', '[{{\"trait_type\": \"Fur\", \"value\": \"Gold\"}}]', 'Degen');
+         INSERT INTO nft_ownership (mint_address, owner, frozen, delegated, ownership_model, updated_at)
+         VALUES ('{WITH_METADATA}', 'owner-1', false, false, 'single', now());
+         INSERT INTO token_accounts (token_address, mint_address, owner, amount, delegated_amount, state, is_native,
+             rent_exempt_reserve)
+         VALUES ('details-test-ata-1', '{WITH_METADATA}', '{HOLDER}', 1, '0', 1, false, '0'),
+             ('details-test-ata-2', '{BARE_MINT}', '{HOLDER}', 150, '0', 1, false, '0'),
+             ('details-test-ata-3', '{BARE_MINT}', '{HOLDER}', 50, '0', 1, false, '0'),
+             ('details-test-ata-4', '{BARE_NFT}', '{HOLDER}', 1, '0', 1, false, '0'),
+             ('details-test-ata-5', 'details-test-unindexed', '{HOLDER}', 7, '0', 1, false, '0');"
+    ))
+    .await
+    .expect("failed to insert fixtures");
+}
+
+async fn details_join(db: &DatabaseConnection) {
+    let details = find_asset_details(db, WITH_METADATA, &AssetInclude::ALL)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(details.mint.metadata.name.as_deref(), Some("Degen Ape #1"));
+    assert_eq!(details.status.as_deref(), Some("active"));
+    let creators = details.creators.unwrap();
+    assert_eq!(creators.len(), 2);
+    assert!(creators.iter().any(|creator| creator.address == "creator-1" && creator.share == 70));
+    let royalty = details.royalty.unwrap();
+    assert_eq!(royalty.basis_points, 420);
+    assert_eq!(royalty.royalty_model, "creators");
+    assert_eq!(details.ownership.unwrap().owner, "owner-1");
+    assert_eq!(details.content.unwrap().image.as_deref(), Some("https://example.com/1.png"));
+    let collection = details.collection.unwrap();
+    assert_eq!(collection.mint.as_deref(), Some("collection-mint"));
+    assert_eq!(collection.family.as_deref(), Some("Degen"));
+
+    // only what was asked for
+    let details = find_asset_details(db, WITH_METADATA, &AssetInclude::parse(Some("ownership")).unwrap())
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(details.ownership.is_some());
+    assert!(details.creators.is_none() && details.royalty.is_none() && details.content.is_none());
+
+    // a token mint without metadata has none of the blocks
+    let details = find_asset_details(db, BARE_MINT, &AssetInclude::ALL)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(details.mint.decimal, 2);
+    assert!(details.status.is_none() && details.creators.is_none() && details.ownership.is_none());
+
+    assert!(find_asset_details(db, "details-test-missing", &AssetInclude::ALL)
+        .await
+        .unwrap()
+        .is_none());
+
+    // the batch answers for every mint it was asked about
+    let mints = [WITH_METADATA, BARE_MINT, "details-test-missing"].map(String::from);
+    let batch = find_details_batch(db, &mints, &AssetInclude::NONE).await.unwrap();
+    assert_eq!(batch.len(), 3);
+    assert!(matches!(&batch[WITH_METADATA], DetailsEntry::Found(details) if details.status.as_deref() == Some("active")));
+    assert!(matches!(&batch[BARE_MINT], DetailsEntry::Found(_)));
+    assert!(matches!(&batch["details-test-missing"], DetailsEntry::NotFound { error: "not_found", .. }));
+}
+
+async fn das_methods(db: &DatabaseConnection) {
+    let asset = get_asset(db, WITH_METADATA).await.unwrap().unwrap();
+    assert_eq!(asset.interface, "V1_NFT");
+    assert_eq!(asset.content.json_uri, "https://example.com/1.json");
+    assert_eq!(asset.content.links.image.as_deref(), Some("https://example.com/1.png"));
+    assert_eq!(asset.content.metadata.attributes.as_ref().unwrap()[0].value, "Gold");
+    assert_eq!(asset.authorities[0].address, "authority");
+    assert_eq!(asset.grouping.as_ref().unwrap()[0].group_value, "collection-mint");
+    assert_eq!(asset.royalty.basis_points, 420);
+    assert_eq!(asset.ownership.owner, "owner-1");
+    assert!(!asset.burnt);
+
+    let batch = get_asset_batch(db, &["details-test-missing".to_string(), WITH_METADATA.to_string()])
+        .await
+        .unwrap();
+    assert!(batch[0].is_none() && batch[1].is_some());
+
+    let params: GetAssetsByOwnerParams =
+        serde_json::from_value(serde_json::json!({ "ownerAddress": "owner-1", "page": 1, "limit": 10 })).unwrap();
+    let owned = get_assets_by_owner(db, &params).await.unwrap();
+    assert_eq!(owned.items.len(), 1);
+    assert_eq!((owned.page, owned.limit, owned.total), (1, 10, 1));
+
+    let params: GetAssetsByCreatorParams =
+        serde_json::from_value(serde_json::json!({ "creatorAddress": "creator-2", "onlyVerified": true })).unwrap();
+    assert!(get_assets_by_creator(db, &params).await.unwrap().items.is_empty());
+
+    let params: SearchAssetsParams = serde_json::from_value(serde_json::json!({
+        "grouping": ["collection", "collection-mint"],
+        "interface": "V1_NFT",
+        "sortBy": { "sortBy": "id", "sortDirection": "asc" }
+    }))
+    .unwrap();
+    let found = search_assets(db, &params).await.unwrap();
+    assert_eq!(found.items[0].id, WITH_METADATA);
+
+    let params: SearchAssetsParams =
+        serde_json::from_value(serde_json::json!({ "grouping": ["collection", "collection-mint"], "burnt": true }))
+            .unwrap();
+    assert!(search_assets(db, &params).await.unwrap().items.is_empty());
+}
+
+async fn holdings(db: &DatabaseConnection) {
+    let nfts = find_holdings(db, HOLDER, HoldingKind::Nfts, &HoldingsQuery::default()).await.unwrap();
+    assert_eq!(nfts.items.len(), 2);
+    let nft = &nfts.items[0];
+    assert_eq!((nft.mint_address.as_str(), nft.ui_amount_string.as_str()), (WITH_METADATA, "1"));
+    assert_eq!(nft.asset.mint.metadata.name.as_deref(), Some("Degen Ape #1"));
+    assert!(nft.asset.content.is_some() && nft.asset.ownership.is_none());
+
+    let query = HoldingsQuery {
+        collection: Some("collection-mint".to_string()),
+        ..Default::default()
+    };
+    let collection = find_holdings(db, HOLDER, HoldingKind::Nfts, &query).await.unwrap();
+    assert_eq!(collection.items.len(), 1);
+    assert_eq!(collection.items[0].mint_address, WITH_METADATA);
+    let query = HoldingsQuery {
+        collection: Some("other-collection".to_string()),
+        ..Default::default()
+    };
+    assert!(find_holdings(db, HOLDER, HoldingKind::Nfts, &query).await.unwrap().items.is_empty());
+
+    // two accounts of the same mint are one holding, the unindexed mint is left out
+    let tokens = find_holdings(db, HOLDER, HoldingKind::Tokens, &HoldingsQuery::default()).await.unwrap();
+    assert_eq!(tokens.items.len(), 1);
+    let token = &tokens.items[0];
+    assert_eq!((token.amount.as_str(), token.ui_amount_string.as_str()), ("200", "2"));
+    assert_eq!((token.decimals, token.token_accounts, token.ui_amount), (2, 2, 2.0));
+    assert!(tokens.next_cursor.is_none());
+
+    // a page of one, then the rest after the cursor
+    let query = HoldingsQuery {
+        size: Some(1),
+        ..Default::default()
+    };
+    let first = find_holdings(db, HOLDER, HoldingKind::Nfts, &query).await.unwrap();
+    assert_eq!(first.items[0].mint_address, WITH_METADATA);
+    assert_eq!(first.next_cursor.as_deref(), Some(WITH_METADATA));
+    let query = HoldingsQuery {
+        size: Some(1),
+        cursor: first.next_cursor,
+        ..Default::default()
+    };
+    let second = find_holdings(db, HOLDER, HoldingKind::Nfts, &query).await.unwrap();
+    assert_eq!(second.items[0].mint_address, BARE_NFT);
+    assert!(second.next_cursor.is_none());
+}
+
+async fn collections(db: &DatabaseConnection) {
+    // the worker refreshes the view on an interval, the fixtures need it now
+    assert!(refresh_collection_stats(db).await.unwrap());
+    let collection = find_collection(db, "collection-mint").await.unwrap().unwrap();
+    assert_eq!(collection.name.as_deref(), Some("Degen Apes"));
+    assert_eq!((collection.members, collection.verified_members, collection.unique_holders), (1, 1, 1));
+    assert_eq!(collection.royalty.basis_points, 420);
+    assert_eq!(collection.creators.len(), 2);
+    assert!(collection.creators.iter().any(|creator| creator.address == "creator-1" && creator.verified));
+    assert!(find_collection(db, "details-test-missing").await.unwrap().is_none());
+
+    for sort in [CollectionSort::Members, CollectionSort::Holders, CollectionSort::Name, CollectionSort::Newest] {
+        let query = CollectionsQuery {
+            sort,
+            size: Some(100),
+            ..Default::default()
+        };
+        let listed = list_collections(db, &query).await.unwrap();
+        assert!(listed.total >= 1);
+        assert!(listed.items.iter().any(|item| item.collection_mint == "collection-mint"));
+    }
+
+    let nfts = find_collection_nfts(db, "collection-mint", &CollectionNftsQuery::default(), &AssetInclude::NONE)
+        .await
+        .unwrap();
+    assert_eq!(nfts.total, 1);
+    assert_eq!(nfts.items[0].mint.mint_address, WITH_METADATA);
+
+    // unverified members are left out unless asked for
+    db.execute_unprepared(&format!(
+        "UPDATE nft_metadata SET collection_verified = false WHERE mint_address = '{WITH_METADATA}'"
+    ))
+    .await
+    .unwrap();
+    let hidden = find_collection_nfts(db, "collection-mint", &CollectionNftsQuery::default(), &AssetInclude::NONE)
+        .await
+        .unwrap();
+    assert_eq!(hidden.total, 0);
+
+    let query = CollectionNftsQuery {
+        verified: Some(false),
+        ..Default::default()
+    };
+    let unverified = find_collection_nfts(db, "collection-mint", &query, &AssetInclude::NONE).await.unwrap();
+    assert_eq!(unverified.total, 1);
+    assert_eq!(unverified.items[0].mint.mint_address, WITH_METADATA);
+}
+
+// all of them share the fixtures, so they run one after another in a single test
+#[tokio::test]
+async fn asset_queries() {
+    let Ok(url) = std::env::var("TEST_DATABASE_URL") else {
+        println!("TEST_DATABASE_URL not set, skipping");
+        return;
+    };
+    let db = Database::connect(url).await.expect("failed to connect to TEST_DATABASE_URL");
+    fixtures(&db).await;
+
+    details_join(&db).await;
+    das_methods(&db).await;
+    holdings(&db).await;
+    collections(&db).await;
+}
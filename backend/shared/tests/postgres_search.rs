@@ -0,0 +1,148 @@
+// runs the postgres search backend against a real database. skipped unless TEST_DATABASE_URL is set,
+// e.g. TEST_DATABASE_URL=postgres://postgres@localhost/search_test cargo test -p shared --test postgres_search.
+// run `cargo run -p migration -- up` against it first
+use shared::{
+    elasticsearch::client::BulkOp,
+    search::{postgres::PgSearchIndex, SearchIndex},
+    types::elasticsearch::{
+        AttributeDoc, AttributeFilter, CollectionDoc, CreatorDoc, NftDoc, PageRequest, SearchFilters, SearchRequest,
+        SearchSort,
+    },
+    Database,
+};
+
+const COLLECTION: &str = "J1S9H3QjnRtBbbuD4HjPV6RpRhwuk4zKbxsnCHuTgh9w";
+const CREATOR: &str = "8nKDMp5ciA6NGxqvxFUmUCQ2RZwQxpBuZWQyNj4nQd9h";
+
+fn doc(mint_address: &str, name: &str, royalty_bps: i32, background: &str) -> NftDoc {
+    NftDoc {
+        mint_address: mint_address.to_string(),
+        nft_name: name.to_string(),
+        symbol: Some("DAPE".to_string()),
+        collection: Some(CollectionDoc {
+            name: Some("Degen Apes".to_string()),
+            mint: Some(COLLECTION.to_string()),
+            verified: true,
+        }),
+        creators: vec![CreatorDoc {
+            address: CREATOR.to_string(),
+            verified: true,
+            share: 100,
+        }],
+        attributes: vec![AttributeDoc {
+            trait_type: "Background".to_string(),
+            value: background.to_string(),
+        }],
+        royalty_bps: Some(royalty_bps),
+        ..Default::default()
+    }
+}
+
+async fn index() -> Option<PgSearchIndex> {
+    let url = std::env::var("TEST_DATABASE_URL").ok()?;
+    let db = Database::connect(url).await.expect("failed to connect to TEST_DATABASE_URL");
+    let index = PgSearchIndex::new(db).await.expect("nft_search_docs is missing, run the migrations");
+
+    let docs = vec![
+        doc("mint-a", "Degen Ape #1", 500, "Blue"),
+        doc("mint-b", "Degen Ape #2", 250, "Red"),
+        doc("mint-c", "Degen Ape #3", 750, "Blue"),
+        NftDoc {
+            collection: None,
+            ..doc("mint-d", "Solana Monkey", 100, "Green")
+        },
+    ];
+    let failures = index
+        .bulk(docs.into_iter().map(BulkOp::from).collect())
+        .await
+        .expect("bulk failed");
+    assert!(failures.is_empty(), "{:?}", failures);
+    Some(index)
+}
+
+// every test uses the same fixture, so they run one after another in a single test
+#[tokio::test]
+async fn postgres_search_backend() {
+    let Some(index) = index().await else {
+        println!("TEST_DATABASE_URL not set, skipping");
+        return;
+    };
+
+    // free text, with a typo
+    let response = index.search("degen", &PageRequest::default()).await.unwrap();
+    assert_eq!(response.total, 3);
+    assert!(response.total_is_exact);
+    let response = index.search("Solana Monky", &PageRequest::default()).await.unwrap();
+    assert_eq!(response.results[0].mint_address, "mint-d");
+
+    // classified queries
+    let response = index.search(CREATOR, &PageRequest::default()).await.unwrap();
+    assert_eq!(response.total, 4);
+    let response = index.search("trait:Background=Blue", &PageRequest::default()).await.unwrap();
+    assert_eq!(response.total, 2);
+
+    // paging by royalty follows the cursor without repeats, size 2 so the last page is a partial one
+    let mut page = PageRequest {
+        size: Some(2),
+        cursor: None,
+        sort: SearchSort::Royalty,
+    };
+    let mut seen = vec![];
+    loop {
+        let response = index.search(COLLECTION, &page).await.unwrap();
+        seen.extend(response.results.into_iter().map(|result| result.mint_address));
+        match response.next_cursor {
+            Some(cursor) => page.cursor = Some(cursor),
+            None => break,
+        }
+    }
+    assert_eq!(seen, vec!["mint-b", "mint-a", "mint-c"]);
+
+    // filters and facets
+    let response = index
+        .search_filtered(&SearchRequest {
+            query: Some("degen".to_string()),
+            filters: SearchFilters {
+                max_royalty_bps: Some(600),
+                attributes: vec![AttributeFilter {
+                    trait_type: "Background".to_string(),
+                    value: "Blue".to_string(),
+                }],
+                ..Default::default()
+            },
+            page: PageRequest::default(),
+            include_facets: true,
+        })
+        .await
+        .unwrap();
+    assert_eq!(response.total, 1);
+    assert_eq!(response.results[0].mint_address, "mint-a");
+    let aggregations = response.aggregations.unwrap();
+    assert_eq!(aggregations.collections[0].count, 1);
+    assert_eq!(aggregations.traits[0].values[0].value, "Blue");
+
+    // suggestions collapse to one per collection
+    let suggestions = index.suggest("deg", None).await.unwrap();
+    assert_eq!(suggestions.results.len(), 1);
+
+    // partial updates and deletes
+    let failures = index
+        .bulk(vec![
+            BulkOp::Update {
+                id: "mint-a".to_string(),
+                partial: serde_json::json!({ "nft_name": "Renamed" }),
+            },
+            BulkOp::Update {
+                id: "missing".to_string(),
+                partial: serde_json::json!({ "owner": null }),
+            },
+        ])
+        .await
+        .unwrap();
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures[0].status, 404);
+    assert_eq!(index.search("renamed", &PageRequest::default()).await.unwrap().total, 1);
+
+    index.delete("mint-d").await.unwrap();
+    assert_eq!(index.search("solana", &PageRequest::default()).await.unwrap().total, 0);
+}
@@ -0,0 +1,202 @@
+// creates webhooks pointing at a local receiver and runs the dispatcher against it. skipped unless
+// TEST_DATABASE_URL is set, run `cargo run -p migration -- up` against it first
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use axum::http::{HeaderMap, StatusCode};
+use chrono::Utc;
+use sea_orm::ConnectionTrait;
+use shared::{
+    api_keys::{create_api_key, revoke_api_key},
+    types::{
+        event::{ChangeEvent, ChangeKind},
+        webhook::{CreateWebhookRequest, DeliveriesQuery, UpdateWebhookRequest, WebhookFilter, WebhookPayload},
+    },
+    webhooks::{
+        create_webhook, dispatcher::{WebhookDispatcher, WebhookDispatcherConfig, SIGNATURE_HEADER, TIMESTAMP_HEADER},
+        enqueue_deliveries, find_webhook, list_deliveries, list_webhooks, sign, update_webhook,
+    },
+    Database, Router,
+};
+
+const MINT: &str = "So11111111111111111111111111111111111111112";
+const COLLECTION: &str = "J1S9H3QjnRtBbbuD4HjPV6RpRhwuk4zKbxsnCHuTgh9w";
+
+type Received = Arc<Mutex<Vec<(HeaderMap, String)>>>;
+
+// /ok records what it got and answers 200, /fail always answers 500
+async fn receiver() -> (String, Received) {
+    let received: Received = Arc::default();
+    let recorder = received.clone();
+    let app = Router::new()
+        .route(
+            "/ok",
+            axum::routing::post(move |headers: HeaderMap, body: String| async move {
+                recorder.lock().unwrap().push((headers, body));
+                StatusCode::OK
+            }),
+        )
+        .route("/fail", axum::routing::post(|| async { StatusCode::INTERNAL_SERVER_ERROR }));
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (address, received)
+}
+
+fn filter(kinds: Vec<ChangeKind>) -> WebhookFilter {
+    WebhookFilter {
+        collections: vec![COLLECTION.to_string()],
+        kinds,
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn webhook_deliveries() {
+    let Ok(url) = std::env::var("TEST_DATABASE_URL") else {
+        println!("TEST_DATABASE_URL not set, skipping");
+        return;
+    };
+    let db = Database::connect(url).await.expect("failed to connect to TEST_DATABASE_URL");
+    db.execute_unprepared("DELETE FROM webhooks WHERE url LIKE 'http://127.0.0.1:%'")
+        .await
+        .unwrap();
+    db.execute_unprepared("DELETE FROM api_keys WHERE name LIKE 'webhooks test%'")
+        .await
+        .unwrap();
+    let (key, _) = create_api_key(&db, "webhooks test", 60, 10).await.unwrap();
+    let (other_key, _) = create_api_key(&db, "webhooks test other", 60, 10).await.unwrap();
+
+    let (address, received) = receiver().await;
+    let ok = create_webhook(
+        &db,
+        key.id,
+        &CreateWebhookRequest {
+            url: format!("{}/ok", address),
+            filter: filter(vec![]),
+        },
+    )
+    .await
+    .unwrap();
+    let failing = create_webhook(
+        &db,
+        key.id,
+        &CreateWebhookRequest {
+            url: format!("{}/fail", address),
+            filter: filter(vec![ChangeKind::Metadata]),
+        },
+    )
+    .await
+    .unwrap();
+    // a different kind, never gets this event
+    create_webhook(
+        &db,
+        key.id,
+        &CreateWebhookRequest {
+            url: format!("{}/ok", address),
+            filter: filter(vec![ChangeKind::Burned]),
+        },
+    )
+    .await
+    .unwrap();
+
+    // another key sees none of them
+    assert_eq!(list_webhooks(&db, key.id).await.unwrap().len(), 3);
+    assert!(list_webhooks(&db, other_key.id).await.unwrap().is_empty());
+    assert!(find_webhook(&db, other_key.id, ok.id).await.unwrap().is_none());
+
+    let event = ChangeEvent {
+        kind: ChangeKind::Metadata,
+        mint_address: MINT.to_string(),
+        owner: None,
+        collection: Some(COLLECTION.to_string()),
+        creators: vec![],
+        at: Utc::now(),
+    };
+    assert_eq!(enqueue_deliveries(&db, &event).await.unwrap(), 2);
+
+    let dispatcher = WebhookDispatcher::new(
+        db.clone(),
+        WebhookDispatcherConfig {
+            max_attempts: 2,
+            disable_after: 2,
+            timeout: Duration::from_secs(5),
+            allow_private_addresses: true, // the receiver is on 127.0.0.1
+            ..Default::default()
+        },
+    );
+    assert_eq!(dispatcher.dispatch_due().await.unwrap(), 2);
+
+    // signed with the secret handed out at creation
+    let (headers, body) = received.lock().unwrap().pop().expect("the receiver got nothing");
+    let timestamp: i64 = headers[TIMESTAMP_HEADER].to_str().unwrap().parse().unwrap();
+    let secret = ok.secret.as_deref().unwrap();
+    assert_eq!(headers[SIGNATURE_HEADER].to_str().unwrap(), sign(secret, timestamp, &body));
+    let payload: WebhookPayload = serde_json::from_str(&body).unwrap();
+    assert_eq!((payload.webhook_id, payload.event.mint_address.as_str()), (ok.id, MINT));
+
+    let log = list_deliveries(&db, ok.id, &DeliveriesQuery::default()).await.unwrap();
+    assert_eq!((log.total, log.items[0].status.as_str()), (1, "delivered"));
+
+    // the failed one backs off
+    let log = list_deliveries(&db, failing.id, &DeliveriesQuery::default()).await.unwrap();
+    let delivery = &log.items[0];
+    assert_eq!((delivery.status.as_str(), delivery.attempts, delivery.response_status), ("pending", 1, Some(500)));
+    assert!(delivery.next_attempt_at > Utc::now().fixed_offset());
+    assert_eq!(dispatcher.dispatch_due().await.unwrap(), 0);
+
+    // its second failure is the last attempt, and the second one in a row for the webhook
+    db.execute_unprepared(&format!(
+        "UPDATE webhook_deliveries SET next_attempt_at = now() - interval '1 second' WHERE webhook_id = '{}'",
+        failing.id
+    ))
+    .await
+    .unwrap();
+    assert_eq!(dispatcher.dispatch_due().await.unwrap(), 1);
+    let log = list_deliveries(&db, failing.id, &DeliveriesQuery::default()).await.unwrap();
+    assert_eq!((log.items[0].status.as_str(), log.items[0].attempts), ("failed", 2));
+    let disabled = find_webhook(&db, key.id, failing.id).await.unwrap().unwrap();
+    assert!(!disabled.active && disabled.disabled_reason.is_some());
+
+    // a disabled webhook gets nothing new until it's switched back on
+    assert_eq!(enqueue_deliveries(&db, &event).await.unwrap(), 1);
+    let request = UpdateWebhookRequest {
+        active: Some(true),
+        ..Default::default()
+    };
+    assert!(update_webhook(&db, other_key.id, failing.id, &request).await.unwrap().is_none());
+    let enabled = update_webhook(&db, key.id, failing.id, &request).await.unwrap().unwrap();
+    assert!(enabled.active && enabled.consecutive_failures == 0 && enabled.disabled_reason.is_none());
+
+    // a receiver gets its deliveries in order, and a failure holds back the ones queued after it
+    enqueue_deliveries(&db, &event).await.unwrap();
+    enqueue_deliveries(&db, &event).await.unwrap();
+    assert_eq!(dispatcher.dispatch_due().await.unwrap(), 4);
+    let ids: Vec<i64> = received
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(_, body)| serde_json::from_str::<WebhookPayload>(body).unwrap().delivery_id)
+        .collect();
+    assert!(ids.len() == 3 && ids.is_sorted());
+
+    let log = list_deliveries(&db, failing.id, &DeliveriesQuery::default()).await.unwrap();
+    let pending: Vec<i32> = log.items.iter().filter(|d| d.status == "pending").map(|d| d.attempts).collect();
+    assert_eq!(pending.len(), 2);
+    assert!(pending.contains(&0) && pending.contains(&1));
+    assert_eq!(dispatcher.dispatch_due().await.unwrap(), 0);
+
+    // revoking the key switches its webhooks off, the pending delivery isn't sent either
+    assert!(revoke_api_key(&db, key.id).await.unwrap());
+    assert!(list_webhooks(&db, key.id).await.unwrap().iter().all(|webhook| !webhook.active));
+    assert_eq!(enqueue_deliveries(&db, &event).await.unwrap(), 0);
+    db.execute_unprepared(&format!(
+        "UPDATE webhook_deliveries SET next_attempt_at = now() - interval '1 second'
+        WHERE webhook_id IN (SELECT id FROM webhooks WHERE api_key_id = '{}')",
+        key.id
+    ))
+    .await
+    .unwrap();
+    assert_eq!(dispatcher.dispatch_due().await.unwrap(), 0);
+}
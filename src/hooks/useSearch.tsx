@@ -1,5 +1,6 @@
 import { useState, useEffect, useCallback, useRef } from 'react';
 import axios, { CancelTokenSource } from 'axios';
+import { indexerHeaders } from '@/services/indexerAPI';
 
 interface NFTSearchResult {
   mint_address: string;
@@ -82,12 +83,13 @@ const useSearch = (): UseSearchReturn => {
       console.log('Searching for:', trimmedQuery);
 
       const response = await axios.get<SearchResponse>(
-        `http://localhost:3001/search/nfts/${encodeURIComponent(trimmedQuery)}`,
+        `http://localhost:3001/suggest?q=${encodeURIComponent(trimmedQuery)}`,
         {
           cancelToken: source.token,
           timeout: 8000,
           headers: {
             'Content-Type': 'application/json',
+            ...indexerHeaders(),
           },
         }
       );
@@ -7,6 +7,8 @@ import Header from '@/components/layout/Header';
 import Footer from '@/components/layout/Footer';
 import TrendingCollections from '@/components/ui/TrendingCollections';
 import NFTDetailModal from '@/components/ui/NFTDetailModal';
+import { ApiErrorBody } from '@/types';
+import { indexerHeaders } from '@/services/indexerAPI';
 
 interface NFTSearchResult {
   mint_address: string;
@@ -79,10 +81,13 @@ export default function Home() {
       setIsLoadingNFTDetails(true);
       
       // Call your backend API to get detailed NFT info
-      const response = await fetch(`http://localhost:3001/details/${mintAddress}`);
+      const response = await fetch(`http://localhost:3001/details/${mintAddress}`, {
+        headers: indexerHeaders(),
+      });
       
       if (!response.ok) {
-        throw new Error(`Failed to fetch NFT details: ${response.statusText}`);
+        const body: ApiErrorBody | null = await response.json().catch(() => null);
+        throw new Error(`Failed to fetch NFT details: ${body?.message ?? response.statusText}`);
       }
       
       const data = await response.json();
@@ -0,0 +1,6 @@
+// src/services/indexerAPI.ts
+// the api server rejects requests without a key unless it runs with API_KEYS_REQUIRED=false
+export const indexerHeaders = (): Record<string, string> => {
+  const apiKey = process.env.NEXT_PUBLIC_API_KEY;
+  return apiKey ? { 'X-API-Key': apiKey } : {};
+};
@@ -10,8 +10,200 @@ export interface NFTResult {
   lastSale?: number;
 }
 
+export type SearchSort = 'relevance' | 'name' | 'newest' | 'royalty';
+
 export interface SearchResponse {
   results: NFTResult[];
+  total: number;
+  total_is_exact: boolean;
+  next_cursor: string | null;
+  aggregations?: SearchAggregations; // only when POST /search asked for include_facets
+}
+
+export interface SearchAggregations {
+  collections: { mint: string; name: string | null; count: number }[];
+  traits: { trait_type: string; count: number; values: { value: string; count: number }[] }[];
+}
+
+export interface Suggestion {
+  mint_address: string;
+  nft_name: string;
+  score: number;
+  collection_name: string | null;
+  collection_mint: string | null;
+  image: string | null;
+}
+
+export interface SuggestResponse {
+  results: Suggestion[];
+}
+
+// GET /details/{mint_address}, optional blocks are missing when excluded via ?include= or not stored
+export interface AssetDetails {
+  mint_address: string;
+  owner: string;
+  mint_authority: string;
+  supply: number;
+  decimal: number;
+  is_initialized: boolean;
+  freeze_authority: string | null;
+  metadata: {
+    name: string | null;
+    symbol: string | null;
+    metadata_uri: string | null;
+    seller_fee_basis_points: number;
+    update_authority: string | null;
+    primary_sale_happened: boolean;
+    is_mutable: boolean;
+  };
+  status: 'active' | 'burned' | 'closed' | null;
+  token_standard: string | null;
+  creators?: { address: string; verified: boolean; share: number }[];
+  royalty?: {
+    royalty_model: string;
+    target: string | null;
+    basis_points: number;
+    percent: number;
+    primary_sale_happened: boolean;
+    locked: boolean;
+  };
+  ownership?: {
+    owner: string;
+    delegate: string | null;
+    frozen: boolean;
+    delegated: boolean;
+    ownership_model: string;
+  };
+  content?: {
+    description: string | null;
+    image: string | null;
+    animation_url: string | null;
+    external_url: string | null;
+    attributes: { trait_type: string; value: string | number }[] | null;
+    properties: Record<string, unknown> | null;
+  };
+  collection?: {
+    mint: string | null;
+    name: string | null;
+    verified: boolean;
+    family: string | null;
+  };
+}
+
+// POST /details with { mint_addresses, include? } answers with one entry per requested mint
+export type DetailsBatchResponse = Record<string, AssetDetails | ApiErrorBody>;
+
+// GET /owners/{wallet}/nfts and /owners/{wallet}/tokens
+export interface Holding {
+  mint_address: string;
+  amount: string; // raw base units
+  decimals: number;
+  ui_amount: number;
+  ui_amount_string: string;
+  token_accounts: number;
+  asset: AssetDetails;
+}
+
+export interface HoldingsResponse {
+  owner: string;
+  items: Holding[];
+  next_cursor: string | null;
+}
+
+// GET /collections/{collection_mint}, and the items of GET /collections
+export interface CollectionSummary {
+  collection_mint: string;
+  name: string | null;
+  symbol: string | null;
+  image: string | null;
+  members: number;
+  verified_members: number;
+  unique_holders: number;
+  creators: { address: string; verified: boolean; members: number }[];
+  royalty: { basis_points: number; percent: number };
+}
+
+export interface CollectionsResponse {
+  items: CollectionSummary[];
+  total: number;
+  page: number;
+  size: number;
+}
+
+export interface CollectionNftsResponse {
+  collection_mint: string;
+  items: AssetDetails[];
+  total: number;
+  page: number;
+  size: number;
+}
+
+// what GET /events (server-sent events, named after `kind`) and GET /events/ws (text messages) deliver.
+// a { kind: 'lagged', skipped } message means the client fell behind and missed that many
+export interface ChangeEvent {
+  kind: 'mint' | 'metadata' | 'ownership' | 'burned' | 'closed';
+  mint_address: string;
+  owner: string | null;
+  collection: string | null;
+  creators: string[];
+  at: string;
+}
+
+// /webhooks CRUD. `secret` is only in the response to the POST that created the webhook
+export interface WebhookFilter {
+  mints: string[];
+  owners: string[];
+  collections: string[];
+  creators: string[];
+  kinds: ChangeEvent['kind'][]; // empty means every kind
+}
+
+export interface Webhook {
+  id: string;
+  url: string;
+  filter: WebhookFilter;
+  active: boolean;
+  consecutive_failures: number;
+  disabled_reason: string | null;
+  secret?: string;
+  created_at: string;
+  updated_at: string;
+}
+
+export interface WebhookDelivery {
+  id: number;
+  event_kind: ChangeEvent['kind'];
+  mint_address: string;
+  status: 'pending' | 'delivered' | 'failed';
+  attempts: number;
+  response_status: number | null;
+  last_error: string | null;
+  next_attempt_at: string;
+  delivered_at: string | null;
+  created_at: string;
+}
+
+// the body POSTed to a webhook url, signed in the X-Webhook-Signature header
+export interface WebhookPayload {
+  delivery_id: number;
+  webhook_id: string;
+  event: ChangeEvent;
+}
+
+// GET /usage, the limits and daily request counts of the api key the request was made with
+export interface UsageResponse {
+  key_id: string;
+  name: string;
+  key_prefix: string;
+  requests_per_minute: number;
+  burst: number;
+  days: { day: string; requests: number; throttled: number }[]; // newest first
+}
+
+// every non 2xx response from the api server has this body. a rate_limited one comes with a Retry-After header
+export interface ApiErrorBody {
+  error: 'not_found' | 'bad_request' | 'unauthorized' | 'rate_limited' | 'service_unavailable' | 'internal_error';
+  message: string;
 }
 
 export interface TrendingCollection {